@@ -4,6 +4,7 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use crate::kernel::traits::vk_filesys::FileTime;
 use crate::kernel::traits::vk_kernel::System;
 
 /// struct village system
@@ -36,6 +37,11 @@ impl System for VillageSystem {
         0
     }
 
+    /// get date time, the rtc is not used
+    fn get_date_time(&mut self) -> FileTime {
+        FileTime::new()
+    }
+
     /// delay ms
     fn delay_ms(&mut self, millis: u32) {
         
@@ -51,6 +57,31 @@ impl System for VillageSystem {
 
     }
 
+    /// irq save
+    fn irq_save(&mut self) -> u32 {
+        0
+    }
+
+    /// irq restore
+    fn irq_restore(&mut self, flags: u32) {
+
+    }
+
+    /// get cpu id
+    fn get_cpu_id(&mut self) -> usize {
+        0
+    }
+
+    /// get cpu count
+    fn get_cpu_count(&mut self) -> usize {
+        1
+    }
+
+    /// send ipi
+    fn send_ipi(&mut self, cpu: usize, irq: isize) {
+
+    }
+
     /// sleep
     fn sleep(&mut self) {
 
@@ -36,6 +36,11 @@ impl Scheduler for VillageScheduler {
     /// start
     fn start(&mut self) {
 
+    }
+
+    /// start cpu
+    fn start_cpu(&mut self) {
+
     }
     
     /// sched
@@ -0,0 +1,211 @@
+//###########################################################################
+// vk_acpi.rs
+// The specific implementation of functions related to acpi tables
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use core::ptr;
+
+// Acpi defines
+const EBDA_SEGMENT_PTR: u32 = 0x40e;
+const EBDA_SEARCH_SIZE: u32 = 0x400;
+const BIOS_ROM_START: u32 = 0xe0000;
+const BIOS_ROM_ENDED: u32 = 0x100000;
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+const RSDP_V1_SIZE: u32 = 20;
+const RSDP_V2_SIZE: u32 = 36;
+const SDT_HEADER_SIZE: u32 = 36;
+const MADT_SIGNATURE: &[u8; 4] = b"APIC";
+const MADT_ENTRIES_OFFSET: u32 = 44;
+
+// Madt entry types
+const MADT_IOAPIC: u8 = 1;
+const MADT_ISA_OVERRIDE: u8 = 2;
+
+// Madt interrupt flags
+const MPS_POLARITY_MASK: u16 = 0x3;
+const MPS_POLARITY_LOW: u16 = 0x3;
+const MPS_TRIGGER_MASK: u16 = 0xc;
+const MPS_TRIGGER_LEVEL: u16 = 0xc;
+
+// Isa irq number
+pub const ISA_IRQ_NUM: usize = 16;
+
+// Struct isa override
+#[derive(Clone, Copy)]
+pub struct IsaOverride {
+    pub gsi: u32,
+    pub level: bool,
+    pub active_low: bool,
+}
+
+// Impl isa override
+impl IsaOverride {
+    // New, isa irqs are edge triggered and active high
+    pub const fn new(gsi: u32) -> Self {
+        Self {
+            gsi,
+            level: false,
+            active_low: false,
+        }
+    }
+}
+
+// Struct madt info
+pub struct MadtInfo {
+    pub ioapic_addr: u32,
+    pub ioapic_gsi_base: u32,
+    pub overrides: [IsaOverride; ISA_IRQ_NUM],
+}
+
+// Struct village acpi
+pub struct VillageAcpi;
+
+// Impl village acpi
+impl VillageAcpi {
+    // Get the io apic and the isa overrides from madt, the io apic
+    // which handles gsi 0 is used when there are more than one
+    pub fn madt() -> Option<MadtInfo> {
+        let madt = Self::find_table(MADT_SIGNATURE)?;
+        let length = Self::read_u32(madt + 4);
+
+        let mut info = MadtInfo {
+            ioapic_addr: 0,
+            ioapic_gsi_base: 0,
+            overrides: core::array::from_fn(|line| IsaOverride::new(line as u32)),
+        };
+
+        let mut offset = MADT_ENTRIES_OFFSET;
+        while offset + 2 <= length {
+            let entry = madt + offset;
+            let typ = Self::read_u8(entry);
+            let size = Self::read_u8(entry + 1) as u32;
+            if size < 2 {
+                break;
+            }
+
+            match typ {
+                MADT_IOAPIC => {
+                    let gsi_base = Self::read_u32(entry + 8);
+                    if info.ioapic_addr == 0 || gsi_base == 0 {
+                        info.ioapic_addr = Self::read_u32(entry + 4);
+                        info.ioapic_gsi_base = gsi_base;
+                    }
+                }
+                MADT_ISA_OVERRIDE => {
+                    let source = Self::read_u8(entry + 3) as usize;
+                    let flags = Self::read_u16(entry + 8);
+                    if source < ISA_IRQ_NUM {
+                        info.overrides[source] = IsaOverride {
+                            gsi: Self::read_u32(entry + 4),
+                            level: (flags & MPS_TRIGGER_MASK) == MPS_TRIGGER_LEVEL,
+                            active_low: (flags & MPS_POLARITY_MASK) == MPS_POLARITY_LOW,
+                        };
+                    }
+                }
+                _ => {}
+            }
+
+            offset += size;
+        }
+
+        if info.ioapic_addr == 0 {
+            return None;
+        }
+
+        Some(info)
+    }
+
+    // Find the system description table by signature
+    fn find_table(signature: &[u8; 4]) -> Option<u32> {
+        let rsdp = Self::find_rsdp()?;
+
+        // Use the xsdt when its address is reachable, otherwise the rsdt
+        let revision = Self::read_u8(rsdp + 15);
+        let xsdt = if revision >= 2 { Self::read_u32(rsdp + 24) } else { 0 };
+        let xsdt_high = if revision >= 2 { Self::read_u32(rsdp + 28) } else { 0 };
+        let (sdt, entry_size) = if xsdt != 0 && xsdt_high == 0 {
+            (xsdt, 8)
+        } else {
+            (Self::read_u32(rsdp + 16), 4)
+        };
+
+        if sdt == 0 || !Self::check_table(sdt) {
+            return None;
+        }
+
+        let count = (Self::read_u32(sdt + 4) - SDT_HEADER_SIZE) / entry_size;
+        for i in 0..count {
+            let entry = sdt + SDT_HEADER_SIZE + i * entry_size;
+            if entry_size == 8 && Self::read_u32(entry + 4) != 0 {
+                continue;
+            }
+
+            let table = Self::read_u32(entry);
+            if table != 0 && Self::read_bytes::<4>(table) == *signature && Self::check_table(table) {
+                return Some(table);
+            }
+        }
+
+        None
+    }
+
+    // Find the root system description pointer in ebda and bios rom
+    fn find_rsdp() -> Option<u32> {
+        let ebda = (Self::read_u16(EBDA_SEGMENT_PTR) as u32) << 4;
+        if ebda != 0 {
+            if let Some(rsdp) = Self::scan_rsdp(ebda, ebda + EBDA_SEARCH_SIZE) {
+                return Some(rsdp);
+            }
+        }
+        Self::scan_rsdp(BIOS_ROM_START, BIOS_ROM_ENDED)
+    }
+
+    // Scan the rsdp on 16 bytes boundaries
+    fn scan_rsdp(start: u32, ended: u32) -> Option<u32> {
+        let mut addr = start;
+        while addr + RSDP_V1_SIZE <= ended {
+            if Self::read_bytes::<8>(addr) == *RSDP_SIGNATURE && Self::checksum(addr, RSDP_V1_SIZE) {
+                if Self::read_u8(addr + 15) < 2 || Self::checksum(addr, RSDP_V2_SIZE) {
+                    return Some(addr);
+                }
+            }
+            addr += 16;
+        }
+        None
+    }
+
+    // Check the length and checksum of table
+    fn check_table(table: u32) -> bool {
+        let length = Self::read_u32(table + 4);
+        length >= SDT_HEADER_SIZE && Self::checksum(table, length)
+    }
+
+    // Checksum, the sum of all bytes is zero
+    fn checksum(addr: u32, size: u32) -> bool {
+        (0..size).fold(0u8, |sum, i| sum.wrapping_add(Self::read_u8(addr + i))) == 0
+    }
+}
+
+// Impl village acpi
+impl VillageAcpi {
+    // Read bytes
+    fn read_bytes<const N: usize>(addr: u32) -> [u8; N] {
+        unsafe { ptr::read_unaligned(addr as *const [u8; N]) }
+    }
+
+    // Read u8
+    fn read_u8(addr: u32) -> u8 {
+        unsafe { ptr::read_volatile(addr as *const u8) }
+    }
+
+    // Read u16
+    fn read_u16(addr: u32) -> u16 {
+        u16::from_le_bytes(Self::read_bytes::<2>(addr))
+    }
+
+    // Read u32
+    fn read_u32(addr: u32) -> u32 {
+        u32::from_le_bytes(Self::read_bytes::<4>(addr))
+    }
+}
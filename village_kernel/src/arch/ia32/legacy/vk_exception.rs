@@ -4,6 +4,8 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_irqchip::VillageIrqChip;
+use super::vk_lapic::{VillageLapic, APIC_SPURIOUS_VECTOR};
 use super::vk_registers::Registers;
 use crate::traits::vk_callback::Callback;
 use crate::vendor::ia32legacy::core::i686::*;
@@ -14,7 +16,7 @@ use core::arch::asm;
 // Constant members
 pub const ISR_NUM: usize = 48;
 pub const RSVD_ISR_SIZE: usize = 0;
-const IDT_ENTRIES: u32 = 48;
+const IDT_ENTRIES: usize = 256;
 const KERNEL_CODE_SEGMENT: u16 = 8;
 
 #[repr(C, packed)]
@@ -36,7 +38,7 @@ struct IdtRegister {
 
 // Struct village exception
 pub struct VillageException {
-    idt: [IdtGate; ISR_NUM],
+    idt: [IdtGate; IDT_ENTRIES],
     idt_reg: IdtRegister,
 }
 
@@ -51,7 +53,7 @@ impl VillageException {
                 rsvd: 0,
                 flags: 0,
                 high_offset: 0,
-            }; ISR_NUM],
+            }; IDT_ENTRIES],
             idt_reg: IdtRegister { limit: 0, base: 0 },
         }
     }
@@ -80,12 +82,12 @@ impl VillageException {
             }
         }
 
+        // The spurious vector of local apic is out of isr vector
+        self.install(APIC_SPURIOUS_VECTOR as usize, VillageLapic::spurious_handler as usize);
+
         // Install handlers
         self.install_handlers();
 
-        // Remap the PIC
-        self.remap_pic();
-
         // Set IDT
         self.set_idt();
     }
@@ -104,62 +106,16 @@ impl VillageException {
         gate.flags = 0x8E;
     }
 
-    // Get pic irq register
-    pub fn get_pic_irq_reg(&self, ocw3: u8) -> u16 {
-        port_byte_out(PIC1_CMD, ocw3);
-        port_byte_out(PIC2_CMD, ocw3);
-        (port_byte_in(PIC2_CMD) as u16) << 8 | port_byte_in(PIC1_CMD) as u16
-    }
-
-    // Get pic irr
-    pub fn get_pic_irr(&self) -> u16 {
-        self.get_pic_irq_reg(PIC_READ_IRR)
-    }
-
-    // Get pic isr
-    pub fn get_pic_isr(&self) -> u16 {
-        self.get_pic_irq_reg(PIC_READ_ISR)
-    }
-
     // Set idt
     fn set_idt(&mut self) {
         self.idt_reg.base = self.idt.as_ptr() as u32;
-        self.idt_reg.limit = (IDT_ENTRIES * core::mem::size_of::<IdtGate>() as u32) as u16 - 1;
+        self.idt_reg.limit = (IDT_ENTRIES * core::mem::size_of::<IdtGate>()) as u16 - 1;
 
         unsafe {
             asm!("lidt [{}]", in(reg) &self.idt_reg as *const _ as u32);
         }
     }
 
-    // Remap pic
-    fn remap_pic(&self) {
-        // Save masks
-        let a1 = port_byte_in(PIC1_DATA);
-        let a2 = port_byte_in(PIC2_DATA);
-
-        // Starts the initialization sequence (in cascade mode)
-        port_byte_out(PIC1_CMD, ICW1_INIT | ICW1_ICW4);
-        port_byte_out(PIC2_CMD, ICW1_INIT | ICW1_ICW4);
-
-        // ICW2: Master PIC vector offset
-        port_byte_out(PIC1_DATA, 0x20);
-        // ICW2: Slave PIC vector offset
-        port_byte_out(PIC2_DATA, 0x28);
-
-        // ICW3: tell Master PIC that there is a slave PIC at IRQ2 (0000 0100)
-        port_byte_out(PIC1_DATA, 0x04);
-        // ICW3: tell Slave PIC its cascade identity (0000 0010)
-        port_byte_out(PIC2_DATA, 0x02);
-
-        // ICW4: have the PICs use 8086 mode (and not 8080 mode)
-        port_byte_out(PIC1_DATA, ICW4_8086);
-        port_byte_out(PIC2_DATA, ICW4_8086);
-
-        // Restore saved masks
-        port_byte_out(PIC1_DATA, a1);
-        port_byte_out(PIC2_DATA, a2);
-    }
-
     // Install handlers
     fn install_handlers(&mut self) {
         macro_rules! install_handler {
@@ -372,21 +328,19 @@ fn stacked_info(regs: &Registers) {
 pub unsafe extern "C" fn irq_handler(regs: *const Registers) {
     let regs = unsafe { core::ptr::read(regs) };
 
-    // Send an EOI to the PICs
-    if (32..=47).contains(&regs.irq) {
-        if regs.irq >= 40 {
-            port_byte_out(PIC2_CMD, PIC_EOI); //slave
-        }
-        if regs.irq >= 32 {
-            port_byte_out(PIC1_CMD, PIC_EOI); //master
-        }
-    }
+    // Send an EOI to the irq chip
+    VillageIrqChip::eoi(regs.irq as isize);
 
-    // Output stacked info
-    if (0..=18).contains(&regs.irq) {
+    // Output stacked info, the no coprocessor trap is used by fpu switching
+    if (0..=18).contains(&regs.irq) && regs.irq != NO_COPROCESSOR_IRQN as u32 {
         stacked_info(&regs);
     }
 
+    // Send an EOI to the local apic when the vector is an ipi
+    if regs.irq == PENDSV_IRQN as u32 {
+        VillageLapic::ack(regs.irq);
+    }
+
     // Handle the interrupt in a more modular way
     kernel().interrupt().handler(regs.irq as isize);
 }
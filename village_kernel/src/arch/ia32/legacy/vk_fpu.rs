@@ -0,0 +1,241 @@
+//###########################################################################
+// vk_fpu.rs
+// The specific implementation of functions related to fpu
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::debug_error;
+use crate::traits::vk_callback::Callback;
+use crate::vendor::ia32legacy::core::i686::NO_COPROCESSOR_IRQN;
+use crate::village::kernel;
+use core::arch::asm;
+
+// Constant members
+const CR0_MP: u32 = 1 << 1;
+const CR0_EM: u32 = 1 << 2;
+const CR0_TS: u32 = 1 << 3;
+const CR0_NE: u32 = 1 << 5;
+const CR4_OSFXSR: u32 = 1 << 9;
+const CR4_OSXMMEXCPT: u32 = 1 << 10;
+const CPUID_FXSR: u32 = 1 << 24;
+const CPUID_SSE: u32 = 1 << 25;
+const MXCSR_DEFAULT: u32 = 0x1f80;
+
+// Struct village fpu
+pub struct VillageFpu {
+    has_fxsr: bool,
+    has_sse: bool,
+}
+
+// Impl village fpu
+impl VillageFpu {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            has_fxsr: false,
+            has_sse: false,
+        }
+    }
+}
+
+// Impl village fpu
+impl VillageFpu {
+    // Setup
+    pub fn setup(&mut self) {
+        // Get the cpu features
+        let features = Self::cpuid_features();
+        self.has_fxsr = (features & CPUID_FXSR) != 0;
+        self.has_sse = (features & CPUID_SSE) != 0;
+
+        // Setup the fpu of bootstrap processor
+        self.setup_cpu();
+
+        // Set the device not available handler
+        let fpu_cb = Callback::new(Self::no_coprocessor_handler as u32).with_instance(self);
+        kernel().interrupt().set_isr_cb(NO_COPROCESSOR_IRQN, fpu_cb);
+    }
+
+    // Setup the fpu of current processor
+    pub fn setup_cpu(&mut self) {
+        // Enable the native fpu and the monitor coprocessor
+        let cr0 = (Self::read_cr0() & !CR0_EM) | CR0_MP | CR0_NE;
+        Self::write_cr0(cr0);
+
+        // Enable fxsave and the sse unit
+        if self.has_fxsr {
+            let mut cr4 = Self::read_cr4() | CR4_OSFXSR;
+            if self.has_sse {
+                cr4 |= CR4_OSXMMEXCPT;
+            }
+            Self::write_cr4(cr4);
+        }
+
+        // Initialize the fpu
+        self.reset();
+
+        // No task owns the fpu
+        kernel().thread().set_fpu_owner(0);
+        self.set_ts();
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        // Clear the device not available handler
+        kernel().interrupt().clear_isr_cb(NO_COPROCESSOR_IRQN);
+
+        // Clear the task switched flag
+        Self::clear_ts();
+    }
+
+    // Switch, the task which owns the fpu does not trap,
+    // the task without fpu area traps to allocate it
+    pub fn switch(&mut self, area: u32) {
+        if area != 0 && area == kernel().thread().get_fpu_owner() {
+            Self::clear_ts();
+        } else {
+            self.set_ts();
+        }
+    }
+}
+
+// Impl village fpu
+impl VillageFpu {
+    // No coprocessor handler, swap the fpu state on the first use after switch,
+    // the area of owner is not released until another area is loaded
+    fn no_coprocessor_handler(&mut self) {
+        Self::clear_ts();
+
+        let owner = kernel().thread().get_fpu_owner();
+        let area = kernel().thread().alloc_task_fpu();
+        if area != 0 && area == owner {
+            return;
+        }
+
+        // The task without fpu area uses a clean state that is not kept
+        if area == 0 {
+            debug_error!("alloc fpu area failed, the fpu state is not kept");
+        }
+
+        // Save the state of the previous owner
+        self.save(owner);
+
+        // Restore the state of current task
+        self.restore(area);
+
+        kernel().thread().set_fpu_owner(area);
+    }
+
+    // Align the state area
+    fn align(area: u32) -> u32 {
+        (area + 15) & !15
+    }
+
+    // Save
+    fn save(&mut self, area: u32) {
+        if area == 0 {
+            return;
+        }
+
+        let area = Self::align(area);
+
+        unsafe {
+            if self.has_fxsr {
+                asm!("fxsave ({0})", in(reg) area, options(att_syntax));
+            } else {
+                asm!("fnsave ({0})", in(reg) area, options(att_syntax));
+            }
+        }
+    }
+
+    // Restore, the task that never used fpu gets a clean state
+    fn restore(&mut self, area: u32) {
+        let area = Self::align(area);
+
+        // The control word of a saved state is never zero
+        if area == 0 || unsafe { *(area as *const u16) } == 0 {
+            self.reset();
+            return;
+        }
+
+        unsafe {
+            if self.has_fxsr {
+                asm!("fxrstor ({0})", in(reg) area, options(att_syntax));
+            } else {
+                asm!("frstor ({0})", in(reg) area, options(att_syntax));
+            }
+        }
+    }
+
+    // Reset
+    fn reset(&mut self) {
+        unsafe {
+            asm!("fninit", options(att_syntax));
+
+            if self.has_sse {
+                let mxcsr: u32 = MXCSR_DEFAULT;
+                asm!("ldmxcsr ({0})", in(reg) &mxcsr as *const u32, options(att_syntax));
+            }
+        }
+    }
+
+    // Set task switched flag
+    fn set_ts(&mut self) {
+        Self::write_cr0(Self::read_cr0() | CR0_TS);
+    }
+
+    // Clear task switched flag
+    fn clear_ts() {
+        unsafe {
+            asm!("clts", options(att_syntax));
+        }
+    }
+
+    // Get the cpu features
+    fn cpuid_features() -> u32 {
+        let edx: u32;
+        unsafe {
+            asm!(
+                "pushl %ebx",
+                "cpuid",
+                "popl %ebx",
+                inout("eax") 1 => _,
+                out("ecx") _,
+                out("edx") edx,
+                options(att_syntax)
+            );
+        }
+        edx
+    }
+
+    // Read cr0
+    fn read_cr0() -> u32 {
+        let cr0: u32;
+        unsafe {
+            asm!("movl %cr0, {0}", out(reg) cr0, options(att_syntax));
+        }
+        cr0
+    }
+
+    // Write cr0
+    fn write_cr0(cr0: u32) {
+        unsafe {
+            asm!("movl {0}, %cr0", in(reg) cr0, options(att_syntax));
+        }
+    }
+
+    // Read cr4
+    fn read_cr4() -> u32 {
+        let cr4: u32;
+        unsafe {
+            asm!("movl %cr4, {0}", out(reg) cr4, options(att_syntax));
+        }
+        cr4
+    }
+
+    // Write cr4
+    fn write_cr4(cr4: u32) {
+        unsafe {
+            asm!("movl {0}, %cr4", in(reg) cr4, options(att_syntax));
+        }
+    }
+}
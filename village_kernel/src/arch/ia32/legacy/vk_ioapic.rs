@@ -0,0 +1,182 @@
+//###########################################################################
+// vk_ioapic.rs
+// The specific implementation of functions related to io apic
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+// Io apic registers
+const IOAPIC_REGSEL: u32 = 0x00;
+const IOAPIC_WINDOW: u32 = 0x10;
+const IOAPIC_VER: u32 = 0x01;
+const IOAPIC_REDTBL: u32 = 0x10;
+
+// Redirection entry defines
+const REDTBL_MASKED: u32 = 1 << 16;
+const REDTBL_LEVEL: u32 = 1 << 15;
+const REDTBL_ACTIVE_LOW: u32 = 1 << 13;
+
+// The io apic base address, zero when the io apic is not used
+static IOAPIC_ADDR: AtomicU32 = AtomicU32::new(0);
+static IOAPIC_ENTRIES: AtomicU32 = AtomicU32::new(0);
+static IOAPIC_GSI_BASE: AtomicU32 = AtomicU32::new(0);
+
+// Struct redirect entry
+#[derive(Clone, Copy)]
+pub struct RedirectEntry {
+    pub vector: u8,
+    pub apic_id: u8,
+    pub masked: bool,
+    pub level: bool,
+    pub active_low: bool,
+}
+
+// Impl redirect entry
+impl RedirectEntry {
+    // New, edge triggered and active high as isa irqs
+    pub const fn new(vector: u8, apic_id: u8) -> Self {
+        Self {
+            vector,
+            apic_id,
+            masked: true,
+            level: false,
+            active_low: false,
+        }
+    }
+}
+
+// Struct village ioapic
+pub struct VillageIoapic;
+
+// Impl village ioapic
+impl VillageIoapic {
+    // Setup, all redirection entries are masked, the first pin is gsi base
+    pub fn setup(addr: u32, gsi_base: u32) -> bool {
+        IOAPIC_ADDR.store(addr, Ordering::Release);
+        IOAPIC_GSI_BASE.store(gsi_base, Ordering::Release);
+
+        // The unmapped address reads as all ones
+        let ver = Self::read(IOAPIC_VER);
+        if ver == 0xffffffff || ver == 0 {
+            IOAPIC_ADDR.store(0, Ordering::Release);
+            return false;
+        }
+
+        // Get the number of redirection entries
+        let entries = ((ver >> 16) & 0xff) + 1;
+        IOAPIC_ENTRIES.store(entries, Ordering::Release);
+
+        for pin in 0..entries {
+            Self::mask(gsi_base + pin);
+        }
+
+        true
+    }
+
+    // Is enabled
+    pub fn is_enabled() -> bool {
+        IOAPIC_ADDR.load(Ordering::Acquire) != 0
+    }
+
+    // Get the number of redirection entries
+    pub fn entries() -> u32 {
+        IOAPIC_ENTRIES.load(Ordering::Acquire)
+    }
+
+    // Set redirection entry
+    pub fn set_redirect(gsi: u32, entry: RedirectEntry) {
+        let Some(pin) = Self::gsi_to_pin(gsi) else {
+            return;
+        };
+
+        let mut low = entry.vector as u32;
+        if entry.masked {
+            low |= REDTBL_MASKED;
+        }
+        if entry.level {
+            low |= REDTBL_LEVEL;
+        }
+        if entry.active_low {
+            low |= REDTBL_ACTIVE_LOW;
+        }
+
+        // Mask the entry before changing the destination
+        Self::write(IOAPIC_REDTBL + pin * 2, REDTBL_MASKED);
+        Self::write(IOAPIC_REDTBL + pin * 2 + 1, (entry.apic_id as u32) << 24);
+        Self::write(IOAPIC_REDTBL + pin * 2, low);
+    }
+
+    // Get redirection entry
+    pub fn get_redirect(gsi: u32) -> Option<RedirectEntry> {
+        let pin = Self::gsi_to_pin(gsi)?;
+        let low = Self::read(IOAPIC_REDTBL + pin * 2);
+        let high = Self::read(IOAPIC_REDTBL + pin * 2 + 1);
+
+        Some(RedirectEntry {
+            vector: (low & 0xff) as u8,
+            apic_id: (high >> 24) as u8,
+            masked: (low & REDTBL_MASKED) != 0,
+            level: (low & REDTBL_LEVEL) != 0,
+            active_low: (low & REDTBL_ACTIVE_LOW) != 0,
+        })
+    }
+
+    // Mask gsi
+    pub fn mask(gsi: u32) {
+        let Some(pin) = Self::gsi_to_pin(gsi) else {
+            return;
+        };
+        let low = Self::read(IOAPIC_REDTBL + pin * 2);
+        Self::write(IOAPIC_REDTBL + pin * 2, low | REDTBL_MASKED);
+    }
+
+    // Unmask gsi
+    pub fn unmask(gsi: u32) {
+        let Some(pin) = Self::gsi_to_pin(gsi) else {
+            return;
+        };
+        let low = Self::read(IOAPIC_REDTBL + pin * 2);
+        Self::write(IOAPIC_REDTBL + pin * 2, low & !REDTBL_MASKED);
+    }
+
+    // Is gsi masked
+    pub fn is_masked(gsi: u32) -> bool {
+        let Some(pin) = Self::gsi_to_pin(gsi) else {
+            return true;
+        };
+        (Self::read(IOAPIC_REDTBL + pin * 2) & REDTBL_MASKED) != 0
+    }
+}
+
+// Impl village ioapic
+impl VillageIoapic {
+    // Gsi to pin, none when the gsi is not handled by the io apic
+    fn gsi_to_pin(gsi: u32) -> Option<u32> {
+        let pin = gsi.checked_sub(IOAPIC_GSI_BASE.load(Ordering::Acquire))?;
+        if Self::is_enabled() && pin < Self::entries() {
+            Some(pin)
+        } else {
+            None
+        }
+    }
+
+    // Read register
+    fn read(reg: u32) -> u32 {
+        let base = IOAPIC_ADDR.load(Ordering::Acquire);
+        unsafe {
+            ptr::write_volatile((base + IOAPIC_REGSEL) as *mut u32, reg);
+            ptr::read_volatile((base + IOAPIC_WINDOW) as *const u32)
+        }
+    }
+
+    // Write register
+    fn write(reg: u32, val: u32) {
+        let base = IOAPIC_ADDR.load(Ordering::Acquire);
+        unsafe {
+            ptr::write_volatile((base + IOAPIC_REGSEL) as *mut u32, reg);
+            ptr::write_volatile((base + IOAPIC_WINDOW) as *mut u32, val);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//###########################################################################
+// vk_irqchip.rs
+// The specific implementation of functions related to irq chip
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_acpi::{IsaOverride, VillageAcpi, ISA_IRQ_NUM};
+use super::vk_ioapic::{RedirectEntry, VillageIoapic};
+use super::vk_lapic::VillageLapic;
+use crate::vendor::ia32legacy::core::i686::*;
+use crate::debug_error;
+use crate::debug_info;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Constant members
+const ISA_IRQ_BASE: isize = 32;
+const ISA_CASCADE_LINE: usize = 2;
+const ISA_TIMER_GSI: u32 = 2;
+pub const DEFAULT_PRIORITY: u8 = 0;
+
+// The apic mode flag, it is read by the irq handler
+static APIC_MODE: AtomicBool = AtomicBool::new(false);
+
+// Struct village irq chip
+pub struct VillageIrqChip {
+    priorities: [u8; ISA_IRQ_NUM],
+    overrides: [IsaOverride; ISA_IRQ_NUM],
+}
+
+// Impl village irq chip
+impl VillageIrqChip {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            priorities: [DEFAULT_PRIORITY; ISA_IRQ_NUM],
+            overrides: [IsaOverride::new(0); ISA_IRQ_NUM],
+        }
+    }
+}
+
+// Impl village irq chip
+impl VillageIrqChip {
+    // Setup
+    pub fn setup(&mut self) {
+        // The pic is remapped even if it is not used, so that the spurious
+        // interrupts of pic do not conflict with the exceptions
+        let (a1, a2) = self.remap_pic();
+
+        // Get the io apic and the isa overrides from madt, the fixed io apic
+        // base and the pc compatible isa wiring are used without madt
+        let (ioapic_addr, gsi_base) = match VillageAcpi::madt() {
+            Some(madt) => {
+                self.overrides = madt.overrides;
+                (madt.ioapic_addr, madt.ioapic_gsi_base)
+            }
+            None => {
+                self.overrides = core::array::from_fn(|line| IsaOverride::new(line as u32));
+                self.overrides[0] = IsaOverride::new(ISA_TIMER_GSI);
+                (IOAPIC_BASE, 0)
+            }
+        };
+
+        // Use the apic when both local apic and io apic are present
+        if VillageLapic::setup() && VillageIoapic::setup(ioapic_addr, gsi_base) {
+            // Mask all lines of pic
+            port_byte_out(PIC1_DATA, 0xff);
+            port_byte_out(PIC2_DATA, 0xff);
+
+            // Route the isa irqs to the bootstrap processor, keep the masks of pic
+            let masks = (a2 as u16) << 8 | a1 as u16;
+            for line in 0..ISA_IRQ_NUM {
+                if line == ISA_CASCADE_LINE {
+                    continue;
+                }
+
+                let isa = self.overrides[line];
+                let mut entry = RedirectEntry::new(
+                    (ISA_IRQ_BASE + line as isize) as u8,
+                    VillageLapic::id() as u8,
+                );
+                entry.masked = (masks & (1 << line)) != 0;
+                entry.level = isa.level;
+                entry.active_low = isa.active_low;
+                VillageIoapic::set_redirect(isa.gsi, entry);
+            }
+
+            APIC_MODE.store(true, Ordering::Release);
+
+            // Output debug info
+            debug_info!("Irq chip uses apic, {} io apic pins", VillageIoapic::entries());
+        } else {
+            APIC_MODE.store(false, Ordering::Release);
+
+            // Output debug info
+            debug_info!("Irq chip uses pic");
+        }
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        if Self::is_apic() {
+            for line in 0..ISA_IRQ_NUM {
+                VillageIoapic::mask(self.line_to_gsi(line));
+            }
+            VillageLapic::stop_timer();
+        }
+    }
+
+    // Is apic mode
+    pub fn is_apic() -> bool {
+        APIC_MODE.load(Ordering::Acquire)
+    }
+
+    // End of interrupt
+    pub fn eoi(irq: isize) {
+        if Self::irq_to_line(irq).is_none() {
+            return;
+        }
+
+        if Self::is_apic() {
+            VillageLapic::eoi();
+        } else {
+            if irq >= ISA_IRQ_BASE + 8 {
+                port_byte_out(PIC2_CMD, PIC_EOI); //slave
+            }
+            port_byte_out(PIC1_CMD, PIC_EOI); //master
+        }
+    }
+
+    // Irq to isa line
+    fn irq_to_line(irq: isize) -> Option<usize> {
+        if irq >= ISA_IRQ_BASE && irq < ISA_IRQ_BASE + ISA_IRQ_NUM as isize {
+            Some((irq - ISA_IRQ_BASE) as usize)
+        } else {
+            None
+        }
+    }
+
+    // Isa line to gsi, the madt overrides the identity mapping
+    fn line_to_gsi(&self, line: usize) -> u32 {
+        self.overrides[line].gsi
+    }
+}
+
+// Impl village irq chip
+impl VillageIrqChip {
+    // Mask irq
+    pub fn mask(&mut self, irq: isize) {
+        if let Some(line) = Self::irq_to_line(irq) {
+            if Self::is_lapic_timer(irq) {
+                VillageLapic::mask_timer(true);
+            } else if Self::is_apic() {
+                VillageIoapic::mask(self.line_to_gsi(line));
+            } else {
+                let (port, bit) = Self::pic_port(line);
+                port_byte_out(port, port_byte_in(port) | bit);
+            }
+        }
+    }
+
+    // Unmask irq
+    pub fn unmask(&mut self, irq: isize) {
+        if let Some(line) = Self::irq_to_line(irq) {
+            if Self::is_lapic_timer(irq) {
+                VillageLapic::mask_timer(false);
+            } else if Self::is_apic() {
+                VillageIoapic::unmask(self.line_to_gsi(line));
+            } else {
+                let (port, bit) = Self::pic_port(line);
+                port_byte_out(port, port_byte_in(port) & !bit);
+            }
+        }
+    }
+
+    // Is irq masked
+    pub fn is_masked(&mut self, irq: isize) -> bool {
+        if let Some(line) = Self::irq_to_line(irq) {
+            if Self::is_lapic_timer(irq) {
+                VillageLapic::is_timer_masked()
+            } else if Self::is_apic() {
+                VillageIoapic::is_masked(self.line_to_gsi(line))
+            } else {
+                let (port, bit) = Self::pic_port(line);
+                (port_byte_in(port) & bit) != 0
+            }
+        } else {
+            false
+        }
+    }
+
+    // Is the systick generated by the local apic timer
+    fn is_lapic_timer(irq: isize) -> bool {
+        irq == SYSTICK_IRQN && Self::is_apic() && VillageLapic::is_timer_running()
+    }
+
+    // Set irq priority, zero is the highest priority
+    pub fn set_priority(&mut self, irq: isize, priority: u8) -> bool {
+        // The apic orders the pending irqs by the fixed vectors,
+        // only the pic can rotate the priority of lines
+        if Self::is_apic() {
+            debug_error!("irq {} set priority failed, not supported in apic mode", irq);
+            return false;
+        }
+
+        if let Some(line) = Self::irq_to_line(irq) {
+            self.priorities[line] = priority;
+            self.apply_pic_priority();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Get irq priority
+    pub fn get_priority(&mut self, irq: isize) -> u8 {
+        match Self::irq_to_line(irq) {
+            Some(line) => self.priorities[line],
+            None => DEFAULT_PRIORITY,
+        }
+    }
+}
+
+// Impl village irq chip
+impl VillageIrqChip {
+    // Get the data port and mask bit of pic line
+    fn pic_port(line: usize) -> (u16, u8) {
+        if line < 8 {
+            (PIC1_DATA, 1 << line)
+        } else {
+            (PIC2_DATA, 1 << (line - 8))
+        }
+    }
+
+    // Rotate the pic, the line with the highest priority is at the top
+    fn apply_pic_priority(&mut self) {
+        for (cmd, lines) in [(PIC1_CMD, 0..8), (PIC2_CMD, 8..16)] {
+            let base = lines.start;
+            let top = lines
+                .min_by_key(|line| (self.priorities[*line], *line))
+                .unwrap_or(base)
+                - base;
+            let bottom = (top + 7) % 8;
+            port_byte_out(cmd, OCW2_SET_PRIORITY | bottom as u8);
+        }
+    }
+
+    // Get pic irq register
+    pub fn get_pic_irq_reg(&self, ocw3: u8) -> u16 {
+        port_byte_out(PIC1_CMD, ocw3);
+        port_byte_out(PIC2_CMD, ocw3);
+        (port_byte_in(PIC2_CMD) as u16) << 8 | port_byte_in(PIC1_CMD) as u16
+    }
+
+    // Get pic irr
+    pub fn get_pic_irr(&self) -> u16 {
+        self.get_pic_irq_reg(PIC_READ_IRR)
+    }
+
+    // Get pic isr
+    pub fn get_pic_isr(&self) -> u16 {
+        self.get_pic_irq_reg(PIC_READ_ISR)
+    }
+
+    // Remap pic, returns the saved masks
+    fn remap_pic(&self) -> (u8, u8) {
+        // Save masks
+        let a1 = port_byte_in(PIC1_DATA);
+        let a2 = port_byte_in(PIC2_DATA);
+
+        // Starts the initialization sequence (in cascade mode)
+        port_byte_out(PIC1_CMD, ICW1_INIT | ICW1_ICW4);
+        port_byte_out(PIC2_CMD, ICW1_INIT | ICW1_ICW4);
+
+        // ICW2: Master PIC vector offset
+        port_byte_out(PIC1_DATA, 0x20);
+        // ICW2: Slave PIC vector offset
+        port_byte_out(PIC2_DATA, 0x28);
+
+        // ICW3: tell Master PIC that there is a slave PIC at IRQ2 (0000 0100)
+        port_byte_out(PIC1_DATA, 0x04);
+        // ICW3: tell Slave PIC its cascade identity (0000 0010)
+        port_byte_out(PIC2_DATA, 0x02);
+
+        // ICW4: have the PICs use 8086 mode (and not 8080 mode)
+        port_byte_out(PIC1_DATA, ICW4_8086);
+        port_byte_out(PIC2_DATA, ICW4_8086);
+
+        // Restore saved masks
+        port_byte_out(PIC1_DATA, a1);
+        port_byte_out(PIC2_DATA, a2);
+
+        (a1, a2)
+    }
+}
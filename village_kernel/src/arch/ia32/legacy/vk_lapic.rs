@@ -0,0 +1,278 @@
+//###########################################################################
+// vk_lapic.rs
+// The specific implementation of functions related to local apic
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::vendor::ia32legacy::core::i686::*;
+use core::arch::{asm, naked_asm};
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+// Local apic registers
+const LAPIC_ID: u32 = 0x020;
+const LAPIC_TPR: u32 = 0x080;
+const LAPIC_EOI: u32 = 0x0b0;
+const LAPIC_SVR: u32 = 0x0f0;
+const LAPIC_ISR: u32 = 0x100;
+const LAPIC_ICR_LOW: u32 = 0x300;
+const LAPIC_ICR_HIGH: u32 = 0x310;
+const LAPIC_LVT_TIMER: u32 = 0x320;
+const LAPIC_TIMER_INIT: u32 = 0x380;
+const LAPIC_TIMER_CURR: u32 = 0x390;
+const LAPIC_TIMER_DIV: u32 = 0x3e0;
+
+// Local apic defines
+const IA32_APIC_BASE_MSR: u32 = 0x1b;
+const APIC_BASE_ENABLE: u32 = 1 << 11;
+const APIC_SVR_ENABLE: u32 = 1 << 8;
+pub const APIC_SPURIOUS_VECTOR: u32 = 0xff;
+const CPUID_APIC: u32 = 1 << 9;
+
+// Local vector table defines
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIV_16: u32 = 0x3;
+const CALIBRATE_MS: u32 = 10;
+
+// Interrupt command defines
+const ICR_FIXED: u32 = 0x000;
+const ICR_INIT: u32 = 0x500;
+const ICR_STARTUP: u32 = 0x600;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+const ICR_ASSERT: u32 = 1 << 14;
+const ICR_ALL_EXCLUDING_SELF: u32 = 3 << 18;
+
+// The local apic base address, zero when the local apic is not used
+static LAPIC_BASE: AtomicU32 = AtomicU32::new(0);
+
+// Struct village lapic
+pub struct VillageLapic;
+
+// Impl village lapic
+impl VillageLapic {
+    // Setup, called by the bootstrap processor
+    pub fn setup() -> bool {
+        if !Self::is_supported() {
+            return false;
+        }
+
+        // Get the base address and enable the local apic globally
+        let msr = Self::read_msr(IA32_APIC_BASE_MSR) | APIC_BASE_ENABLE;
+        Self::write_msr(IA32_APIC_BASE_MSR, msr);
+        LAPIC_BASE.store(msr & 0xfffff000, Ordering::Release);
+
+        // Enable the local apic of bootstrap processor
+        Self::enable();
+
+        true
+    }
+
+    // Enable the local apic of current processor
+    pub fn enable() {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        Self::write(LAPIC_TPR, 0);
+        Self::write(LAPIC_SVR, APIC_SVR_ENABLE | APIC_SPURIOUS_VECTOR);
+    }
+
+    // Is supported
+    pub fn is_supported() -> bool {
+        let edx: u32;
+        unsafe {
+            asm!(
+                "pushl %ebx",
+                "cpuid",
+                "popl %ebx",
+                inout("eax") 1 => _,
+                out("ecx") _,
+                out("edx") edx,
+                options(att_syntax)
+            );
+        }
+        (edx & CPUID_APIC) != 0
+    }
+
+    // Is enabled
+    pub fn is_enabled() -> bool {
+        LAPIC_BASE.load(Ordering::Acquire) != 0
+    }
+
+    // Get the local apic id of current processor
+    pub fn id() -> u32 {
+        if !Self::is_enabled() {
+            return 0;
+        }
+        Self::read(LAPIC_ID) >> 24
+    }
+
+    // End of interrupt
+    pub fn eoi() {
+        if Self::is_enabled() {
+            Self::write(LAPIC_EOI, 0);
+        }
+    }
+
+    // Acknowledge the vector when it is delivered by the local apic
+    pub fn ack(vector: u32) {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        let isr = Self::read(LAPIC_ISR + (vector / 32) * 0x10);
+        if (isr & (1 << (vector % 32))) != 0 {
+            Self::write(LAPIC_EOI, 0);
+        }
+    }
+
+    // Spurious handler, the spurious interrupt does not need an eoi
+    #[unsafe(naked)]
+    pub unsafe extern "C" fn spurious_handler() {
+        naked_asm!("iretl", options(att_syntax));
+    }
+
+    // Send the fixed ipi to the processor
+    pub fn send_ipi(apic_id: u32, vector: u32) {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        Self::write(LAPIC_ICR_HIGH, apic_id << 24);
+        Self::write(LAPIC_ICR_LOW, ICR_FIXED | ICR_ASSERT | vector);
+        Self::wait_for_delivery();
+    }
+
+    // Send the init ipi to all application processors
+    pub fn send_init_all() {
+        Self::write(LAPIC_ICR_HIGH, 0);
+        Self::write(LAPIC_ICR_LOW, ICR_ALL_EXCLUDING_SELF | ICR_ASSERT | ICR_INIT);
+        Self::wait_for_delivery();
+    }
+
+    // Send the startup ipi to all application processors
+    pub fn send_sipi_all(page: u32) {
+        Self::write(LAPIC_ICR_HIGH, 0);
+        Self::write(LAPIC_ICR_LOW, ICR_ALL_EXCLUDING_SELF | ICR_ASSERT | ICR_STARTUP | (page & 0xff));
+        Self::wait_for_delivery();
+    }
+
+    // Calibrate the timer against the pit, returns the timer ticks per ms
+    pub fn calibrate_timer() -> u32 {
+        if !Self::is_enabled() {
+            return 0;
+        }
+
+        // Set the pit channel 2 gate low and disable the speaker
+        let gate = port_byte_in(TIMER_GATE) & !0x03;
+        port_byte_out(TIMER_GATE, gate);
+
+        // Program the pit channel 2 in one shot mode
+        let count = TIMER_FREQ * CALIBRATE_MS / 1000;
+        port_byte_out(TIMER_CMD, 0xb0);
+        port_byte_out(TIMER_CH2, (count & 0xff) as u8);
+        port_byte_out(TIMER_CH2, ((count >> 8) & 0xff) as u8);
+
+        // Start counting both timers
+        Self::write(LAPIC_TIMER_DIV, TIMER_DIV_16);
+        Self::write(LAPIC_LVT_TIMER, LVT_MASKED);
+        port_byte_out(TIMER_GATE, gate | 0x01);
+        Self::write(LAPIC_TIMER_INIT, 0xffffffff);
+
+        // Wait for the pit output goes high
+        while (port_byte_in(TIMER_GATE) & 0x20) == 0 {
+            core::hint::spin_loop();
+        }
+
+        // Stop the timer
+        let elapsed = 0xffffffff - Self::read(LAPIC_TIMER_CURR);
+        Self::write(LAPIC_TIMER_INIT, 0);
+        port_byte_out(TIMER_GATE, gate);
+
+        elapsed / CALIBRATE_MS
+    }
+
+    // Start the periodic timer of current processor
+    pub fn start_timer(vector: u32, ticks: u32) {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        Self::write(LAPIC_TIMER_DIV, TIMER_DIV_16);
+        Self::write(LAPIC_LVT_TIMER, LVT_TIMER_PERIODIC | vector);
+        Self::write(LAPIC_TIMER_INIT, ticks);
+    }
+
+    // Stop the timer of current processor
+    pub fn stop_timer() {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        Self::write(LAPIC_LVT_TIMER, LVT_MASKED);
+        Self::write(LAPIC_TIMER_INIT, 0);
+    }
+
+    // Mask the timer of current processor
+    pub fn mask_timer(masked: bool) {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        let lvt = Self::read(LAPIC_LVT_TIMER);
+        if masked {
+            Self::write(LAPIC_LVT_TIMER, lvt | LVT_MASKED);
+        } else {
+            Self::write(LAPIC_LVT_TIMER, lvt & !LVT_MASKED);
+        }
+    }
+
+    // Is the timer of current processor running
+    pub fn is_timer_running() -> bool {
+        Self::is_enabled() && Self::read(LAPIC_TIMER_INIT) != 0
+    }
+
+    // Is the timer of current processor masked
+    pub fn is_timer_masked() -> bool {
+        !Self::is_enabled() || (Self::read(LAPIC_LVT_TIMER) & LVT_MASKED) != 0
+    }
+
+    // Wait for the ipi delivered
+    fn wait_for_delivery() {
+        while (Self::read(LAPIC_ICR_LOW) & ICR_DELIVERY_PENDING) != 0 {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// Impl village lapic
+impl VillageLapic {
+    // Read register
+    pub fn read(reg: u32) -> u32 {
+        let base = LAPIC_BASE.load(Ordering::Acquire);
+        unsafe { ptr::read_volatile((base + reg) as *const u32) }
+    }
+
+    // Write register
+    pub fn write(reg: u32, val: u32) {
+        let base = LAPIC_BASE.load(Ordering::Acquire);
+        unsafe { ptr::write_volatile((base + reg) as *mut u32, val) };
+    }
+
+    // Read msr
+    fn read_msr(msr: u32) -> u32 {
+        let low: u32;
+        unsafe {
+            asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") _, options(att_syntax));
+        }
+        low
+    }
+
+    // Write msr
+    fn write_msr(msr: u32, low: u32) {
+        unsafe {
+            asm!("wrmsr", in("ecx") msr, in("eax") low, in("edx") 0, options(att_syntax));
+        }
+    }
+}
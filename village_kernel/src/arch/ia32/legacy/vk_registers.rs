@@ -5,6 +5,9 @@
 // $Copyright: Copyright (C) village
 //###########################################################################
 
+// The fxsave area size with the alignment padding
+pub const FPU_STATE_SIZE: u32 = 512 + 16;
+
 #[repr(C)]
 pub struct Registers {
     // Pushed by pusha.
@@ -0,0 +1,102 @@
+//###########################################################################
+// vk_rtc.rs
+// The specific implementation of functions related to cmos rtc
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::traits::vk_filesys::FileTime;
+use crate::vendor::ia32legacy::core::i686::{port_byte_in, port_byte_out};
+
+// Cmos ports
+const CMOS_ADDR_PORT: u16 = 0x70;
+const CMOS_DATA_PORT: u16 = 0x71;
+
+// Cmos registers
+const RTC_SECOND: u8 = 0x00;
+const RTC_MINUTE: u8 = 0x02;
+const RTC_HOUR: u8 = 0x04;
+const RTC_DAY: u8 = 0x07;
+const RTC_MONTH: u8 = 0x08;
+const RTC_YEAR: u8 = 0x09;
+const RTC_STATUS_A: u8 = 0x0a;
+const RTC_STATUS_B: u8 = 0x0b;
+
+// Rtc defines
+const RTC_UPDATING: u8 = 1 << 7;
+const RTC_24_HOUR: u8 = 1 << 1;
+const RTC_BINARY: u8 = 1 << 2;
+const RTC_HOUR_PM: u8 = 1 << 7;
+const RTC_NMI_DISABLE: u8 = 1 << 7;
+const RTC_CENTURY: u16 = 2000;
+const RTC_READ_RETRY: usize = 8;
+
+// Struct village rtc
+pub struct VillageRtc;
+
+// Impl village rtc
+impl VillageRtc {
+    // Read register
+    fn read(reg: u8) -> u8 {
+        port_byte_out(CMOS_ADDR_PORT, RTC_NMI_DISABLE | reg);
+        port_byte_in(CMOS_DATA_PORT)
+    }
+
+    // Read the raw registers when the rtc is not updating
+    fn read_raw() -> [u8; 6] {
+        while Self::read(RTC_STATUS_A) & RTC_UPDATING != 0 {}
+
+        [
+            Self::read(RTC_SECOND),
+            Self::read(RTC_MINUTE),
+            Self::read(RTC_HOUR),
+            Self::read(RTC_DAY),
+            Self::read(RTC_MONTH),
+            Self::read(RTC_YEAR),
+        ]
+    }
+
+    // Bcd to binary
+    fn bcd_to_bin(value: u8) -> u8 {
+        (value & 0x0f) + (value >> 4) * 10
+    }
+
+    // Get date time, the registers are read until two reads are same
+    pub fn get_date_time() -> FileTime {
+        let mut raw = Self::read_raw();
+        for _ in 0..RTC_READ_RETRY {
+            let again = Self::read_raw();
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+
+        let status = Self::read(RTC_STATUS_B);
+        let pm = raw[2] & RTC_HOUR_PM != 0;
+        raw[2] &= !RTC_HOUR_PM;
+
+        if status & RTC_BINARY == 0 {
+            for value in raw.iter_mut() {
+                *value = Self::bcd_to_bin(*value);
+            }
+        }
+
+        // The 12 hour clock is converted to 24 hour clock
+        let mut hour = raw[2];
+        if status & RTC_24_HOUR == 0 {
+            hour %= 12;
+            if pm {
+                hour += 12;
+            }
+        }
+
+        FileTime {
+            year: RTC_CENTURY + raw[5] as u16,
+            month: raw[4],
+            day: raw[3],
+            hour,
+            minute: raw[1],
+            second: raw[0],
+        }
+    }
+}
@@ -4,23 +4,32 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_fpu::VillageFpu;
+use super::vk_segment::VillageSegment;
 use crate::traits::vk_callback::Callback;
 use crate::traits::vk_kernel::Scheduler;
 use crate::vendor::ia32legacy::core::i686::{PENDSV_IRQN, SYSTICK_IRQN};
 use crate::village::kernel;
 use crate::debug_info;
 use core::arch::{asm, naked_asm};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 // Struct village scheduler
 pub struct VillageScheduler {
-    is_ready: bool,
+    segment: VillageSegment,
+    fpu: VillageFpu,
+    is_ready: AtomicBool,
 }
 
 // Impl village scheduler
 impl VillageScheduler {
     // New
     pub const fn new() -> Self {
-        Self { is_ready: false }
+        Self {
+            segment: VillageSegment::new(),
+            fpu: VillageFpu::new(),
+            is_ready: AtomicBool::new(false),
+        }
     }
 }
 
@@ -29,15 +38,21 @@ impl VillageScheduler {
     // Setup
     pub fn setup(&mut self) {
         // Clear start schedule flag
-        self.is_ready = false;
+        self.is_ready.store(false, Ordering::Release);
+
+        // Setup segment
+        self.segment.setup();
+
+        // Setup fpu
+        self.fpu.setup();
 
         // Set the PendSV interrupt handler
-        let pendsv_cb = Callback::new(Self::pend_sv_handler as u32);
+        let pendsv_cb = Callback::new(Self::pend_sv_handler as u32).with_instance(self);
         kernel().interrupt().set_isr_cb(PENDSV_IRQN, pendsv_cb);
 
         // Add the systick interrupt handler
-        let sched_cb = Callback::new(Self::sched as u32).with_instance(self);
-        kernel().interrupt().add_isr_cb(SYSTICK_IRQN, sched_cb);
+        let tick_cb = Callback::new(Self::tick as u32).with_instance(self);
+        kernel().interrupt().add_isr_cb(SYSTICK_IRQN, tick_cb);
 
         // Output debug info
         debug_info!("Scheduler setup completed!");
@@ -46,11 +61,32 @@ impl VillageScheduler {
     // Exit
     pub fn exit(&mut self) {
         // Delete the systick interrupt handler
-        let sched_cb = Callback::new(Self::sched as u32).with_instance(self);
-        kernel().interrupt().del_isr_cb(SYSTICK_IRQN, sched_cb);
+        let tick_cb = Callback::new(Self::tick as u32).with_instance(self);
+        kernel().interrupt().del_isr_cb(SYSTICK_IRQN, tick_cb);
 
         // Clear the pend sv interrupt handler
         kernel().interrupt().clear_isr_cb(PENDSV_IRQN);
+
+        // Exit fpu
+        self.fpu.exit();
+    }
+
+    // Systick, the systick only arrives at the bootstrap processor
+    fn tick(&mut self) {
+        if !self.is_ready.load(Ordering::Acquire) {
+            return;
+        }
+
+        // Reschedule the application processors
+        let cpu = kernel().system().get_cpu_id();
+        for target in 0..kernel().system().get_cpu_count() {
+            if target != cpu {
+                kernel().system().send_ipi(target, PENDSV_IRQN);
+            }
+        }
+
+        // Reschedule current processor
+        self.sched();
     }
 }
 
@@ -65,13 +101,30 @@ impl Scheduler for VillageScheduler {
         kernel().system().enable_irq();
 
         // Set start schedule flag
-        self.is_ready = true;
+        self.is_ready.store(true, Ordering::Release);
+    }
+
+    // Start scheduler on application processor
+    fn start_cpu(&mut self) {
+        // Wait for the bootstrap processor
+        while !self.is_ready.load(Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+
+        // Setup the fpu of current processor
+        self.fpu.setup_cpu();
+
+        // Set interrupt flag
+        kernel().system().enable_irq();
+
+        // Switch to the idle task of current processor
+        self.sched();
     }
 
     // Rescheduler task
     fn sched(&mut self) {
         // Not ready to schedule
-        if !self.is_ready {
+        if !self.is_ready.load(Ordering::Acquire) {
             return;
         }
 
@@ -96,6 +149,21 @@ impl VillageScheduler {
         kernel().thread().select_next_task();
     }
 
+    // Switch task tls
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn switch_task_tls(scheduler: *mut VillageScheduler) {
+        let cpu = kernel().system().get_cpu_id();
+        let tls = kernel().thread().get_task_tls();
+        unsafe { (*scheduler).segment.set_tls_base(cpu, tls) };
+    }
+
+    // Switch task fpu
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn switch_task_fpu(scheduler: *mut VillageScheduler) {
+        let area = kernel().thread().get_task_fpu();
+        unsafe { (*scheduler).fpu.switch(area) };
+    }
+
     // Get task psp
     #[unsafe(no_mangle)]
     unsafe extern "C" fn get_task_psp() -> u32 {
@@ -115,6 +183,12 @@ impl VillageScheduler {
             "call save_task_psp",
             "addl $4, %esp",
             "call select_next_task",
+            "pushl 20(%esp)",
+            "call switch_task_tls",
+            "addl $4, %esp",
+            "pushl 20(%esp)",
+            "call switch_task_fpu",
+            "addl $4, %esp",
             "call get_task_psp",
             "movl %eax, %esp",
             "popl %edi",
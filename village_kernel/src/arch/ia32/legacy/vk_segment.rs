@@ -0,0 +1,132 @@
+//###########################################################################
+// vk_segment.rs
+// The specific implementation of functions related to segment
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_smp::MAX_CPUS;
+use core::arch::asm;
+
+// Constant members, each cpu has its own tls segment
+const TLS_GDT_INDEX: usize = 3;
+const GDT_ENTRIES: usize = TLS_GDT_INDEX + MAX_CPUS;
+pub const KERNEL_CODE_SEGMENT: u16 = 0x08;
+pub const KERNEL_DATA_SEGMENT: u16 = 0x10;
+pub const TLS_SEGMENT: u16 = 0x18;
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Default)]
+struct GdtEntry {
+    limit_low: u16,
+    base_low: u16,
+    base_mid: u8,
+    access: u8,
+    granularity: u8,
+    base_high: u8,
+}
+
+// Impl gdt entry
+impl GdtEntry {
+    // New
+    const fn new(base: u32, access: u8) -> Self {
+        Self {
+            limit_low: 0xffff,
+            base_low: (base & 0xffff) as u16,
+            base_mid: ((base >> 16) & 0xff) as u8,
+            access,
+            granularity: 0xcf,
+            base_high: ((base >> 24) & 0xff) as u8,
+        }
+    }
+
+    // Null
+    const fn null() -> Self {
+        Self {
+            limit_low: 0,
+            base_low: 0,
+            base_mid: 0,
+            access: 0,
+            granularity: 0,
+            base_high: 0,
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Default)]
+struct GdtRegister {
+    limit: u16,
+    base: u32,
+}
+
+// Struct village segment
+pub struct VillageSegment {
+    gdt: [GdtEntry; GDT_ENTRIES],
+    gdt_reg: GdtRegister,
+}
+
+// Impl village segment
+impl VillageSegment {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            gdt: Self::init_gdt(),
+            gdt_reg: GdtRegister { limit: 0, base: 0 },
+        }
+    }
+}
+
+// Impl village segment
+impl VillageSegment {
+    // Init gdt
+    const fn init_gdt() -> [GdtEntry; GDT_ENTRIES] {
+        let mut gdt = [GdtEntry::new(0, 0x92); GDT_ENTRIES];
+        gdt[0] = GdtEntry::null();
+        gdt[1] = GdtEntry::new(0, 0x9a);
+        gdt
+    }
+
+    // Setup
+    pub fn setup(&mut self) {
+        self.gdt_reg.base = self.gdt.as_ptr() as u32;
+        self.gdt_reg.limit = (GDT_ENTRIES * core::mem::size_of::<GdtEntry>()) as u16 - 1;
+
+        unsafe {
+            // Load gdt and reload the code segment
+            asm!(
+                "lgdt ({0})",
+                "ljmp ${1}, $2f",
+                "2:",
+                in(reg) &self.gdt_reg as *const _ as u32,
+                const KERNEL_CODE_SEGMENT,
+                options(att_syntax)
+            );
+
+            // Reload the data segments
+            asm!(
+                "movw {0:x}, %ds",
+                "movw {0:x}, %es",
+                "movw {0:x}, %fs",
+                "movw {0:x}, %ss",
+                in(reg) KERNEL_DATA_SEGMENT,
+                options(att_syntax)
+            );
+        }
+
+        // Load the tls segment
+        self.set_tls_base(0, 0);
+    }
+
+    // Set the tls segment base of the cpu and reload gs
+    pub fn set_tls_base(&mut self, cpu: usize, base: u32) {
+        self.gdt[TLS_GDT_INDEX + cpu] = GdtEntry::new(base, 0x92);
+
+        unsafe {
+            asm!(
+                "movw {0:x}, %gs",
+                in(reg) TLS_SEGMENT + (cpu as u16) * 8,
+                options(att_syntax)
+            );
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//###########################################################################
+// vk_smp.rs
+// The specific implementation of functions related to smp
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_lapic::VillageLapic;
+use crate::village::kernel;
+use crate::debug_info;
+use core::arch::{asm, global_asm};
+use core::ptr;
+
+// Constant members
+pub const MAX_CPUS: usize = 8;
+const AP_TRAMPOLINE_BASE: u32 = 0x7000;
+const AP_PARAMS_BASE: u32 = 0x7f00;
+const AP_STACK_SIZE: u32 = 8192;
+const AP_INVALID_ID: u32 = u32::MAX;
+
+// Struct ap params, shared with the ap trampoline
+#[repr(C, packed)]
+struct ApParams {
+    gdt_limit: u16,
+    gdt_base: u32,
+    idt_limit: u16,
+    idt_base: u32,
+    count: u32,
+    stack_base: u32,
+    stack_size: u32,
+    entry: u32,
+    instance: u32,
+}
+
+// The ap trampoline, it is copied to AP_TRAMPOLINE_BASE and started by SIPI
+global_asm!(
+    ".pushsection .text.ap_trampoline, \"ax\"",
+    ".global ap_trampoline_start",
+    ".global ap_trampoline_ended",
+    ".code16",
+    "ap_trampoline_start:",
+    "cli",
+    "cld",
+    "xorw %ax, %ax",
+    "movw %ax, %ds",
+    "lgdtl {base} + (ap_trampoline_gdtr - ap_trampoline_start)",
+    "movl %cr0, %eax",
+    "orl $1, %eax",
+    "movl %eax, %cr0",
+    "ljmpl $0x08, ${base} + (ap_trampoline_32 - ap_trampoline_start)",
+    ".code32",
+    "ap_trampoline_32:",
+    "movw $0x10, %ax",
+    "movw %ax, %ds",
+    "movw %ax, %es",
+    "movw %ax, %ss",
+    "movw %ax, %fs",
+    "movw %ax, %gs",
+    // Load the kernel gdt and idt
+    "lgdt {params}",
+    "lidt {params} + 6",
+    "ljmp $0x08, ${base} + (ap_trampoline_reload - ap_trampoline_start)",
+    "ap_trampoline_reload:",
+    // Get the processor index
+    "movl $1, %eax",
+    "lock xaddl %eax, {params} + 12",
+    "cmpl ${max}, %eax",
+    "jae 2f",
+    // Set the processor stack
+    "movl %eax, %ecx",
+    "incl %eax",
+    "imull {params} + 20, %eax",
+    "addl {params} + 16, %eax",
+    "movl %eax, %esp",
+    // Call ap_entry(instance, index)
+    "pushl %ecx",
+    "pushl {params} + 28",
+    "call *{params} + 24",
+    "2:",
+    "hlt",
+    "jmp 2b",
+    ".balign 8",
+    "ap_trampoline_gdt:",
+    ".quad 0x0000000000000000",
+    ".quad 0x00cf9a000000ffff",
+    ".quad 0x00cf92000000ffff",
+    "ap_trampoline_gdtr:",
+    ".word 23",
+    ".long {base} + (ap_trampoline_gdt - ap_trampoline_start)",
+    "ap_trampoline_ended:",
+    ".popsection",
+    base = const AP_TRAMPOLINE_BASE,
+    params = const AP_PARAMS_BASE,
+    max = const MAX_CPUS - 1,
+    options(att_syntax)
+);
+
+// Struct village smp
+pub struct VillageSmp {
+    apic_ids: [u32; MAX_CPUS],
+    cpu_count: usize,
+    ap_stacks: u32,
+}
+
+// Impl village smp
+impl VillageSmp {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            apic_ids: [AP_INVALID_ID; MAX_CPUS],
+            cpu_count: 1,
+            ap_stacks: 0,
+        }
+    }
+}
+
+// Impl village smp
+impl VillageSmp {
+    // Setup
+    pub fn setup(&mut self) {
+        self.apic_ids = [AP_INVALID_ID; MAX_CPUS];
+        self.apic_ids[0] = 0;
+        self.cpu_count = 1;
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        self.cpu_count = 1;
+    }
+
+    // Start the application processors
+    pub fn start(&mut self) {
+        // Use the local apic of bootstrap processor
+        if !VillageLapic::setup() {
+            debug_info!("Local apic not found, running on single cpu");
+            return;
+        }
+        self.apic_ids[0] = VillageLapic::id();
+
+        // Copy the trampoline into low memory
+        unsafe extern "C" {
+            unsafe static ap_trampoline_start: u8;
+            unsafe static ap_trampoline_ended: u8;
+        }
+        let start = &raw const ap_trampoline_start as u32;
+        let ended = &raw const ap_trampoline_ended as u32;
+        unsafe {
+            ptr::copy_nonoverlapping(
+                start as *const u8,
+                AP_TRAMPOLINE_BASE as *mut u8,
+                (ended - start) as usize,
+            );
+        }
+
+        // Allocate the processor stacks
+        let stacks_size = AP_STACK_SIZE * (MAX_CPUS - 1) as u32;
+        self.ap_stacks = kernel().memory().alloc(stacks_size);
+
+        // Fill the params
+        let params = ApParams {
+            gdt_limit: 0,
+            gdt_base: 0,
+            idt_limit: 0,
+            idt_base: 0,
+            count: 0,
+            stack_base: self.ap_stacks,
+            stack_size: AP_STACK_SIZE,
+            entry: Self::ap_entry as u32,
+            instance: self as *mut _ as u32,
+        };
+        unsafe {
+            ptr::write_unaligned(AP_PARAMS_BASE as *mut ApParams, params);
+            asm!("sgdt ({0})", in(reg) AP_PARAMS_BASE, options(att_syntax));
+            asm!("sidt ({0})", in(reg) AP_PARAMS_BASE + 6, options(att_syntax));
+        }
+
+        // The delay needs the systick
+        kernel().system().enable_irq();
+
+        // INIT-SIPI-SIPI sequence
+        VillageLapic::send_init_all();
+        kernel().system().delay_ms(10);
+        VillageLapic::send_sipi_all(AP_TRAMPOLINE_BASE >> 12);
+        kernel().system().delay_ms(1);
+        VillageLapic::send_sipi_all(AP_TRAMPOLINE_BASE >> 12);
+        kernel().system().delay_ms(100);
+
+        kernel().system().disable_irq();
+
+        // Get the number of started application processors
+        let count = unsafe { ptr::read_volatile((AP_PARAMS_BASE + 12) as *const u32) } as usize;
+        self.cpu_count = 1 + count.min(MAX_CPUS - 1);
+
+        // Output debug info
+        debug_info!("Smp started, {} cpus online", self.cpu_count);
+    }
+
+    // Application processor entry
+    unsafe extern "C" fn ap_entry(smp: *mut VillageSmp, index: u32) {
+        // Enable the local apic of current processor
+        VillageLapic::enable();
+
+        // Record the local apic id
+        let cpu = index as usize + 1;
+        unsafe { (*smp).apic_ids[cpu] = VillageLapic::id() };
+
+        // Start scheduling on current processor
+        kernel().scheduler().start_cpu();
+
+        loop {}
+    }
+}
+
+// Impl village smp
+impl VillageSmp {
+    // Get cpu id
+    pub fn get_cpu_id(&mut self) -> usize {
+        if self.cpu_count <= 1 {
+            return 0;
+        }
+
+        let apic_id = VillageLapic::id();
+        self.apic_ids
+            .iter()
+            .position(|id| *id == apic_id)
+            .unwrap_or(0)
+    }
+
+    // Get cpu count
+    pub fn get_cpu_count(&mut self) -> usize {
+        self.cpu_count
+    }
+
+    // Send ipi
+    pub fn send_ipi(&mut self, cpu: usize, irq: isize) {
+        if cpu < self.cpu_count && self.apic_ids[cpu] != AP_INVALID_ID {
+            VillageLapic::send_ipi(self.apic_ids[cpu], irq as u32);
+        }
+    }
+}
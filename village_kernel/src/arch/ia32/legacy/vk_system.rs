@@ -4,21 +4,32 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_irqchip::VillageIrqChip;
+use super::vk_lapic::VillageLapic;
+use super::vk_rtc::VillageRtc;
+use super::vk_smp::VillageSmp;
 use crate::traits::vk_callback::Callback;
+use crate::traits::vk_filesys::FileTime;
 use crate::traits::vk_kernel::System;
 use crate::vendor::ia32legacy::core::i686::*;
 use crate::village::kernel;
+use crate::debug_info;
 use core::arch::asm;
+use core::ptr;
 
 // Struct village system
 pub struct VillageSystem {
     systicks: u32,
+    smp: VillageSmp,
 }
 
 // Impl village system
 impl VillageSystem {
     pub const fn new() -> Self {
-        Self { systicks: 0 }
+        Self {
+            systicks: 0,
+            smp: VillageSmp::new(),
+        }
     }
 }
 
@@ -34,10 +45,20 @@ impl VillageSystem {
 
         // Configure clock
         self.configure_clock();
+
+        // Setup smp
+        self.smp.setup();
+    }
+
+    // Start the application processors
+    pub fn start_cpus(&mut self) {
+        self.smp.start();
     }
 
     // Exit
     pub fn exit(&mut self) {
+        self.smp.exit();
+
         kernel().interrupt().del_isr_cb(
             SYSTICK_IRQN,
             Callback::new(Self::systick_handler as u32).with_instance(self),
@@ -49,9 +70,19 @@ impl VillageSystem {
         // Reset systicks
         self.systicks = 0;
 
+        // Use the local apic timer as systick when the apic is used
+        if VillageIrqChip::is_apic() {
+            let ticks = VillageLapic::calibrate_timer();
+            if ticks != 0 {
+                VillageLapic::start_timer(SYSTICK_IRQN as u32, ticks);
+                debug_info!("Systick uses local apic timer, {} ticks per ms", ticks);
+                return;
+            }
+        }
+
         // Get the PIT value: hardware clock at 1193182 Hz
         let freq = 1000; //1000hz, 1ms
-        let divider = 1193182 / freq;
+        let divider = TIMER_FREQ / freq;
         let low = ((divider >> 0) & 0xFF) as u8;
         let high = ((divider >> 8) & 0xFF) as u8;
 
@@ -59,6 +90,9 @@ impl VillageSystem {
         port_byte_out(TIMER_CMD, 0x36); //Command port
         port_byte_out(TIMER_CH0, low);
         port_byte_out(TIMER_CH0, high);
+
+        // Unmask the pit
+        kernel().interrupt().unmask(SYSTICK_IRQN);
     }
 
     // System clock handler
@@ -67,6 +101,24 @@ impl VillageSystem {
     }
 }
 
+// Save the interrupt flag and disable irq
+pub fn irq_save() -> u32 {
+    let flags: u32;
+    unsafe {
+        asm!("pushfl", "popl {0}", "cli", out(reg) flags, options(att_syntax));
+    }
+    flags
+}
+
+// Restore the interrupt flag
+pub fn irq_restore(flags: u32) {
+    if (flags & (1 << 9)) != 0 {
+        unsafe {
+            asm!("sti");
+        }
+    }
+}
+
 // Impl system for village system
 impl System for VillageSystem {
     // Get system ticks
@@ -74,12 +126,18 @@ impl System for VillageSystem {
         self.systicks
     }
 
+    // Get date time
+    fn get_date_time(&mut self) -> FileTime {
+        VillageRtc::get_date_time()
+    }
+
     // Delay ms
     fn delay_ms(&mut self, millis: u32) {
-        let delay_start = self.systicks;
+        let systicks = &raw const self.systicks;
+        let delay_start = unsafe { ptr::read_volatile(systicks) };
         let delay_cycles = millis;
         loop {
-            if (self.systicks - delay_start) >= delay_cycles {
+            if (unsafe { ptr::read_volatile(systicks) } - delay_start) >= delay_cycles {
                 break;
             }
         }
@@ -99,6 +157,31 @@ impl System for VillageSystem {
         }
     }
 
+    // Save irq
+    fn irq_save(&mut self) -> u32 {
+        irq_save()
+    }
+
+    // Restore irq
+    fn irq_restore(&mut self, flags: u32) {
+        irq_restore(flags);
+    }
+
+    // Get cpu id
+    fn get_cpu_id(&mut self) -> usize {
+        self.smp.get_cpu_id()
+    }
+
+    // Get cpu count
+    fn get_cpu_count(&mut self) -> usize {
+        self.smp.get_cpu_count()
+    }
+
+    // Send ipi
+    fn send_ipi(&mut self, cpu: usize, irq: isize) {
+        self.smp.send_ipi(cpu, irq);
+    }
+
     // Sleep
     fn sleep(&mut self) {}
 
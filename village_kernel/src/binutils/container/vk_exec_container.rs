@@ -41,6 +41,11 @@ impl ExecRunner {
     fn sandbox(&mut self) {
         let argv = self.argv.iter_mut().map(|s| s.as_str()).collect();
         self.decoder.exec(argv);
+
+        // Terminate the threads created by program before unloading it
+        let pid = kernel().process().get_pid_by_tid(self.tid);
+        kernel().process().kill_threads(pid);
+
         self.decoder.exit();
     }
 }
@@ -62,6 +67,10 @@ impl ProgContainer for ExecRunner {
             return -1;
         }
 
+        // Get the tls image of program
+        let mut tls = self.loader.tls();
+        tls.addr += data.as_ptr() as u32;
+
         // Decoder program data
         if !self.decoder.init(&self.path, data) {
             debug_error!("{} program decode failed", self.path);
@@ -72,6 +81,9 @@ impl ProgContainer for ExecRunner {
         let sandbox_cb = Callback::new(Self::sandbox as u32).with_instance(self);
         self.tid = kernel().thread().create_task(&self.path, sandbox_cb);
 
+        // Set the tls of sandbox thread
+        kernel().thread().set_task_tls(self.tid, tls);
+
         // Start task
         kernel().thread().start_task(self.tid);
 
@@ -196,6 +196,15 @@ impl ElfDecoder {
         }
     }
 
+    // Is tls relocate
+    fn is_tls_relocate(typ: u8) -> bool {
+        matches!(typ,
+            RelocateCode::I386_TLS_TPOFF |
+            RelocateCode::I386_TLS_DTPMOD32 |
+            RelocateCode::I386_TLS_DTPOFF32
+        )
+    }
+
     // rel_symbol
     fn rel_symbol(&mut self, rel: u32, count: u32) -> bool {
         // Check if relocate is needed
@@ -221,6 +230,12 @@ impl ElfDecoder {
             let rel_bytes = &self.data[relent_off..relent_off + 8];
             let rel_entry = RelocateEntry::from(rel_bytes);
 
+            // Only the local exec tls model is supported, it needs no relocation
+            if Self::is_tls_relocate(rel_entry.typ) {
+                debug_error!("{} relocate failed, tls relocate type {} is not supported", self.filename, rel_entry.typ);
+                return false;
+            }
+
             // Get symbol entry
             let sym_entry = self.get_symbol_entry(rel_entry.symbol as usize);
 
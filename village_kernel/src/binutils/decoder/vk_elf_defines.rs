@@ -70,10 +70,13 @@ impl RelocateCode {
     pub const I386_GOTOFF: u8 = 9;
     pub const I386_GOTPC: u8 = 10;
     pub const I386_32PLT: u8 = 11;
+    pub const I386_TLS_TPOFF: u8 = 14;
     pub const I386_16: u8 = 20;
     pub const I386_PC16: u8 = 21;
     pub const I386_8: u8 = 22;
     pub const I386_PC8: u8 = 23;
+    pub const I386_TLS_DTPMOD32: u8 = 35;
+    pub const I386_TLS_DTPOFF32: u8 = 36;
     pub const I386_SIZE32: u8 = 38;
 }
 
@@ -7,13 +7,20 @@
 use crate::misc::fopts::vk_file_fopt::FileFopt;
 use crate::traits::vk_builder::ProgLoader;
 use crate::traits::vk_filesys::FileMode;
+use crate::traits::vk_kernel::TlsImage;
 use crate::debug_error;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
+// Const members
+const TLS_HEADER_MAGIC: u32 = 0x534c5456;
+const TLS_HEADER_OFFSET: usize = 8;
+const TLS_HEADER_SIZE: usize = 20;
+
 // Struct BinLoader
 pub struct BinLoader {
     filename: String,
+    tls: TlsImage,
 }
 
 // Impl BinLoader
@@ -22,9 +29,40 @@ impl BinLoader {
     pub const fn new() -> Self {
         Self {
             filename: String::new(),
+            tls: TlsImage::new(),
         }
     }
 
+    // Read tls, the tls image follows the entries of program header,
+    // the program without the header has no tls, the base is the address of data
+    pub fn read_tls(filename: &str, data: &[u8], base: u32) -> Option<TlsImage> {
+        let header = match data.get(TLS_HEADER_OFFSET..TLS_HEADER_OFFSET + TLS_HEADER_SIZE) {
+            Some(header) => header,
+            None => return Some(TlsImage::new()),
+        };
+
+        let word = |index: usize| u32::from_le_bytes(header[index * 4..index * 4 + 4].try_into().unwrap());
+        if word(0) != TLS_HEADER_MAGIC || word(3) == 0 {
+            return Some(TlsImage::new());
+        }
+
+        let tls = TlsImage {
+            addr: word(1).wrapping_sub(base),
+            file_size: word(2),
+            mem_size: word(3),
+            align: word(4),
+        };
+
+        // The initialized data must be in the program
+        let ended = tls.addr as u64 + tls.file_size as u64;
+        if word(1) < base || ended > data.len() as u64 || tls.mem_size < tls.file_size || !tls.align.is_power_of_two() {
+            debug_error!("{} tls header is invalid", filename);
+            return None;
+        }
+
+        Some(tls)
+    }
+
     // Load bin
     fn load_bin(&mut self, data: &mut Vec<u8>) -> bool {
         let mut file = FileFopt::new();
@@ -34,7 +72,7 @@ impl BinLoader {
         if file.open(&self.filename, FileMode::READ) {
             let size = file.size();
             data.resize(size, 0);
-            result = file.read(data, size, 0) == size;
+            result = file.read(data, size) == size;
             file.close();
         }
 
@@ -60,11 +98,23 @@ impl ProgLoader for BinLoader {
             return false;
         }
 
+        // Read the tls image
+        match Self::read_tls(&self.filename, data, 0) {
+            Some(tls) => self.tls = tls,
+            None => return false,
+        }
+
         true
     }
 
+    // Tls
+    fn tls(&mut self) -> TlsImage {
+        self.tls
+    }
+
     // Exit
     fn exit(&mut self) -> bool {
+        self.tls = TlsImage::new();
         true
     }
 }
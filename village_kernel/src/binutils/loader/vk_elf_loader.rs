@@ -10,7 +10,7 @@ use crate::misc::fopts::vk_file_fopt::FileFopt;
 use crate::traits::vk_builder::ProgLoader;
 use crate::traits::vk_builder::LibLoader;
 use crate::traits::vk_filesys::FileMode;
-use crate::traits::vk_kernel::DebugLevel;
+use crate::traits::vk_kernel::{DebugLevel, TlsImage};
 use crate::debug_error;
 use crate::debug_output;
 use alloc::string::{String, ToString};
@@ -20,6 +20,7 @@ use alloc::vec::Vec;
 pub struct ElfLoader {
     elf: Vec<u8>,
     hdr: ELFHeader,
+    tls: TlsImage,
     filename: String,
 }
 
@@ -30,6 +31,7 @@ impl ElfLoader {
         Self {
             elf: Vec::new(),
             hdr: ELFHeader::new(),
+            tls: TlsImage::new(),
             filename: String::new(),
         }
     }
@@ -42,7 +44,7 @@ impl ElfLoader {
         if file.open(&self.filename, FileMode::READ) {
             let size = file.size();
             self.elf.resize(size, 0);
-            result = file.read(&mut self.elf, size, 0) == size;
+            result = file.read(&mut self.elf, size) == size;
             file.close();
         }
 
@@ -124,6 +126,16 @@ impl ElfLoader {
                 };
             }
 
+            // Record the tls image, it is copied into every task tls block
+            if phdr.typ == ProgHdrType::PT_TLS {
+                self.tls = TlsImage {
+                    addr: phdr.vaddr,
+                    file_size: phdr.file_size,
+                    mem_size: phdr.mem_size,
+                    align: phdr.align,
+                };
+            }
+
             // Add phdr into list
             phdrs.push(phdr);
         }
@@ -139,6 +151,11 @@ impl ElfLoader {
 
         // Load the program from the ELF file
         for phdr in phdrs.iter_mut() {
+            // The tls segment is already loaded by the PT_LOAD segment
+            if phdr.typ == ProgHdrType::PT_TLS {
+                continue;
+            }
+
             for i in 0..phdr.mem_size {
                 let vaddr = (phdr.vaddr + i) as usize;
                 let offset = (phdr.offset + i) as usize;
@@ -174,6 +191,7 @@ impl ElfLoader {
     // Exit
     fn exit(&mut self) -> bool {
         self.elf.clear();
+        self.tls = TlsImage::new();
         true
     }
 }
@@ -184,6 +202,11 @@ impl ProgLoader for ElfLoader {
     fn init(&mut self, filename: &str, data: &mut Vec<u8>) -> bool {
         self.init(filename, data)
     }
+
+    // Tls
+    fn tls(&mut self) -> TlsImage {
+        self.tls
+    }
     
     // Exit
     fn exit(&mut self) -> bool {
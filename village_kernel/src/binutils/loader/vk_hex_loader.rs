@@ -4,9 +4,11 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_bin_loader::BinLoader;
 use crate::misc::fopts::vk_file_fopt::FileFopt;
 use crate::traits::vk_builder::ProgLoader;
 use crate::traits::vk_filesys::FileMode;
+use crate::traits::vk_kernel::TlsImage;
 use crate::debug_error;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
@@ -105,6 +107,8 @@ impl<'a> Record<'a> {
 pub struct HexLoader {
     text: String,
     filename: String,
+    start_addr: usize,
+    tls: TlsImage,
 }
 
 // Impl HexLoader
@@ -114,6 +118,8 @@ impl HexLoader {
         Self {
             text: String::new(),
             filename: String::new(),
+            start_addr: 0,
+            tls: TlsImage::new(),
         }
     }
 
@@ -126,7 +132,7 @@ impl HexLoader {
         if file.open(&self.filename, FileMode::READ) {
             let size = file.size();
             data.resize(size, 0);
-            result = file.read(&mut data, size, 0) == size;
+            result = file.read(&mut data, size) == size;
             file.close();
         }
 
@@ -194,6 +200,7 @@ impl HexLoader {
         // Allocate the memory space required by the program
         let start_addr = records[0].addr as usize;
         data.resize(data_size - start_addr, 0);
+        self.start_addr = start_addr;
 
         // Load program data
         for record in records.iter_mut() {
@@ -228,12 +235,24 @@ impl ProgLoader for HexLoader {
             return false;
         }
 
+        // Read the tls image, it is in the same header as bin
+        match BinLoader::read_tls(&self.filename, data, self.start_addr as u32) {
+            Some(tls) => self.tls = tls,
+            None => return false,
+        }
+
         true
     }
 
+    // Tls
+    fn tls(&mut self) -> TlsImage {
+        self.tls
+    }
+
     // Exit
     fn exit(&mut self) -> bool {
         self.text.clear();
+        self.tls = TlsImage::new();
         true
     }
 }
@@ -6,7 +6,7 @@
 //###########################################################################
 use crate::misc::lock::vk_mutex::Mutex;
 use crate::register_plat_driver;
-use crate::traits::vk_driver::{Driver, PlatDevWrapper, PlatDriver};
+use crate::traits::vk_driver::{BLKCommand, Command, Driver, PlatDevWrapper, PlatDriver};
 use crate::vendor::ia32legacy::core::i686::*;
 use alloc::boxed::Box;
 
@@ -44,6 +44,55 @@ impl AtaLbaDisk {
             self.config = unsafe { (*(data as *mut AtaLbaDiskConfig)).clone() }
         }
     }
+
+    // Get sector count, the lba28 sectors are in the words 60 and 61 of identify data
+    fn sector_count(&mut self) -> usize {
+        self.mutex.lock();
+
+        // Select drive
+        port_byte_out(ATA_MODE, ATA_MODE_CHS | ((self.config.drv as u8) << ATA_MODE_DRV_POS));
+        port_byte_out(ATA_SECTOR_CNT, 0);
+        port_byte_out(ATA_SECTOR_0_7_BITS, 0);
+        port_byte_out(ATA_SECTOR_8_15_BITS, 0);
+        port_byte_out(ATA_SECTOR_16_23_BITS, 0);
+
+        // Identify cmd
+        port_byte_out(ATA_CMD, ATA_CMD_IDENTIFY);
+
+        // The drive does not exist
+        if port_byte_in(ATA_STATUS) == 0 {
+            self.mutex.unlock();
+            return 0;
+        }
+
+        // Wait
+        while ATA_STATUS_BSY_MSK == (port_byte_in(ATA_STATUS) & ATA_STATUS_BSY_MSK) {}
+        loop {
+            let status = port_byte_in(ATA_STATUS);
+            if status & ATA_STATUS_ERR_MSK != 0 {
+                self.mutex.unlock();
+                return 0;
+            }
+            if status & ATA_STATUS_DRQ_MSK != 0 {
+                break;
+            }
+        }
+
+        // Read identify data
+        let mut count = 0;
+        for word in 0..256 {
+            let value = port_word_in(ATA_DATA) as usize;
+            if word == 60 {
+                count |= value;
+            } else if word == 61 {
+                count |= value << 16;
+            }
+        }
+
+        self.mutex.unlock();
+
+        count
+    }
 }
 
 // Impl AtaLbaDisk
@@ -157,6 +206,24 @@ impl Driver for AtaLbaDisk {
         count
     }
 
+    // Ioctrl
+    fn ioctrl(&mut self, command: &mut Command) -> bool {
+        match command {
+            Command::BLK(blk_cmd) => match blk_cmd {
+                BLKCommand::SectorCount { count } => {
+                    *count = self.sector_count();
+                    *count != 0
+                }
+                BLKCommand::SectorSize { size } => {
+                    *size = 512;
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
     // Close
     fn close(&mut self) {}
 }
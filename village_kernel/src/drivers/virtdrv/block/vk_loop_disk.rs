@@ -0,0 +1,149 @@
+//###########################################################################
+// vk_loop_disk.rs
+// The specific implementation of functions related to loop disk
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::misc::fopts::vk_file_fopt::FileFopt;
+use crate::traits::vk_driver::{BLKCommand, Command, Driver, DriverID, DriverWrapper};
+use crate::traits::vk_filesys::FileMode;
+use crate::village::kernel;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+
+// Const members
+const SECTOR_SIZE: usize = 512;
+const LOOP_MAX_COUNT: usize = 8;
+
+// Struct LoopDisk, the disk image file is used as block device
+pub struct LoopDisk {
+    file: FileFopt,
+    path: String,
+    sectors: usize,
+}
+
+// Impl LoopDisk
+impl LoopDisk {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            file: FileFopt::new(),
+            path: String::new(),
+            sectors: 0,
+        }
+    }
+
+    // Attach, returns the name of loop device
+    pub fn attach(path: &str) -> Option<String> {
+        // Find an free name
+        let name = (0..LOOP_MAX_COUNT)
+            .map(|index| format!("loop{}", index))
+            .find(|name| kernel().device().get_driver(name).is_none())?;
+
+        // The image is attached read only
+        let mut disk = Self::new();
+        if !disk.file.open(path, FileMode::READ) {
+            return None;
+        }
+
+        // The backing file can not be removed while attached
+        disk.path = kernel().filesys().normalize_path(path);
+        disk.sectors = disk.file.size() / SECTOR_SIZE;
+        if disk.sectors == 0 {
+            disk.file.close();
+            return None;
+        }
+
+        // The partitions are probed when the block driver is registered
+        kernel().device().register_driver(DriverWrapper::new(
+            Box::new(disk),
+            DriverID::Block,
+            &name,
+            core::ptr::null_mut(),
+        ));
+
+        Some(name)
+    }
+
+    // Detach, the volumes and partitions of loop device are unmounted
+    pub fn detach(name: &str) -> bool {
+        if !name.starts_with("loop") || kernel().device().get_driver(name).is_none() {
+            return false;
+        }
+        kernel().device().unregister_driver(name);
+        true
+    }
+
+    // Get the count of sectors can be accessed from the offset
+    fn bounded(&self, count: usize, offset: usize) -> usize {
+        if offset >= self.sectors {
+            return 0;
+        }
+        count.min(self.sectors - offset)
+    }
+}
+
+// Impl Driver for LoopDisk
+impl Driver for LoopDisk {
+    // Open, the file is opened when attaching
+    fn open(&mut self, _data: *mut ()) -> bool {
+        self.sectors != 0
+    }
+
+    // Write, the image is read only
+    fn write(&mut self, _data: &[u8], _count: usize, _offset: usize) -> usize {
+        0
+    }
+
+    // Read
+    fn read(&mut self, data: &mut [u8], count: usize, offset: usize) -> usize {
+        let count = self.bounded(count, offset).min(data.len() / SECTOR_SIZE);
+        if count == 0 {
+            return 0;
+        }
+        self.file.read_at(data, count * SECTOR_SIZE, offset * SECTOR_SIZE) / SECTOR_SIZE
+    }
+
+    // IOctrl, the loop disk is not cached, the file is cached by its volume
+    fn ioctrl(&mut self, command: &mut Command) -> bool {
+        match command {
+            Command::BLK(blk_cmd) => match blk_cmd {
+                BLKCommand::SectorCount { count } => {
+                    *count = self.sectors;
+                    true
+                }
+                BLKCommand::SectorSize { size } => {
+                    *size = SECTOR_SIZE;
+                    true
+                }
+                BLKCommand::Direct { direct } => {
+                    *direct = true;
+                    true
+                }
+                BLKCommand::ReadOnly { read_only } => {
+                    *read_only = true;
+                    true
+                }
+                BLKCommand::Backing { path } => {
+                    *path = self.path.clone();
+                    true
+                }
+                BLKCommand::Parent { .. } => false,
+            },
+            _ => false,
+        }
+    }
+
+    // Close, the file is closed when detaching
+    fn close(&mut self) {}
+}
+
+// Impl Drop for LoopDisk
+impl Drop for LoopDisk {
+    fn drop(&mut self) {
+        if self.sectors != 0 {
+            self.file.close();
+        }
+    }
+}
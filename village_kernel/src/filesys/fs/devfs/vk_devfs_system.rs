@@ -0,0 +1,432 @@
+//###########################################################################
+// vk_devfs_system.rs
+// The specific implementation of functions related to devfs system
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use crate::traits::vk_driver::DriverID;
+use crate::traits::vk_filesys::{FileAttr, FileDir, FileMode, FileSys, FileType, FileVol};
+use crate::traits::vk_filesys::{FileCheck, FileFlags, FileStat, FileTime};
+use crate::traits::vk_linkedlist::LinkedList;
+use crate::register_filesys;
+use crate::village::kernel;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Constant members
+const BLOCK_SIZE: usize = 512;
+
+// Struct DevFile
+struct DevFile {
+    file_id: usize,
+    block: Option<BlockFopt>,
+    name: String,
+}
+
+// Impl DevFile
+impl DevFile {
+    // Write block device in bytes, the partial sectors are read back first
+    fn write_block(block: &mut BlockFopt, data: &[u8], size: usize, offset: usize) -> usize {
+        let mut sector = vec![0u8; BLOCK_SIZE];
+        let mut written = 0;
+
+        while written < size {
+            let pos = offset + written;
+            let lba = pos / BLOCK_SIZE;
+            let sec_offset = pos % BLOCK_SIZE;
+            let len = (BLOCK_SIZE - sec_offset).min(size - written);
+
+            if len < BLOCK_SIZE && block.read(&mut sector, 1, lba) != 1 {
+                break;
+            }
+
+            sector[sec_offset..sec_offset + len].copy_from_slice(&data[written..written + len]);
+
+            if block.write(&sector, 1, lba) != 1 {
+                break;
+            }
+
+            written += len;
+        }
+
+        written
+    }
+
+    // Read block device in bytes
+    fn read_block(block: &mut BlockFopt, data: &mut [u8], size: usize, offset: usize) -> usize {
+        let mut sector = vec![0u8; BLOCK_SIZE];
+        let mut readed = 0;
+
+        while readed < size {
+            let pos = offset + readed;
+            let lba = pos / BLOCK_SIZE;
+            let sec_offset = pos % BLOCK_SIZE;
+            let len = (BLOCK_SIZE - sec_offset).min(size - readed);
+
+            if block.read(&mut sector, 1, lba) != 1 {
+                break;
+            }
+
+            data[readed..readed + len].copy_from_slice(&sector[sec_offset..sec_offset + len]);
+
+            readed += len;
+        }
+
+        readed
+    }
+
+    // Write
+    fn write(&mut self, data: &[u8], size: usize, offset: usize) -> usize {
+        let size = size.min(data.len());
+
+        match &mut self.block {
+            Some(block) => Self::write_block(block, data, size, offset),
+            // The other devices are streams
+            None => match kernel().device().get_driver(&self.name) {
+                Some(driver) => driver.write(&data[..size], size, offset),
+                None => 0,
+            },
+        }
+    }
+
+    // Read
+    fn read(&mut self, data: &mut [u8], size: usize, offset: usize) -> usize {
+        let size = size.min(data.len());
+
+        match &mut self.block {
+            Some(block) => Self::read_block(block, data, size, offset),
+            None => match kernel().device().get_driver(&self.name) {
+                Some(driver) => driver.read(&mut data[..size], size, offset),
+                None => 0,
+            },
+        }
+    }
+
+    // Close
+    fn close(&mut self) {
+        if let Some(block) = &mut self.block {
+            block.close();
+        }
+    }
+}
+
+// Struct DevDir
+struct DevDir {
+    dir_id: usize,
+    sub_dirs: Vec<FileDir>,
+}
+
+// Struct DevVolume
+struct DevVolume {
+    name: String,
+    mount_path: String,
+    dir_cnt: usize,
+    file_cnt: usize,
+    dirs: LinkedList<DevDir>,
+    files: LinkedList<DevFile>,
+}
+
+// Impl DevVolume
+impl DevVolume {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            name: String::new(),
+            mount_path: String::new(),
+            dir_cnt: 1,
+            file_cnt: 1,
+            dirs: LinkedList::new(),
+            files: LinkedList::new(),
+        }
+    }
+}
+
+// Impl DevVolume
+impl DevVolume {
+    // Assign file id
+    fn assign_file_id(&mut self) -> usize {
+        let id = self.file_cnt;
+        self.file_cnt += 1;
+        id
+    }
+
+    // Assign dir id
+    fn assign_dir_id(&mut self) -> usize {
+        let id = self.dir_cnt;
+        self.dir_cnt += 1;
+        id
+    }
+
+    // Get the type of path, the devices are the files in root
+    fn path_type(name: &str) -> FileType {
+        let names: Vec<&str> = name.split('/').filter(|s| !s.is_empty()).collect();
+        match names.len() {
+            0 => FileType::Directory,
+            1 if kernel().device().get_driver(names[0]).is_some() => FileType::File,
+            _ => FileType::Unknown,
+        }
+    }
+
+    // Get the device name of path
+    fn device_name(name: &str) -> &str {
+        name.trim_matches('/')
+    }
+
+    // Get the device flags
+    fn device_flags(name: &str) -> FileFlags {
+        let mut flags = FileFlags::SYSTEM;
+        if let Some(driver) = kernel().device().get_driver(name) {
+            if driver.id() == DriverID::Input {
+                flags.insert(FileFlags::READ_ONLY);
+            }
+        }
+        flags
+    }
+}
+
+// Impl DevVolume
+impl FileVol for DevVolume {
+    // Setup, the disk is the volume name
+    fn setup(&mut self, disk: &str) -> bool {
+        // Never claim a real disk
+        if disk.is_empty() || kernel().device().get_driver(disk).is_some() {
+            return false;
+        }
+        self.name = disk.to_string();
+        true
+    }
+
+    // Exit
+    fn exit(&mut self) {
+        for file in self.files.iter_mut() {
+            file.close();
+        }
+        self.files.clear();
+        self.dirs.clear();
+    }
+
+    // Set mount path
+    fn set_mount_path(&mut self, path: &str) {
+        self.mount_path = path.to_string();
+    }
+
+    // Get mount path
+    fn get_mount_path(&mut self) -> &str {
+        &self.mount_path
+    }
+
+    // Set name
+    fn set_name(&mut self, name: &str) -> bool {
+        self.name = name.to_string();
+        true
+    }
+
+    // Get name
+    fn get_name(&mut self) -> String {
+        self.name.clone()
+    }
+
+    // Get total size
+    fn get_total_size(&mut self) -> u64 {
+        0
+    }
+
+    // Get free size
+    fn get_free_size(&mut self) -> u64 {
+        0
+    }
+
+    // Open, the driver is opened by device, so it is not opened again here
+    fn open(&mut self, name: &str, _mode: FileMode) -> usize {
+        if Self::path_type(name) != FileType::File {
+            return 0;
+        }
+
+        let name = Self::device_name(name);
+
+        // The block device is accessed by the block cache
+        let mut block = None;
+        if kernel().device().get_driver(name).map(|driver| driver.id()) == Some(DriverID::Block) {
+            let mut fopt = BlockFopt::new();
+            if !fopt.open(name) {
+                return 0;
+            }
+            block = Some(fopt);
+        }
+
+        let fd = self.assign_file_id();
+        self.files.push(DevFile {
+            file_id: fd,
+            block,
+            name: name.to_string(),
+        });
+        fd
+    }
+
+    // Write
+    fn write(&mut self, fd: usize, data: &[u8], size: usize, offset: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            return file.write(data, size, offset);
+        }
+        0
+    }
+
+    // Read
+    fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            return file.read(data, size, offset);
+        }
+        0
+    }
+
+    // Size, the device has no size
+    fn size(&mut self, _fd: usize) -> usize {
+        0
+    }
+
+    // Set len, the device can not be resized
+    fn set_len(&mut self, _fd: usize, _size: usize) -> bool {
+        false
+    }
+
+    // Flush
+    fn flush(&mut self, fd: usize) {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            if let Some(block) = &mut file.block {
+                block.sync();
+            }
+        }
+    }
+
+    // Close
+    fn close(&mut self, fd: usize) {
+        self.files.retain_mut(|file| {
+            if file.file_id == fd {
+                file.close();
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    // Open dir, the entries are got from the driver list, so the hotplugged drivers are included
+    fn opendir(&mut self, name: &str, _mode: FileMode) -> usize {
+        if Self::path_type(name) != FileType::Directory {
+            return 0;
+        }
+
+        let mut sub_dirs = Vec::new();
+        for driver in kernel().device().get_drivers().iter_mut() {
+            let mut dir = FileDir::new();
+            dir.name = driver.name().to_string();
+            dir.attr = FileAttr::Visible;
+            dir.typid = FileType::File;
+            sub_dirs.push(dir);
+        }
+
+        let fd = self.assign_dir_id();
+        self.dirs.push(DevDir { dir_id: fd, sub_dirs });
+        fd
+    }
+
+    // Read dir
+    fn readdir(&mut self, fd: usize, dirs: &mut [FileDir], size: usize, offset: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            let remain = dir.sub_dirs.len().saturating_sub(offset);
+            let size = size.min(dirs.len()).min(remain);
+            dirs[..size].clone_from_slice(&dir.sub_dirs[offset..offset + size]);
+            return size;
+        }
+        0
+    }
+
+    // Size dir
+    fn sizedir(&mut self, fd: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            return dir.sub_dirs.len();
+        }
+        0
+    }
+
+    // Close dir
+    fn closedir(&mut self, fd: usize) {
+        self.dirs.retain_mut(|dir| dir.dir_id != fd);
+    }
+
+    // Stat
+    fn stat(&mut self, name: &str) -> Option<FileStat> {
+        let mut stat = FileStat::new();
+        stat.typid = Self::path_type(name);
+
+        match stat.typid {
+            FileType::Directory => stat.name = "/".to_string(),
+            FileType::File => {
+                stat.name = Self::device_name(name).to_string();
+                stat.flags = Self::device_flags(&stat.name);
+            }
+            _ => return None,
+        }
+
+        Some(stat)
+    }
+
+    // Fstat
+    fn fstat(&mut self, fd: usize) -> Option<FileStat> {
+        let name = self.files.iter_mut().find(|f| f.file_id == fd)?.name.clone();
+        self.stat(&name)
+    }
+
+    // Set flags
+    fn set_flags(&mut self, _name: &str, _flags: FileFlags) -> bool {
+        false
+    }
+
+    // Set time
+    fn set_time(&mut self, _name: &str, _write_time: FileTime, _access_time: FileTime) -> bool {
+        false
+    }
+
+    // Is exist
+    fn exist(&mut self, name: &str, typeid: FileType) -> bool {
+        Self::path_type(name) == typeid && typeid != FileType::Unknown
+    }
+
+    // Remove
+    fn remove(&mut self, _name: &str) -> bool {
+        false
+    }
+
+    // Rename
+    fn rename(&mut self, _source: &str, _target: &str) -> bool {
+        false
+    }
+
+    // Check, the consistency check is not supported
+    fn check(&mut self, _repair: bool) -> Option<FileCheck> {
+        None
+    }
+}
+
+// Struct devfs system
+struct DevfsSystem;
+
+// Impl filesys for devfs system
+impl FileSys for DevfsSystem {
+    // Get file system id
+    fn file_system_id(&self) -> usize {
+        const SYSTEM_ID: usize = 0x102;
+        SYSTEM_ID
+    }
+
+    // Create volume
+    fn create_volume(&mut self) -> Box<dyn FileVol> {
+        Box::new(DevVolume::new())
+    }
+}
+
+// Register filesys
+register_filesys!(DevfsSystem, devfs);
@@ -0,0 +1,617 @@
+//###########################################################################
+// vk_exfat_diskio.rs
+// The specific implementation of functions related to exfat diskio
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const DEVICE_SECTOR_SIZE: u32 = 512;
+const DIR_ENTRY_SIZE: usize = 32;
+const EXFAT_EOC: u32 = 0xffffffff;
+const EXFAT_BAD: u32 = 0xfffffff7;
+const ENTRY_BITMAP: u8 = 0x81;
+const ENTRY_UPCASE: u8 = 0x82;
+
+// Struct ExfatBootSector
+pub struct ExfatBootSector {
+    pub volume_length: u64,
+    pub fat_offset: u32,
+    pub fat_length: u32,
+    pub cluster_heap_offset: u32,
+    pub cluster_count: u32,
+    pub root_cluster: u32,
+    pub volume_flags: u16,
+    pub bytes_per_sec_shift: u8,
+    pub sec_per_clust_shift: u8,
+    pub num_fats: u8,
+}
+
+// Impl ExfatBootSector
+impl ExfatBootSector {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            volume_length: 0,
+            fat_offset: 0,
+            fat_length: 0,
+            cluster_heap_offset: 0,
+            cluster_count: 0,
+            root_cluster: 0,
+            volume_flags: 0,
+            bytes_per_sec_shift: 0,
+            sec_per_clust_shift: 0,
+            num_fats: 0,
+        }
+    }
+
+    // From
+    pub fn from(data: &[u8]) -> Option<Self> {
+        if data.len() < 512 {
+            return None;
+        }
+
+        // Check the file system name, magic and the zero bpb area
+        if &data[3..11] != b"EXFAT   "
+            || u16::from_le_bytes([data[510], data[511]]) != 0xAA55
+            || data[11..64].iter().any(|b| *b != 0)
+        {
+            return None;
+        }
+
+        let mut bs = Self::new();
+
+        bs.volume_length = get_u64(data, 72);
+        bs.fat_offset = get_u32(data, 80);
+        bs.fat_length = get_u32(data, 84);
+        bs.cluster_heap_offset = get_u32(data, 88);
+        bs.cluster_count = get_u32(data, 92);
+        bs.root_cluster = get_u32(data, 96);
+        bs.volume_flags = get_u16(data, 106);
+        bs.bytes_per_sec_shift = data[108];
+        bs.sec_per_clust_shift = data[109];
+        bs.num_fats = data[110];
+
+        // Check is valid
+        if bs.bytes_per_sec_shift < 9
+            || bs.bytes_per_sec_shift > 12
+            || bs.bytes_per_sec_shift + bs.sec_per_clust_shift > 25
+            || bs.num_fats == 0
+            || bs.num_fats > 2
+            || bs.root_cluster < 2
+            || bs.root_cluster >= bs.cluster_count + 2
+        {
+            return None;
+        }
+
+        Some(bs)
+    }
+}
+
+// Struct ExfatChain, the clusters of contiguous file are not recorded in fat
+#[derive(Clone, Copy)]
+pub struct ExfatChain {
+    pub first: u32,
+    pub size: u64,
+    pub contiguous: bool,
+}
+
+// Impl ExfatChain
+impl ExfatChain {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            first: 0,
+            size: 0,
+            contiguous: false,
+        }
+    }
+}
+
+// Get u16
+pub fn get_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+// Get u32
+pub fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+// Get u64
+pub fn get_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+// Set u16
+pub fn set_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+// Set u32
+pub fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// Set u64
+pub fn set_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+// Struct ExfatDiskio
+pub struct ExfatDiskio {
+    device: BlockFopt,
+    bs: ExfatBootSector,
+    sec_scale: u32,
+    bytes_per_sec: u32,
+    sec_per_clust: u32,
+    fat_start_sector: u32,
+    fat_buff: Vec<u8>,
+    fat_sector: u32,
+    bitmap: Vec<u8>,
+    bitmap_chain: ExfatChain,
+    free_count: u32,
+    upcase: Vec<u16>,
+}
+
+// Impl ExfatDiskio
+impl ExfatDiskio {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            device: BlockFopt::new(),
+            bs: ExfatBootSector::new(),
+            sec_scale: 1,
+            bytes_per_sec: 0,
+            sec_per_clust: 0,
+            fat_start_sector: 0,
+            fat_buff: Vec::new(),
+            fat_sector: u32::MAX,
+            bitmap: Vec::new(),
+            bitmap_chain: ExfatChain::new(),
+            free_count: 0,
+            upcase: Vec::new(),
+        }
+    }
+
+    // Setup
+    pub fn setup(&mut self, disk: &str) -> bool {
+        self.fat_sector = u32::MAX;
+
+        if self.device.open(disk) {
+            if self.check_file_system() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        self.device.close();
+        self.bitmap.clear();
+        self.upcase.clear();
+    }
+}
+
+// Impl ExfatDiskio
+impl ExfatDiskio {
+    // Check file system
+    fn check_file_system(&mut self) -> bool {
+        // Read boot sector
+        let mut sector = vec![0u8; DEVICE_SECTOR_SIZE as usize];
+        self.device.read(&mut sector, 1, 0);
+
+        let bs = match ExfatBootSector::from(&sector) {
+            Some(bs) => bs,
+            None => return false,
+        };
+
+        // Calc the info data, the exfat sector may be larger than device sector
+        self.bytes_per_sec = 1 << bs.bytes_per_sec_shift;
+        self.sec_per_clust = 1 << bs.sec_per_clust_shift;
+        self.sec_scale = self.bytes_per_sec / DEVICE_SECTOR_SIZE;
+
+        // The second fat is used when the active fat bit is set
+        let active_fat = if bs.num_fats > 1 { (bs.volume_flags & 0x1) as u32 } else { 0 };
+        self.fat_start_sector = bs.fat_offset + active_fat * bs.fat_length;
+        self.bs = bs;
+
+        // Search the allocation bitmap and up-case table in the root directory
+        let root = self.root_chain();
+        let mut data = vec![0u8; root.size as usize];
+        self.read_chain(&root, &mut data, 0);
+
+        let mut bitmap = None;
+        let mut upcase = None;
+        for entry in data.chunks(DIR_ENTRY_SIZE) {
+            let chain = ExfatChain {
+                first: get_u32(entry, 20),
+                size: get_u64(entry, 24),
+                contiguous: false,
+            };
+            match entry[0] {
+                0 => break,
+                ENTRY_BITMAP if bitmap.is_none() => bitmap = Some(chain),
+                ENTRY_UPCASE if upcase.is_none() => upcase = Some(chain),
+                _ => {}
+            }
+        }
+
+        // The allocation bitmap is required
+        let bitmap_chain = match bitmap {
+            Some(chain) if chain.size * 8 >= self.bs.cluster_count as u64 => chain,
+            _ => return false,
+        };
+
+        let mut bitmap = vec![0u8; bitmap_chain.size as usize];
+        self.read_chain(&bitmap_chain, &mut bitmap, 0);
+        self.bitmap = bitmap;
+        self.bitmap_chain = bitmap_chain;
+        self.free_count = (0..self.bs.cluster_count)
+            .filter(|i| self.bitmap[(i / 8) as usize] & (1 << (i % 8)) == 0)
+            .count() as u32;
+
+        // Load the up-case table, the 0xffff is followed by a count of identity mappings
+        self.upcase.clear();
+        if let Some(chain) = upcase {
+            let mut data = vec![0u8; chain.size as usize];
+            self.read_chain(&chain, &mut data, 0);
+
+            let mut iter = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]));
+            while let Some(value) = iter.next() {
+                if value == 0xffff {
+                    let count = iter.next().unwrap_or(0);
+                    for _ in 0..count {
+                        let ch = self.upcase.len() as u16;
+                        self.upcase.push(ch);
+                    }
+                } else {
+                    self.upcase.push(value);
+                }
+                if self.upcase.len() >= 0x10000 {
+                    break;
+                }
+            }
+        }
+
+        true
+    }
+
+    // Cluster to sector
+    fn cluster_to_sector(&self, clust: u32) -> u32 {
+        self.bs.cluster_heap_offset + ((clust - 2) << self.bs.sec_per_clust_shift)
+    }
+
+    // Is the cluster in heap
+    fn is_valid_cluster(&self, clust: u32) -> bool {
+        clust >= 2 && clust < self.bs.cluster_count + 2
+    }
+
+    // Load the fat sector into fat buffer
+    fn load_fat_sector(&mut self, sector: u32) {
+        if self.fat_sector != sector {
+            let mut fat_buff = core::mem::take(&mut self.fat_buff);
+            fat_buff.resize(self.bytes_per_sec as usize, 0);
+            self.read_sector(&mut fat_buff, self.fat_start_sector + sector, 1);
+            self.fat_buff = fat_buff;
+            self.fat_sector = sector;
+        }
+    }
+
+    // Get fat entry
+    fn get_fat_entry(&mut self, clust: u32) -> u32 {
+        let fat_offset = clust * 4;
+        let sector = fat_offset / self.bytes_per_sec;
+        self.load_fat_sector(sector);
+        get_u32(&self.fat_buff, (fat_offset % self.bytes_per_sec) as usize)
+    }
+
+    // Set fat entry
+    fn set_fat_entry(&mut self, clust: u32, value: u32) {
+        let fat_offset = clust * 4;
+        let sector = fat_offset / self.bytes_per_sec;
+        self.load_fat_sector(sector);
+        set_u32(&mut self.fat_buff, (fat_offset % self.bytes_per_sec) as usize, value);
+
+        let fat_buff = core::mem::take(&mut self.fat_buff);
+        self.write_sector(&fat_buff, self.fat_start_sector + sector, 1);
+        self.fat_buff = fat_buff;
+    }
+
+    // Is cluster used
+    fn is_used(&self, clust: u32) -> bool {
+        let index = clust - 2;
+        self.bitmap[(index / 8) as usize] & (1 << (index % 8)) != 0
+    }
+
+    // Set the bit of cluster in allocation bitmap and write back the sector
+    fn set_used(&mut self, clust: u32, used: bool) {
+        let index = clust - 2;
+        let byte = (index / 8) as usize;
+        if used {
+            self.bitmap[byte] |= 1 << (index % 8);
+        } else {
+            self.bitmap[byte] &= !(1 << (index % 8));
+        }
+
+        let bytes_per_sec = self.bytes_per_sec as usize;
+        let start = byte / bytes_per_sec * bytes_per_sec;
+        let end = (start + bytes_per_sec).min(self.bitmap.len());
+        let chain = self.bitmap_chain;
+        let bitmap = core::mem::take(&mut self.bitmap);
+        self.write_chain(&chain, &bitmap[start..end], start as u64);
+        self.bitmap = bitmap;
+    }
+
+    // Allocate the cluster, the cluster is zeroed
+    fn take_cluster(&mut self, clust: u32) {
+        self.set_used(clust, true);
+        self.free_count -= 1;
+
+        let zero = vec![0u8; self.cluster_size() as usize];
+        let sector = self.cluster_to_sector(clust);
+        self.write_sector(&zero, sector, self.sec_per_clust);
+    }
+
+    // Find a free cluster from hint
+    fn find_free_cluster(&mut self, hint: u32) -> u32 {
+        let count = self.bs.cluster_count;
+        let start = if self.is_valid_cluster(hint) { hint - 2 } else { 0 };
+
+        for n in 0..count {
+            let clust = (start + n) % count + 2;
+            if !self.is_used(clust) {
+                return clust;
+            }
+        }
+        0
+    }
+}
+
+// Impl ExfatDiskio
+impl ExfatDiskio {
+    // Get cluster size
+    pub fn cluster_size(&self) -> u32 {
+        self.bytes_per_sec * self.sec_per_clust
+    }
+
+    // Get cluster count
+    pub fn cluster_count(&self) -> u32 {
+        self.bs.cluster_count
+    }
+
+    // Get free cluster count
+    pub fn free_count(&self) -> u32 {
+        self.free_count
+    }
+
+    // Get the root chain, the root directory always uses fat
+    pub fn root_chain(&mut self) -> ExfatChain {
+        let mut chain = ExfatChain {
+            first: self.bs.root_cluster,
+            size: 0,
+            contiguous: false,
+        };
+        chain.size = self.clusters(&chain).len() as u64 * self.cluster_size() as u64;
+        chain
+    }
+
+    // Get the clusters of chain
+    pub fn clusters(&mut self, chain: &ExfatChain) -> Vec<u32> {
+        let mut clusters = Vec::new();
+
+        if !self.is_valid_cluster(chain.first) {
+            return clusters;
+        }
+
+        if chain.contiguous {
+            let count = chain.size.div_ceil(self.cluster_size() as u64) as u32;
+            for i in 0..count.min(self.bs.cluster_count + 2 - chain.first) {
+                clusters.push(chain.first + i);
+            }
+        } else {
+            let mut clust = chain.first;
+            while self.is_valid_cluster(clust) && clusters.len() < self.bs.cluster_count as usize {
+                clusters.push(clust);
+                clust = self.get_fat_entry(clust);
+                if clust == EXFAT_EOC || clust == EXFAT_BAD {
+                    break;
+                }
+            }
+        }
+
+        clusters
+    }
+
+    // Resize the chain to the count of clusters, the contiguous chain is
+    // converted to fat chain when the next cluster is used
+    pub fn resize(&mut self, chain: &mut ExfatChain, count: u32) -> bool {
+        let mut clusters = self.clusters(chain);
+        let cluster_size = self.cluster_size() as u64;
+
+        // Shrink
+        if clusters.len() > count as usize {
+            for clust in clusters.drain(count as usize..) {
+                self.set_used(clust, false);
+                self.free_count += 1;
+            }
+            if let Some(last) = clusters.last() {
+                if !chain.contiguous {
+                    self.set_fat_entry(*last, EXFAT_EOC);
+                }
+            } else {
+                chain.first = 0;
+                chain.contiguous = true;
+            }
+        }
+
+        // Grow
+        while clusters.len() < count as usize {
+            if self.free_count == 0 {
+                chain.size = clusters.len() as u64 * cluster_size;
+                return false;
+            }
+
+            let last = clusters.last().copied();
+            let next = last.map(|c| c + 1).unwrap_or(2);
+            let clust = if self.is_valid_cluster(next) && !self.is_used(next) {
+                next
+            } else {
+                self.find_free_cluster(next)
+            };
+            self.take_cluster(clust);
+
+            match last {
+                Some(last) => {
+                    if chain.contiguous && clust != last + 1 {
+                        for pair in clusters.windows(2) {
+                            self.set_fat_entry(pair[0], pair[1]);
+                        }
+                        chain.contiguous = false;
+                    }
+                    if !chain.contiguous {
+                        self.set_fat_entry(last, clust);
+                        self.set_fat_entry(clust, EXFAT_EOC);
+                    }
+                }
+                None => {
+                    chain.first = clust;
+                    if !chain.contiguous {
+                        self.set_fat_entry(clust, EXFAT_EOC);
+                    }
+                }
+            }
+            clusters.push(clust);
+        }
+
+        chain.size = clusters.len() as u64 * cluster_size;
+        true
+    }
+
+    // Read chain data at offset, the data is limited by the allocated clusters
+    pub fn read_chain(&mut self, chain: &ExfatChain, data: &mut [u8], offset: u64) -> usize {
+        let cluster_size = self.cluster_size() as u64;
+        let mut buff = vec![0u8; cluster_size as usize];
+        let mut done = 0;
+
+        for (i, clust) in self.clusters(chain).into_iter().enumerate() {
+            let start = i as u64 * cluster_size;
+            let end = start + cluster_size;
+            let pos = offset + done as u64;
+            if done >= data.len() {
+                break;
+            }
+            if pos >= end {
+                continue;
+            }
+
+            let inner = (pos - start) as usize;
+            let len = (cluster_size as usize - inner).min(data.len() - done);
+            let sector = self.cluster_to_sector(clust);
+            self.read_sector(&mut buff, sector, self.sec_per_clust);
+            data[done..done + len].copy_from_slice(&buff[inner..inner + len]);
+            done += len;
+        }
+
+        done
+    }
+
+    // Write chain data at offset, the clusters must be allocated
+    pub fn write_chain(&mut self, chain: &ExfatChain, data: &[u8], offset: u64) -> usize {
+        let cluster_size = self.cluster_size() as u64;
+        let mut buff = vec![0u8; cluster_size as usize];
+        let mut done = 0;
+
+        for (i, clust) in self.clusters(chain).into_iter().enumerate() {
+            let start = i as u64 * cluster_size;
+            let end = start + cluster_size;
+            let pos = offset + done as u64;
+            if done >= data.len() {
+                break;
+            }
+            if pos >= end {
+                continue;
+            }
+
+            // Write sectors directly, the partial sectors are read before written
+            let inner = (pos - start) as usize;
+            let len = (cluster_size as usize - inner).min(data.len() - done);
+            let bytes_per_sec = self.bytes_per_sec as usize;
+            let fst_sec = inner / bytes_per_sec;
+            let end_sec = (inner + len).div_ceil(bytes_per_sec);
+            let sectors = &mut buff[fst_sec * bytes_per_sec..end_sec * bytes_per_sec];
+            let sector = self.cluster_to_sector(clust) + fst_sec as u32;
+            let count = (end_sec - fst_sec) as u32;
+
+            if inner % bytes_per_sec != 0 || len % bytes_per_sec != 0 {
+                self.read_sector(sectors, sector, count);
+            }
+            let skip = inner - fst_sec * bytes_per_sec;
+            sectors[skip..skip + len].copy_from_slice(&data[done..done + len]);
+            self.write_sector(sectors, sector, count);
+            done += len;
+        }
+
+        done
+    }
+
+    // Up case of the character
+    pub fn up_case(&self, ch: u16) -> u16 {
+        match self.upcase.get(ch as usize) {
+            Some(upper) => *upper,
+            None if ch < 0x80 => (ch as u8).to_ascii_uppercase() as u16,
+            None => ch,
+        }
+    }
+
+    // Compare name without case
+    pub fn name_equal(&self, a: &str, b: &str) -> bool {
+        let mut a = a.encode_utf16();
+        let mut b = b.encode_utf16();
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return true,
+                (Some(x), Some(y)) if self.up_case(x) == self.up_case(y) => {}
+                _ => return false,
+            }
+        }
+    }
+
+    // Calc the name hash of up case name
+    pub fn name_hash(&self, name: &str) -> u16 {
+        let mut hash: u16 = 0;
+        for ch in name.encode_utf16() {
+            for byte in self.up_case(ch).to_le_bytes() {
+                hash = hash.rotate_right(1).wrapping_add(byte as u16);
+            }
+        }
+        hash
+    }
+
+    // Write Sector
+    pub fn write_sector(&mut self, data: &[u8], sector: u32, sec_size: u32) -> u32 {
+        self.device.write(
+            data,
+            (sec_size * self.sec_scale) as usize,
+            (sector * self.sec_scale) as usize,
+        ) as u32
+            / self.sec_scale
+    }
+
+    // Read Sector
+    pub fn read_sector(&mut self, data: &mut [u8], sector: u32, sec_size: u32) -> u32 {
+        self.device.read(
+            data,
+            (sec_size * self.sec_scale) as usize,
+            (sector * self.sec_scale) as usize,
+        ) as u32
+            / self.sec_scale
+    }
+}
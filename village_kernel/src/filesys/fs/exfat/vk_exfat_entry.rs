@@ -0,0 +1,298 @@
+//###########################################################################
+// vk_exfat_entry.rs
+// The specific implementation of functions related to exfat entry
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_exfat_diskio::{get_u16, get_u32, get_u64, set_u16, set_u32, set_u64, ExfatChain};
+use crate::filesys::fs::fat::vk_fat_object::FatObject;
+use crate::traits::vk_filesys::{FileAttr, FileFlags, FileTime, FileType};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+pub const DIR_ENTRY_SIZE: usize = 32;
+const NAME_CHARS_PER_ENTRY: usize = 15;
+pub const MAX_NAME_LEN: usize = 255;
+
+// Struct ExfatEntryType
+pub struct ExfatEntryType;
+
+// Impl ExfatEntryType
+impl ExfatEntryType {
+    pub const END_OF_DIR: u8 = 0x00;
+    pub const IN_USE: u8 = 0x80;
+    pub const LABEL: u8 = 0x83;
+    pub const FILE: u8 = 0x85;
+    pub const STREAM: u8 = 0xc0;
+    pub const NAME: u8 = 0xc1;
+}
+
+// Struct ExfatEntryAttr
+pub struct ExfatEntryAttr;
+
+// Impl ExfatEntryAttr
+impl ExfatEntryAttr {
+    pub const READ_ONLY: u16 = 0x01;
+    pub const HIDDEN: u16 = 0x02;
+    pub const SYSTEM: u16 = 0x04;
+    pub const DIRECTORY: u16 = 0x10;
+    pub const ARCHIVE: u16 = 0x20;
+}
+
+// Struct ExfatStreamFlags
+pub struct ExfatStreamFlags;
+
+// Impl ExfatStreamFlags
+impl ExfatStreamFlags {
+    pub const ALLOC_POSSIBLE: u8 = 0x01;
+    pub const NO_FAT_CHAIN: u8 = 0x02;
+}
+
+// Struct ExfatObject, the file entry set
+#[derive(Clone)]
+pub struct ExfatObject {
+    pub name: String,
+    pub attr: u16,
+    pub create_time: u32,
+    pub modify_time: u32,
+    pub access_time: u32,
+    pub valid_size: u64,
+    pub chain: ExfatChain,
+    pub parent: ExfatChain,
+    pub offset: u64,
+    pub count: usize,
+}
+
+// Impl ExfatObject
+impl ExfatObject {
+    // New
+    pub fn new(name: &str, attr: u16) -> Self {
+        Self {
+            name: String::from(name),
+            attr,
+            create_time: 0,
+            modify_time: 0,
+            access_time: 0,
+            valid_size: 0,
+            chain: ExfatChain {
+                first: 0,
+                size: 0,
+                contiguous: true,
+            },
+            parent: ExfatChain::new(),
+            offset: 0,
+            count: 0,
+        }
+    }
+
+    // Root, the root has no entry set
+    pub fn root(chain: ExfatChain) -> Self {
+        let mut obj = Self::new("", ExfatEntryAttr::DIRECTORY);
+        obj.chain = chain;
+        obj.valid_size = chain.size;
+        obj
+    }
+
+    // Is root
+    pub fn is_root(&self) -> bool {
+        self.count == 0
+    }
+
+    // Get the count of entries in set
+    pub fn entry_count(name: &str) -> usize {
+        2 + name.encode_utf16().count().div_ceil(NAME_CHARS_PER_ENTRY)
+    }
+
+    // Calc the checksum of entry set, the checksum field is skipped
+    pub fn checksum(data: &[u8]) -> u16 {
+        let mut checksum: u16 = 0;
+        for (i, byte) in data.iter().enumerate() {
+            if i == 2 || i == 3 {
+                continue;
+            }
+            checksum = checksum.rotate_right(1).wrapping_add(*byte as u16);
+        }
+        checksum
+    }
+
+    // From entry set data, the data starts with the file entry
+    pub fn from(data: &[u8]) -> Option<Self> {
+        if data.len() < DIR_ENTRY_SIZE * 3 || data[0] != ExfatEntryType::FILE {
+            return None;
+        }
+
+        let count = data[1] as usize + 1;
+        if count < 3 || data.len() < count * DIR_ENTRY_SIZE {
+            return None;
+        }
+
+        let set = &data[..count * DIR_ENTRY_SIZE];
+        if Self::checksum(set) != get_u16(set, 2) {
+            return None;
+        }
+
+        // Stream extension entry
+        let stream = &set[DIR_ENTRY_SIZE..DIR_ENTRY_SIZE * 2];
+        if stream[0] != ExfatEntryType::STREAM {
+            return None;
+        }
+
+        // File name entries
+        let name_len = stream[3] as usize;
+        let mut name = Vec::new();
+        for entry in set[DIR_ENTRY_SIZE * 2..].chunks(DIR_ENTRY_SIZE) {
+            if entry[0] != ExfatEntryType::NAME {
+                break;
+            }
+            for i in 0..NAME_CHARS_PER_ENTRY {
+                if name.len() < name_len {
+                    name.push(get_u16(entry, 2 + i * 2));
+                }
+            }
+        }
+
+        let mut obj = Self::new("", get_u16(set, 4));
+        obj.name = String::from_utf16_lossy(&name);
+        obj.create_time = get_u32(set, 8);
+        obj.modify_time = get_u32(set, 12);
+        obj.access_time = get_u32(set, 16);
+        obj.valid_size = get_u64(stream, 8);
+        obj.chain = ExfatChain {
+            first: get_u32(stream, 20),
+            size: get_u64(stream, 24),
+            contiguous: stream[1] & ExfatStreamFlags::NO_FAT_CHAIN != 0,
+        };
+        obj.count = count;
+
+        Some(obj)
+    }
+
+    // To entry set data
+    pub fn to_bytes(&self, name_hash: u16) -> Vec<u8> {
+        let name: Vec<u16> = self.name.encode_utf16().collect();
+        let count = Self::entry_count(&self.name);
+        let mut data = vec![0u8; count * DIR_ENTRY_SIZE];
+
+        // File entry
+        data[0] = ExfatEntryType::FILE;
+        data[1] = (count - 1) as u8;
+        set_u16(&mut data, 4, self.attr);
+        set_u32(&mut data, 8, self.create_time);
+        set_u32(&mut data, 12, self.modify_time);
+        set_u32(&mut data, 16, self.access_time);
+
+        // Stream extension entry, the empty file has no chain
+        let mut flags = ExfatStreamFlags::ALLOC_POSSIBLE;
+        if self.chain.contiguous && self.chain.first != 0 {
+            flags |= ExfatStreamFlags::NO_FAT_CHAIN;
+        }
+
+        let stream = &mut data[DIR_ENTRY_SIZE..DIR_ENTRY_SIZE * 2];
+        stream[0] = ExfatEntryType::STREAM;
+        stream[1] = flags;
+        stream[3] = name.len() as u8;
+        set_u16(stream, 4, name_hash);
+        set_u64(stream, 8, self.valid_size);
+        set_u32(stream, 20, self.chain.first);
+        set_u64(stream, 24, self.chain.size);
+
+        // File name entries
+        for (i, chars) in name.chunks(NAME_CHARS_PER_ENTRY).enumerate() {
+            let offset = DIR_ENTRY_SIZE * (2 + i);
+            data[offset] = ExfatEntryType::NAME;
+            for (j, ch) in chars.iter().enumerate() {
+                set_u16(&mut data, offset + 2 + j * 2, *ch);
+            }
+        }
+
+        let checksum = Self::checksum(&data);
+        set_u16(&mut data, 2, checksum);
+
+        data
+    }
+
+    // Is valid name
+    pub fn is_valid_name(name: &str) -> bool {
+        let invalid = ['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+        let len = name.encode_utf16().count();
+        len > 0
+            && len <= MAX_NAME_LEN
+            && name != "."
+            && name != ".."
+            && !name.chars().any(|c| (c as u32) < 0x20 || invalid.contains(&c))
+    }
+}
+
+// Impl ExfatObject
+impl ExfatObject {
+    // Get object type
+    pub fn get_object_type(&self) -> FileType {
+        if self.attr & ExfatEntryAttr::DIRECTORY != 0 {
+            FileType::Directory
+        } else {
+            FileType::File
+        }
+    }
+
+    // Get object attr
+    pub fn get_object_attr(&self) -> FileAttr {
+        if self.attr & ExfatEntryAttr::HIDDEN != 0 {
+            FileAttr::Hidden
+        } else {
+            FileAttr::Visible
+        }
+    }
+
+    // Get flags
+    pub fn get_flags(&self) -> FileFlags {
+        let mut flags = FileFlags::NONE;
+
+        if (self.attr & ExfatEntryAttr::READ_ONLY) != 0 {
+            flags.insert(FileFlags::READ_ONLY);
+        }
+        if (self.attr & ExfatEntryAttr::HIDDEN) != 0 {
+            flags.insert(FileFlags::HIDDEN);
+        }
+        if (self.attr & ExfatEntryAttr::SYSTEM) != 0 {
+            flags.insert(FileFlags::SYSTEM);
+        }
+        if (self.attr & ExfatEntryAttr::ARCHIVE) != 0 {
+            flags.insert(FileFlags::ARCHIVE);
+        }
+
+        flags
+    }
+
+    // Set flags, the directory bit is kept
+    pub fn set_flags(&mut self, flags: FileFlags) {
+        let mut attr = self.attr & ExfatEntryAttr::DIRECTORY;
+
+        if flags.contains(FileFlags::READ_ONLY) {
+            attr |= ExfatEntryAttr::READ_ONLY;
+        }
+        if flags.contains(FileFlags::HIDDEN) {
+            attr |= ExfatEntryAttr::HIDDEN;
+        }
+        if flags.contains(FileFlags::SYSTEM) {
+            attr |= ExfatEntryAttr::SYSTEM;
+        }
+        if flags.contains(FileFlags::ARCHIVE) {
+            attr |= ExfatEntryAttr::ARCHIVE;
+        }
+
+        self.attr = attr;
+    }
+
+    // Decode timestamp, the date is in high half same as fat
+    pub fn decode_time(timestamp: u32) -> FileTime {
+        FatObject::decode_time((timestamp >> 16) as u16, timestamp as u16)
+    }
+
+    // Encode timestamp
+    pub fn encode_time(time: &FileTime) -> u32 {
+        let (date, time) = FatObject::encode_time(time);
+        ((date as u32) << 16) | time as u32
+    }
+}
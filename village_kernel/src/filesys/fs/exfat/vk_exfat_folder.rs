@@ -0,0 +1,249 @@
+//###########################################################################
+// vk_exfat_folder.rs
+// The specific implementation of functions related to exfat folder
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_exfat_diskio::{get_u16, set_u16, ExfatChain, ExfatDiskio};
+use super::vk_exfat_entry::{ExfatEntryAttr, ExfatEntryType, ExfatObject, DIR_ENTRY_SIZE};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const LABEL_MAX_LEN: usize = 11;
+
+// Struct ExfatFolder
+pub struct ExfatFolder;
+
+// Impl ExfatFolder
+impl ExfatFolder {
+    // Read the directory data
+    fn read_dir(diskio: &mut ExfatDiskio, dir: &ExfatChain) -> Vec<u8> {
+        let mut data = vec![0u8; dir.size as usize];
+        let size = diskio.read_chain(dir, &mut data, 0);
+        data.truncate(size);
+        data
+    }
+
+    // Find the offset of free entries, the directory is extended when no enough entries
+    fn alloc_entries(diskio: &mut ExfatDiskio, dir: &mut ExfatObject, count: usize) -> Option<u64> {
+        let data = Self::read_dir(diskio, &dir.chain);
+
+        let mut run_start = 0;
+        let mut run_len = 0;
+        for (i, entry) in data.chunks(DIR_ENTRY_SIZE).enumerate() {
+            if entry[0] & ExfatEntryType::IN_USE != 0 {
+                run_len = 0;
+                continue;
+            }
+            if run_len == 0 {
+                run_start = i * DIR_ENTRY_SIZE;
+            }
+            run_len += 1;
+            if run_len == count {
+                return Some(run_start as u64);
+            }
+        }
+
+        // Extend the directory with zeroed clusters
+        let offset = if run_len > 0 { run_start } else { data.len() } as u64;
+        let need = offset + (count * DIR_ENTRY_SIZE) as u64;
+        let clusters = need.div_ceil(diskio.cluster_size() as u64) as u32;
+        if !diskio.resize(&mut dir.chain, clusters) {
+            return None;
+        }
+        dir.valid_size = dir.chain.size;
+
+        // Update the size of directory, the root has no entry set
+        if !dir.is_root() {
+            Self::update(diskio, dir);
+        }
+
+        Some(offset)
+    }
+
+    // Write the entry set of object
+    fn write_entries(diskio: &mut ExfatDiskio, obj: &ExfatObject) {
+        let data = obj.to_bytes(diskio.name_hash(&obj.name));
+        diskio.write_chain(&obj.parent, &data, obj.offset);
+    }
+}
+
+// Impl ExfatFolder
+impl ExfatFolder {
+    // Read the objects in directory
+    pub fn entries(diskio: &mut ExfatDiskio, dir: &ExfatObject) -> Vec<ExfatObject> {
+        let data = Self::read_dir(diskio, &dir.chain);
+        let mut objs = Vec::new();
+        let mut offset = 0;
+
+        while offset + DIR_ENTRY_SIZE <= data.len() {
+            let typid = data[offset];
+            if typid == ExfatEntryType::END_OF_DIR {
+                break;
+            }
+
+            if typid == ExfatEntryType::FILE {
+                if let Some(mut obj) = ExfatObject::from(&data[offset..]) {
+                    obj.parent = dir.chain;
+                    obj.offset = offset as u64;
+                    offset += obj.count * DIR_ENTRY_SIZE;
+                    objs.push(obj);
+                    continue;
+                }
+            }
+
+            offset += DIR_ENTRY_SIZE;
+        }
+
+        objs
+    }
+
+    // Search object by name, the name is not case sensitive
+    pub fn search(diskio: &mut ExfatDiskio, dir: &ExfatObject, name: &str) -> Option<ExfatObject> {
+        if dir.attr & ExfatEntryAttr::DIRECTORY == 0 {
+            return None;
+        }
+        Self::entries(diskio, dir)
+            .into_iter()
+            .find(|obj| diskio.name_equal(&obj.name, name))
+    }
+
+    // Is empty
+    pub fn is_empty(diskio: &mut ExfatDiskio, dir: &ExfatObject) -> bool {
+        Self::entries(diskio, dir).is_empty()
+    }
+
+    // Create object, the directory has one zeroed cluster
+    pub fn create(diskio: &mut ExfatDiskio, dir: &mut ExfatObject, name: &str, attr: u16) -> Option<ExfatObject> {
+        if !ExfatObject::is_valid_name(name) || Self::search(diskio, dir, name).is_some() {
+            return None;
+        }
+
+        let mut obj = ExfatObject::new(name, attr);
+
+        if attr & ExfatEntryAttr::DIRECTORY != 0 {
+            if !diskio.resize(&mut obj.chain, 1) {
+                return None;
+            }
+            obj.valid_size = obj.chain.size;
+        }
+
+        match Self::alloc_entries(diskio, dir, ExfatObject::entry_count(name)) {
+            Some(offset) => {
+                obj.parent = dir.chain;
+                obj.offset = offset;
+                obj.count = ExfatObject::entry_count(name);
+                Self::write_entries(diskio, &obj);
+                Some(obj)
+            }
+            None => {
+                diskio.resize(&mut obj.chain, 0);
+                None
+            }
+        }
+    }
+
+    // Update the entry set of object
+    pub fn update(diskio: &mut ExfatDiskio, obj: &ExfatObject) {
+        if !obj.is_root() {
+            Self::write_entries(diskio, obj);
+        }
+    }
+
+    // Remove the entry set of object, the clusters are not freed
+    pub fn remove(diskio: &mut ExfatDiskio, obj: &ExfatObject) {
+        if obj.is_root() {
+            return;
+        }
+
+        let mut data = vec![0u8; obj.count * DIR_ENTRY_SIZE];
+        diskio.read_chain(&obj.parent, &mut data, obj.offset);
+        for entry in data.chunks_mut(DIR_ENTRY_SIZE) {
+            entry[0] &= !ExfatEntryType::IN_USE;
+        }
+        diskio.write_chain(&obj.parent, &data, obj.offset);
+    }
+
+    // Rename object, the new entry set is written before the old one removed
+    pub fn rename(diskio: &mut ExfatDiskio, obj: ExfatObject, dir: &mut ExfatObject, name: &str) -> Option<ExfatObject> {
+        if !ExfatObject::is_valid_name(name) {
+            return None;
+        }
+
+        let count = ExfatObject::entry_count(name);
+        let offset = Self::alloc_entries(diskio, dir, count)?;
+
+        let mut newobj = obj.clone();
+        newobj.name = String::from(name);
+        newobj.parent = dir.chain;
+        newobj.offset = offset;
+        newobj.count = count;
+        Self::write_entries(diskio, &newobj);
+
+        // The old parent may be extended when it is the same directory
+        let mut oldobj = obj;
+        if oldobj.parent.first == dir.chain.first {
+            oldobj.parent = dir.chain;
+        }
+        Self::remove(diskio, &oldobj);
+
+        Some(newobj)
+    }
+
+    // Get volume label
+    pub fn get_vol_lab(diskio: &mut ExfatDiskio) -> String {
+        let root = diskio.root_chain();
+        let data = Self::read_dir(diskio, &root);
+
+        for entry in data.chunks(DIR_ENTRY_SIZE) {
+            match entry[0] {
+                ExfatEntryType::END_OF_DIR => break,
+                ExfatEntryType::LABEL => {
+                    let len = (entry[1] as usize).min(LABEL_MAX_LEN);
+                    let chars: Vec<u16> = (0..len).map(|i| get_u16(entry, 2 + i * 2)).collect();
+                    return String::from_utf16_lossy(&chars);
+                }
+                _ => {}
+            }
+        }
+
+        String::new()
+    }
+
+    // Set volume label, the label entry is created when not exists
+    pub fn set_vol_lab(diskio: &mut ExfatDiskio, name: &str) -> bool {
+        let chars: Vec<u16> = name.encode_utf16().collect();
+        if chars.len() > LABEL_MAX_LEN {
+            return false;
+        }
+
+        let mut root = ExfatObject::root(diskio.root_chain());
+        let data = Self::read_dir(diskio, &root.chain);
+
+        // The label entry without in use bit is an empty label
+        let label = ExfatEntryType::LABEL & !ExfatEntryType::IN_USE;
+        let someoffset = data
+            .chunks(DIR_ENTRY_SIZE)
+            .take_while(|entry| entry[0] != ExfatEntryType::END_OF_DIR)
+            .position(|entry| entry[0] == ExfatEntryType::LABEL || entry[0] == label)
+            .map(|pos| (pos * DIR_ENTRY_SIZE) as u64);
+
+        let offset = match someoffset {
+            Some(offset) => offset,
+            None => match Self::alloc_entries(diskio, &mut root, 1) {
+                Some(offset) => offset,
+                None => return false,
+            },
+        };
+
+        let mut entry = [0u8; DIR_ENTRY_SIZE];
+        entry[0] = if chars.is_empty() { label } else { ExfatEntryType::LABEL };
+        entry[1] = chars.len() as u8;
+        for (i, ch) in chars.iter().enumerate() {
+            set_u16(&mut entry, 2 + i * 2, *ch);
+        }
+        diskio.write_chain(&root.chain, &entry, offset) == DIR_ENTRY_SIZE
+    }
+}
@@ -0,0 +1,480 @@
+//###########################################################################
+// vk_exfat_system.rs
+// The specific implementation of functions related to exfat system
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_exfat_diskio::ExfatDiskio;
+use super::vk_exfat_entry::{ExfatEntryAttr, ExfatObject};
+use super::vk_exfat_folder::ExfatFolder;
+use crate::traits::vk_filesys::{FileDir, FileMode, FileSys, FileType, FileVol};
+use crate::traits::vk_filesys::{FileCheck, FileFlags, FileStat, FileTime};
+use crate::traits::vk_linkedlist::LinkedList;
+use crate::register_filesys;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Struct ExfatFile
+struct ExfatFile {
+    file_id: usize,
+    object: ExfatObject,
+}
+
+// Struct ExfatDir
+struct ExfatDir {
+    dir_id: usize,
+    sub_dirs: Vec<FileDir>,
+}
+
+// Struct ExfatVolume
+struct ExfatVolume {
+    diskio: ExfatDiskio,
+    mount_path: String,
+    dir_cnt: usize,
+    file_cnt: usize,
+    dirs: LinkedList<ExfatDir>,
+    files: LinkedList<ExfatFile>,
+}
+
+// Impl ExfatVolume
+impl ExfatVolume {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            diskio: ExfatDiskio::new(),
+            mount_path: String::new(),
+            dir_cnt: 1,
+            file_cnt: 1,
+            dirs: LinkedList::new(),
+            files: LinkedList::new(),
+        }
+    }
+}
+
+// Impl ExfatVolume
+impl ExfatVolume {
+    // Assign file id
+    fn assign_file_id(&mut self) -> usize {
+        let id = self.file_cnt;
+        self.file_cnt += 1;
+        id
+    }
+
+    // Assign dir id
+    fn assign_dir_id(&mut self) -> usize {
+        let id = self.dir_cnt;
+        self.dir_cnt += 1;
+        id
+    }
+
+    // Get Base name
+    fn base_name(&mut self, path: &str) -> String {
+        let path = path.trim_end_matches('/');
+        match path.rfind('/') {
+            Some(pos) => path[pos + 1..].to_string(),
+            None => path.to_string(),
+        }
+    }
+
+    // Search path
+    fn search_path(&mut self, path: &str, reserve: usize) -> Option<ExfatObject> {
+        let names: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let deep = names.len().checked_sub(reserve)?;
+
+        let mut obj = ExfatObject::root(self.diskio.root_chain());
+
+        for name in names.iter().take(deep) {
+            obj = ExfatFolder::search(&mut self.diskio, &obj, name)?;
+        }
+
+        Some(obj)
+    }
+
+    // Create path
+    fn create_path(&mut self, path: &str, attr: u16) -> Option<ExfatObject> {
+        let mut parent = self.search_path(path, 1)?;
+        if parent.get_object_type() == FileType::Directory {
+            let name = self.base_name(path);
+            return ExfatFolder::create(&mut self.diskio, &mut parent, &name, attr);
+        }
+        None
+    }
+
+    // Delete path, the directory must be empty
+    fn delete_path(&mut self, path: &str) -> bool {
+        if let Some(mut obj) = self.search_entry(path) {
+            if obj.get_object_type() == FileType::Directory && !ExfatFolder::is_empty(&mut self.diskio, &obj) {
+                return false;
+            }
+
+            ExfatFolder::remove(&mut self.diskio, &obj);
+
+            // Free the clusters
+            self.diskio.resize(&mut obj.chain, 0);
+            return true;
+        }
+        false
+    }
+
+    // Rename path
+    fn rename_path(&mut self, source: &str, target: &str) -> bool {
+        // Can not move a directory into itself
+        let source_dir = format!("{}/", source.trim_end_matches('/'));
+        if target.starts_with(&source_dir) {
+            return false;
+        }
+
+        // The target must not exist
+        if self.search_path(target, 0).is_some() {
+            return false;
+        }
+
+        if let Some(srcobj) = self.search_entry(source) {
+            if let Some(mut parent) = self.search_path(target, 1) {
+                if parent.get_object_type() == FileType::Directory {
+                    let name = self.base_name(target);
+                    return ExfatFolder::rename(&mut self.diskio, srcobj, &mut parent, &name).is_some();
+                }
+            }
+        }
+        false
+    }
+
+    // Search the object can be updated, the root has no entry
+    fn search_entry(&mut self, name: &str) -> Option<ExfatObject> {
+        let names = name.split('/').filter(|s| !s.is_empty()).count();
+        if names == 0 {
+            return None;
+        }
+        self.search_path(name, 0)
+    }
+
+    // Get object stat
+    fn object_stat(&mut self, obj: &ExfatObject) -> FileStat {
+        let mut stat = FileStat::new();
+
+        stat.name = obj.name.clone();
+        stat.typid = obj.get_object_type();
+        stat.flags = obj.get_flags();
+        stat.size = obj.chain.size;
+        stat.clusters = self.diskio.clusters(&obj.chain).len() as u32;
+        stat.create_time = ExfatObject::decode_time(obj.create_time);
+        stat.write_time = ExfatObject::decode_time(obj.modify_time);
+        stat.access_time = ExfatObject::decode_time(obj.access_time);
+
+        stat
+    }
+}
+
+// Impl ExfatVolume
+impl FileVol for ExfatVolume {
+    // Setup
+    fn setup(&mut self, disk: &str) -> bool {
+        self.diskio.setup(disk)
+    }
+
+    // Exit
+    fn exit(&mut self) {
+        self.files.clear();
+        self.dirs.clear();
+        self.diskio.exit();
+    }
+
+    // Set mount path
+    fn set_mount_path(&mut self, path: &str) {
+        self.mount_path = path.to_string();
+    }
+
+    // Get mount path
+    fn get_mount_path(&mut self) -> &str {
+        &self.mount_path
+    }
+
+    // Set name
+    fn set_name(&mut self, name: &str) -> bool {
+        ExfatFolder::set_vol_lab(&mut self.diskio, name)
+    }
+
+    // Get name
+    fn get_name(&mut self) -> String {
+        let name = ExfatFolder::get_vol_lab(&mut self.diskio);
+        if name.is_empty() {
+            return "EXFAT".to_string();
+        }
+        name
+    }
+
+    // Get total size
+    fn get_total_size(&mut self) -> u64 {
+        self.diskio.cluster_count() as u64 * self.diskio.cluster_size() as u64
+    }
+
+    // Get free size
+    fn get_free_size(&mut self) -> u64 {
+        self.diskio.free_count() as u64 * self.diskio.cluster_size() as u64
+    }
+
+    // Open
+    fn open(&mut self, name: &str, mode: FileMode) -> usize {
+        // Search or create path
+        let someobj = match self.search_path(name, 0) {
+            Some(obj) => Some(obj),
+            None if mode.contains(FileMode::CREATE_NEW) => self.create_path(name, ExfatEntryAttr::ARCHIVE),
+            None => None,
+        };
+
+        // Open file
+        if let Some(object) = someobj {
+            if object.get_object_type() != FileType::File {
+                return 0;
+            }
+
+            let fd = self.assign_file_id();
+            self.files.push(ExfatFile {
+                file_id: fd,
+                object,
+            });
+
+            // Truncate the file to zero length
+            if mode.contains(FileMode::TRUNCATE) && !self.set_len(fd, 0) {
+                self.close(fd);
+                return 0;
+            }
+            return fd;
+        }
+        0
+    }
+
+    // Write
+    fn write(&mut self, fd: usize, data: &[u8], size: usize, offset: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            let obj = &mut file.object;
+            let offset = offset as u64;
+
+            // Resize the clusters when the data is written after the end
+            let size = size.min(data.len());
+            let end = offset + size as u64;
+            let new_size = obj.chain.size.max(end);
+            let clusters = new_size.div_ceil(self.diskio.cluster_size() as u64) as u32;
+            let old_size = obj.chain.size;
+            if !self.diskio.resize(&mut obj.chain, clusters) {
+                obj.chain.size = obj.chain.size.min(old_size);
+                obj.valid_size = obj.valid_size.min(obj.chain.size);
+                ExfatFolder::update(&mut self.diskio, obj);
+                return 0;
+            }
+
+            // The data after valid size is undefined on disk
+            if offset > obj.valid_size {
+                let zero = vec![0u8; (offset - obj.valid_size) as usize];
+                self.diskio.write_chain(&obj.chain, &zero, obj.valid_size);
+            }
+
+            let written = self.diskio.write_chain(&obj.chain, &data[..size], offset);
+
+            obj.chain.size = new_size;
+            obj.valid_size = obj.valid_size.max(end);
+            ExfatFolder::update(&mut self.diskio, obj);
+
+            return written;
+        }
+        0
+    }
+
+    // Read
+    fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            let obj = &file.object;
+            let offset = offset as u64;
+            if offset >= obj.chain.size {
+                return 0;
+            }
+
+            let size = (size.min(data.len()) as u64).min(obj.chain.size - offset) as usize;
+            let readed = self.diskio.read_chain(&obj.chain, &mut data[..size], offset);
+
+            // The data after valid size reads as zero
+            if offset + readed as u64 > obj.valid_size {
+                let start = obj.valid_size.saturating_sub(offset) as usize;
+                data[start..readed].fill(0);
+            }
+            return readed;
+        }
+        0
+    }
+
+    // Size
+    fn size(&mut self, fd: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            return file.object.chain.size as usize;
+        }
+        0
+    }
+
+    // Set len, the data after valid size reads as zero
+    fn set_len(&mut self, fd: usize, size: usize) -> bool {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            let obj = &mut file.object;
+            let size = size as u64;
+            let clusters = size.div_ceil(self.diskio.cluster_size() as u64) as u32;
+            let old_size = obj.chain.size;
+            let result = self.diskio.resize(&mut obj.chain, clusters);
+
+            obj.chain.size = if result { size } else { obj.chain.size.min(old_size) };
+            obj.valid_size = obj.valid_size.min(obj.chain.size);
+            ExfatFolder::update(&mut self.diskio, obj);
+            return result;
+        }
+        false
+    }
+
+    // Flush
+    fn flush(&mut self, fd: usize) {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            ExfatFolder::update(&mut self.diskio, &file.object);
+        }
+    }
+
+    // Close
+    fn close(&mut self, fd: usize) {
+        self.files.retain_mut(|file| file.file_id != fd);
+    }
+
+    // Open dir
+    fn opendir(&mut self, name: &str, mode: FileMode) -> usize {
+        // Search or create path
+        let someobj = match self.search_path(name, 0) {
+            Some(obj) => Some(obj),
+            None if mode.contains(FileMode::CREATE_NEW) => self.create_path(name, ExfatEntryAttr::DIRECTORY),
+            None => None,
+        };
+
+        // Open dir
+        if let Some(obj) = someobj {
+            if obj.get_object_type() != FileType::Directory {
+                return 0;
+            }
+
+            let mut sub_dirs = Vec::new();
+            for sub in ExfatFolder::entries(&mut self.diskio, &obj) {
+                let mut dir = FileDir::new();
+                dir.typid = sub.get_object_type();
+                dir.attr = sub.get_object_attr();
+                dir.name = sub.name;
+                sub_dirs.push(dir);
+            }
+
+            let fd = self.assign_dir_id();
+            self.dirs.push(ExfatDir { dir_id: fd, sub_dirs });
+            return fd;
+        }
+        0
+    }
+
+    // Read dir
+    fn readdir(&mut self, fd: usize, dirs: &mut [FileDir], size: usize, offset: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            let remain = dir.sub_dirs.len().saturating_sub(offset);
+            let size = size.min(dirs.len()).min(remain);
+            dirs[..size].clone_from_slice(&dir.sub_dirs[offset..offset + size]);
+            return size;
+        }
+        0
+    }
+
+    // Size dir
+    fn sizedir(&mut self, fd: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            return dir.sub_dirs.len();
+        }
+        0
+    }
+
+    // Close dir
+    fn closedir(&mut self, fd: usize) {
+        self.dirs.retain_mut(|dir| dir.dir_id != fd);
+    }
+
+    // Stat
+    fn stat(&mut self, name: &str) -> Option<FileStat> {
+        let obj = self.search_path(name, 0)?;
+        Some(self.object_stat(&obj))
+    }
+
+    // Fstat
+    fn fstat(&mut self, fd: usize) -> Option<FileStat> {
+        let obj = self.files.iter_mut().find(|f| f.file_id == fd)?.object.clone();
+        Some(self.object_stat(&obj))
+    }
+
+    // Set flags
+    fn set_flags(&mut self, name: &str, flags: FileFlags) -> bool {
+        if let Some(mut obj) = self.search_entry(name) {
+            obj.set_flags(flags);
+            ExfatFolder::update(&mut self.diskio, &obj);
+            return true;
+        }
+        false
+    }
+
+    // Set time
+    fn set_time(&mut self, name: &str, write_time: FileTime, access_time: FileTime) -> bool {
+        if let Some(mut obj) = self.search_entry(name) {
+            if write_time.is_valid() {
+                obj.modify_time = ExfatObject::encode_time(&write_time);
+            }
+            if access_time.is_valid() {
+                obj.access_time = ExfatObject::encode_time(&access_time);
+            }
+            ExfatFolder::update(&mut self.diskio, &obj);
+            return true;
+        }
+        false
+    }
+
+    // Is exist
+    fn exist(&mut self, name: &str, typeid: FileType) -> bool {
+        if let Some(obj) = self.search_path(name, 0) {
+            return obj.get_object_type() == typeid;
+        }
+        false
+    }
+
+    // Remove
+    fn remove(&mut self, name: &str) -> bool {
+        self.delete_path(name)
+    }
+
+    // Rename
+    fn rename(&mut self, source: &str, target: &str) -> bool {
+        self.rename_path(source, target)
+    }
+
+    // Check, the consistency check is not supported
+    fn check(&mut self, _repair: bool) -> Option<FileCheck> {
+        None
+    }
+}
+
+// Struct exfat system
+struct ExfatSystem;
+
+// Impl filesys for exfat system
+impl FileSys for ExfatSystem {
+    // Get file system id
+    fn file_system_id(&self) -> usize {
+        const SYSTEM_ID: usize = 7;
+        SYSTEM_ID
+    }
+
+    // Create volume
+    fn create_volume(&mut self) -> Box<dyn FileVol> {
+        Box::new(ExfatVolume::new())
+    }
+}
+
+// Register filesys
+register_filesys!(ExfatSystem, exfat);
@@ -0,0 +1,538 @@
+//###########################################################################
+// vk_ext2_diskio.rs
+// The specific implementation of functions related to ext2 diskio
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+pub const ROOT_INODE: u32 = 2;
+const SECTOR_SIZE: u32 = 512;
+const SUPER_SECTOR: u32 = 2;
+const SUPER_SIZE: usize = 1024;
+const EXT2_MAGIC: u16 = 0xef53;
+const GROUP_DESC_SIZE: usize = 32;
+const GOOD_OLD_INODE_SIZE: u32 = 128;
+const GOOD_OLD_FIRST_INODE: u32 = 11;
+
+// Struct Ext2Feature
+pub struct Ext2Feature;
+
+// Impl Ext2Feature
+impl Ext2Feature {
+    pub const INCOMPAT_FILETYPE: u32 = 0x0002;
+    pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+    pub const RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+    pub const RO_COMPAT_BTREE_DIR: u32 = 0x0004;
+}
+
+// Struct SuperBlock
+pub struct SuperBlock {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub free_blocks_count: u32,
+    pub free_inodes_count: u32,
+    pub first_data_block: u32,
+    pub log_block_size: u32,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub magic: u16,
+    pub rev_level: u32,
+    pub first_ino: u32,
+    pub inode_size: u32,
+    pub feature_compat: u32,
+    pub feature_incompat: u32,
+    pub feature_ro_compat: u32,
+    pub volume_name: [u8; 16],
+}
+
+// Impl SuperBlock
+impl SuperBlock {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            inodes_count: 0,
+            blocks_count: 0,
+            free_blocks_count: 0,
+            free_inodes_count: 0,
+            first_data_block: 0,
+            log_block_size: 0,
+            blocks_per_group: 0,
+            inodes_per_group: 0,
+            magic: 0,
+            rev_level: 0,
+            first_ino: 0,
+            inode_size: 0,
+            feature_compat: 0,
+            feature_incompat: 0,
+            feature_ro_compat: 0,
+            volume_name: [0u8; 16],
+        }
+    }
+
+    // From
+    pub fn from(data: &[u8]) -> Option<Self> {
+        if data.len() < SUPER_SIZE {
+            return None;
+        }
+
+        let mut sb = Self::new();
+
+        sb.inodes_count = get_u32(data, 0);
+        sb.blocks_count = get_u32(data, 4);
+        sb.free_blocks_count = get_u32(data, 12);
+        sb.free_inodes_count = get_u32(data, 16);
+        sb.first_data_block = get_u32(data, 20);
+        sb.log_block_size = get_u32(data, 24);
+        sb.blocks_per_group = get_u32(data, 32);
+        sb.inodes_per_group = get_u32(data, 40);
+        sb.magic = get_u16(data, 56);
+        sb.rev_level = get_u32(data, 76);
+
+        // The dynamic revision has variable inode size
+        if sb.rev_level == 0 {
+            sb.first_ino = GOOD_OLD_FIRST_INODE;
+            sb.inode_size = GOOD_OLD_INODE_SIZE;
+        } else {
+            sb.first_ino = get_u32(data, 84);
+            sb.inode_size = get_u16(data, 88) as u32;
+            sb.feature_compat = get_u32(data, 92);
+            sb.feature_incompat = get_u32(data, 96);
+            sb.feature_ro_compat = get_u32(data, 100);
+        }
+
+        sb.volume_name.copy_from_slice(&data[120..136]);
+
+        // Check is valid
+        if sb.magic != EXT2_MAGIC
+            || sb.log_block_size > 2
+            || sb.blocks_per_group == 0
+            || sb.inodes_per_group == 0
+            || sb.inode_size < GOOD_OLD_INODE_SIZE
+        {
+            return None;
+        }
+
+        // Check the geometry, the bitmap of group is one block
+        let block_size = 1024u32 << sb.log_block_size;
+        if sb.first_data_block >= sb.blocks_count
+            || sb.blocks_per_group > block_size * 8
+            || sb.inodes_per_group > block_size * 8
+            || sb.inode_size > block_size
+        {
+            return None;
+        }
+
+        Some(sb)
+    }
+
+    // Update the changed fields into data
+    pub fn update(&self, data: &mut [u8]) {
+        set_u32(data, 12, self.free_blocks_count);
+        set_u32(data, 16, self.free_inodes_count);
+        data[120..136].copy_from_slice(&self.volume_name);
+    }
+}
+
+// Struct GroupDesc
+#[derive(Clone)]
+pub struct GroupDesc {
+    pub block_bitmap: u32,
+    pub inode_bitmap: u32,
+    pub inode_table: u32,
+    pub free_blocks_count: u16,
+    pub free_inodes_count: u16,
+    pub used_dirs_count: u16,
+}
+
+// Impl GroupDesc
+impl GroupDesc {
+    // From
+    pub fn from(data: &[u8]) -> Self {
+        Self {
+            block_bitmap: get_u32(data, 0),
+            inode_bitmap: get_u32(data, 4),
+            inode_table: get_u32(data, 8),
+            free_blocks_count: get_u16(data, 12),
+            free_inodes_count: get_u16(data, 14),
+            used_dirs_count: get_u16(data, 16),
+        }
+    }
+
+    // Update the changed fields into data
+    pub fn update(&self, data: &mut [u8]) {
+        set_u16(data, 12, self.free_blocks_count);
+        set_u16(data, 14, self.free_inodes_count);
+        set_u16(data, 16, self.used_dirs_count);
+    }
+}
+
+// Get u16
+pub fn get_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+// Get u32
+pub fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+// Set u16
+pub fn set_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+// Set u32
+pub fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// Struct Ext2Diskio
+pub struct Ext2Diskio {
+    device: BlockFopt,
+    sb: SuperBlock,
+    sb_data: Vec<u8>,
+    groups: Vec<GroupDesc>,
+    gdt_data: Vec<u8>,
+    gdt_block: u32,
+    block_size: u32,
+    read_only: bool,
+}
+
+// Impl Ext2Diskio
+impl Ext2Diskio {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            device: BlockFopt::new(),
+            sb: SuperBlock::new(),
+            sb_data: Vec::new(),
+            groups: Vec::new(),
+            gdt_data: Vec::new(),
+            gdt_block: 0,
+            block_size: 0,
+            read_only: false,
+        }
+    }
+
+    // Setup
+    pub fn setup(&mut self, disk: &str) -> bool {
+
+        if self.device.open(disk) {
+            if self.check_file_system() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        self.device.close();
+        self.groups.clear();
+    }
+}
+
+// Impl Ext2Diskio
+impl Ext2Diskio {
+    // Check file system
+    fn check_file_system(&mut self) -> bool {
+        // Read super block
+        let mut data = vec![0u8; SUPER_SIZE];
+        self.read_sector(&mut data, SUPER_SECTOR, (SUPER_SIZE as u32) / SECTOR_SIZE);
+
+        let sb = match SuperBlock::from(&data) {
+            Some(sb) => sb,
+            None => return false,
+        };
+
+        // Unsupported incompatible features, such as extents and 64bit
+        if (sb.feature_incompat & !Ext2Feature::INCOMPAT_FILETYPE) != 0 {
+            return false;
+        }
+
+        // Unsupported read only compatible features can be read but not written
+        let ro_compat = Ext2Feature::RO_COMPAT_SPARSE_SUPER
+            | Ext2Feature::RO_COMPAT_LARGE_FILE
+            | Ext2Feature::RO_COMPAT_BTREE_DIR;
+        self.read_only = (sb.feature_ro_compat & !ro_compat) != 0;
+
+        self.block_size = 1024 << sb.log_block_size;
+        self.gdt_block = sb.first_data_block + 1;
+
+        // Read group descriptors table
+        let group_count = (sb.blocks_count - sb.first_data_block).div_ceil(sb.blocks_per_group);
+        let gdt_blocks = (group_count * GROUP_DESC_SIZE as u32).div_ceil(self.block_size);
+
+        self.sb = sb;
+        self.sb_data = data;
+
+        let mut gdt_data = vec![0u8; (gdt_blocks * self.block_size) as usize];
+        for i in 0..gdt_blocks {
+            let offset = (i * self.block_size) as usize;
+            let end = offset + self.block_size as usize;
+            self.read_block(&mut gdt_data[offset..end], self.gdt_block + i);
+        }
+
+        self.groups.clear();
+        for i in 0..group_count as usize {
+            let offset = i * GROUP_DESC_SIZE;
+            self.groups.push(GroupDesc::from(&gdt_data[offset..offset + GROUP_DESC_SIZE]));
+        }
+        self.gdt_data = gdt_data;
+
+        true
+    }
+
+    // Write super block and group descriptors table
+    fn write_meta(&mut self, group: usize) {
+        let mut sb_data = core::mem::take(&mut self.sb_data);
+        self.sb.update(&mut sb_data);
+        self.write_sector(&sb_data, SUPER_SECTOR, (SUPER_SIZE as u32) / SECTOR_SIZE);
+        self.sb_data = sb_data;
+
+        let offset = group * GROUP_DESC_SIZE;
+        self.groups[group].update(&mut self.gdt_data[offset..offset + GROUP_DESC_SIZE]);
+
+        let block = offset as u32 / self.block_size;
+        let start = (block * self.block_size) as usize;
+        let end = start + self.block_size as usize;
+        let gdt_data = core::mem::take(&mut self.gdt_data);
+        self.write_block(&gdt_data[start..end], self.gdt_block + block);
+        self.gdt_data = gdt_data;
+    }
+
+    // Find and set a zero bit in bitmap block, the limit is the bits in group
+    fn alloc_bit(&mut self, bitmap: u32, limit: u32, start: u32) -> Option<u32> {
+        let mut data = vec![0u8; self.block_size as usize];
+        self.read_block(&mut data, bitmap);
+
+        for i in start..limit {
+            let (byte, bit) = ((i / 8) as usize, i % 8);
+            if data[byte] & (1 << bit) == 0 {
+                data[byte] |= 1 << bit;
+                self.write_block(&data, bitmap);
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    // Clear the bit in bitmap block, returns whether the bit is set
+    fn free_bit(&mut self, bitmap: u32, index: u32) -> bool {
+        let mut data = vec![0u8; self.block_size as usize];
+        self.read_block(&mut data, bitmap);
+
+        let (byte, bit) = ((index / 8) as usize, index % 8);
+        if data[byte] & (1 << bit) == 0 {
+            return false;
+        }
+
+        data[byte] &= !(1 << bit);
+        self.write_block(&data, bitmap);
+        true
+    }
+
+    // Get the count of blocks in group
+    fn blocks_in_group(&self, group: usize) -> u32 {
+        let start = self.sb.first_data_block + group as u32 * self.sb.blocks_per_group;
+        (self.sb.blocks_count - start).min(self.sb.blocks_per_group)
+    }
+}
+
+// Impl Ext2Diskio
+impl Ext2Diskio {
+    // Get super block
+    pub fn get_super(&mut self) -> &mut SuperBlock {
+        &mut self.sb
+    }
+
+    // Get block size
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    // Get inode size
+    pub fn inode_size(&self) -> u32 {
+        self.sb.inode_size
+    }
+
+    // Is read only
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Has file type in directory entry
+    pub fn has_filetype(&self) -> bool {
+        (self.sb.feature_incompat & Ext2Feature::INCOMPAT_FILETYPE) != 0
+    }
+
+    // Get volume name
+    pub fn get_volume_name(&self) -> String {
+        let len = self.sb.volume_name.iter().position(|c| *c == 0).unwrap_or(16);
+        String::from_utf8_lossy(&self.sb.volume_name[..len]).into_owned()
+    }
+
+    // Set volume name
+    pub fn set_volume_name(&mut self, name: &str) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let bytes = name.as_bytes();
+        let len = bytes.len().min(16);
+        self.sb.volume_name = [0u8; 16];
+        self.sb.volume_name[..len].copy_from_slice(&bytes[..len]);
+        self.write_meta(0);
+        true
+    }
+
+    // Get the location of inode, returns the block and the offset in block
+    pub fn inode_location(&mut self, ino: u32) -> Option<(u32, usize)> {
+        if ino == 0 || ino > self.sb.inodes_count {
+            return None;
+        }
+        let group = ((ino - 1) / self.sb.inodes_per_group) as usize;
+        let index = (ino - 1) % self.sb.inodes_per_group;
+        let offset = index * self.sb.inode_size;
+        let table = self.groups.get(group)?.inode_table;
+        Some((table + offset / self.block_size, (offset % self.block_size) as usize))
+    }
+
+    // Allocate block, the block is zeroed
+    pub fn alloc_block(&mut self, hint: u32) -> u32 {
+        if self.read_only || self.sb.free_blocks_count == 0 {
+            return 0;
+        }
+
+        let count = self.groups.len();
+        let first = (hint.saturating_sub(self.sb.first_data_block) / self.sb.blocks_per_group) as usize;
+
+        for n in 0..count {
+            let group = (first + n) % count;
+            if self.groups[group].free_blocks_count == 0 {
+                continue;
+            }
+
+            let bitmap = self.groups[group].block_bitmap;
+            let limit = self.blocks_in_group(group);
+            if let Some(bit) = self.alloc_bit(bitmap, limit, 0) {
+                self.groups[group].free_blocks_count -= 1;
+                self.sb.free_blocks_count -= 1;
+                self.write_meta(group);
+
+                let block = self.sb.first_data_block + group as u32 * self.sb.blocks_per_group + bit;
+                let zero = vec![0u8; self.block_size as usize];
+                self.write_block(&zero, block);
+                return block;
+            }
+        }
+        0
+    }
+
+    // Free block
+    pub fn free_block(&mut self, block: u32) {
+        if block < self.sb.first_data_block || block >= self.sb.blocks_count {
+            return;
+        }
+
+        let index = block - self.sb.first_data_block;
+        let group = (index / self.sb.blocks_per_group) as usize;
+        let bitmap = self.groups[group].block_bitmap;
+
+        if self.free_bit(bitmap, index % self.sb.blocks_per_group) {
+            self.groups[group].free_blocks_count += 1;
+            self.sb.free_blocks_count += 1;
+            self.write_meta(group);
+        }
+    }
+
+    // Allocate inode, the directories are spread by the free inodes of group
+    pub fn alloc_inode(&mut self, parent: u32, is_dir: bool) -> u32 {
+        if self.read_only || self.sb.free_inodes_count == 0 {
+            return 0;
+        }
+
+        let count = self.groups.len();
+        let first = if is_dir {
+            (0..count).max_by_key(|g| self.groups[*g].free_inodes_count).unwrap_or(0)
+        } else {
+            ((parent.max(1) - 1) / self.sb.inodes_per_group) as usize
+        };
+
+        for n in 0..count {
+            let group = (first + n) % count;
+            if self.groups[group].free_inodes_count == 0 {
+                continue;
+            }
+
+            // The reserved inodes are in the first group
+            let start = if group == 0 { self.sb.first_ino - 1 } else { 0 };
+            let bitmap = self.groups[group].inode_bitmap;
+            if let Some(bit) = self.alloc_bit(bitmap, self.sb.inodes_per_group, start) {
+                self.groups[group].free_inodes_count -= 1;
+                if is_dir {
+                    self.groups[group].used_dirs_count += 1;
+                }
+                self.sb.free_inodes_count -= 1;
+                self.write_meta(group);
+                return group as u32 * self.sb.inodes_per_group + bit + 1;
+            }
+        }
+        0
+    }
+
+    // Free inode
+    pub fn free_inode(&mut self, ino: u32, is_dir: bool) {
+        if ino == 0 || ino > self.sb.inodes_count {
+            return;
+        }
+
+        let group = ((ino - 1) / self.sb.inodes_per_group) as usize;
+        let bitmap = self.groups[group].inode_bitmap;
+
+        if self.free_bit(bitmap, (ino - 1) % self.sb.inodes_per_group) {
+            self.groups[group].free_inodes_count += 1;
+            if is_dir {
+                self.groups[group].used_dirs_count =
+                    self.groups[group].used_dirs_count.saturating_sub(1);
+            }
+            self.sb.free_inodes_count += 1;
+            self.write_meta(group);
+        }
+    }
+
+    // Write block
+    pub fn write_block(&mut self, data: &[u8], block: u32) -> u32 {
+        let sec_per_block = self.block_size / SECTOR_SIZE;
+        self.write_sector(data, block * sec_per_block, sec_per_block) / sec_per_block
+    }
+
+    // Read block
+    pub fn read_block(&mut self, data: &mut [u8], block: u32) -> u32 {
+        let sec_per_block = self.block_size / SECTOR_SIZE;
+        self.read_sector(data, block * sec_per_block, sec_per_block) / sec_per_block
+    }
+
+    // Write Sector
+    pub fn write_sector(&mut self, data: &[u8], sector: u32, sec_size: u32) -> u32 {
+        self.device.write(
+            data,
+            sec_size as usize,
+            (sector) as usize,
+        ) as u32
+    }
+
+    // Read Sector
+    pub fn read_sector(&mut self, data: &mut [u8], sector: u32, sec_size: u32) -> u32 {
+        self.device.read(
+            data,
+            sec_size as usize,
+            (sector) as usize,
+        ) as u32
+    }
+}
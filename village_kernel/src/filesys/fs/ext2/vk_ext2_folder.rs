@@ -0,0 +1,257 @@
+//###########################################################################
+// vk_ext2_folder.rs
+// The specific implementation of functions related to ext2 folder
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_ext2_diskio::{get_u16, get_u32, set_u16, set_u32, Ext2Diskio};
+use super::vk_ext2_inode::Ext2Inode;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const ENTRY_HEADER_SIZE: usize = 8;
+const INDEX_FLAG: u32 = 0x1000;
+
+// Struct Ext2EntryType
+pub struct Ext2EntryType;
+
+// Impl Ext2EntryType
+impl Ext2EntryType {
+    pub const UNKNOWN: u8 = 0;
+    pub const FILE: u8 = 1;
+    pub const DIRECTORY: u8 = 2;
+    pub const SYMLINK: u8 = 7;
+}
+
+// Struct Ext2Entry
+#[derive(Clone)]
+pub struct Ext2Entry {
+    pub inode: u32,
+    pub name: String,
+    pub file_type: u8,
+}
+
+// Struct Ext2Folder
+pub struct Ext2Folder;
+
+// Impl Ext2Folder
+impl Ext2Folder {
+    // Get the size of entry with name
+    fn entry_size(name_len: usize) -> usize {
+        (ENTRY_HEADER_SIZE + name_len + 3) & !3
+    }
+
+    // Write entry into block data
+    fn write_entry(diskio: &Ext2Diskio, data: &mut [u8], offset: usize, rec_len: usize, ino: u32, name: &str, file_type: u8) {
+        let name = name.as_bytes();
+        set_u32(data, offset, ino);
+        set_u16(data, offset + 4, rec_len as u16);
+        data[offset + 6] = name.len() as u8;
+        data[offset + 7] = if diskio.has_filetype() { file_type } else { 0 };
+        data[offset + ENTRY_HEADER_SIZE..offset + ENTRY_HEADER_SIZE + name.len()].copy_from_slice(name);
+    }
+
+    // Walk the entries, the walker returns true to stop, then the block is written back when changed
+    fn walk(
+        diskio: &mut Ext2Diskio,
+        dir: &mut Ext2Inode,
+        walker: &mut dyn FnMut(&Ext2Diskio, &mut [u8], usize, Option<usize>) -> (bool, bool),
+    ) -> bool {
+        let block_size = diskio.block_size() as usize;
+        let blocks = dir.size as usize / block_size;
+        let mut data = vec![0u8; block_size];
+
+        for index in 0..blocks {
+            let block = dir.map_block(diskio, index as u32, false);
+            if block == 0 {
+                continue;
+            }
+            diskio.read_block(&mut data, block);
+
+            let mut offset = 0;
+            let mut prev = None;
+            while offset + ENTRY_HEADER_SIZE <= block_size {
+                let rec_len = get_u16(&data, offset + 4) as usize;
+                if rec_len < ENTRY_HEADER_SIZE || offset + rec_len > block_size {
+                    break;
+                }
+
+                let (stop, changed) = walker(diskio, &mut data, offset, prev);
+                if changed {
+                    diskio.write_block(&data, block);
+                }
+                if stop {
+                    return true;
+                }
+
+                prev = Some(offset);
+                offset += rec_len;
+            }
+        }
+
+        false
+    }
+
+    // Parse entry
+    fn parse_entry(data: &[u8], offset: usize) -> Ext2Entry {
+        let name_len = data[offset + 6] as usize;
+        let start = offset + ENTRY_HEADER_SIZE;
+        Ext2Entry {
+            inode: get_u32(data, offset),
+            name: String::from_utf8_lossy(&data[start..start + name_len]).into_owned(),
+            file_type: data[offset + 7],
+        }
+    }
+
+    // Mark the directory is modified, the hash index is not maintained
+    fn touch(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode) {
+        if dir.flags & INDEX_FLAG != 0 {
+            dir.flags &= !INDEX_FLAG;
+            dir.save(diskio);
+        }
+    }
+}
+
+// Impl Ext2Folder
+impl Ext2Folder {
+    // Read entries
+    pub fn entries(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode) -> Vec<Ext2Entry> {
+        let mut entries = Vec::new();
+        Self::walk(diskio, dir, &mut |_, data, offset, _| {
+            let entry = Self::parse_entry(data, offset);
+            if entry.inode != 0 {
+                entries.push(entry);
+            }
+            (false, false)
+        });
+        entries
+    }
+
+    // Search entry by name
+    pub fn search(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode, name: &str) -> Option<Ext2Entry> {
+        let mut result = None;
+        Self::walk(diskio, dir, &mut |_, data, offset, _| {
+            let entry = Self::parse_entry(data, offset);
+            if entry.inode != 0 && entry.name == name {
+                result = Some(entry);
+                return (true, false);
+            }
+            (false, false)
+        });
+        result
+    }
+
+    // Is empty, only the dot entries
+    pub fn is_empty(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode) -> bool {
+        Self::entries(diskio, dir)
+            .iter()
+            .all(|entry| entry.name == "." || entry.name == "..")
+    }
+
+    // Add entry
+    pub fn add(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode, name: &str, ino: u32, file_type: u8) -> bool {
+        if name.is_empty() || name.len() > 255 {
+            return false;
+        }
+
+        let need = Self::entry_size(name.len());
+
+        // Use the free space in the existing entries
+        let added = Self::walk(diskio, dir, &mut |diskio, data, offset, _| {
+            let rec_len = get_u16(data, offset + 4) as usize;
+            let entry_ino = get_u32(data, offset);
+
+            if entry_ino == 0 && rec_len >= need {
+                Self::write_entry(diskio, data, offset, rec_len, ino, name, file_type);
+                return (true, true);
+            }
+
+            let used = Self::entry_size(data[offset + 6] as usize);
+            if entry_ino != 0 && rec_len >= used + need {
+                set_u16(data, offset + 4, used as u16);
+                Self::write_entry(diskio, data, offset + used, rec_len - used, ino, name, file_type);
+                return (true, true);
+            }
+
+            (false, false)
+        });
+
+        if added {
+            Self::touch(diskio, dir);
+            return true;
+        }
+
+        // Append a new block
+        let block_size = diskio.block_size() as usize;
+        let index = dir.size / block_size as u32;
+        let block = dir.map_block(diskio, index, true);
+        if block == 0 {
+            return false;
+        }
+
+        let mut data = vec![0u8; block_size];
+        Self::write_entry(diskio, &mut data, 0, block_size, ino, name, file_type);
+        diskio.write_block(&data, block);
+
+        dir.size += block_size as u32;
+        dir.flags &= !INDEX_FLAG;
+        dir.save(diskio);
+        true
+    }
+
+    // Remove entry, the space is merged into the previous entry
+    pub fn remove(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode, name: &str) -> bool {
+        let removed = Self::walk(diskio, dir, &mut |_, data, offset, prev| {
+            let entry = Self::parse_entry(data, offset);
+            if entry.inode == 0 || entry.name != name {
+                return (false, false);
+            }
+
+            match prev {
+                Some(prev) => {
+                    let rec_len = get_u16(data, prev + 4) + get_u16(data, offset + 4);
+                    set_u16(data, prev + 4, rec_len);
+                }
+                None => set_u32(data, offset, 0),
+            }
+            (true, true)
+        });
+
+        if removed {
+            Self::touch(diskio, dir);
+        }
+        removed
+    }
+
+    // Init the dot entries of new directory
+    pub fn init(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode, parent: u32) -> bool {
+        let block_size = diskio.block_size() as usize;
+        let block = dir.map_block(diskio, 0, true);
+        if block == 0 {
+            return false;
+        }
+
+        let dot_size = Self::entry_size(1);
+        let mut data = vec![0u8; block_size];
+        Self::write_entry(diskio, &mut data, 0, dot_size, dir.ino, ".", Ext2EntryType::DIRECTORY);
+        Self::write_entry(diskio, &mut data, dot_size, block_size - dot_size, parent, "..", Ext2EntryType::DIRECTORY);
+        diskio.write_block(&data, block);
+
+        dir.size = block_size as u32;
+        true
+    }
+
+    // Set the parent of directory
+    pub fn set_parent(diskio: &mut Ext2Diskio, dir: &mut Ext2Inode, parent: u32) -> bool {
+        Self::walk(diskio, dir, &mut |_, data, offset, _| {
+            let entry = Self::parse_entry(data, offset);
+            if entry.inode != 0 && entry.name == ".." {
+                set_u32(data, offset, parent);
+                return (true, true);
+            }
+            (false, false)
+        })
+    }
+}
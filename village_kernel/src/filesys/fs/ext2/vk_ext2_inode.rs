@@ -0,0 +1,506 @@
+//###########################################################################
+// vk_ext2_inode.rs
+// The specific implementation of functions related to ext2 inode
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_ext2_diskio::{get_u16, get_u32, set_u16, set_u32, Ext2Diskio};
+use crate::traits::vk_filesys::{FileTime, FileType};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const DIRECT_BLOCKS: usize = 12;
+const FAST_LINK_SIZE: u32 = 60;
+const SECTOR_SIZE: u32 = 512;
+const SECS_PER_DAY: u32 = 86400;
+
+// Struct Ext2Mode
+pub struct Ext2Mode;
+
+// Impl Ext2Mode
+impl Ext2Mode {
+    pub const TYPE_MASK: u16 = 0xf000;
+    pub const SYMLINK: u16 = 0xa000;
+    pub const FILE: u16 = 0x8000;
+    pub const DIRECTORY: u16 = 0x4000;
+    pub const PERM_MASK: u16 = 0x0fff;
+    pub const WRITE_MASK: u16 = 0o222;
+}
+
+// Struct Ext2Inode
+#[derive(Clone)]
+pub struct Ext2Inode {
+    pub ino: u32,
+    pub mode: u16,
+    pub uid: u16,
+    pub size: u32,
+    pub atime: u32,
+    pub ctime: u32,
+    pub mtime: u32,
+    pub dtime: u32,
+    pub gid: u16,
+    pub links_count: u16,
+    pub blocks: u32,
+    pub flags: u32,
+    pub block: [u32; 15],
+    raw: Vec<u8>,
+}
+
+// Impl Ext2Inode
+impl Ext2Inode {
+    // New
+    pub fn new(ino: u32, mode: u16, inode_size: u32) -> Self {
+        Self {
+            ino,
+            mode,
+            uid: 0,
+            size: 0,
+            atime: 0,
+            ctime: 0,
+            mtime: 0,
+            dtime: 0,
+            gid: 0,
+            links_count: 0,
+            blocks: 0,
+            flags: 0,
+            block: [0u32; 15],
+            raw: vec![0u8; inode_size as usize],
+        }
+    }
+
+    // From
+    pub fn from(ino: u32, data: &[u8]) -> Self {
+        let mut inode = Self::new(ino, 0, data.len() as u32);
+
+        inode.mode = get_u16(data, 0);
+        inode.uid = get_u16(data, 2);
+        inode.size = get_u32(data, 4);
+        inode.atime = get_u32(data, 8);
+        inode.ctime = get_u32(data, 12);
+        inode.mtime = get_u32(data, 16);
+        inode.dtime = get_u32(data, 20);
+        inode.gid = get_u16(data, 24);
+        inode.links_count = get_u16(data, 26);
+        inode.blocks = get_u32(data, 28);
+        inode.flags = get_u32(data, 32);
+
+        for i in 0..15 {
+            inode.block[i] = get_u32(data, 40 + i * 4);
+        }
+
+        inode.raw.copy_from_slice(data);
+        inode
+    }
+
+    // Update the fields into the raw data
+    fn update(&mut self) {
+        let mut raw = core::mem::take(&mut self.raw);
+
+        set_u16(&mut raw, 0, self.mode);
+        set_u16(&mut raw, 2, self.uid);
+        set_u32(&mut raw, 4, self.size);
+        set_u32(&mut raw, 8, self.atime);
+        set_u32(&mut raw, 12, self.ctime);
+        set_u32(&mut raw, 16, self.mtime);
+        set_u32(&mut raw, 20, self.dtime);
+        set_u16(&mut raw, 24, self.gid);
+        set_u16(&mut raw, 26, self.links_count);
+        set_u32(&mut raw, 28, self.blocks);
+        set_u32(&mut raw, 32, self.flags);
+
+        for i in 0..15 {
+            set_u32(&mut raw, 40 + i * 4, self.block[i]);
+        }
+
+        // The size high is the directory acl for directory
+        if self.is_file() {
+            set_u32(&mut raw, 108, 0);
+        }
+
+        self.raw = raw;
+    }
+
+    // Load inode
+    pub fn load(diskio: &mut Ext2Diskio, ino: u32) -> Option<Self> {
+        let (block, offset) = diskio.inode_location(ino)?;
+        let mut data = vec![0u8; diskio.block_size() as usize];
+        diskio.read_block(&mut data, block);
+
+        let inode_size = diskio.inode_size() as usize;
+        Some(Self::from(ino, &data[offset..offset + inode_size]))
+    }
+
+    // Save inode
+    pub fn save(&mut self, diskio: &mut Ext2Diskio) {
+        if let Some((block, offset)) = diskio.inode_location(self.ino) {
+            self.update();
+
+            let mut data = vec![0u8; diskio.block_size() as usize];
+            diskio.read_block(&mut data, block);
+            data[offset..offset + self.raw.len()].copy_from_slice(&self.raw);
+            diskio.write_block(&data, block);
+        }
+    }
+}
+
+// Impl Ext2Inode
+impl Ext2Inode {
+    // Is directory
+    pub fn is_dir(&self) -> bool {
+        (self.mode & Ext2Mode::TYPE_MASK) == Ext2Mode::DIRECTORY
+    }
+
+    // Is regular file
+    pub fn is_file(&self) -> bool {
+        (self.mode & Ext2Mode::TYPE_MASK) == Ext2Mode::FILE
+    }
+
+    // Is symbolic link
+    pub fn is_symlink(&self) -> bool {
+        (self.mode & Ext2Mode::TYPE_MASK) == Ext2Mode::SYMLINK
+    }
+
+    // Get file type, the symbolic link is shown as file
+    pub fn get_type(&self) -> FileType {
+        if self.is_dir() {
+            FileType::Directory
+        } else if self.is_file() || self.is_symlink() {
+            FileType::File
+        } else {
+            FileType::Unknown
+        }
+    }
+
+    // Is fast symbolic link, the fast link has no data block except the extended attribute block
+    pub fn is_fast_link(&self, diskio: &Ext2Diskio) -> bool {
+        let file_acl = get_u32(&self.raw, 104);
+        let acl_blocks = if file_acl != 0 { diskio.block_size() / SECTOR_SIZE } else { 0 };
+        self.is_symlink() && self.size < FAST_LINK_SIZE && self.blocks == acl_blocks
+    }
+
+    // Read symbolic link, the short target is stored in the block pointers
+    pub fn read_link(&mut self, diskio: &mut Ext2Diskio) -> Option<String> {
+        if !self.is_symlink() {
+            return None;
+        }
+
+        let mut data = vec![0u8; self.size as usize];
+        if self.is_fast_link(diskio) {
+            let mut bytes = Vec::new();
+            for ptr in self.block.iter() {
+                bytes.extend_from_slice(&ptr.to_le_bytes());
+            }
+            data.copy_from_slice(&bytes[..self.size as usize]);
+        } else {
+            let size = self.size as usize;
+            if self.read(diskio, &mut data, 0) != size {
+                return None;
+            }
+        }
+
+        Some(String::from_utf8_lossy(&data).into_owned())
+    }
+}
+
+// Impl Ext2Inode
+impl Ext2Inode {
+    // Get the count of pointers in block
+    fn ptrs_per_block(diskio: &Ext2Diskio) -> u64 {
+        (diskio.block_size() / 4) as u64
+    }
+
+    // Map the file block index to disk block, allocate the missing blocks when alloc is set
+    pub fn map_block(&mut self, diskio: &mut Ext2Diskio, index: u32, alloc: bool) -> u32 {
+        let ptrs = Self::ptrs_per_block(diskio);
+        let sec_per_block = diskio.block_size() / SECTOR_SIZE;
+        let mut index = index as u64;
+
+        // Direct blocks
+        if index < DIRECT_BLOCKS as u64 {
+            let slot = index as usize;
+            if self.block[slot] == 0 && alloc {
+                self.block[slot] = diskio.alloc_block(self.hint());
+                if self.block[slot] != 0 {
+                    self.blocks += sec_per_block;
+                }
+            }
+            return self.block[slot];
+        }
+        index -= DIRECT_BLOCKS as u64;
+
+        // Single, double and triple indirect blocks
+        let mut span = ptrs;
+        for level in 1..=3usize {
+            if index < span {
+                let slot = DIRECT_BLOCKS + level - 1;
+                if self.block[slot] == 0 {
+                    if !alloc {
+                        return 0;
+                    }
+                    self.block[slot] = diskio.alloc_block(self.hint());
+                    if self.block[slot] == 0 {
+                        return 0;
+                    }
+                    self.blocks += sec_per_block;
+                }
+                let root = self.block[slot];
+                return self.map_indirect(diskio, root, level, index, alloc);
+            }
+            index -= span;
+            span *= ptrs;
+        }
+
+        0
+    }
+
+    // Map the block in indirect tree
+    fn map_indirect(&mut self, diskio: &mut Ext2Diskio, root: u32, level: usize, index: u64, alloc: bool) -> u32 {
+        let ptrs = Self::ptrs_per_block(diskio);
+        let sec_per_block = diskio.block_size() / SECTOR_SIZE;
+        let mut data = vec![0u8; diskio.block_size() as usize];
+        let mut block = root;
+
+        for depth in (0..level).rev() {
+            let span = ptrs.pow(depth as u32);
+            let slot = ((index / span) % ptrs) as usize * 4;
+
+            diskio.read_block(&mut data, block);
+            let mut next = get_u32(&data, slot);
+
+            if next == 0 {
+                if !alloc {
+                    return 0;
+                }
+                next = diskio.alloc_block(block);
+                if next == 0 {
+                    return 0;
+                }
+                self.blocks += sec_per_block;
+                set_u32(&mut data, slot, next);
+                diskio.write_block(&data, block);
+            }
+
+            block = next;
+        }
+
+        block
+    }
+
+    // Get the hint for block allocation
+    fn hint(&self) -> u32 {
+        self.block.iter().rev().find(|b| **b != 0).copied().unwrap_or(0)
+    }
+
+    // Truncate blocks, the blocks after the keep count are freed
+    pub fn truncate(&mut self, diskio: &mut Ext2Diskio, keep: u32) {
+        let ptrs = Self::ptrs_per_block(diskio);
+        let sec_per_block = diskio.block_size() / SECTOR_SIZE;
+        let keep = keep as u64;
+
+        // Direct blocks
+        for slot in 0..DIRECT_BLOCKS {
+            if slot as u64 >= keep && self.block[slot] != 0 {
+                diskio.free_block(self.block[slot]);
+                self.block[slot] = 0;
+                self.blocks = self.blocks.saturating_sub(sec_per_block);
+            }
+        }
+
+        // Indirect blocks
+        let mut base = DIRECT_BLOCKS as u64;
+        let mut span = ptrs;
+        for level in 1..=3usize {
+            let slot = DIRECT_BLOCKS + level - 1;
+            if self.block[slot] != 0 && keep < base + span {
+                let sub_keep = keep.saturating_sub(base);
+                if self.free_indirect(diskio, self.block[slot], level, sub_keep) {
+                    self.block[slot] = 0;
+                }
+            }
+            base += span;
+            span *= ptrs;
+        }
+    }
+
+    // Resize, the tail of the last block is cleared when extended
+    pub fn resize(&mut self, diskio: &mut Ext2Diskio, size: u32) {
+        let block_size = diskio.block_size();
+
+        if size > self.size {
+            let blk_offset = (self.size % block_size) as usize;
+            let block = match blk_offset {
+                0 => 0,
+                _ => self.map_block(diskio, self.size / block_size, false),
+            };
+            if block != 0 {
+                let mut buff = vec![0u8; block_size as usize];
+                diskio.read_block(&mut buff, block);
+                buff[blk_offset..].fill(0);
+                diskio.write_block(&buff, block);
+            }
+        } else {
+            self.truncate(diskio, size.div_ceil(block_size));
+        }
+
+        self.size = size;
+    }
+
+    // Free the indirect tree after keep count, returns whether the root is freed
+    fn free_indirect(&mut self, diskio: &mut Ext2Diskio, root: u32, level: usize, keep: u64) -> bool {
+        let ptrs = Self::ptrs_per_block(diskio);
+        let sec_per_block = diskio.block_size() / SECTOR_SIZE;
+        let sub_span = ptrs.pow(level as u32 - 1);
+
+        let mut data = vec![0u8; diskio.block_size() as usize];
+        diskio.read_block(&mut data, root);
+
+        let mut changed = false;
+        for i in 0..ptrs {
+            let sub_start = i * sub_span;
+            if sub_start + sub_span <= keep {
+                continue;
+            }
+
+            let ptr = get_u32(&data, i as usize * 4);
+            if ptr == 0 {
+                continue;
+            }
+
+            let freed = if level == 1 {
+                diskio.free_block(ptr);
+                self.blocks = self.blocks.saturating_sub(sec_per_block);
+                true
+            } else {
+                self.free_indirect(diskio, ptr, level - 1, keep.saturating_sub(sub_start))
+            };
+
+            if freed {
+                set_u32(&mut data, i as usize * 4, 0);
+                changed = true;
+            }
+        }
+
+        if keep == 0 {
+            diskio.free_block(root);
+            self.blocks = self.blocks.saturating_sub(sec_per_block);
+            return true;
+        }
+
+        if changed {
+            diskio.write_block(&data, root);
+        }
+        false
+    }
+
+    // Read data
+    pub fn read(&mut self, diskio: &mut Ext2Diskio, data: &mut [u8], offset: usize) -> usize {
+        let size = self.size as usize;
+        if offset >= size {
+            return 0;
+        }
+
+        let block_size = diskio.block_size() as usize;
+        let size = data.len().min(size - offset);
+        let mut buff = vec![0u8; block_size];
+        let mut readed = 0;
+
+        while readed < size {
+            let pos = offset + readed;
+            let blk_offset = pos % block_size;
+            let len = (block_size - blk_offset).min(size - readed);
+
+            // The hole is read as zero
+            let block = self.map_block(diskio, (pos / block_size) as u32, false);
+            if block == 0 {
+                buff.fill(0);
+            } else {
+                diskio.read_block(&mut buff, block);
+            }
+
+            data[readed..readed + len].copy_from_slice(&buff[blk_offset..blk_offset + len]);
+            readed += len;
+        }
+
+        readed
+    }
+
+    // Write data, the blocks are allocated when needed
+    pub fn write(&mut self, diskio: &mut Ext2Diskio, data: &[u8], offset: usize) -> usize {
+        let block_size = diskio.block_size() as usize;
+        let mut buff = vec![0u8; block_size];
+        let mut written = 0;
+
+        while written < data.len() {
+            let pos = offset + written;
+            let blk_offset = pos % block_size;
+            let len = (block_size - blk_offset).min(data.len() - written);
+
+            let block = self.map_block(diskio, (pos / block_size) as u32, true);
+            if block == 0 {
+                break;
+            }
+
+            if len < block_size {
+                diskio.read_block(&mut buff, block);
+            }
+            buff[blk_offset..blk_offset + len].copy_from_slice(&data[written..written + len]);
+            diskio.write_block(&buff, block);
+
+            written += len;
+        }
+
+        written
+    }
+}
+
+// Convert the unix time to file time, zero is unknown time
+pub fn to_file_time(secs: u32) -> FileTime {
+    if secs == 0 {
+        return FileTime::new();
+    }
+
+    // Days to civil date
+    let days = (secs / SECS_PER_DAY) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let rem = secs % SECS_PER_DAY;
+    FileTime {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+        hour: (rem / 3600) as u8,
+        minute: (rem % 3600 / 60) as u8,
+        second: (rem % 60) as u8,
+    }
+}
+
+// Convert the file time to unix time
+pub fn to_unix_time(time: &FileTime) -> u32 {
+    if !time.is_valid() || time.year < 1970 {
+        return 0;
+    }
+
+    // Civil date to days
+    let year = time.year as i64 - if time.month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = time.month as i64;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + time.day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    (days as u32) * SECS_PER_DAY
+        + time.hour as u32 * 3600
+        + time.minute as u32 * 60
+        + time.second as u32
+}
@@ -0,0 +1,641 @@
+//###########################################################################
+// vk_ext2_system.rs
+// The specific implementation of functions related to ext2 system
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_ext2_diskio::{Ext2Diskio, ROOT_INODE};
+use super::vk_ext2_folder::{Ext2EntryType, Ext2Folder};
+use super::vk_ext2_inode::{to_file_time, to_unix_time, Ext2Inode, Ext2Mode};
+use crate::traits::vk_filesys::{FileAttr, FileDir, FileMode, FileSys, FileType, FileVol};
+use crate::traits::vk_filesys::{FileCheck, FileFlags, FileStat, FileTime};
+use crate::traits::vk_linkedlist::LinkedList;
+use crate::register_filesys;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const MAX_SYMLINKS: usize = 8;
+const FILE_PERM: u16 = 0o644;
+const DIR_PERM: u16 = 0o755;
+
+// Struct Ext2File
+struct Ext2File {
+    file_id: usize,
+    inode: Ext2Inode,
+}
+
+// Struct Ext2Dir
+struct Ext2Dir {
+    dir_id: usize,
+    sub_dirs: Vec<FileDir>,
+}
+
+// Struct Ext2Volume
+struct Ext2Volume {
+    diskio: Ext2Diskio,
+    mount_path: String,
+    dir_cnt: usize,
+    file_cnt: usize,
+    dirs: LinkedList<Ext2Dir>,
+    files: LinkedList<Ext2File>,
+}
+
+// Impl Ext2Volume
+impl Ext2Volume {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            diskio: Ext2Diskio::new(),
+            mount_path: String::new(),
+            dir_cnt: 1,
+            file_cnt: 1,
+            dirs: LinkedList::new(),
+            files: LinkedList::new(),
+        }
+    }
+}
+
+// Impl Ext2Volume
+impl Ext2Volume {
+    // Assign file id
+    fn assign_file_id(&mut self) -> usize {
+        let id = self.file_cnt;
+        self.file_cnt += 1;
+        id
+    }
+
+    // Assign dir id
+    fn assign_dir_id(&mut self) -> usize {
+        let id = self.dir_cnt;
+        self.dir_cnt += 1;
+        id
+    }
+
+    // Split path into the parent path and base name
+    fn split_path(path: &str) -> (&str, &str) {
+        let path = path.trim_end_matches('/');
+        match path.rfind('/') {
+            Some(pos) => (&path[..pos], &path[pos + 1..]),
+            None => ("", path),
+        }
+    }
+
+    // Lookup path, the symbolic links are followed, the last one is followed when follow is set.
+    // The absolute link target is resolved in this volume
+    fn lookup(&mut self, path: &str, follow: bool) -> Option<Ext2Inode> {
+        let mut names: Vec<String> = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .rev()
+            .map(|s| s.to_string())
+            .collect();
+        let mut stack = vec![Ext2Inode::load(&mut self.diskio, ROOT_INODE)?];
+        let mut links = 0;
+
+        while let Some(name) = names.pop() {
+            let current = stack.last_mut()?;
+            if !current.is_dir() {
+                return None;
+            }
+
+            if name == "." {
+                continue;
+            } else if name == ".." {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                continue;
+            }
+
+            let entry = Ext2Folder::search(&mut self.diskio, current, &name)?;
+            let mut inode = Ext2Inode::load(&mut self.diskio, entry.inode)?;
+
+            if inode.is_symlink() && (follow || !names.is_empty()) {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return None;
+                }
+
+                let target = inode.read_link(&mut self.diskio)?;
+                if target.starts_with('/') {
+                    stack.truncate(1);
+                }
+                names.extend(target.split('/').filter(|s| !s.is_empty()).rev().map(|s| s.to_string()));
+                continue;
+            }
+
+            stack.push(inode);
+        }
+
+        stack.pop()
+    }
+
+    // Create path
+    fn create_path(&mut self, path: &str, is_dir: bool) -> Option<Ext2Inode> {
+        if self.diskio.is_read_only() {
+            return None;
+        }
+
+        let (parent_path, name) = Self::split_path(path);
+        let mut parent = self.lookup(parent_path, true)?;
+        if !parent.is_dir() || name.is_empty() || Ext2Folder::search(&mut self.diskio, &mut parent, name).is_some() {
+            return None;
+        }
+
+        // Allocate inode
+        let ino = self.diskio.alloc_inode(parent.ino, is_dir);
+        if ino == 0 {
+            return None;
+        }
+
+        let (mode, file_type) = if is_dir {
+            (Ext2Mode::DIRECTORY | DIR_PERM, Ext2EntryType::DIRECTORY)
+        } else {
+            (Ext2Mode::FILE | FILE_PERM, Ext2EntryType::FILE)
+        };
+
+        let mut inode = Ext2Inode::new(ino, mode, self.diskio.inode_size());
+        inode.links_count = if is_dir { 2 } else { 1 };
+
+        // Create the dot entries and add into parent
+        let mut result = !is_dir || Ext2Folder::init(&mut self.diskio, &mut inode, parent.ino);
+        inode.save(&mut self.diskio);
+        result = result && Ext2Folder::add(&mut self.diskio, &mut parent, name, ino, file_type);
+
+        if !result {
+            inode.truncate(&mut self.diskio, 0);
+            self.diskio.free_inode(ino, is_dir);
+            return None;
+        }
+
+        if is_dir {
+            parent.links_count += 1;
+            parent.save(&mut self.diskio);
+        }
+
+        Some(inode)
+    }
+
+    // Delete path, the directory must be empty
+    fn delete_path(&mut self, path: &str) -> bool {
+        if self.diskio.is_read_only() {
+            return false;
+        }
+
+        let (parent_path, name) = Self::split_path(path);
+        let mut parent = match self.lookup(parent_path, true) {
+            Some(parent) if parent.is_dir() => parent,
+            _ => return false,
+        };
+
+        let entry = match Ext2Folder::search(&mut self.diskio, &mut parent, name) {
+            Some(entry) if name != "." && name != ".." => entry,
+            _ => return false,
+        };
+
+        let mut inode = match Ext2Inode::load(&mut self.diskio, entry.inode) {
+            Some(inode) => inode,
+            None => return false,
+        };
+
+        let is_dir = inode.is_dir();
+        if is_dir && !Ext2Folder::is_empty(&mut self.diskio, &mut inode) {
+            return false;
+        }
+
+        if !Ext2Folder::remove(&mut self.diskio, &mut parent, name) {
+            return false;
+        }
+
+        if is_dir {
+            parent.links_count = parent.links_count.saturating_sub(1);
+            parent.save(&mut self.diskio);
+            inode.links_count = 0;
+        } else {
+            inode.links_count = inode.links_count.saturating_sub(1);
+        }
+
+        // Free the inode when no links, the fast link has no data block
+        if inode.links_count == 0 {
+            if !inode.is_fast_link(&self.diskio) {
+                inode.truncate(&mut self.diskio, 0);
+            }
+            inode.dtime = inode.mtime.max(1);
+            inode.save(&mut self.diskio);
+            self.diskio.free_inode(inode.ino, is_dir);
+        } else {
+            inode.save(&mut self.diskio);
+        }
+
+        true
+    }
+
+    // Rename path
+    fn rename_path(&mut self, source: &str, target: &str) -> bool {
+        if self.diskio.is_read_only() {
+            return false;
+        }
+
+        // Can not move a directory into itself
+        let source_dir = format!("{}/", source.trim_end_matches('/'));
+        if target.starts_with(&source_dir) {
+            return false;
+        }
+
+        // The target must not exist
+        if self.lookup(target, false).is_some() {
+            return false;
+        }
+
+        let (source_parent, source_name) = Self::split_path(source);
+        let (target_parent, target_name) = Self::split_path(target);
+
+        let mut sparent = match self.lookup(source_parent, true) {
+            Some(parent) if parent.is_dir() => parent,
+            _ => return false,
+        };
+        let entry = match Ext2Folder::search(&mut self.diskio, &mut sparent, source_name) {
+            Some(entry) if source_name != "." && source_name != ".." => entry,
+            _ => return false,
+        };
+        let mut tparent = match self.lookup(target_parent, true) {
+            Some(parent) if parent.is_dir() => parent,
+            _ => return false,
+        };
+
+        // Add the new entry, then remove the old one
+        if !Ext2Folder::add(&mut self.diskio, &mut tparent, target_name, entry.inode, entry.file_type) {
+            return false;
+        }
+        if sparent.ino == tparent.ino {
+            sparent = tparent.clone();
+        }
+        Ext2Folder::remove(&mut self.diskio, &mut sparent, source_name);
+
+        // Update the parent of directory
+        if sparent.ino != tparent.ino {
+            if let Some(mut inode) = Ext2Inode::load(&mut self.diskio, entry.inode) {
+                if inode.is_dir() {
+                    Ext2Folder::set_parent(&mut self.diskio, &mut inode, tparent.ino);
+
+                    if let Some(mut parent) = Ext2Inode::load(&mut self.diskio, sparent.ino) {
+                        parent.links_count = parent.links_count.saturating_sub(1);
+                        parent.save(&mut self.diskio);
+                    }
+                    if let Some(mut parent) = Ext2Inode::load(&mut self.diskio, tparent.ino) {
+                        parent.links_count += 1;
+                        parent.save(&mut self.diskio);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    // Get inode stat
+    fn inode_stat(&mut self, inode: &mut Ext2Inode, name: &str) -> FileStat {
+        let mut stat = FileStat::new();
+
+        stat.name = name.to_string();
+        stat.typid = inode.get_type();
+        if inode.mode & Ext2Mode::WRITE_MASK == 0 {
+            stat.flags.insert(FileFlags::READ_ONLY);
+        }
+        if name.starts_with('.') && name != "." && name != ".." {
+            stat.flags.insert(FileFlags::HIDDEN);
+        }
+        stat.size = inode.size as u64;
+        stat.clusters = inode.blocks / (self.diskio.block_size() / 512);
+        stat.mode = inode.mode & Ext2Mode::PERM_MASK;
+        stat.uid = inode.uid;
+        stat.gid = inode.gid;
+        stat.link = inode.read_link(&mut self.diskio).unwrap_or_default();
+        stat.create_time = to_file_time(inode.ctime);
+        stat.write_time = to_file_time(inode.mtime);
+        stat.access_time = to_file_time(inode.atime);
+
+        stat
+    }
+
+    // Get the file type of entry
+    fn entry_type(&mut self, ino: u32, file_type: u8) -> FileType {
+        match file_type {
+            Ext2EntryType::DIRECTORY => FileType::Directory,
+            Ext2EntryType::FILE | Ext2EntryType::SYMLINK => FileType::File,
+            Ext2EntryType::UNKNOWN => match Ext2Inode::load(&mut self.diskio, ino) {
+                Some(inode) => inode.get_type(),
+                None => FileType::Unknown,
+            },
+            _ => FileType::Unknown,
+        }
+    }
+}
+
+// Impl Ext2Volume
+impl FileVol for Ext2Volume {
+    // Setup
+    fn setup(&mut self, disk: &str) -> bool {
+        self.diskio.setup(disk)
+    }
+
+    // Exit
+    fn exit(&mut self) {
+        self.files.clear();
+        self.dirs.clear();
+        self.diskio.exit();
+    }
+
+    // Set mount path
+    fn set_mount_path(&mut self, path: &str) {
+        self.mount_path = path.to_string();
+    }
+
+    // Get mount path
+    fn get_mount_path(&mut self) -> &str {
+        &self.mount_path
+    }
+
+    // Set name
+    fn set_name(&mut self, name: &str) -> bool {
+        self.diskio.set_volume_name(name)
+    }
+
+    // Get name
+    fn get_name(&mut self) -> String {
+        self.diskio.get_volume_name()
+    }
+
+    // Get total size
+    fn get_total_size(&mut self) -> u64 {
+        let block_size = self.diskio.block_size() as u64;
+        self.diskio.get_super().blocks_count as u64 * block_size
+    }
+
+    // Get free size
+    fn get_free_size(&mut self) -> u64 {
+        let block_size = self.diskio.block_size() as u64;
+        self.diskio.get_super().free_blocks_count as u64 * block_size
+    }
+
+    // Open
+    fn open(&mut self, name: &str, mode: FileMode) -> usize {
+        // Search or create path
+        let someinode = match self.lookup(name, true) {
+            Some(inode) => Some(inode),
+            None if mode.contains(FileMode::CREATE_NEW) => self.create_path(name, false),
+            None => None,
+        };
+
+        // Open file
+        if let Some(inode) = someinode {
+            if inode.is_dir() {
+                return 0;
+            }
+
+            let fd = self.assign_file_id();
+            self.files.push(Ext2File {
+                file_id: fd,
+                inode,
+            });
+
+            // Truncate the file to zero length
+            if mode.contains(FileMode::TRUNCATE) && !self.set_len(fd, 0) {
+                self.close(fd);
+                return 0;
+            }
+            return fd;
+        }
+        0
+    }
+
+    // Write
+    fn write(&mut self, fd: usize, data: &[u8], size: usize, offset: usize) -> usize {
+        if self.diskio.is_read_only() {
+            return 0;
+        }
+
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            let size = size.min(data.len());
+            if offset as u64 + size as u64 > u32::MAX as u64 {
+                return 0;
+            }
+
+            // The gap after the end of file is read as zero
+            if offset > file.inode.size as usize {
+                file.inode.resize(&mut self.diskio, offset as u32);
+            }
+
+            let written = file.inode.write(&mut self.diskio, &data[..size], offset);
+
+            // The file is extended when the data is written after the end
+            if offset + written > file.inode.size as usize {
+                file.inode.size = (offset + written) as u32;
+            }
+            file.inode.save(&mut self.diskio);
+
+            return written;
+        }
+        0
+    }
+
+    // Read
+    fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            let size = size.min(data.len());
+            return file.inode.read(&mut self.diskio, &mut data[..size], offset);
+        }
+        0
+    }
+
+    // Size
+    fn size(&mut self, fd: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            return file.inode.size as usize;
+        }
+        0
+    }
+
+    // Set len
+    fn set_len(&mut self, fd: usize, size: usize) -> bool {
+        if self.diskio.is_read_only() || size > u32::MAX as usize {
+            return false;
+        }
+
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            file.inode.resize(&mut self.diskio, size as u32);
+            file.inode.save(&mut self.diskio);
+            return true;
+        }
+        false
+    }
+
+    // Flush
+    fn flush(&mut self, fd: usize) {
+        if self.diskio.is_read_only() {
+            return;
+        }
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            file.inode.save(&mut self.diskio);
+        }
+    }
+
+    // Close
+    fn close(&mut self, fd: usize) {
+        self.files.retain_mut(|file| file.file_id != fd);
+    }
+
+    // Open dir
+    fn opendir(&mut self, name: &str, mode: FileMode) -> usize {
+        // Search or create path
+        let someinode = match self.lookup(name, true) {
+            Some(inode) => Some(inode),
+            None if mode.contains(FileMode::CREATE_NEW) => self.create_path(name, true),
+            None => None,
+        };
+
+        // Open dir
+        if let Some(mut inode) = someinode {
+            if !inode.is_dir() {
+                return 0;
+            }
+
+            let mut sub_dirs = Vec::new();
+            for entry in Ext2Folder::entries(&mut self.diskio, &mut inode) {
+                let mut dir = FileDir::new();
+                dir.typid = self.entry_type(entry.inode, entry.file_type);
+                dir.attr = if entry.name.starts_with('.') && entry.name != "." && entry.name != ".." {
+                    FileAttr::Hidden
+                } else {
+                    FileAttr::Visible
+                };
+                dir.name = entry.name;
+                sub_dirs.push(dir);
+            }
+
+            let fd = self.assign_dir_id();
+            self.dirs.push(Ext2Dir { dir_id: fd, sub_dirs });
+            return fd;
+        }
+        0
+    }
+
+    // Read dir
+    fn readdir(&mut self, fd: usize, dirs: &mut [FileDir], size: usize, offset: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            let remain = dir.sub_dirs.len().saturating_sub(offset);
+            let size = size.min(dirs.len()).min(remain);
+            dirs[..size].clone_from_slice(&dir.sub_dirs[offset..offset + size]);
+            return size;
+        }
+        0
+    }
+
+    // Size dir
+    fn sizedir(&mut self, fd: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            return dir.sub_dirs.len();
+        }
+        0
+    }
+
+    // Close dir
+    fn closedir(&mut self, fd: usize) {
+        self.dirs.retain_mut(|dir| dir.dir_id != fd);
+    }
+
+    // Stat, the last symbolic link is not followed
+    fn stat(&mut self, name: &str) -> Option<FileStat> {
+        let mut inode = self.lookup(name, false)?;
+        let (_, base) = Self::split_path(name);
+        Some(self.inode_stat(&mut inode, base))
+    }
+
+    // Fstat
+    fn fstat(&mut self, fd: usize) -> Option<FileStat> {
+        let mut inode = self.files.iter_mut().find(|f| f.file_id == fd)?.inode.clone();
+        Some(self.inode_stat(&mut inode, ""))
+    }
+
+    // Set flags, the read only flag is mapped to the write permissions
+    fn set_flags(&mut self, name: &str, flags: FileFlags) -> bool {
+        if self.diskio.is_read_only() {
+            return false;
+        }
+        if let Some(mut inode) = self.lookup(name, false) {
+            if flags.contains(FileFlags::READ_ONLY) {
+                inode.mode &= !Ext2Mode::WRITE_MASK;
+            } else if inode.mode & Ext2Mode::WRITE_MASK == 0 {
+                inode.mode |= 0o200;
+            }
+            inode.save(&mut self.diskio);
+            return true;
+        }
+        false
+    }
+
+    // Set time
+    fn set_time(&mut self, name: &str, write_time: FileTime, access_time: FileTime) -> bool {
+        if self.diskio.is_read_only() {
+            return false;
+        }
+        if let Some(mut inode) = self.lookup(name, false) {
+            if write_time.is_valid() {
+                inode.mtime = to_unix_time(&write_time);
+            }
+            if access_time.is_valid() {
+                inode.atime = to_unix_time(&access_time);
+            }
+            inode.save(&mut self.diskio);
+            return true;
+        }
+        false
+    }
+
+    // Is exist
+    fn exist(&mut self, name: &str, typeid: FileType) -> bool {
+        if let Some(inode) = self.lookup(name, true) {
+            return inode.get_type() == typeid;
+        }
+        false
+    }
+
+    // Remove
+    fn remove(&mut self, name: &str) -> bool {
+        self.delete_path(name)
+    }
+
+    // Rename
+    fn rename(&mut self, source: &str, target: &str) -> bool {
+        self.rename_path(source, target)
+    }
+
+    // Check, the consistency check is not supported
+    fn check(&mut self, _repair: bool) -> Option<FileCheck> {
+        None
+    }
+}
+
+// Struct ext2 system
+struct Ext2System;
+
+// Impl filesys for ext2 system
+impl FileSys for Ext2System {
+    // Get file system id
+    fn file_system_id(&self) -> usize {
+        const SYSTEM_ID: usize = 0x83;
+        SYSTEM_ID
+    }
+
+    // Create volume
+    fn create_volume(&mut self) -> Box<dyn FileVol> {
+        Box::new(Ext2Volume::new())
+    }
+}
+
+// Register filesys
+register_filesys!(Ext2System, ext2);
@@ -0,0 +1,463 @@
+//###########################################################################
+// vk_fat_checker.rs
+// The specific implementation of functions related to fat checker
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_fat_diskio::{FatDiskio, FatType};
+use super::vk_fat_entry::{FatEntry, FatEntryAttr, FatLongEntry, FatShortEntry};
+use super::vk_fat_object::FatObject;
+use crate::traits::vk_filesys::FileCheck;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const DIR_ENTRY_SIZE: usize = 32;
+const DIR_SEQ_FLAG: u8 = 0x40;
+const DIR_FREE_FLAG: u8 = 0xe5;
+
+// Struct FatDirBuffer, the whole directory is loaded to check the long name sequence
+struct FatDirBuffer {
+    sectors: Vec<u32>,
+    dirty: Vec<bool>,
+    data: Vec<u8>,
+}
+
+// Struct FatChecker
+pub struct FatChecker<'a> {
+    diskio: &'a mut FatDiskio,
+    repair: bool,
+    used: Vec<bool>,
+    bad_flag: u32,
+    eoc_flag: u32,
+    report: FileCheck,
+}
+
+// Impl FatChecker
+impl<'a> FatChecker<'a> {
+    // New
+    pub fn new(diskio: &'a mut FatDiskio, repair: bool) -> Self {
+        let (bad_flag, eoc_flag) = match diskio.get_info().fat_type {
+            FatType::Fat12 => (0xff7, 0xff8),
+            FatType::Fat16 => (0xfff7, 0xfff8),
+            _ => (0x0ffffff7, 0x0ffffff8),
+        };
+        let count = diskio.get_info().cluster_count as usize + 2;
+
+        Self {
+            diskio,
+            repair,
+            used: vec![false; count],
+            bad_flag,
+            eoc_flag,
+            report: FileCheck::new(),
+        }
+    }
+
+    // Check
+    pub fn check(mut self) -> FileCheck {
+        let fat_type = &self.diskio.get_info().fat_type;
+        if *fat_type == FatType::None || *fat_type == FatType::Exfat {
+            self.report.messages.push(String::from("Only fat12, fat16 and fat32 can be checked"));
+            return self.report;
+        }
+
+        self.check_fat_copies();
+        self.check_tree();
+        self.check_lost_clusters();
+
+        self.report
+    }
+}
+
+// Impl FatChecker
+impl<'a> FatChecker<'a> {
+    // Check the fat copies are same as the first fat
+    fn check_fat_copies(&mut self) {
+        let info = self.diskio.get_info();
+        let fat_size = info.fat_size;
+        let fat_start = info.fat_start_sector;
+        let num_fats = (info.fat_end_sector - info.fat_start_sector + 1) / fat_size;
+        let bytes_per_sec = info.bytes_per_sec as usize;
+
+        let mut first = vec![0u8; bytes_per_sec];
+        let mut copy = vec![0u8; bytes_per_sec];
+
+        for i in 1..num_fats {
+            let mut differs = 0;
+
+            for sector in 0..fat_size {
+                self.diskio.read_sector(&mut first, fat_start + sector, 1);
+                self.diskio.read_sector(&mut copy, fat_start + i * fat_size + sector, 1);
+
+                if first != copy {
+                    differs += 1;
+                    if self.repair {
+                        self.diskio.write_sector(&first, fat_start + i * fat_size + sector, 1);
+                    }
+                }
+            }
+
+            if differs > 0 {
+                let message = format!("FAT copy {} differs from FAT 1 in {} sectors", i + 1, differs);
+                self.report.error(message, self.repair);
+            }
+        }
+    }
+
+    // Is the cluster in data area
+    fn is_valid_cluster(&self, clust: u32) -> bool {
+        clust >= 2 && (clust as usize) < self.used.len()
+    }
+
+    // Walk the chain and mark the clusters used, the chain is cut at the first broken
+    // link when repair. Returns None when the first cluster is invalid
+    fn walk_chain(&mut self, fst_clust: u32, path: &str) -> Option<Vec<u32>> {
+        if !self.is_valid_cluster(fst_clust)
+            || self.used[fst_clust as usize]
+            || self.diskio.get_fat_entry(fst_clust) == 0
+        {
+            let message = format!("{}: invalid start cluster {}", path, fst_clust);
+            self.report.error(message, self.repair);
+            return None;
+        }
+
+        let mut clusters = vec![fst_clust];
+        self.used[fst_clust as usize] = true;
+
+        loop {
+            let clust = *clusters.last().unwrap();
+            let next = self.diskio.get_fat_entry(clust);
+
+            if next >= self.eoc_flag {
+                break;
+            }
+
+            let problem = if next == self.bad_flag {
+                Some("bad cluster")
+            } else if !self.is_valid_cluster(next) || self.diskio.get_fat_entry(next) == 0 {
+                Some("broken link")
+            } else if self.used[next as usize] {
+                Some("cross-linked cluster")
+            } else {
+                None
+            };
+
+            if let Some(problem) = problem {
+                let message = format!("{}: {} {} after cluster {}", path, problem, next, clust);
+                self.report.error(message, self.repair);
+                if self.repair {
+                    self.diskio.end_cluster(clust);
+                }
+                break;
+            }
+
+            self.used[next as usize] = true;
+            clusters.push(next);
+        }
+
+        Some(clusters)
+    }
+
+    // Free the clusters at the end of chain, the chain is kept with count clusters
+    fn cut_chain(&mut self, clusters: &[u32], count: usize) {
+        self.diskio.end_cluster(clusters[count - 1]);
+        for clust in clusters[count..].iter() {
+            self.diskio.set_fat_entry(*clust, 0);
+            self.used[*clust as usize] = false;
+        }
+    }
+
+    // Load directory
+    fn load_dir(&mut self, clusters: &[u32]) -> FatDirBuffer {
+        let info = self.diskio.get_info();
+        let bytes_per_sec = info.bytes_per_sec as usize;
+
+        // The fat12/16 root directory is in the fixed area
+        let sectors: Vec<u32> = if clusters.is_empty() {
+            (info.root_start_sector..info.root_start_sector + info.root_sector_count).collect()
+        } else {
+            let data_start = info.data_start_sector;
+            let sec_per_clust = info.sec_per_clust;
+            clusters
+                .iter()
+                .flat_map(|clust| {
+                    let fst_sec = data_start + (clust - 2) * sec_per_clust;
+                    fst_sec..fst_sec + sec_per_clust
+                })
+                .collect()
+        };
+
+        let mut data = vec![0u8; sectors.len() * bytes_per_sec];
+        for (i, sector) in sectors.iter().enumerate() {
+            self.diskio.read_sector(&mut data[i * bytes_per_sec..], *sector, 1);
+        }
+
+        FatDirBuffer {
+            dirty: vec![false; sectors.len()],
+            sectors,
+            data,
+        }
+    }
+
+    // Store the dirty sectors of directory
+    fn store_dir(&mut self, dir: &FatDirBuffer) {
+        let bytes_per_sec = self.diskio.get_info().bytes_per_sec as usize;
+        for (i, sector) in dir.sectors.iter().enumerate() {
+            if dir.dirty[i] {
+                self.diskio.write_sector(&dir.data[i * bytes_per_sec..], *sector, 1);
+            }
+        }
+    }
+
+    // Set the entry bytes of directory
+    fn set_entry(&mut self, dir: &mut FatDirBuffer, index: usize, bytes: &[u8; 32]) {
+        let offset = index * DIR_ENTRY_SIZE;
+        dir.data[offset..offset + DIR_ENTRY_SIZE].copy_from_slice(bytes);
+        dir.dirty[offset / self.diskio.get_info().bytes_per_sec as usize] = true;
+    }
+
+    // Free the long name entries
+    fn free_long_entries(&mut self, dir: &mut FatDirBuffer, longs: &[(usize, FatLongEntry)]) {
+        for (index, entry) in longs {
+            let mut entry = *entry;
+            entry.ord = DIR_FREE_FLAG;
+            self.set_entry(dir, *index, &entry.as_bytes());
+        }
+    }
+
+    // Get the name of entries
+    fn entry_name(short: &FatShortEntry, longs: &[(usize, FatLongEntry)]) -> String {
+        let mut obj = FatObject::from(&mut [FatEntry::Short(*short)]);
+        if longs.is_empty() {
+            return obj.get_object_name();
+        }
+
+        let mut entries: Vec<FatEntry> = longs.iter().map(|(_, e)| FatEntry::Long(*e)).collect();
+        entries.push(FatEntry::Short(*short));
+        FatObject::from(&mut entries).get_object_name()
+    }
+
+    // Check the directory tree from root
+    fn check_tree(&mut self) {
+        let info = self.diskio.get_info();
+        let root_cluster = info.root_cluster;
+        let is_fat32 = info.fat_type == FatType::Fat32;
+
+        let root = if is_fat32 {
+            match self.walk_chain(root_cluster, "/") {
+                Some(clusters) => clusters,
+                None => return,
+            }
+        } else {
+            Vec::new()
+        };
+
+        let mut dirs = vec![(String::from(""), root)];
+        while let Some((path, clusters)) = dirs.pop() {
+            let mut subdirs = self.check_dir(&path, &clusters);
+            dirs.append(&mut subdirs);
+        }
+    }
+
+    // Check the entries of directory, returns the sub directories
+    fn check_dir(&mut self, path: &str, clusters: &[u32]) -> Vec<(String, Vec<u32>)> {
+        let mut dir = self.load_dir(clusters);
+        let mut subdirs = Vec::new();
+        let mut longs: Vec<(usize, FatLongEntry)> = Vec::new();
+        let count = dir.data.len() / DIR_ENTRY_SIZE;
+
+        for index in 0..count {
+            let offset = index * DIR_ENTRY_SIZE;
+            let bytes = &dir.data[offset..offset + DIR_ENTRY_SIZE];
+
+            // The end of directory
+            if bytes[0] == 0 {
+                break;
+            }
+
+            let entry = match FatEntry::from_bytes(bytes) {
+                Some(entry) => entry,
+                None => {
+                    if !longs.is_empty() {
+                        self.orphan_long_entries(&mut dir, &mut longs, path);
+                    }
+                    continue;
+                }
+            };
+
+            match entry {
+                FatEntry::Long(long) => {
+                    // The sequence starts with the last flag and ends with ord 1
+                    let ord = long.ord & !DIR_SEQ_FLAG;
+                    let starts = long.ord & DIR_SEQ_FLAG != 0;
+                    let follows = match longs.last() {
+                        Some((_, last)) => !starts && (last.ord & !DIR_SEQ_FLAG) == ord + 1 && last.chksum == long.chksum,
+                        None => starts,
+                    };
+
+                    if !follows {
+                        if !longs.is_empty() {
+                            self.orphan_long_entries(&mut dir, &mut longs, path);
+                        }
+                        if !starts {
+                            longs.push((index, long));
+                            self.orphan_long_entries(&mut dir, &mut longs, path);
+                            continue;
+                        }
+                    }
+                    longs.push((index, long));
+                }
+                FatEntry::Short(mut short) => {
+                    // Check the long name checksum
+                    if !longs.is_empty() {
+                        let complete = longs.last().map(|(_, e)| e.ord & !DIR_SEQ_FLAG) == Some(1);
+                        let chksum = FatObject::calculate_checksum(&short.name);
+                        if !complete || longs[0].1.chksum != chksum {
+                            let name = Self::entry_name(&short, &[]);
+                            let message = format!("{}/{}: bad long name checksum", path, name);
+                            self.report.error(message, self.repair);
+                            if self.repair {
+                                let entries = core::mem::take(&mut longs);
+                                self.free_long_entries(&mut dir, &entries);
+                            }
+                            longs.clear();
+                        }
+                    }
+
+                    let name = Self::entry_name(&short, &longs);
+                    let entries = core::mem::take(&mut longs);
+
+                    let attr = short.attr & (FatEntryAttr::DIRECTORY | FatEntryAttr::VOLUME_ID);
+                    if attr == FatEntryAttr::VOLUME_ID || name == "." || name == ".." {
+                        continue;
+                    }
+
+                    // The long name entries are freed with the short entry
+                    let fullpath = format!("{}/{}", path, name);
+                    if self.check_entry(&mut short, &fullpath, &mut subdirs) && self.repair {
+                        self.set_entry(&mut dir, index, &short.as_bytes());
+                        if short.name[0] == DIR_FREE_FLAG {
+                            self.free_long_entries(&mut dir, &entries);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !longs.is_empty() {
+            self.orphan_long_entries(&mut dir, &mut longs, path);
+        }
+
+        self.store_dir(&dir);
+        subdirs
+    }
+
+    // Orphan long name entries without short entry
+    fn orphan_long_entries(&mut self, dir: &mut FatDirBuffer, longs: &mut Vec<(usize, FatLongEntry)>, path: &str) {
+        let message = format!("{}: {} orphaned long name entries", if path.is_empty() { "/" } else { path }, longs.len());
+        self.report.error(message, self.repair);
+        if self.repair {
+            let entries = core::mem::take(longs);
+            self.free_long_entries(dir, &entries);
+        }
+        longs.clear();
+    }
+
+    // Check the entry, returns true when the entry is changed
+    fn check_entry(&mut self, short: &mut FatShortEntry, path: &str, subdirs: &mut Vec<(String, Vec<u32>)>) -> bool {
+        let fst_clust = ((short.fst_clust_hi as u32) << 16) | short.fst_clust_lo as u32;
+        let is_dir = short.attr & FatEntryAttr::DIRECTORY != 0;
+        let info = self.diskio.get_info();
+        let clust_size = info.bytes_per_sec * info.sec_per_clust;
+
+        // Directory
+        if is_dir {
+            match self.walk_chain(fst_clust, path) {
+                Some(clusters) => subdirs.push((String::from(path), clusters)),
+                None => {
+                    if self.repair {
+                        short.name[0] = DIR_FREE_FLAG;
+                        return true;
+                    }
+                }
+            }
+            return false;
+        }
+
+        // Empty file
+        if fst_clust == 0 {
+            if short.file_size != 0 {
+                let message = format!("{}: size {} but no clusters", path, short.file_size);
+                self.report.error(message, self.repair);
+                short.file_size = 0;
+                return true;
+            }
+            return false;
+        }
+
+        // File chain
+        let clusters = match self.walk_chain(fst_clust, path) {
+            Some(clusters) => clusters,
+            None => {
+                short.fst_clust_hi = 0;
+                short.fst_clust_lo = 0;
+                short.file_size = 0;
+                return true;
+            }
+        };
+
+        // The empty file keeps the cluster allocated at creation
+        let needed = short.file_size.div_ceil(clust_size).max(1) as usize;
+        if clusters.len() < needed {
+            let message = format!("{}: size {} is larger than {} clusters", path, short.file_size, clusters.len());
+            self.report.error(message, self.repair);
+            short.file_size = clusters.len() as u32 * clust_size;
+            return true;
+        }
+
+        if clusters.len() > needed {
+            let message = format!("{}: {} clusters but size {}", path, clusters.len(), short.file_size);
+            self.report.error(message, self.repair);
+            if self.repair {
+                self.cut_chain(&clusters, needed);
+            }
+        }
+
+        false
+    }
+
+    // Check the clusters are used but not referenced
+    fn check_lost_clusters(&mut self) {
+        let max_clust = self.used.len() as u32;
+        let mut lost = Vec::new();
+
+        for clust in 2..max_clust {
+            let entry = self.diskio.get_fat_entry(clust);
+            if entry != 0 && entry != self.bad_flag && !self.used[clust as usize] {
+                lost.push((clust, entry));
+            }
+        }
+
+        if lost.is_empty() {
+            return;
+        }
+
+        // The chain head is not linked by other lost clusters
+        let chains = lost
+            .iter()
+            .filter(|(clust, _)| !lost.iter().any(|(_, next)| next == clust))
+            .count();
+
+        let message = format!("{} lost clusters in {} chains", lost.len(), chains);
+        self.report.error(message, self.repair);
+
+        if self.repair {
+            for (clust, _) in lost {
+                self.diskio.set_fat_entry(clust, 0);
+            }
+        }
+    }
+}
@@ -4,17 +4,20 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
-use crate::misc::fopts::vk_dev_fopt::DevFopt;
+use super::vk_fat_journal::FatJournal;
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use alloc::string::ToString;
 use alloc::vec;
 use alloc::vec::Vec;
 
 // Const mebers
 const DIR_ENTRY_SIZE: u8 = 32;
+const FAT12_EOC_FLAG: u16 = 0xff8;
 const FAT16_EOC_FLAG: u16 = 0xfff8;
 const FAT32_EOC_FLAG: u32 = 0xffffff8;
 
 // Enum FatType
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum FatType {
     None = 0,
     Fat12,
@@ -331,9 +334,11 @@ impl DiskIndex {
 
 // Struct FatDiskio
 pub struct FatDiskio {
-    starting_lba: u32,
-    device: DevFopt,
+    device: BlockFopt,
     info: FileSystemInfo,
+    fat_buff: Vec<u8>,
+    fat_sector: u32,
+    journal: FatJournal,
 }
 
 // Impl FatDiskio
@@ -341,29 +346,63 @@ impl FatDiskio {
     // New
     pub const fn new() -> Self {
         Self {
-            starting_lba: 0,
-            device: DevFopt::new(),
+            device: BlockFopt::new(),
             info: FileSystemInfo::new(),
+            fat_buff: Vec::new(),
+            fat_sector: u32::MAX,
+            journal: FatJournal::new(),
         }
     }
 
     // Setup
-    pub fn setup(&mut self, disk: &str, starting_lba: u32) -> bool {
-        self.starting_lba = starting_lba;
+    pub fn setup(&mut self, disk: &str) -> bool {
+        self.fat_sector = u32::MAX;
 
-        if self.device.open(disk) {
-            if self.check_file_system() {
-                return true;
-            }
-        }
-
-        false
+        self.device.open(disk) && self.check_file_system()
     }
 
     // Exit
     pub fn exit(&mut self) {
+        self.journal.exit();
         self.device.close();
     }
+
+    // Setup journal, the journal is stored in the contiguous clusters
+    pub fn setup_journal(&mut self, fst_clust: u32, clust_cnt: u32) -> bool {
+        let disk = self.device.get_name().to_string();
+        let start_sector = self.cluster_to_sector(fst_clust);
+        let sectors = clust_cnt * self.info.sec_per_clust;
+        let result = self.journal.setup(&disk, start_sector, sectors, self.info.bytes_per_sec);
+
+        // The fat sector may be changed by replay
+        self.fat_sector = u32::MAX;
+        result
+    }
+
+    // Is read only, the journal of read only device is not replayed
+    pub fn is_read_only(&mut self) -> bool {
+        self.device.is_read_only()
+    }
+
+    // Is journaled
+    pub fn is_journaled(&self) -> bool {
+        self.journal.is_enabled()
+    }
+
+    // Begin transaction, the metadata writes are logged until commit
+    pub fn begin(&mut self) {
+        self.journal.begin();
+    }
+
+    // Commit transaction
+    pub fn commit(&mut self) -> bool {
+        self.journal.commit()
+    }
+
+    // Is journal full, the transaction should be committed
+    pub fn is_journal_full(&self) -> bool {
+        self.journal.is_full()
+    }
 }
 
 // Impl FatDiskio
@@ -376,6 +415,11 @@ impl FatDiskio {
 
         // Check dbr
         if let Some(dbr) = DBR::from(&sector) {
+            // The exfat has no bpb, the fields are zero
+            if dbr.bpb.bytes_per_sec == 0 || dbr.bpb.sec_per_clust == 0 || dbr.bpb.num_fats == 0 {
+                return false;
+            }
+
             // Calc fat size
             if 0 != dbr.bpb.fat_sz_16 {
                 self.info.fat_size = dbr.bpb.fat_sz_16 as u32;
@@ -402,9 +446,8 @@ impl FatDiskio {
             // Calc fat12/16 root dir sector
             self.info.root_start_sector =
                 dbr.bpb.reserved_sec_cnt as u32 + (dbr.bpb.num_fats as u32 * self.info.fat_size);
-            self.info.root_sector_count = ((dbr.bpb.root_ent_cnt as u32 * DIR_ENTRY_SIZE as u32)
-                + (dbr.bpb.bytes_per_sec as u32 - 1))
-                / dbr.bpb.bytes_per_sec as u32;
+            self.info.root_sector_count = (dbr.bpb.root_ent_cnt as u32 * DIR_ENTRY_SIZE as u32)
+                .div_ceil(dbr.bpb.bytes_per_sec as u32);
 
             // Calc fat data sector
             self.info.data_start_sector = dbr.bpb.reserved_sec_cnt as u32
@@ -413,7 +456,7 @@ impl FatDiskio {
             self.info.data_sector_count = self.info.total_sectors
                 - (dbr.bpb.reserved_sec_cnt as u32
                     + (dbr.bpb.num_fats as u32 * self.info.fat_size)
-                    - self.info.root_sector_count);
+                    + self.info.root_sector_count);
 
             // Calc counts of clusters
             self.info.cluster_count = self.info.data_sector_count / dbr.bpb.sec_per_clust as u32;
@@ -446,274 +489,184 @@ impl FatDiskio {
     }
 
     // Cluster to sector
-    fn cluster_to_sector(&mut self, clust: u32) -> u32 {
+    pub fn cluster_to_sector(&mut self, clust: u32) -> u32 {
         ((clust - 2) * self.info.sec_per_clust) + self.info.data_start_sector
     }
 
-    // Get nexct cluster
-    fn get_next_cluster(&mut self, cluster: u32) -> u32 {
-        let mut is_eoc = false;
-        let mut fat_offset = 0;
-        let mut fat_max_offset = 0;
-        let mut fat_cluster = 0;
-
-        if self.info.fat_type == FatType::Fat16 {
-            fat_offset = cluster as u32 * 2;
-            fat_max_offset = self.info.bytes_per_sec / 2;
-        } else if self.info.fat_type == FatType::Fat32 {
-            fat_offset = cluster as u32 * 4;
-            fat_max_offset = self.info.bytes_per_sec / 4;
-        }
-
-        let this_fat_sec_num = self.info.fat_start_sector + (fat_offset / self.info.bytes_per_sec);
-        let this_fat_ent_offset = cluster % fat_max_offset;
-
-        let mut sec_buff = vec![0u8; self.info.bytes_per_sec as usize];
-
-        self.read_sector(&mut sec_buff, this_fat_sec_num, 1);
+    // Get the location of fat entry, the sector is relative to the fat start
+    fn fat_entry_pos(&mut self, clust: u32) -> Option<(u32, usize)> {
+        let fat_offset = match self.info.fat_type {
+            FatType::Fat12 => clust + clust / 2,
+            FatType::Fat16 => clust * 2,
+            FatType::Fat32 => clust * 4,
+            _ => return None,
+        };
+        let bytes_per_sec = self.info.bytes_per_sec;
+        Some((fat_offset / bytes_per_sec, (fat_offset % bytes_per_sec) as usize))
+    }
 
-        if self.info.fat_type == FatType::Fat16 {
-            let ptr = sec_buff.as_ptr() as *const u16;
-            fat_cluster = unsafe { *ptr.add(this_fat_ent_offset as usize) } as u32;
-            if fat_cluster >= FAT16_EOC_FLAG as u32 {
-                is_eoc = true;
-            }
-        } else if self.info.fat_type == FatType::Fat32 {
-            let ptr = sec_buff.as_ptr() as *const u32;
-            fat_cluster = unsafe { *ptr.add(this_fat_ent_offset as usize) } & 0x0fffffff;
-            if fat_cluster >= FAT32_EOC_FLAG {
-                is_eoc = true;
-            }
+    // Get the location of the next byte, the fat12 entry may span two sectors
+    fn next_byte_pos(&mut self, sector: u32, offset: usize) -> (u32, usize) {
+        if offset + 1 < self.info.bytes_per_sec as usize {
+            (sector, offset + 1)
+        } else {
+            (sector + 1, 0)
         }
-
-        if is_eoc { 0 } else { fat_cluster }
     }
 
-    // Set next cluster
-    fn set_next_cluster(&mut self, mut clust: u32) -> u32 {
-        let mut fat_offset = 0;
-        let mut fat_max_offset = 0;
-        let mut fat_cluster = if clust < 2 { 2 } else { clust };
-        let is_alloc_mode = clust < 2;
-
-        if self.info.fat_type == FatType::Fat16 {
-            fat_offset = fat_cluster as u32 * 2;
-            fat_max_offset = self.info.bytes_per_sec / 2;
-        } else if self.info.fat_type == FatType::Fat32 {
-            fat_offset = fat_cluster as u32 * 4;
-            fat_max_offset = self.info.bytes_per_sec / 4;
+    // Load the fat sector into fat buffer
+    fn load_fat_sector(&mut self, sector: u32) {
+        if self.fat_sector != sector {
+            let mut fat_buff = core::mem::take(&mut self.fat_buff);
+            fat_buff.resize(self.info.bytes_per_sec as usize, 0);
+            self.read_sector(&mut fat_buff, self.info.fat_start_sector + sector, 1);
+            self.fat_buff = fat_buff;
+            self.fat_sector = sector;
         }
+    }
 
-        let this_fat_sec_num = self.info.fat_start_sector + (fat_offset / self.info.bytes_per_sec);
-        let this_fat_ent_offset = fat_cluster % fat_max_offset;
-        let mut next_fat_sec_num = this_fat_sec_num;
-        let mut next_fat_ent_offset = this_fat_ent_offset;
-
-        let mut sec_buff = vec![0u8; self.info.bytes_per_sec as usize];
-
-        // Read sector buff
-        self.read_sector(&mut sec_buff, this_fat_sec_num, 1);
-
-        // Search next fat clust
-        loop {
-            next_fat_ent_offset += 1;
-
-            if next_fat_ent_offset >= fat_max_offset {
-                next_fat_ent_offset = 0;
+    // Store the fat buffer into all copies of fat
+    fn store_fat_sector(&mut self, sector: u32) {
+        let fat_buff = core::mem::take(&mut self.fat_buff);
+        let num_fats = (self.info.fat_end_sector - self.info.fat_start_sector + 1) / self.info.fat_size;
+        for i in 0..num_fats {
+            let fat_sector = self.info.fat_start_sector + i * self.info.fat_size + sector;
+            self.write_sector(&fat_buff, fat_sector, 1);
+        }
+        self.fat_buff = fat_buff;
+    }
 
-                if next_fat_sec_num <= self.info.fat_end_sector {
-                    self.read_sector(&mut sec_buff, next_fat_sec_num, 1);
-                    next_fat_sec_num += 1;
-                } else {
-                    drop(sec_buff);
-                    return 0;
-                }
-            }
+    // Get end of chain flag
+    fn eoc_flag(&mut self) -> u32 {
+        match self.info.fat_type {
+            FatType::Fat12 => FAT12_EOC_FLAG as u32,
+            FatType::Fat16 => FAT16_EOC_FLAG as u32,
+            _ => FAT32_EOC_FLAG,
+        }
+    }
 
-            if self.info.fat_type == FatType::Fat16 {
-                let ptr = sec_buff.as_ptr() as *const u16;
-                clust = unsafe { *ptr.add(next_fat_ent_offset as usize) } as u32;
-            } else if self.info.fat_type == FatType::Fat32 {
-                let ptr = sec_buff.as_ptr() as *const u32;
-                clust = unsafe { *ptr.add(next_fat_ent_offset as usize) } & 0x0fffffff;
+    // Get fat entry
+    pub fn get_fat_entry(&mut self, clust: u32) -> u32 {
+        if let Some((sector, offset)) = self.fat_entry_pos(clust) {
+            // The fat12 entry is 12 bits in two bytes
+            if self.info.fat_type == FatType::Fat12 {
+                let (next_sector, next_offset) = self.next_byte_pos(sector, offset);
+                self.load_fat_sector(sector);
+                let low = self.fat_buff[offset];
+                self.load_fat_sector(next_sector);
+                let high = self.fat_buff[next_offset];
+
+                let entry = u16::from_le_bytes([low, high]) as u32;
+                return if clust & 1 != 0 { entry >> 4 } else { entry & 0xfff };
             }
 
-            fat_cluster += 1;
+            self.load_fat_sector(sector);
+            let buff = &self.fat_buff;
 
-            if clust == 0 {
-                break;
+            if self.info.fat_type == FatType::Fat16 {
+                return u16::from_le_bytes([buff[offset], buff[offset + 1]]) as u32;
+            } else {
+                let entry = u32::from_le_bytes([
+                    buff[offset],
+                    buff[offset + 1],
+                    buff[offset + 2],
+                    buff[offset + 3],
+                ]);
+                return entry & 0x0fffffff;
             }
         }
+        0
+    }
 
-        // Set fat clust list table
-        if self.info.fat_type == FatType::Fat16 {
-            if next_fat_sec_num == this_fat_sec_num {
-                if !is_alloc_mode {
-                    let ptr = sec_buff.as_mut_ptr() as *mut u16;
-                    unsafe { *ptr.add(this_fat_ent_offset as usize) = fat_cluster as u16 };
+    // Set fat entry, all copies of fat are updated
+    pub fn set_fat_entry(&mut self, clust: u32, value: u32) {
+        if let Some((sector, offset)) = self.fat_entry_pos(clust) {
+            // The fat12 entry shares the half byte with the neighbour
+            if self.info.fat_type == FatType::Fat12 {
+                let (next_sector, next_offset) = self.next_byte_pos(sector, offset);
+                let value = value & 0xfff;
+                let odd = clust & 1 != 0;
+
+                self.load_fat_sector(sector);
+                let low = self.fat_buff[offset];
+                self.fat_buff[offset] = if odd { (low & 0x0f) | (value << 4) as u8 } else { value as u8 };
+
+                if next_sector != sector {
+                    self.store_fat_sector(sector);
+                    self.load_fat_sector(next_sector);
                 }
 
-                let ptr = sec_buff.as_mut_ptr() as *mut u16;
-                unsafe { *ptr.add(next_fat_ent_offset as usize) = FAT16_EOC_FLAG };
-                self.write_sector(&sec_buff, next_fat_sec_num, 1);
-            } else {
-                let ptr = sec_buff.as_mut_ptr() as *mut u16;
-                unsafe { *ptr.add(next_fat_ent_offset as usize) = FAT16_EOC_FLAG };
-                self.write_sector(&sec_buff, next_fat_sec_num, 1);
-
-                if !is_alloc_mode {
-                    self.read_sector(&mut sec_buff, this_fat_sec_num, 1);
-                    let ptr = sec_buff.as_mut_ptr() as *mut u16;
-                    unsafe { *ptr.add(this_fat_ent_offset as usize) = fat_cluster as u16 };
-                    self.write_sector(&sec_buff, this_fat_sec_num, 1);
-                }
+                let high = self.fat_buff[next_offset];
+                self.fat_buff[next_offset] = if odd { (value >> 4) as u8 } else { (high & 0xf0) | (value >> 8) as u8 };
+                self.store_fat_sector(next_sector);
+                return;
             }
-        } else if self.info.fat_type == FatType::Fat32 {
-            if next_fat_sec_num == this_fat_sec_num {
-                if !is_alloc_mode {
-                    let ptr = sec_buff.as_mut_ptr() as *mut u32;
-                    unsafe {
-                        *ptr.add(this_fat_ent_offset as usize) &= 0xf0000000;
-                        *ptr.add(this_fat_ent_offset as usize) |= fat_cluster;
-                    }
-                }
 
-                let ptr = sec_buff.as_mut_ptr() as *mut u32;
-                unsafe {
-                    *ptr.add(next_fat_ent_offset as usize) &= 0xf0000000;
-                    *ptr.add(next_fat_ent_offset as usize) |= FAT32_EOC_FLAG;
-                }
-                self.write_sector(&sec_buff, next_fat_sec_num, 1);
+            self.load_fat_sector(sector);
+
+            if self.info.fat_type == FatType::Fat16 {
+                let bytes = (value as u16).to_le_bytes();
+                self.fat_buff[offset..offset + 2].copy_from_slice(&bytes);
             } else {
-                let ptr = sec_buff.as_mut_ptr() as *mut u32;
-                unsafe {
-                    *ptr.add(next_fat_ent_offset as usize) &= 0xf0000000;
-                    *ptr.add(next_fat_ent_offset as usize) |= FAT32_EOC_FLAG;
-                }
-                self.write_sector(&sec_buff, next_fat_sec_num, 1);
-
-                if !is_alloc_mode {
-                    self.read_sector(&mut sec_buff, this_fat_sec_num, 1);
-                    let ptr = sec_buff.as_mut_ptr() as *mut u32;
-                    unsafe {
-                        *ptr.add(this_fat_ent_offset as usize) &= 0xf0000000;
-                        *ptr.add(this_fat_ent_offset as usize) |= fat_cluster;
-                    }
-                    self.write_sector(&sec_buff, this_fat_sec_num, 1);
-                }
+                let old = u32::from_le_bytes([
+                    self.fat_buff[offset],
+                    self.fat_buff[offset + 1],
+                    self.fat_buff[offset + 2],
+                    self.fat_buff[offset + 3],
+                ]);
+                let bytes = ((old & 0xf0000000) | (value & 0x0fffffff)).to_le_bytes();
+                self.fat_buff[offset..offset + 4].copy_from_slice(&bytes);
             }
-        }
-
-        drop(sec_buff);
 
-        fat_cluster
+            self.store_fat_sector(sector);
+        }
     }
 
-    // Clear prev cluster
-    fn clear_prev_cluster(&mut self, clust: u32) -> u32 {
-        let mut fat_offset = 0;
-        let mut fat_max_offset = 0;
-        let mut fat_cluster: u32;
-
-        if self.info.fat_type == FatType::Fat16 {
-            fat_offset = clust as u32 * 2;
-            fat_max_offset = self.info.bytes_per_sec / 2;
-        } else if self.info.fat_type == FatType::Fat32 {
-            fat_offset = clust as u32 * 4;
-            fat_max_offset = self.info.bytes_per_sec / 4;
+    // Get nexct cluster
+    pub fn get_next_cluster(&mut self, clust: u32) -> u32 {
+        let entry = self.get_fat_entry(clust);
+        if entry < 2 || entry >= self.eoc_flag() {
+            0
+        } else {
+            entry
         }
+    }
 
-        let this_fat_sec_num = self.info.fat_start_sector + (fat_offset / self.info.bytes_per_sec);
-        let this_fat_ent_offset = clust % fat_max_offset;
-        let mut prev_fat_sec_num = this_fat_sec_num;
-        let mut prev_fat_ent_offset = this_fat_ent_offset;
-
-        let mut sec_buff = vec![0u8; self.info.bytes_per_sec as usize];
-
-        // Read sector buff
-        self.read_sector(&mut sec_buff, this_fat_sec_num, 1);
-
-        // Search prev fat clust
-        loop {
-            if prev_fat_ent_offset == 0 {
-                prev_fat_ent_offset = fat_max_offset;
+    // Find free cluster, search from the start cluster and wrap around
+    fn find_free_cluster(&mut self, start: u32) -> u32 {
+        let max_clust = self.info.cluster_count + 2;
+        let start = if start < 2 || start >= max_clust { 2 } else { start };
 
-                if prev_fat_sec_num >= self.info.fat_start_sector {
-                    self.read_sector(&mut sec_buff, prev_fat_sec_num, 1);
-                    prev_fat_sec_num -= 1;
-                } else {
-                    drop(sec_buff);
-                    return 0;
-                }
-            } else {
-                prev_fat_ent_offset -= 1;
-            }
-
-            if self.info.fat_type == FatType::Fat16 {
-                let ptr = sec_buff.as_ptr() as *const u16;
-                fat_cluster = unsafe { *ptr.add(prev_fat_ent_offset as usize) } as u32;
-                if fat_cluster == clust {
-                    break;
-                }
-            } else if self.info.fat_type == FatType::Fat32 {
-                let ptr = sec_buff.as_ptr() as *const u32;
-                fat_cluster = unsafe { *ptr.add(prev_fat_ent_offset as usize) } & 0x0fffffff;
-                if fat_cluster == clust {
-                    break;
-                }
+        for clust in (start..max_clust).chain(2..start) {
+            if self.get_fat_entry(clust) == 0 {
+                return clust;
             }
         }
+        0
+    }
 
-        // Set fat clust list table
-        if self.info.fat_type == FatType::Fat16 {
-            if prev_fat_sec_num == this_fat_sec_num {
-                let ptr = sec_buff.as_mut_ptr() as *mut u16;
-                unsafe {
-                    *ptr.add(this_fat_ent_offset as usize) = 0;
-                    *ptr.add(prev_fat_ent_offset as usize) = FAT16_EOC_FLAG;
-                }
-                self.write_sector(&sec_buff, prev_fat_sec_num, 1);
-            } else {
-                let ptr = sec_buff.as_mut_ptr() as *mut u16;
-                unsafe { *ptr.add(prev_fat_ent_offset as usize) = FAT16_EOC_FLAG };
-                self.write_sector(&sec_buff, prev_fat_sec_num, 1);
-
-                self.read_sector(&mut sec_buff, this_fat_sec_num, 1);
-                let ptr = sec_buff.as_mut_ptr() as *mut u16;
-                unsafe { *ptr.add(this_fat_ent_offset as usize) = 0 };
-                self.write_sector(&sec_buff, this_fat_sec_num, 1);
-            }
-        } else if self.info.fat_type == FatType::Fat32 {
-            if prev_fat_sec_num == this_fat_sec_num {
-                let ptr = sec_buff.as_mut_ptr() as *mut u32;
-                unsafe {
-                    *ptr.add(this_fat_ent_offset as usize) &= 0xf0000000;
-                    *ptr.add(this_fat_ent_offset as usize) |= 0;
-                    *ptr.add(prev_fat_ent_offset as usize) &= 0xf0000000;
-                    *ptr.add(prev_fat_ent_offset as usize) |= FAT32_EOC_FLAG;
-                }
-                self.write_sector(&sec_buff, prev_fat_sec_num, 1);
-            } else {
-                let ptr = sec_buff.as_mut_ptr() as *mut u32;
-                unsafe {
-                    *ptr.add(prev_fat_ent_offset as usize) &= 0xf0000000;
-                    *ptr.add(prev_fat_ent_offset as usize) |= FAT32_EOC_FLAG;
-                }
-                self.write_sector(&sec_buff, prev_fat_sec_num, 1);
+    // Count free clusters
+    pub fn count_free_clusters(&mut self) -> u32 {
+        let max_clust = self.info.cluster_count + 2;
+        (2..max_clust)
+            .filter(|clust| self.get_fat_entry(*clust) == 0)
+            .count() as u32
+    }
 
-                self.read_sector(&mut sec_buff, this_fat_sec_num, 1);
-                let ptr = sec_buff.as_mut_ptr() as *mut u32;
-                unsafe {
-                    *ptr.add(this_fat_ent_offset as usize) &= 0xf0000000;
-                    *ptr.add(this_fat_ent_offset as usize) |= 0;
-                }
-                self.write_sector(&sec_buff, this_fat_sec_num, 1);
+    // Set next cluster, alloc a free cluster and link it after clust
+    fn set_next_cluster(&mut self, clust: u32) -> u32 {
+        let next = self.find_free_cluster(clust + 1);
+
+        if next != 0 {
+            let eoc = self.eoc_flag();
+            self.set_fat_entry(next, eoc);
+
+            if clust >= 2 {
+                self.set_fat_entry(clust, next);
             }
         }
 
-        drop(sec_buff);
-
-        fat_cluster
+        next
     }
 }
 
@@ -724,7 +677,7 @@ impl FatDiskio {
         let mut index = DiskIndex::new();
 
         if fst_clust < 2 {
-            if self.info.fat_type == FatType::Fat16 {
+            if self.info.fat_type == FatType::Fat12 || self.info.fat_type == FatType::Fat16 {
                 index.clust = 0;
                 index.sector = self.info.root_start_sector;
             } else if self.info.fat_type == FatType::Fat32 {
@@ -741,7 +694,7 @@ impl FatDiskio {
 
     // Get next index
     pub fn get_next_index(&mut self, index: &mut DiskIndex) {
-        // FAT16 root dir
+        // FAT12/16 root dir
         if index.clust < 2 {
             let dir_ended_sec = self.info.root_start_sector + self.info.root_sector_count;
             index.sector = if index.sector + 1 < dir_ended_sec {
@@ -768,22 +721,38 @@ impl FatDiskio {
 
 // Impl FatDiskio
 impl FatDiskio {
-    // Write sector
+    // Write sector, the sectors are logged in the journal transaction
     pub fn write_sector(&mut self, data: &[u8], sector: u32, sec_size: u32) -> u32 {
+        if self.journal.is_active() {
+            return self.journal.log(data, sector, sec_size);
+        }
+        self.write_data(data, sector, sec_size)
+    }
+
+    // Write data sector, the file data are not logged in the journal
+    pub fn write_data(&mut self, data: &[u8], sector: u32, sec_size: u32) -> u32 {
+        if self.journal.is_active() {
+            self.journal.discard(sector, sec_size);
+        }
         self.device.write(
             data,
             sec_size as usize,
-            (sector + self.starting_lba) as usize,
+            (sector) as usize,
         ) as u32
     }
 
-    // Read Sector
+    // Read Sector, the sectors logged in the journal transaction are newer
     pub fn read_sector(&mut self, data: &mut [u8], sector: u32, sec_size: u32) -> u32 {
-        self.device.read(
+        let result = self.device.read(
             data,
             sec_size as usize,
-            (sector + self.starting_lba) as usize,
-        ) as u32
+            (sector) as usize,
+        ) as u32;
+
+        if self.journal.is_active() {
+            self.journal.overlay(data, sector, sec_size);
+        }
+        result
     }
 }
 
@@ -799,12 +768,15 @@ impl FatDiskio {
             let sector = self.cluster_to_sector(cluster);
             let offset = i * bytes_per_sec * sec_per_clust;
 
-            if sec_per_clust != self.write_sector(&data[offset as usize..], sector, sec_per_clust) {
+            if sec_per_clust != self.write_data(&data[offset as usize..], sector, sec_per_clust) {
                 return i + 1;
             }
 
-            if clust_size > 1 {
-                let next_clust = self.set_next_cluster(cluster);
+            if i + 1 < clust_size {
+                let mut next_clust = self.get_next_cluster(cluster);
+                if next_clust == 0 {
+                    next_clust = self.set_next_cluster(cluster);
+                }
                 if next_clust == 0 {
                     return i + 1;
                 }
@@ -853,7 +825,7 @@ impl FatDiskio {
         for i in 0..clust_size {
             let sector = self.cluster_to_sector(cluster);
 
-            if sec_per_clust != self.write_sector(&zero, sector, sec_per_clust) {
+            if sec_per_clust != self.write_data(&zero, sector, sec_per_clust) {
                 return i + 1;
             }
 
@@ -869,7 +841,7 @@ impl FatDiskio {
         clust_size
     }
 
-    // Alloc cluster
+    // Alloc cluster, the clusters are not cleared
     pub fn alloc_cluster(&mut self, clust_size: u32) -> u32 {
         let mut cluster = 0;
         let mut first_cluster = 0;
@@ -879,23 +851,38 @@ impl FatDiskio {
                 first_cluster = cluster;
             }
             if cluster == 0 {
+                self.free_cluster(first_cluster, 0);
                 return 0;
             }
-            self.clear_cluster(cluster, 1);
         }
         first_cluster
     }
 
-    // Free cluster
+    // Free cluster, frees clust_size clusters of the chain starting at clust,
+    // the whole chain is freed when clust_size is 0
     pub fn free_cluster(&mut self, clust: u32, clust_size: u32) -> u32 {
         let mut cluster = clust;
-        for _ in 0..clust_size {
-            cluster = self.clear_prev_cluster(cluster);
-            if cluster == 0 {
-                return 0;
-            }
+        let mut count = 0;
+
+        while cluster >= 2 && (clust_size == 0 || count < clust_size) {
+            let next_clust = self.get_next_cluster(cluster);
+            self.set_fat_entry(cluster, 0);
+            count += 1;
+            cluster = next_clust;
         }
-        cluster
+
+        count
+    }
+
+    // Link cluster, set the next cluster of clust
+    pub fn link_cluster(&mut self, clust: u32, next: u32) {
+        self.set_fat_entry(clust, next);
+    }
+
+    // End cluster, mark clust as the end of chain
+    pub fn end_cluster(&mut self, clust: u32) {
+        let eoc = self.eoc_flag();
+        self.set_fat_entry(clust, eoc);
     }
 }
 
@@ -247,7 +247,7 @@ impl FatEntry {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     // Is long name entry
@@ -329,8 +329,8 @@ impl<'a> FatEntryIterator<'a> {
         self
     }
 
-    // Next
-    pub fn next(&mut self) -> bool {
+    // Advance to the next entry
+    pub fn advance(&mut self) -> bool {
         self.diskidx.index += 1;
 
         if self.diskidx.index >= self.diskio.get_info().entries_per_sec {
@@ -373,7 +373,7 @@ impl<'a> FatEntryIterator<'a> {
 impl<'a> Drop for FatEntryIterator<'a> {
     fn drop(&mut self) {
         if self.wrt_mode {
-            self.diskio.write_sector(&mut self.disksec, self.diskidx.sector, 1);
+            self.diskio.write_sector(&self.disksec, self.diskidx.sector, 1);
         }
         self.disksec.clear();
     }
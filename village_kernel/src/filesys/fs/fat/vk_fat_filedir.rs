@@ -8,18 +8,27 @@ use super::vk_fat_diskio::FatDiskio;
 use super::vk_fat_folder::FatFolder;
 use super::vk_fat_object::FatObject;
 use crate::traits::vk_filesys::{FileDir, FileMode};
+use alloc::vec;
 use alloc::vec::Vec;
 
 // Struct FatFile
 pub struct FatFile {
     myself: FatObject,
     file_id: usize,
-    file_mode: FileMode,
     file_size: u32,
     fst_clust: u32,
-    sector_size: u32,
-    clust_size: u32,
-    buffer: Vec<u8>,
+    clust_bytes: u32,
+    is_resized: bool,
+
+    // Chain cursor, the sequential io does not walk from the first cluster
+    curr_index: u32,
+    curr_clust: u32,
+
+    // Cluster cache
+    cache: Vec<u8>,
+    cache_index: u32,
+    cache_clust: u32,
+    cache_dirty: bool,
 }
 
 // Impl FatFile
@@ -29,12 +38,16 @@ impl FatFile {
         Self {
             myself: FatObject::new(),
             file_id: fd,
-            file_mode: FileMode::OPEN_EXISTING,
             file_size: 0,
             fst_clust: 0,
-            sector_size: 0,
-            clust_size: 0,
-            buffer: Vec::new(),
+            clust_bytes: 0,
+            is_resized: false,
+            curr_index: 0,
+            curr_clust: 0,
+            cache: Vec::new(),
+            cache_index: 0,
+            cache_clust: 0,
+            cache_dirty: false,
         }
     }
 
@@ -44,68 +57,261 @@ impl FatFile {
     }
 }
 
+// Impl FatFile
+impl FatFile {
+    // Locate the cluster of the index in the chain, the chain is extended when alloc
+    fn locate(&mut self, diskio: &mut FatDiskio, index: u32, alloc: bool) -> u32 {
+        // The file has no cluster
+        if self.fst_clust < 2 {
+            if !alloc {
+                return 0;
+            }
+
+            self.fst_clust = diskio.alloc_cluster(1);
+            if self.fst_clust == 0 {
+                return 0;
+            }
+
+            self.myself.set_fst_clust(self.fst_clust);
+            self.is_resized = true;
+        }
+
+        // Walk from the first cluster when the index is behind the cursor
+        if self.curr_clust < 2 || index < self.curr_index {
+            self.curr_index = 0;
+            self.curr_clust = self.fst_clust;
+        }
+
+        while self.curr_index < index {
+            let mut next = diskio.get_next_cluster(self.curr_clust);
+
+            if next == 0 {
+                if !alloc {
+                    return 0;
+                }
+
+                next = diskio.alloc_cluster(1);
+                if next == 0 {
+                    return 0;
+                }
+
+                diskio.link_cluster(self.curr_clust, next);
+            }
+
+            self.curr_clust = next;
+            self.curr_index += 1;
+        }
+
+        self.curr_clust
+    }
+
+    // Load the cluster of the index into cache
+    fn load(&mut self, diskio: &mut FatDiskio, index: u32, alloc: bool) -> bool {
+        if self.cache_clust != 0 && self.cache_index == index {
+            return true;
+        }
+
+        self.sync(diskio);
+
+        let clust = self.locate(diskio, index, alloc);
+        if clust == 0 {
+            return false;
+        }
+
+        diskio.read_cluster(&mut self.cache, clust, 1);
+        self.cache_index = index;
+        self.cache_clust = clust;
+        true
+    }
+
+    // Sync the cache to disk
+    fn sync(&mut self, diskio: &mut FatDiskio) {
+        if self.cache_dirty && self.cache_clust != 0 {
+            diskio.write_cluster(&self.cache, self.cache_clust, 1);
+        }
+        self.cache_dirty = false;
+    }
+
+    // Write data into the clusters, the transaction is committed when the journal is full
+    fn write_data(&mut self, diskio: &mut FatDiskio, data: &[u8], offset: usize) -> usize {
+        let mut written = 0usize;
+
+        while written < data.len() {
+            // Commit the cluster allocations, the journal has no space for more
+            if diskio.is_journal_full() {
+                let result = diskio.commit();
+                diskio.begin();
+                if !result {
+                    break;
+                }
+            }
+
+            let pos = (offset + written) as u32;
+            let index = pos / self.clust_bytes;
+            let clust_offset = (pos % self.clust_bytes) as usize;
+            let len = (self.clust_bytes as usize - clust_offset).min(data.len() - written);
+
+            if !self.load(diskio, index, true) {
+                break;
+            }
+
+            self.cache[clust_offset..clust_offset + len]
+                .copy_from_slice(&data[written..written + len]);
+            self.cache_dirty = true;
+
+            written += len;
+        }
+
+        written
+    }
+
+    // Fill zero from the end of file to the size, the clusters may hold old data
+    fn fill_zero(&mut self, diskio: &mut FatDiskio, size: usize) -> bool {
+        let zero = vec![0u8; self.clust_bytes as usize];
+
+        while (self.file_size as usize) < size {
+            let len = (size - self.file_size as usize).min(zero.len());
+            let written = self.write_data(diskio, &zero[..len], self.file_size as usize);
+
+            self.file_size += written as u32;
+            self.is_resized = true;
+
+            if written != len {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Free the clusters beyond the file size
+    fn shrink(&mut self, diskio: &mut FatDiskio) {
+        if self.fst_clust < 2 {
+            return;
+        }
+
+        // The empty file has no cluster
+        let clust_cnt = self.file_size.div_ceil(self.clust_bytes);
+        if clust_cnt == 0 {
+            diskio.free_cluster(self.fst_clust, 0);
+            self.fst_clust = 0;
+            self.myself.set_fst_clust(0);
+            self.curr_index = 0;
+            self.curr_clust = 0;
+            self.cache_clust = 0;
+            self.cache_dirty = false;
+            return;
+        }
+        let last_index = clust_cnt - 1;
+
+        let last_clust = self.locate(diskio, last_index, false);
+        if last_clust == 0 {
+            return;
+        }
+
+        let next = diskio.get_next_cluster(last_clust);
+        if next != 0 {
+            diskio.end_cluster(last_clust);
+            diskio.free_cluster(next, 0);
+
+            if self.cache_index > last_index {
+                self.cache_clust = 0;
+                self.cache_dirty = false;
+            }
+        }
+    }
+}
+
 // Impl FatFile
 impl FatFile {
     // Open
-    pub fn open(&mut self, diskio: &mut FatDiskio, mut fatobj: FatObject, mode: FileMode) {
+    pub fn open(&mut self, diskio: &mut FatDiskio, mut fatobj: FatObject) {
         let bytes_per_sec = diskio.get_info().bytes_per_sec;
         let sec_per_clust = diskio.get_info().sec_per_clust;
 
-        self.file_mode = mode;
         self.file_size = fatobj.get_file_size();
+        self.fst_clust = fatobj.get_fst_clust();
+        self.clust_bytes = bytes_per_sec * sec_per_clust;
+        self.cache.resize(self.clust_bytes as usize, 0);
 
-        if self.file_size > 0 {
-            self.fst_clust = fatobj.get_fst_clust();
-            self.sector_size = (self.file_size + bytes_per_sec - 1) / bytes_per_sec;
-            self.clust_size = (self.sector_size + sec_per_clust - 1) / sec_per_clust;
+        self.myself = fatobj;
+    }
 
-            let buf_len = (self.clust_size * sec_per_clust * bytes_per_sec) as usize;
-            self.buffer.resize(buf_len, 0);
+    // Write, the gap after the end of file is filled with zero
+    pub fn write(&mut self, diskio: &mut FatDiskio, data: &[u8], size: usize, offset: usize) -> usize {
+        let size = size.min(data.len());
+        if offset as u64 + size as u64 > u32::MAX as u64 {
+            return 0;
+        }
 
-            self.clust_size =
-                diskio.read_cluster(&mut self.buffer, self.fst_clust, self.clust_size);
+        // The clusters are allocated in transactions when the file is extended
+        let extend = offset + size > self.file_size as usize;
+        if extend {
+            diskio.begin();
         }
 
-        self.myself = fatobj;
-    }
+        let mut written = 0;
+        if offset <= self.file_size as usize || self.fill_zero(diskio, offset) {
+            written = self.write_data(diskio, &data[..size], offset);
+        }
 
-    // Write
-    pub fn write(&mut self, data: &[u8], size: usize, offset: usize) -> usize {
-        let mut wrt_offset = 0usize;
+        if extend && !diskio.commit() {
+            return 0;
+        }
 
-        if self.file_mode.contains(FileMode::OPEN_APPEND) {
-            wrt_offset = self.file_size as usize + offset;
-        } else if self.file_mode.contains(FileMode::WRITE) {
-            wrt_offset = offset;
+        // The file is extended when the data is written after the end
+        if offset + written > self.file_size as usize {
+            self.file_size = (offset + written) as u32;
+            self.is_resized = true;
         }
 
-        let new_size = wrt_offset + size;
+        written
+    }
 
-        if new_size > self.buffer.len() {
-            self.buffer.resize(new_size, 0);
+    // Set len, the file is truncated or extended with zero
+    pub fn set_len(&mut self, diskio: &mut FatDiskio, size: usize) -> bool {
+        if size > u32::MAX as usize {
+            return false;
         }
 
-        self.buffer[wrt_offset..(wrt_offset + size)].copy_from_slice(&data[0..size]);
-
-        self.file_size = new_size as u32;
-        self.file_mode = FileMode::OPEN_APPEND;
+        if size > self.file_size as usize {
+            diskio.begin();
+            let result = self.fill_zero(diskio, size);
+            return diskio.commit() && result;
+        }
 
-        size
+        if size < self.file_size as usize {
+            self.file_size = size as u32;
+            self.is_resized = true;
+        }
+        true
     }
 
     // Read
-    pub fn read(&mut self, data: &mut [u8], mut size: usize, offset: usize) -> usize {
-        if self.file_size > 0 {
-            if (self.file_size as usize) < offset + size {
-                size = self.file_size as usize - offset;
+    pub fn read(&mut self, diskio: &mut FatDiskio, data: &mut [u8], size: usize, offset: usize) -> usize {
+        if offset >= self.file_size as usize {
+            return 0;
+        }
+
+        let size = size.min(data.len()).min(self.file_size as usize - offset);
+        let mut readed = 0usize;
+
+        while readed < size {
+            let pos = (offset + readed) as u32;
+            let index = pos / self.clust_bytes;
+            let clust_offset = (pos % self.clust_bytes) as usize;
+            let len = (self.clust_bytes as usize - clust_offset).min(size - readed);
+
+            if !self.load(diskio, index, false) {
+                break;
             }
 
-            data.copy_from_slice(&self.buffer[offset..offset + size]);
+            data[readed..readed + len]
+                .copy_from_slice(&self.cache[clust_offset..clust_offset + len]);
 
-            size
-        } else {
-            0
+            readed += len;
         }
+
+        readed
     }
 
     // Size
@@ -113,17 +319,31 @@ impl FatFile {
         self.file_size as usize
     }
 
+    // Get object, the size and first cluster are not flushed yet
+    pub fn get_object(&mut self) -> FatObject {
+        let mut fatobj = self.myself.clone();
+        fatobj.set_file_size(self.file_size);
+        fatobj.set_fst_clust(self.fst_clust);
+        fatobj
+    }
+
     // Flush
     pub fn flush(&mut self, diskio: &mut FatDiskio) {
-        if self.clust_size == diskio.write_cluster(&self.buffer, self.fst_clust, self.clust_size) {
+        self.sync(diskio);
+
+        if self.is_resized {
+            self.shrink(diskio);
             self.myself.set_file_size(self.file_size);
             FatFolder::update(diskio, self.myself.clone());
+            self.is_resized = false;
         }
     }
 
     // Close
-    pub fn close(&mut self) {
-        self.buffer.clear();
+    pub fn close(&mut self, diskio: &mut FatDiskio) {
+        self.flush(diskio);
+        self.cache.clear();
+        self.cache_clust = 0;
     }
 }
 
@@ -181,11 +401,10 @@ impl FatDir {
             size
         };
 
-        for i in 0..resize as usize {
-            let obj = &mut self.sub_objs[offset + i];
-            dirs[i].name = obj.get_object_name();
-            dirs[i].attr = obj.get_object_attr();
-            dirs[i].typid = obj.get_object_type();
+        for (dir, obj) in dirs.iter_mut().zip(self.sub_objs[offset..].iter_mut()).take(resize) {
+            dir.name = obj.get_object_name();
+            dir.attr = obj.get_object_attr();
+            dir.typid = obj.get_object_type();
         }
 
         resize
@@ -16,6 +16,9 @@ use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
 
+// Const members
+const ALIAS_MAX_NUM: u32 = 999999;
+
 // Struct FatFolder
 pub struct FatFolder {
     myself: FatObject,
@@ -41,15 +44,28 @@ impl FatFolder {
 
     // Create fatobj
     pub fn create(diskio: &mut FatDiskio, mut selfobj: FatObject, name: &str, attr: u8) -> Option<FatObject> {
-        let myself_clust = selfobj.get_fst_clust();
-        let newobj_clust = diskio.alloc_cluster(1);
+        if !FatObject::is_valid_name(name) {
+            return None;
+        }
 
+        let mut folder = Self::init(diskio, selfobj.clone());
         let mut newobj = vec![FatObject::new(); 1];
         newobj[0].set_name(name);
         newobj[0].set_attribute(attr);
+        if !folder.set_unique_alias(&mut newobj[0]) {
+            return None;
+        }
+
+        let myself_clust = selfobj.get_fst_clust();
+        let newobj_clust = diskio.alloc_cluster(1);
         newobj[0].set_fst_clust(newobj_clust);
 
-        if Self::init(diskio, selfobj).write(diskio, &mut newobj) {
+        // Clear the directory cluster, the free entries end the directory
+        if (attr & FatEntryAttr::DIRECTORY) != 0 && newobj_clust != 0 {
+            diskio.clear_cluster(newobj_clust, 1);
+        }
+
+        if folder.write(diskio, &mut newobj) {
             if (attr & FatEntryAttr::DIRECTORY) != 0 {
                 let mut dotobjs = vec![FatObject::new(); 2];
                 dotobjs[0] = FatObject::new_dot_dir(newobj_clust);
@@ -59,6 +75,11 @@ impl FatFolder {
             return Some(newobj[0].clone());
         }
 
+        // Free the cluster when the entries can not be written
+        if newobj_clust != 0 {
+            diskio.free_cluster(newobj_clust, 0);
+        }
+
         None
     }
 
@@ -67,7 +88,7 @@ impl FatFolder {
         let mut folder = Self::init(diskio, selfobj);
 
         for fatobj in folder.fatobjs.iter_mut() {
-            if fatobj.get_name() == name {
+            if fatobj.is_name_matched(name) {
                 return Some(fatobj.clone());
             }
         }
@@ -75,6 +96,62 @@ impl FatFolder {
         None
     }
 
+    // Rename fatobj, the entries are moved into the parent and the data is not copied
+    pub fn rename(diskio: &mut FatDiskio, mut selfobj: FatObject, mut parent: FatObject, name: &str) -> Option<FatObject> {
+        if !FatObject::is_valid_name(name) {
+            return None;
+        }
+
+        let mut folder = Self::init(diskio, parent.clone());
+        let mut newobj = vec![FatObject::new(); 1];
+        newobj[0].set_name(name);
+        if !folder.set_unique_alias(&mut newobj[0]) {
+            return None;
+        }
+        newobj[0].set_attribute(selfobj.get_attribute());
+        newobj[0].set_create_tenth(selfobj.get_create_tenth());
+        newobj[0].set_create_time(selfobj.get_create_time());
+        newobj[0].set_create_date(selfobj.get_create_date());
+        newobj[0].set_last_acc_date(selfobj.get_last_acc_date());
+        newobj[0].set_write_time(selfobj.get_write_time());
+        newobj[0].set_write_date(selfobj.get_write_date());
+        newobj[0].set_fst_clust(selfobj.get_fst_clust());
+        newobj[0].set_file_size(selfobj.get_file_size());
+
+        // Write the new entries before the old entries are freed
+        let parent_clust = parent.get_fst_clust();
+        if !folder.write(diskio, &mut newobj) {
+            return None;
+        }
+        Self::remove(diskio, selfobj.clone());
+
+        // Update the parent of directory
+        if selfobj.get_object_type() == FileType::Directory {
+            let mut folder = Self::init(diskio, newobj[0].clone());
+            for fatobj in folder.fatobjs.iter_mut() {
+                if fatobj.get_name() == ".." {
+                    fatobj.set_fst_clust(parent_clust);
+                    Self::update(diskio, fatobj.clone());
+                    break;
+                }
+            }
+        }
+
+        Some(newobj[0].clone())
+    }
+
+    // Is empty, the dot entries are not counted
+    pub fn is_empty(diskio: &mut FatDiskio, selfobj: FatObject) -> bool {
+        let mut folder = Self::init(diskio, selfobj);
+        for fatobj in folder.fatobjs.iter_mut() {
+            let name = fatobj.get_name();
+            if name != "." && name != ".." && fatobj.get_object_type() != FileType::Volume {
+                return false;
+            }
+        }
+        true
+    }
+
     // Update fatobj
     pub fn update(diskio: &mut FatDiskio, mut fatobj: FatObject) {
         let mut folder = Self::new();
@@ -93,7 +170,7 @@ impl FatFolder {
         let mut folder = Self::init(diskio, FatObject::root());
         for fatobj in folder.fatobjs.iter_mut() {
             if fatobj.get_object_type() == FileType::Volume {
-                fatobj.set_name(name);
+                fatobj.set_label(name);
                 Self::update(diskio, fatobj.clone());
                 break;
             }
@@ -114,6 +191,21 @@ impl FatFolder {
 
 // Impl FatFolder
 impl FatFolder {
+    // Set unique alias, the numeric tail is added when the short name is lossy
+    fn set_unique_alias(&mut self, newobj: &mut FatObject) -> bool {
+        if !newobj.is_lossy() {
+            return true;
+        }
+
+        for num in 1..=ALIAS_MAX_NUM {
+            newobj.set_numeric_tail(num);
+            if !self.fatobjs.iter_mut().any(|fatobj| fatobj.is_short_name_equal(newobj)) {
+                return true;
+            }
+        }
+        false
+    }
+
     // Find space
     fn find_space(&mut self, diskio: &mut FatDiskio, req_size: usize) -> Option<DiskIndex> {
         let mut start_idx = DiskIndex::new();
@@ -136,7 +228,7 @@ impl FatFolder {
                 free_cnt = 0;
             }
 
-            if !iter.next() {
+            if !iter.advance() {
                 break;
             }
         }
@@ -153,7 +245,7 @@ impl FatFolder {
         for (i, entry) in entries.iter().enumerate() {
             iter.set_item(*entry);
 
-            if !iter.next() {
+            if !iter.advance() {
                 return i;
             }
         }
@@ -177,7 +269,7 @@ impl FatFolder {
             loop {
                 if let Some(entry) = iter.get_item() {
                     // Record entry index
-                    if entries.len() == 0 {
+                    if entries.is_empty() {
                         start_index = iter.get_index();
                     }
 
@@ -193,7 +285,7 @@ impl FatFolder {
                     }
                 }
 
-                if !iter.next() {
+                if !iter.advance() {
                     break;
                 }
             }
@@ -210,6 +302,8 @@ impl FatFolder {
                     return false;
                 }
                 self.fatobjs.push(obj.clone());
+            } else {
+                return false;
             }
         }
         true
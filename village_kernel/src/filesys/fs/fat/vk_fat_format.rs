@@ -0,0 +1,448 @@
+//###########################################################################
+// vk_fat_format.rs
+// The specific implementation of functions related to fat format
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_fat_diskio::{FatDiskio, FatType, FileSystemInfo};
+use super::vk_fat_journal::FatJournal;
+use super::vk_fat_entry::FatEntryAttr;
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use crate::village::kernel;
+use alloc::string::{String, ToString};
+use alloc::vec;
+
+// Const members
+const BYTES_PER_SEC: u32 = 512;
+const NUM_FATS: u32 = 2;
+const ROOT_ENT_CNT: u32 = 512;
+const FAT32_RSVD_SEC_CNT: u32 = 32;
+const FAT32_ROOT_CLUST: u32 = 2;
+const FAT32_FS_INFO_SEC: u32 = 1;
+const FAT32_BK_BOOT_SEC: u32 = 6;
+const FAT12_MAX_CLUST: u32 = 4084;
+const FAT16_MAX_CLUST: u32 = 65524;
+const FAT32_MAX_CLUST: u32 = 0x0ffffff5;
+const MEDIA_FIXED: u8 = 0xf8;
+const ZERO_CHUNK_SECS: u32 = 32;
+const JOURNAL_SECTORS: u32 = 2048;
+const LABEL_INVALID_CHARS: &str = "\"*+,./:;<=>?[\\]|";
+
+// Struct FatFormat
+pub struct FatFormat {
+    device: BlockFopt,
+    total_sectors: u32,
+    hidden_sectors: u32,
+    fat_type: FatType,
+    sec_per_clust: u32,
+    label: [u8; 11],
+    has_label: bool,
+    has_journal: bool,
+}
+
+// Impl FatFormat
+impl FatFormat {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            device: BlockFopt::new(),
+            total_sectors: 0,
+            hidden_sectors: 0,
+            fat_type: FatType::None,
+            sec_per_clust: 0,
+            label: *b"NO NAME    ",
+            has_label: false,
+            has_journal: false,
+        }
+    }
+
+    // Setup, the total sectors are read from device when it is 0
+    pub fn setup(&mut self, disk: &str, total_sectors: u32) -> bool {
+        if !self.device.open(disk) {
+            return false;
+        }
+
+        self.total_sectors = if total_sectors != 0 {
+            total_sectors
+        } else {
+            self.device.get_sector_count() as u32
+        };
+
+        self.total_sectors != 0
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        self.device.close();
+    }
+
+    // Set fat type, the type is selected by size when it is none
+    pub fn set_fat_type(&mut self, fat_type: FatType) {
+        self.fat_type = fat_type;
+    }
+
+    // Set hidden sectors, the starting lba of the partition where the volume is
+    pub fn set_hidden_sectors(&mut self, sectors: u32) {
+        self.hidden_sectors = sectors;
+    }
+
+    // Set sectors per cluster, the size is selected by fat type when it is 0
+    pub fn set_sec_per_clust(&mut self, sec_per_clust: u32) -> bool {
+        if sec_per_clust > 128 || !(sec_per_clust == 0 || sec_per_clust.is_power_of_two()) {
+            return false;
+        }
+        self.sec_per_clust = sec_per_clust;
+        true
+    }
+
+    // Set journal, the metadata updates are logged in the journal file
+    pub fn set_journal(&mut self, enable: bool) {
+        self.has_journal = enable;
+    }
+
+    // Set label, the label is in upper case and padded with space
+    pub fn set_label(&mut self, label: &str) -> bool {
+        let label = label.to_uppercase();
+
+        if label.is_empty()
+            || label.len() > 11
+            || label.starts_with(' ')
+            || label.chars().any(|c| !c.is_ascii() || c < ' ' || LABEL_INVALID_CHARS.contains(c))
+        {
+            return false;
+        }
+
+        self.label = [b' '; 11];
+        self.label[..label.len()].copy_from_slice(label.as_bytes());
+        self.has_label = true;
+        true
+    }
+}
+
+// Impl FatFormat
+impl FatFormat {
+    // Auto fat type, fat12 for the small disk up to 16MB, fat16 up to 512MB
+    fn auto_fat_type(&self) -> FatType {
+        if self.total_sectors <= 32768 {
+            FatType::Fat12
+        } else if self.total_sectors <= 1048576 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        }
+    }
+
+    // Auto sectors per cluster, the cluster size follows the common defaults
+    fn auto_sec_per_clust(&self, fat_type: FatType) -> u32 {
+        let sectors = self.total_sectors;
+        match fat_type {
+            FatType::Fat12 => {
+                let mut sec_per_clust = 1;
+                while sec_per_clust < 128 && sectors / sec_per_clust > FAT12_MAX_CLUST {
+                    sec_per_clust *= 2;
+                }
+                sec_per_clust
+            }
+            FatType::Fat16 => match sectors {
+                0..=262144 => 4,
+                262145..=524288 => 8,
+                524289..=1048576 => 16,
+                1048577..=2097152 => 32,
+                _ => 64,
+            },
+            _ => match sectors {
+                0..=16777216 => 8,
+                16777217..=33554432 => 16,
+                33554433..=67108864 => 32,
+                _ => 64,
+            },
+        }
+    }
+
+    // Calculate the layout, returns None when the cluster count does not fit the type
+    fn layout(&self, fat_type: FatType, sec_per_clust: u32) -> Option<FileSystemInfo> {
+        let is_fat32 = fat_type == FatType::Fat32;
+        let reserved_sec_cnt = if is_fat32 { FAT32_RSVD_SEC_CNT } else { 1 };
+        let root_ent_cnt = if is_fat32 { 0 } else { ROOT_ENT_CNT };
+        let root_sector_count = root_ent_cnt * 32 / BYTES_PER_SEC;
+        let entry_bits = match fat_type {
+            FatType::Fat12 => 12,
+            FatType::Fat16 => 16,
+            _ => 32,
+        };
+
+        // The fat size grows until it covers all the clusters
+        let mut fat_size = 1;
+        let cluster_count = loop {
+            let meta_sectors = reserved_sec_cnt + NUM_FATS * fat_size + root_sector_count;
+            if meta_sectors >= self.total_sectors {
+                return None;
+            }
+
+            let cluster_count = (self.total_sectors - meta_sectors) / sec_per_clust;
+            let fat_bytes = ((cluster_count as u64 + 2) * entry_bits).div_ceil(8);
+            let needed = fat_bytes.div_ceil(BYTES_PER_SEC as u64) as u32;
+
+            if needed <= fat_size {
+                break cluster_count;
+            }
+            fat_size = needed;
+        };
+
+        let (min, max) = match fat_type {
+            FatType::Fat12 => (1, FAT12_MAX_CLUST),
+            FatType::Fat16 => (FAT12_MAX_CLUST + 1, FAT16_MAX_CLUST),
+            _ => (FAT16_MAX_CLUST + 1, FAT32_MAX_CLUST),
+        };
+        if cluster_count < min || cluster_count > max {
+            return None;
+        }
+
+        let mut info = FileSystemInfo::new();
+        info.fat_type = fat_type;
+        info.fat_size = fat_size;
+        info.reserved_sec_cnt = reserved_sec_cnt;
+        info.total_sectors = self.total_sectors;
+        info.cluster_count = cluster_count;
+        info.fat_start_sector = reserved_sec_cnt;
+        info.fat_end_sector = reserved_sec_cnt + NUM_FATS * fat_size - 1;
+        info.root_cluster = if is_fat32 { FAT32_ROOT_CLUST } else { 0 };
+        info.root_start_sector = reserved_sec_cnt + NUM_FATS * fat_size;
+        info.root_sector_count = root_sector_count;
+        info.data_start_sector = info.root_start_sector + root_sector_count;
+        info.data_sector_count = self.total_sectors - info.data_start_sector;
+        info.entries_per_sec = BYTES_PER_SEC / 32;
+        info.bytes_per_sec = BYTES_PER_SEC;
+        info.sec_per_clust = sec_per_clust;
+        Some(info)
+    }
+
+    // Select the layout, the cluster size is adjusted when it is not given
+    fn select_layout(&self) -> Option<FileSystemInfo> {
+        let fat_type = match self.fat_type {
+            FatType::Fat12 | FatType::Fat16 | FatType::Fat32 => self.fat_type,
+            _ => self.auto_fat_type(),
+        };
+
+        if self.sec_per_clust != 0 {
+            return self.layout(fat_type, self.sec_per_clust);
+        }
+
+        // Try the default size first, then the smaller and bigger sizes
+        let preferred = self.auto_sec_per_clust(fat_type);
+        let mut sizes = vec![preferred];
+        let mut size = preferred;
+        while size > 1 {
+            size /= 2;
+            sizes.push(size);
+        }
+        size = preferred;
+        while size < 128 {
+            size *= 2;
+            sizes.push(size);
+        }
+
+        sizes.into_iter().find_map(|sec_per_clust| self.layout(fat_type, sec_per_clust))
+    }
+}
+
+// Impl FatFormat
+impl FatFormat {
+    // Write sector
+    fn write_sector(&mut self, data: &[u8], sector: u32, count: u32) -> bool {
+        self.device.write(data, count as usize, sector as usize) == count as usize
+    }
+
+    // Clear sectors
+    fn clear_sectors(&mut self, sector: u32, count: u32) -> bool {
+        let zero = vec![0u8; (ZERO_CHUNK_SECS * BYTES_PER_SEC) as usize];
+        let mut done = 0;
+
+        while done < count {
+            let chunk = (count - done).min(ZERO_CHUNK_SECS);
+            if !self.write_sector(&zero, sector + done, chunk) {
+                return false;
+            }
+            done += chunk;
+        }
+        true
+    }
+
+    // Build the boot sector
+    fn boot_sector(&self, info: &FileSystemInfo) -> [u8; 512] {
+        let mut data = [0u8; 512];
+        let is_fat32 = info.fat_type == FatType::Fat32;
+        let vol_id = kernel().system().get_ticks() ^ self.total_sectors.rotate_left(16);
+
+        // Boot sector
+        data[0..3].copy_from_slice(if is_fat32 { &[0xeb, 0x58, 0x90] } else { &[0xeb, 0x3c, 0x90] });
+        data[3..11].copy_from_slice(b"VILLAGE ");
+
+        // Bios parameter block
+        let tot_sec_16 = if !is_fat32 && info.total_sectors < 0x10000 { info.total_sectors } else { 0 };
+        let tot_sec_32 = if tot_sec_16 == 0 { info.total_sectors } else { 0 };
+        let fat_sz_16 = if is_fat32 { 0 } else { info.fat_size };
+        let root_ent_cnt = info.root_sector_count * BYTES_PER_SEC / 32;
+
+        data[11..13].copy_from_slice(&(BYTES_PER_SEC as u16).to_le_bytes());
+        data[13] = info.sec_per_clust as u8;
+        data[14..16].copy_from_slice(&(info.reserved_sec_cnt as u16).to_le_bytes());
+        data[16] = NUM_FATS as u8;
+        data[17..19].copy_from_slice(&(root_ent_cnt as u16).to_le_bytes());
+        data[19..21].copy_from_slice(&(tot_sec_16 as u16).to_le_bytes());
+        data[21] = MEDIA_FIXED;
+        data[22..24].copy_from_slice(&(fat_sz_16 as u16).to_le_bytes());
+        data[24..26].copy_from_slice(&63u16.to_le_bytes());
+        data[26..28].copy_from_slice(&255u16.to_le_bytes());
+        data[28..32].copy_from_slice(&self.hidden_sectors.to_le_bytes());
+        data[32..36].copy_from_slice(&tot_sec_32.to_le_bytes());
+
+        // Extended boot record
+        let ext = if is_fat32 {
+            data[36..40].copy_from_slice(&info.fat_size.to_le_bytes());
+            data[44..48].copy_from_slice(&info.root_cluster.to_le_bytes());
+            data[48..50].copy_from_slice(&(FAT32_FS_INFO_SEC as u16).to_le_bytes());
+            data[50..52].copy_from_slice(&(FAT32_BK_BOOT_SEC as u16).to_le_bytes());
+            64
+        } else {
+            36
+        };
+
+        let fil_sys_type: &[u8; 8] = match info.fat_type {
+            FatType::Fat12 => b"FAT12   ",
+            FatType::Fat16 => b"FAT16   ",
+            _ => b"FAT32   ",
+        };
+
+        data[ext] = 0x80;
+        data[ext + 2] = 0x29;
+        data[ext + 3..ext + 7].copy_from_slice(&vol_id.to_le_bytes());
+        data[ext + 7..ext + 18].copy_from_slice(&self.label);
+        data[ext + 18..ext + 26].copy_from_slice(fil_sys_type);
+
+        // Magic
+        data[510] = 0x55;
+        data[511] = 0xaa;
+        data
+    }
+
+    // Build the fs info sector, the root cluster is the only used cluster
+    fn fs_info_sector(&self, info: &FileSystemInfo) -> [u8; 512] {
+        let mut data = [0u8; 512];
+        data[0..4].copy_from_slice(&0x41615252u32.to_le_bytes());
+        data[484..488].copy_from_slice(&0x61417272u32.to_le_bytes());
+        data[488..492].copy_from_slice(&(info.cluster_count - 1).to_le_bytes());
+        data[492..496].copy_from_slice(&(info.root_cluster + 1).to_le_bytes());
+        data[508..512].copy_from_slice(&0xaa550000u32.to_le_bytes());
+        data
+    }
+
+    // Build the first fat sector, the first two entries hold the media and end flags
+    fn fat_sector(&self, info: &FileSystemInfo) -> [u8; 512] {
+        let mut data = [0u8; 512];
+        match info.fat_type {
+            FatType::Fat12 => {
+                data[0..3].copy_from_slice(&[MEDIA_FIXED, 0xff, 0xff]);
+            }
+            FatType::Fat16 => {
+                data[0..2].copy_from_slice(&(0xff00 | MEDIA_FIXED as u16).to_le_bytes());
+                data[2..4].copy_from_slice(&0xffffu16.to_le_bytes());
+            }
+            _ => {
+                data[0..4].copy_from_slice(&(0x0fffff00 | MEDIA_FIXED as u32).to_le_bytes());
+                data[4..8].copy_from_slice(&0x0fffffffu32.to_le_bytes());
+                data[8..12].copy_from_slice(&0x0fffffffu32.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    // Build the first root directory sector with the volume label entry
+    fn root_sector(&self) -> [u8; 512] {
+        let mut data = [0u8; 512];
+        if self.has_label {
+            data[0..11].copy_from_slice(&self.label);
+            data[11] = FatEntryAttr::VOLUME_ID;
+        }
+        data
+    }
+
+    // Format, returns the layout of the new file system
+    pub fn format(&mut self) -> Option<FileSystemInfo> {
+        let info = self.select_layout()?;
+        let is_fat32 = info.fat_type == FatType::Fat32;
+
+        // Clear the reserved sectors, fats and root directory
+        let root_sectors = if is_fat32 { info.sec_per_clust } else { info.root_sector_count };
+        if !self.clear_sectors(0, info.data_start_sector + root_sectors) {
+            return None;
+        }
+
+        // Write boot sector, fat32 has the fs info and backup boot sectors
+        let boot = self.boot_sector(&info);
+        if !self.write_sector(&boot, 0, 1) {
+            return None;
+        }
+
+        if is_fat32 {
+            let fs_info = self.fs_info_sector(&info);
+            if !self.write_sector(&fs_info, FAT32_FS_INFO_SEC, 1)
+                || !self.write_sector(&boot, FAT32_BK_BOOT_SEC, 1)
+                || !self.write_sector(&fs_info, FAT32_BK_BOOT_SEC + FAT32_FS_INFO_SEC, 1)
+            {
+                return None;
+            }
+        }
+
+        // Write fats
+        let fat = self.fat_sector(&info);
+        for i in 0..NUM_FATS {
+            if !self.write_sector(&fat, info.fat_start_sector + i * info.fat_size, 1) {
+                return None;
+            }
+        }
+
+        // Write root directory
+        let root = self.root_sector();
+        let root_start = if is_fat32 { info.data_start_sector } else { info.root_start_sector };
+        if !self.write_sector(&root, root_start, 1) {
+            return None;
+        }
+
+        if !self.device.sync() {
+            return None;
+        }
+
+        // Create the journal file, up to 1MB or 1/16 of volume
+        if self.has_journal && !self.create_journal(&info) {
+            return None;
+        }
+
+        Some(info)
+    }
+
+    // Create journal
+    fn create_journal(&mut self, info: &FileSystemInfo) -> bool {
+        let disk = self.device.get_name().to_string();
+        let sectors = JOURNAL_SECTORS.min(info.total_sectors / 16);
+
+        let mut diskio = FatDiskio::new();
+        if !diskio.setup(&disk) {
+            return false;
+        }
+
+        let result = FatJournal::create(&mut diskio, sectors);
+        diskio.exit();
+        result
+    }
+
+    // Has journal
+    pub fn has_journal(&self) -> bool {
+        self.has_journal
+    }
+
+    // Get label
+    pub fn get_label(&self) -> String {
+        String::from_utf8_lossy(&self.label).trim_end().into()
+    }
+}
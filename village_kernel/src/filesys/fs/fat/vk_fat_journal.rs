@@ -0,0 +1,423 @@
+//###########################################################################
+// vk_fat_journal.rs
+// The specific implementation of functions related to fat journal
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_fat_diskio::FatDiskio;
+use super::vk_fat_entry::FatEntryAttr;
+use super::vk_fat_folder::FatFolder;
+use super::vk_fat_object::FatObject;
+use crate::debug_error;
+use crate::debug_warning;
+use crate::filesys::vk_partition::crc32;
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+pub const JOURNAL_NAME: &str = "JOURNAL.SYS";
+const JOURNAL_MAGIC: &[u8; 8] = b"VKJOURNL";
+const JOURNAL_CLEAN: u32 = 0;
+const JOURNAL_COMMIT: u32 = 1;
+
+// Struct JournalHeader, it is the first sector of journal
+struct JournalHeader {
+    magic: [u8; 8],
+    state: u32,
+    sequence: u32,
+    count: u32,
+    checksum: u32,
+}
+
+// Impl JournalHeader
+impl JournalHeader {
+    // New
+    const fn new() -> Self {
+        Self {
+            magic: *JOURNAL_MAGIC,
+            state: JOURNAL_CLEAN,
+            sequence: 0,
+            count: 0,
+            checksum: 0,
+        }
+    }
+
+    // From
+    fn from(data: &[u8]) -> Self {
+        let mut header = Self::new();
+        header.magic.copy_from_slice(&data[0..8]);
+        header.state = u32::from_le_bytes(data[8..12].try_into().unwrap());
+        header.sequence = u32::from_le_bytes(data[12..16].try_into().unwrap());
+        header.count = u32::from_le_bytes(data[16..20].try_into().unwrap());
+        header.checksum = u32::from_le_bytes(data[20..24].try_into().unwrap());
+        header
+    }
+
+    // As bytes
+    fn as_bytes(&self, bytes_per_sec: usize) -> Vec<u8> {
+        let mut data = vec![0u8; bytes_per_sec];
+        data[0..8].copy_from_slice(&self.magic);
+        data[8..12].copy_from_slice(&self.state.to_le_bytes());
+        data[12..16].copy_from_slice(&self.sequence.to_le_bytes());
+        data[16..20].copy_from_slice(&self.count.to_le_bytes());
+        data[20..24].copy_from_slice(&self.checksum.to_le_bytes());
+        data
+    }
+}
+
+// Struct FatJournal
+pub struct FatJournal {
+    device: BlockFopt,
+    start_sector: u32,
+    sectors: u32,
+    bytes_per_sec: usize,
+    sequence: u32,
+    enabled: bool,
+    depth: u32,
+    pending: Vec<(u32, Vec<u8>)>,
+}
+
+// Impl FatJournal
+impl FatJournal {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            device: BlockFopt::new(),
+            start_sector: 0,
+            sectors: 0,
+            bytes_per_sec: 0,
+            sequence: 0,
+            enabled: false,
+            depth: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    // Setup, the committed transaction is replayed and the uncommitted one is dropped
+    pub fn setup(&mut self, disk: &str, start_sector: u32, sectors: u32, bytes_per_sec: u32) -> bool {
+        if !self.device.open(disk) {
+            return false;
+        }
+
+        self.start_sector = start_sector;
+        self.sectors = sectors;
+        self.bytes_per_sec = bytes_per_sec as usize;
+        self.depth = 0;
+        self.pending.clear();
+
+        // The journal needs the header, one descriptor and one data sector at least
+        if sectors < 3 {
+            debug_error!("The fat journal is too small");
+            return false;
+        }
+
+        self.enabled = self.replay();
+        self.enabled
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        if self.enabled {
+            self.depth = 0;
+            self.flush();
+            self.device.close();
+            self.enabled = false;
+        }
+    }
+
+    // Is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Is active, the metadata writes are logged in an transaction,
+    // the transaction failed to flush is kept until it is flushed
+    pub fn is_active(&self) -> bool {
+        self.enabled && (self.depth > 0 || !self.pending.is_empty())
+    }
+
+    // Is full, the transaction should be committed before logging more sectors
+    pub fn is_full(&self) -> bool {
+        let count = self.pending.len();
+        self.depth > 0 && (1 + self.descriptor_sectors(count) + count as u32) * 2 >= self.sectors
+    }
+}
+
+// Impl FatJournal
+impl FatJournal {
+    // Write sectors of journal
+    fn write_journal(&mut self, data: &[u8], sector: u32, count: u32) -> bool {
+        let lba = (self.start_sector + sector) as usize;
+        self.device.write(data, count as usize, lba) == count as usize
+    }
+
+    // Read sectors of journal
+    fn read_journal(&mut self, data: &mut [u8], sector: u32, count: u32) -> bool {
+        let lba = (self.start_sector + sector) as usize;
+        self.device.read(data, count as usize, lba) == count as usize
+    }
+
+    // Write sector to the home location
+    fn write_home(&mut self, data: &[u8], sector: u32) -> bool {
+        self.device.write(data, 1, sector as usize) == 1
+    }
+
+    // Write header
+    fn write_header(&mut self, state: u32, count: u32, checksum: u32) -> bool {
+        let mut header = JournalHeader::new();
+        header.state = state;
+        header.sequence = self.sequence;
+        header.count = count;
+        header.checksum = checksum;
+        let data = header.as_bytes(self.bytes_per_sec);
+        self.write_journal(&data, 0, 1) && self.device.sync()
+    }
+
+    // Get the count of descriptor sectors
+    fn descriptor_sectors(&self, count: usize) -> u32 {
+        (count * 4).div_ceil(self.bytes_per_sec) as u32
+    }
+
+    // Replay, returns false when the journal is invalid
+    fn replay(&mut self) -> bool {
+        let mut data = vec![0u8; self.bytes_per_sec];
+        if !self.read_journal(&mut data, 0, 1) {
+            return false;
+        }
+
+        let header = JournalHeader::from(&data);
+        if &header.magic != JOURNAL_MAGIC {
+            debug_error!("The fat journal header is invalid");
+            return false;
+        }
+        self.sequence = header.sequence;
+
+        if header.state != JOURNAL_COMMIT {
+            return true;
+        }
+
+        // Read descriptors and data
+        let count = header.count as usize;
+        let desc_sectors = self.descriptor_sectors(count);
+        if count == 0 || 1 + desc_sectors + count as u32 > self.sectors {
+            debug_warning!("The fat journal transaction {} is damaged, dropped", header.sequence);
+            return self.write_header(JOURNAL_CLEAN, 0, 0);
+        }
+
+        let mut log = vec![0u8; (desc_sectors as usize + count) * self.bytes_per_sec];
+        if !self.read_journal(&mut log, 1, desc_sectors + count as u32) {
+            return false;
+        }
+
+        if crc32(&log) != header.checksum {
+            debug_warning!("The fat journal transaction {} is incomplete, dropped", header.sequence);
+            return self.write_header(JOURNAL_CLEAN, 0, 0);
+        }
+
+        // Write the logged sectors to home locations
+        let data_offset = desc_sectors as usize * self.bytes_per_sec;
+        for i in 0..count {
+            let sector = u32::from_le_bytes(log[i * 4..i * 4 + 4].try_into().unwrap());
+            let offset = data_offset + i * self.bytes_per_sec;
+            let data = log[offset..offset + self.bytes_per_sec].to_vec();
+            if !self.write_home(&data, sector) {
+                return false;
+            }
+        }
+
+        if !self.device.sync() {
+            return false;
+        }
+
+        debug_warning!("The fat journal transaction {} is replayed", header.sequence);
+        self.write_header(JOURNAL_CLEAN, 0, 0)
+    }
+
+    // Flush the pending sectors, they are written to journal before home locations,
+    // they are kept to be flushed again when the writing is failed
+    fn flush(&mut self) -> bool {
+        if self.pending.is_empty() {
+            return true;
+        }
+
+        let count = self.pending.len();
+        let desc_sectors = self.descriptor_sectors(count);
+
+        // The transaction can not be written atomically, it is dropped
+        if 1 + desc_sectors + count as u32 > self.sectors {
+            debug_error!("The fat journal is full, the transaction of {} sectors is dropped", count);
+            self.pending.clear();
+            return false;
+        }
+
+        // The data are written before the metadata which refers to them
+        if !self.device.sync() {
+            return false;
+        }
+
+        // Write the transaction into journal
+        let mut log = vec![0u8; (desc_sectors as usize + count) * self.bytes_per_sec];
+        let data_offset = desc_sectors as usize * self.bytes_per_sec;
+        for (i, (sector, data)) in self.pending.iter().enumerate() {
+            let offset = data_offset + i * self.bytes_per_sec;
+            log[i * 4..i * 4 + 4].copy_from_slice(&sector.to_le_bytes());
+            log[offset..offset + self.bytes_per_sec].copy_from_slice(data);
+        }
+
+        self.sequence = self.sequence.wrapping_add(1);
+        if !self.write_journal(&log, 1, desc_sectors + count as u32)
+            || !self.device.sync()
+            || !self.write_header(JOURNAL_COMMIT, count as u32, crc32(&log))
+        {
+            debug_error!("Write the fat journal transaction {} failed", self.sequence);
+            return false;
+        }
+
+        // Write the sectors to home locations, the committed journal is replayed on
+        // mount when it is failed
+        let pending = core::mem::take(&mut self.pending);
+        let mut result = true;
+        for (sector, data) in pending.iter() {
+            result &= self.write_home(data, *sector);
+        }
+        result &= self.device.sync();
+
+        if !result {
+            debug_error!("Write the fat journal transaction {} home failed", self.sequence);
+            self.pending = pending;
+            return false;
+        }
+
+        // The transaction is done
+        self.write_header(JOURNAL_CLEAN, 0, 0)
+    }
+}
+
+// Impl FatJournal
+impl FatJournal {
+    // Begin transaction, the nested transactions are committed by the outermost one
+    pub fn begin(&mut self) {
+        if self.enabled {
+            self.depth += 1;
+        }
+    }
+
+    // Commit transaction
+    pub fn commit(&mut self) -> bool {
+        if !self.enabled || self.depth == 0 {
+            return true;
+        }
+
+        self.depth -= 1;
+        if self.depth > 0 {
+            return true;
+        }
+
+        self.flush()
+    }
+
+    // Log sectors, the sector is logged once in an transaction
+    pub fn log(&mut self, data: &[u8], sector: u32, count: u32) -> u32 {
+        for i in 0..count {
+            let offset = i as usize * self.bytes_per_sec;
+            let data = &data[offset..offset + self.bytes_per_sec];
+
+            match self.pending.iter_mut().find(|(pending, _)| *pending == sector + i) {
+                Some((_, buff)) => buff.copy_from_slice(data),
+                None => self.pending.push((sector + i, data.to_vec())),
+            }
+        }
+        count
+    }
+
+    // Discard the logged sectors, they are overwritten by data
+    pub fn discard(&mut self, sector: u32, count: u32) {
+        self.pending.retain(|(pending, _)| *pending < sector || *pending >= sector + count);
+    }
+
+    // Overlay the logged sectors on the read data
+    pub fn overlay(&mut self, data: &mut [u8], sector: u32, count: u32) {
+        for (pending, buff) in self.pending.iter() {
+            if *pending >= sector && *pending < sector + count {
+                let offset = (*pending - sector) as usize * self.bytes_per_sec;
+                data[offset..offset + self.bytes_per_sec].copy_from_slice(buff);
+            }
+        }
+    }
+}
+
+// Impl FatJournal
+impl FatJournal {
+    // Locate the journal file, returns the first cluster and the count of contiguous clusters
+    pub fn locate(diskio: &mut FatDiskio) -> Option<(u32, u32)> {
+        let mut fatobj = FatFolder::search(diskio, FatObject::root(), JOURNAL_NAME)?;
+
+        if fatobj.get_attribute() & FatEntryAttr::SYSTEM == 0 {
+            return None;
+        }
+
+        // The clusters of journal must be contiguous
+        let fst_clust = fatobj.get_fst_clust();
+        let max_count = diskio.get_info().cluster_count;
+        let mut clust = fst_clust;
+        let mut count = 0;
+
+        while clust >= 2 && count < max_count {
+            count += 1;
+            let next = diskio.get_next_cluster(clust);
+            if next != 0 && next != clust + 1 {
+                debug_error!("The fat journal is fragmented");
+                return None;
+            }
+            clust = next;
+        }
+
+        if count == 0 {
+            return None;
+        }
+
+        Some((fst_clust, count))
+    }
+
+    // Create the journal file in root directory, the clusters are contiguous on new volume
+    pub fn create(diskio: &mut FatDiskio, sectors: u32) -> bool {
+        let bytes_per_sec = diskio.get_info().bytes_per_sec;
+        let sec_per_clust = diskio.get_info().sec_per_clust;
+        let clusters = sectors.div_ceil(sec_per_clust).max(1);
+
+        let attr = FatEntryAttr::READ_ONLY | FatEntryAttr::HIDDEN | FatEntryAttr::SYSTEM;
+        let mut fatobj = match FatFolder::create(diskio, FatObject::root(), JOURNAL_NAME, attr) {
+            Some(fatobj) => fatobj,
+            None => return false,
+        };
+
+        // Extend the chain
+        let mut clust = fatobj.get_fst_clust();
+        if clust < 2 {
+            return false;
+        }
+        for _ in 1..clusters {
+            let next = diskio.alloc_cluster(1);
+            if next != clust + 1 {
+                debug_error!("Create the fat journal failed, no contiguous clusters");
+                if next != 0 {
+                    diskio.free_cluster(next, 0);
+                }
+                return false;
+            }
+            diskio.link_cluster(clust, next);
+            clust = next;
+        }
+
+        fatobj.set_file_size(clusters * sec_per_clust * bytes_per_sec);
+        FatFolder::update(diskio, fatobj.clone());
+
+        // Write the clean header
+        let mut header = JournalHeader::new();
+        header.sequence = 0;
+        let data = header.as_bytes(bytes_per_sec as usize);
+        let sector = diskio.cluster_to_sector(fatobj.get_fst_clust());
+        diskio.write_sector(&data, sector, 1) == 1
+    }
+}
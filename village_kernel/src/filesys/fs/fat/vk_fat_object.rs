@@ -4,19 +4,25 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_fat_codepage::FatCodePage;
 use super::vk_fat_diskio::DiskIndex;
 use super::vk_fat_entry::{FatEntryAttr, FatEntryNSFlag};
 use super::vk_fat_entry::{FatEntry, FatLongEntry, FatShortEntry};
-use crate::traits::vk_filesys::{FileAttr, FileType};
+use crate::traits::vk_filesys::{FileAttr, FileFlags, FileTime, FileType};
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 
 // Const members
 const LONG_NAME_SIZE: u8 = 13;
+const LONG_NAME_MAX_LEN: usize = 255;
+const LONG_NAME_INVALID_CHARS: &str = "\"*/:<>?\\|";
+const SHORT_NAME_INVALID_CHARS: &[u8] = b"\"*+,/:;<=>?[\\]|\x7f";
 const DIR_SEQ_FLAG: u8 = 0x40;
 const DIR_FREE_FLAG: u8 = 0xe5;
+const DIR_KANJI_FLAG: u8 = 0x05;
 
 // Struct FatObject
 #[derive(Clone)]
@@ -24,6 +30,7 @@ pub struct FatObject {
     index: DiskIndex,
     short_entry: FatShortEntry,
     long_entries: Option<Box<[FatLongEntry]>>,
+    lossy: bool,
 }
 
 // Impl FatObject
@@ -34,6 +41,7 @@ impl FatObject {
             index: DiskIndex::new(),
             short_entry: FatShortEntry::new(),
             long_entries: None,
+            lossy: false,
         }
     }
 
@@ -87,12 +95,12 @@ impl FatObject {
 
         for entry in entries {
             match entry {
-                FatEntry::Long(entry) => long_entries.push(entry.clone()),
-                FatEntry::Short(entry) => obj.short_entry = entry.clone(),
+                FatEntry::Long(entry) => long_entries.push(*entry),
+                FatEntry::Short(entry) => obj.short_entry = *entry,
             }
         }
 
-        if long_entries.len() > 0 {
+        if !long_entries.is_empty() {
             obj.long_entries = Some(long_entries.into_boxed_slice());
         }
 
@@ -159,15 +167,46 @@ impl FatObject {
 
 // Impl FatObject
 impl FatObject {
-    // Set name
+    // Set name, the numeric tail is added by folder when the short name is lossy
     pub fn set_name(&mut self, name: &str) {
         self.clear_long_name();
+        self.short_entry.nt_res &= !(FatEntryNSFlag::BODY | FatEntryNSFlag::EXT);
 
-        if Self::requires_long_name(name) {
-            self.set_long_name(name);
+        // Special cases for root, "." and ".."
+        if name == "/" || name == "." || name == ".." {
+            self.short_entry.name = [b' '; 11];
+            self.short_entry.name[..name.len()].copy_from_slice(name.as_bytes());
+            self.lossy = false;
+            return;
+        }
+
+        // The checksum of long name is calculated from the short name
+        let (short_name, nt_res, lossy) = Self::make_short_name(name);
+        self.short_entry.name = short_name;
+        self.lossy = lossy;
+
+        // The case flags are used when the short name keeps the whole name
+        self.short_entry.nt_res |= nt_res;
+        if !lossy && self.get_short_name() == name {
+            return;
         }
 
-        self.set_short_name(name)
+        self.short_entry.nt_res &= !(FatEntryNSFlag::BODY | FatEntryNSFlag::EXT);
+        self.set_long_name(name);
+    }
+
+    // Set label, the volume label is stored in short name without long name
+    pub fn set_label(&mut self, name: &str) {
+        self.clear_long_name();
+        self.short_entry.name = [b' '; 11];
+
+        let oems = name.chars().map(|c| Self::upper_char(c).and_then(FatCodePage::char_to_oem).unwrap_or(b'_'));
+        for (i, oem) in oems.take(11).enumerate() {
+            self.short_entry.name[i] = oem;
+        }
+        if self.short_entry.name[0] == DIR_FREE_FLAG {
+            self.short_entry.name[0] = DIR_KANJI_FLAG;
+        }
     }
 
     // Get name
@@ -178,171 +217,269 @@ impl FatObject {
         self.get_short_name()
     }
 
+    // Is name matched, the long name and short name are compared without case
+    pub fn is_name_matched(&mut self, name: &str) -> bool {
+        if let Some(long_name) = self.get_long_name()
+            && Self::equal_ignore_case(&long_name, name)
+        {
+            return true;
+        }
+        Self::equal_ignore_case(&self.get_short_name(), name)
+    }
+
     // Is long name
     pub fn is_long_name(&mut self) -> bool {
         self.long_entries.is_some()
     }
 
-    // Set short name
-    fn set_short_name(&mut self, name: &str) {
-        let mut is_body_lowed_case = true;
-        let mut is_ext_lowed_caset = true;
+    // Is lossy, the short name does not keep the name and needs numeric tail
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
 
-        // Special cases for "." and ".."
-        if name == "." {
-            self.short_entry.name[0] = '.' as u8;
-            return;
-        } else if name == ".." {
-            self.short_entry.name[0] = '.' as u8;
-            self.short_entry.name[1] = '.' as u8;
-            return;
-        }
+    // Is short name equal
+    pub fn is_short_name_equal(&self, other: &FatObject) -> bool {
+        self.short_entry.name == other.short_entry.name
+    }
+
+    // Set numeric tail, the tail "~n" replaces the end of short name body
+    pub fn set_numeric_tail(&mut self, num: u32) {
+        let tail = format!("~{}", num);
+        let body_len = self.short_entry.name[..8].iter().position(|&c| c == b' ').unwrap_or(8);
+        let body_len = body_len.min(self.tail_basis_len()).min(8 - tail.len());
 
-        // Split 83 name
-        let (base, ext) = Self::split_83_name(name);
+        let mut name = [b' '; 11];
+        name[..body_len].copy_from_slice(&self.short_entry.name[..body_len]);
+        name[body_len..body_len + tail.len()].copy_from_slice(tail.as_bytes());
+        name[8..].copy_from_slice(&self.short_entry.name[8..]);
+        self.short_entry.name = name;
 
-        // Name body
-        for (i, &c) in base.iter().enumerate() {
-            if is_body_lowed_case && (c >= b'A' && c <= b'Z') {
-                is_body_lowed_case = false;
+        // Update the checksum of long name
+        let checksum = Self::calculate_checksum(&self.short_entry.name);
+        if let Some(long_entries) = &mut self.long_entries {
+            for entry in long_entries.iter_mut() {
+                entry.chksum = checksum;
             }
+        }
+    }
 
-            self.short_entry.name[i] = c.to_ascii_uppercase();
+    // Get the length of short name body before numeric tail
+    fn tail_basis_len(&self) -> usize {
+        let body = &self.short_entry.name[..8];
+        let body_len = body.iter().position(|&c| c == b' ').unwrap_or(8);
+        match body[..body_len].iter().rposition(|&c| c == b'~') {
+            Some(pos) if pos + 1 < body_len && body[pos + 1..body_len].iter().all(u8::is_ascii_digit) => pos,
+            _ => body_len,
         }
+    }
 
-        // Name ext
-        for (i, &c) in ext.iter().enumerate() {
-            if is_ext_lowed_caset && (c >= b'A' && c <= b'Z') {
-                is_ext_lowed_caset = false;
-            }
+    // Make short name, returns the name, the case flags and the lossy flag
+    fn make_short_name(name: &str) -> ([u8; 11], u8, bool) {
+        let mut short_name = [b' '; 11];
+        let mut lossy = false;
 
-            self.short_entry.name[8 + i] = c.to_ascii_uppercase();
+        // The leading periods and all the spaces are stripped
+        let stripped: String = name.trim_start_matches('.').chars().filter(|&c| c != ' ').collect();
+        if stripped.chars().count() != name.chars().count() {
+            lossy = true;
+        }
+
+        // The extension is after the last period
+        let (body, ext) = match stripped.rfind('.') {
+            Some(pos) => (&stripped[..pos], &stripped[pos + 1..]),
+            None => (stripped.as_str(), ""),
+        };
+
+        // Convert the chars into oem chars
+        let mut body_case = (false, false);
+        let mut ext_case = (false, false);
+        let body_oems = Self::make_oem_chars(body, &mut lossy, &mut body_case);
+        let ext_oems = Self::make_oem_chars(ext, &mut lossy, &mut ext_case);
+
+        if body_oems.len() > 8 || ext_oems.len() > 3 || body_oems.is_empty() {
+            lossy = true;
+        }
+
+        // The empty body is replaced
+        if body_oems.is_empty() {
+            short_name[0] = b'_';
+        }
+        for (i, &oem) in body_oems.iter().take(8).enumerate() {
+            short_name[i] = oem;
+        }
+        for (i, &oem) in ext_oems.iter().take(3).enumerate() {
+            short_name[8 + i] = oem;
         }
 
-        // Set nt res
-        if is_body_lowed_case {
-            self.short_entry.nt_res |= FatEntryNSFlag::BODY;
+        if short_name[0] == DIR_FREE_FLAG {
+            short_name[0] = DIR_KANJI_FLAG;
         }
 
-        if is_ext_lowed_caset {
-            self.short_entry.nt_res |= FatEntryNSFlag::EXT;
+        // The case flags, mixed case names need long name
+        let mut nt_res = 0;
+        if body_case.0 && !body_case.1 {
+            nt_res |= FatEntryNSFlag::BODY;
         }
+        if ext_case.0 && !ext_case.1 {
+            nt_res |= FatEntryNSFlag::EXT;
+        }
+
+        (short_name, nt_res, lossy)
+    }
+
+    // Make oem chars, the chars are upper case and the invalid chars are replaced
+    fn make_oem_chars(name: &str, lossy: &mut bool, case: &mut (bool, bool)) -> Vec<u8> {
+        let mut oems = Vec::new();
+
+        for c in name.chars() {
+            if c == '.' {
+                *lossy = true;
+                continue;
+            }
+
+            if c.is_lowercase() {
+                case.0 = true;
+            } else if c.is_uppercase() {
+                case.1 = true;
+            }
+
+            match Self::upper_char(c).and_then(FatCodePage::char_to_oem) {
+                Some(oem) if oem >= 0x20 && !SHORT_NAME_INVALID_CHARS.contains(&oem) => oems.push(oem),
+                _ => {
+                    *lossy = true;
+                    oems.push(b'_');
+                }
+            }
+        }
+
+        oems
+    }
+
+    // Upper char, returns None when the char is not mapped to one char
+    fn upper_char(c: char) -> Option<char> {
+        let mut upper = c.to_uppercase();
+        match (upper.next(), upper.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    // Equal ignore case
+    fn equal_ignore_case(a: &str, b: &str) -> bool {
+        a.chars().flat_map(char::to_uppercase).eq(b.chars().flat_map(char::to_uppercase))
     }
 
     // Get short name
     fn get_short_name(&mut self) -> String {
-        let mut name = String::new();
+        let mut oems = self.short_entry.name;
+        if oems[0] == DIR_KANJI_FLAG {
+            oems[0] = DIR_FREE_FLAG;
+        }
+
+        // The volume label is the whole 11 chars
+        if FileType::Volume == self.get_object_type() {
+            let name: String = oems.iter().map(|&c| FatCodePage::oem_to_char(c)).collect();
+            return String::from(name.trim_end_matches(' '));
+        }
+
         let is_body_lowed_case = (self.short_entry.nt_res & FatEntryNSFlag::BODY) != 0;
         let is_ext_lowed_case = (self.short_entry.nt_res & FatEntryNSFlag::EXT) != 0;
 
+        let mut name = String::new();
+
         // 8.3 name body
-        for &c in &self.short_entry.name[..8] {
-            if c == b' ' {
-                break;
-            }
-            if is_body_lowed_case && (c >= b'A' && c <= b'Z') {
-                name.push(c.to_ascii_lowercase() as char);
-            } else {
-                name.push(c as char);
-            }
+        Self::push_oem_chars(&mut name, &oems[..8], is_body_lowed_case);
+
+        // 8.3 name ext
+        if oems[8] != b' ' {
+            name.push('.');
+            Self::push_oem_chars(&mut name, &oems[8..], is_ext_lowed_case);
         }
 
-        if self.short_entry.name[8] != b' ' {
-            // 8.3 name dot
-            if FileType::Volume == self.get_object_type() {
-                name.push(' ');
-            } else {
-                name.push('.');
-            }
+        name
+    }
 
-            // 8.3 name ext
-            for &c in &self.short_entry.name[8..11] {
-                if c == b' ' {
-                    break;
-                }
-                if is_ext_lowed_case && (c >= b'A' && c <= b'Z') {
-                    name.push(c.to_ascii_lowercase() as char);
-                } else {
-                    name.push(c as char);
-                }
+    // Push oem chars, the chars are lower case when the case flag is set
+    fn push_oem_chars(name: &mut String, oems: &[u8], lower: bool) {
+        for &oem in oems.iter() {
+            if oem == b' ' {
+                break;
+            }
+            let c = FatCodePage::oem_to_char(oem);
+            if lower {
+                name.extend(c.to_lowercase());
+            } else {
+                name.push(c);
             }
         }
-
-        name
     }
 
-    // Set long name
+    // Set long name, the name is stored in utf16 and padded with 0xffff after the terminator
     fn set_long_name(&mut self, name: &str) {
         let checksum = Self::calculate_checksum(&self.short_entry.name);
-        let entries_needed = Self::calc_lfe_needed(name);
+        let mut chars = name.encode_utf16().collect::<Vec<_>>();
+        let entries_needed = chars.len().div_ceil(LONG_NAME_SIZE as usize);
+
+        if chars.len() % LONG_NAME_SIZE as usize != 0 {
+            chars.push(0);
+        }
+        chars.resize(entries_needed * LONG_NAME_SIZE as usize, 0xffff);
 
         let mut entries = vec![FatLongEntry::new(); entries_needed].into_boxed_slice();
 
         // Fill long name entries
-        let chars = name.encode_utf16().collect::<Vec<_>>();
         let mut pos = 0;
 
         for (i, entry) in entries.iter_mut().rev().enumerate() {
-            entry.ord = (entries_needed - i) as u8 | DIR_SEQ_FLAG;
-            entry.attr = 0x0F;
+            entry.ord = (i + 1) as u8;
+            entry.attr = FatEntryAttr::LONG_NAME;
             entry.chksum = checksum;
 
             // Fill names
             for j in 0..5 {
-                entry.name1[j] = chars.get(pos).copied().unwrap_or(0);
+                entry.name1[j] = chars[pos];
                 pos += 1;
             }
 
             for j in 0..6 {
-                entry.name2[j] = chars.get(pos).copied().unwrap_or(0);
+                entry.name2[j] = chars[pos];
                 pos += 1;
             }
 
             for j in 0..2 {
-                entry.name3[j] = chars.get(pos).copied().unwrap_or(0);
+                entry.name3[j] = chars[pos];
                 pos += 1;
             }
         }
 
-        // Set store size
-        entries[0].ord = DIR_SEQ_FLAG + entries.len() as u8;
+        // Set the last entry flag
+        entries[0].ord |= DIR_SEQ_FLAG;
 
         // Set long entries
         self.long_entries = Some(entries);
     }
 
-    // Get long name
+    // Get long name, the long name is ignored when the checksum is not matched
     fn get_long_name(&mut self) -> Option<String> {
-        if let Some(long_entries) = &self.long_entries {
-            let mut name = String::new();
-
-            for entry in long_entries.iter().rev() {
-                for &c in &entry.name1 {
-                    if c == 0 {
-                        return Some(name);
-                    }
-                    name.push(char::from_u32(c as u32)?);
-                }
-
-                for &c in &entry.name2 {
-                    if c == 0 {
-                        return Some(name);
-                    }
-                    name.push(char::from_u32(c as u32)?);
-                }
-
-                for &c in &entry.name3 {
-                    if c == 0 {
-                        return Some(name);
-                    }
-                    name.push(char::from_u32(c as u32)?);
-                }
-            }
+        let long_entries = self.long_entries.as_ref()?;
+        let checksum = Self::calculate_checksum(&self.short_entry.name);
 
-            return Some(name);
+        if long_entries.iter().any(|entry| entry.chksum != checksum) {
+            return None;
         }
 
-        None
+        let chars = long_entries
+            .iter()
+            .rev()
+            .flat_map(|entry| entry.name1.iter().chain(entry.name2.iter()).chain(entry.name3.iter()))
+            .copied()
+            .take_while(|&c| c != 0);
+
+        Some(
+            char::decode_utf16(chars)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+        )
     }
 
     // Clear long name
@@ -351,44 +488,23 @@ impl FatObject {
     }
 
     // calc chksum
-    fn calculate_checksum(name: &[u8]) -> u8 {
+    pub fn calculate_checksum(name: &[u8]) -> u8 {
         let mut sum = 0u8;
         for &c in name {
-            sum = ((sum & 1) << 7) + (sum >> 1) + c;
+            sum = ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(c);
         }
         sum
     }
 
-    // Requires long name
-    fn requires_long_name(name: &str) -> bool {
-        let (base, ext) = Self::split_83_name(name);
-        base.len() > 8 || ext.len() > 3 || name.chars().any(|c| c > '\u{7F}')
-    }
-
-    // Split 8.3 name
-    fn split_83_name(name: &str) -> (&[u8], &[u8]) {
-        let bytes = name.as_bytes();
-        let dot_pos = bytes.iter().position(|&b| b == b'.').unwrap_or(bytes.len());
-
-        let base = if dot_pos > 8 {
-            &bytes[..8]
-        } else {
-            &bytes[..dot_pos]
-        };
-        let ext = if dot_pos < bytes.len() {
-            &bytes[dot_pos + 1..]
-        } else {
-            &[]
-        };
-
-        (base, if ext.len() > 3 { &ext[..3] } else { ext })
-    }
-
-    // Calculate lfn entries needed
-    fn calc_lfe_needed(name: &str) -> usize {
-        let char_count = name.chars().count();
-        let long_name_size = LONG_NAME_SIZE as usize;
-        (char_count + long_name_size - 2) / (long_name_size - 1)
+    // Is valid name, the long name is up to 255 utf16 chars
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name != "."
+            && name != ".."
+            && !name.ends_with(' ')
+            && !name.ends_with('.')
+            && name.encode_utf16().count() <= LONG_NAME_MAX_LEN
+            && !name.chars().any(|c| c < ' ' || LONG_NAME_INVALID_CHARS.contains(c))
     }
 }
 
@@ -504,4 +620,105 @@ impl FatObject {
     pub fn get_index(&mut self) -> DiskIndex {
         self.index.clone()
     }
+
+    // Is same entry, the objects are stored at the same disk index
+    pub fn is_same_entry(&mut self, other: &mut FatObject) -> bool {
+        self.index.sector == other.index.sector && self.index.index == other.index.index
+    }
+}
+
+// Impl FatObject stat
+impl FatObject {
+    // Get flags
+    pub fn get_flags(&self) -> FileFlags {
+        let mut flags = FileFlags::NONE;
+        let attr = self.short_entry.attr;
+
+        if (attr & FatEntryAttr::READ_ONLY) != 0 {
+            flags.insert(FileFlags::READ_ONLY);
+        }
+        if (attr & FatEntryAttr::HIDDEN) != 0 {
+            flags.insert(FileFlags::HIDDEN);
+        }
+        if (attr & FatEntryAttr::SYSTEM) != 0 {
+            flags.insert(FileFlags::SYSTEM);
+        }
+        if (attr & FatEntryAttr::ARCHIVE) != 0 {
+            flags.insert(FileFlags::ARCHIVE);
+        }
+
+        flags
+    }
+
+    // Set flags, the directory and volume bits are kept
+    pub fn set_flags(&mut self, flags: FileFlags) {
+        let mut attr = self.short_entry.attr & (FatEntryAttr::DIRECTORY | FatEntryAttr::VOLUME_ID);
+
+        if flags.contains(FileFlags::READ_ONLY) {
+            attr |= FatEntryAttr::READ_ONLY;
+        }
+        if flags.contains(FileFlags::HIDDEN) {
+            attr |= FatEntryAttr::HIDDEN;
+        }
+        if flags.contains(FileFlags::SYSTEM) {
+            attr |= FatEntryAttr::SYSTEM;
+        }
+        if flags.contains(FileFlags::ARCHIVE) {
+            attr |= FatEntryAttr::ARCHIVE;
+        }
+
+        self.short_entry.attr = attr;
+    }
+
+    // Decode fat date and time
+    pub fn decode_time(date: u16, time: u16) -> FileTime {
+        if date == 0 {
+            return FileTime::new();
+        }
+
+        FileTime {
+            year: 1980 + (date >> 9),
+            month: ((date >> 5) & 0x0f) as u8,
+            day: (date & 0x1f) as u8,
+            hour: (time >> 11) as u8,
+            minute: ((time >> 5) & 0x3f) as u8,
+            second: ((time & 0x1f) * 2) as u8,
+        }
+    }
+
+    // Encode fat date and time
+    pub fn encode_time(time: &FileTime) -> (u16, u16) {
+        let year = time.year.clamp(1980, 2107) - 1980;
+        let date = (year << 9) | ((time.month as u16) << 5) | time.day as u16;
+        let time = ((time.hour as u16) << 11) | ((time.minute as u16) << 5) | (time.second as u16 / 2);
+        (date, time)
+    }
+
+    // Get create time
+    pub fn get_create_file_time(&self) -> FileTime {
+        Self::decode_time(self.short_entry.crt_date, self.short_entry.crt_time)
+    }
+
+    // Get write time
+    pub fn get_write_file_time(&self) -> FileTime {
+        Self::decode_time(self.short_entry.wrt_date, self.short_entry.wrt_time)
+    }
+
+    // Get access time, fat only records the date of access
+    pub fn get_access_file_time(&self) -> FileTime {
+        Self::decode_time(self.short_entry.lst_acc_date, 0)
+    }
+
+    // Set write time
+    pub fn set_write_file_time(&mut self, time: &FileTime) {
+        let (date, time) = Self::encode_time(time);
+        self.short_entry.wrt_date = date;
+        self.short_entry.wrt_time = time;
+    }
+
+    // Set access time
+    pub fn set_access_file_time(&mut self, time: &FileTime) {
+        let (date, _) = Self::encode_time(time);
+        self.short_entry.lst_acc_date = date;
+    }
 }
@@ -4,15 +4,20 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_fat_checker::FatChecker;
 use super::vk_fat_diskio::FatDiskio;
 use super::vk_fat_entry::FatEntryAttr;
 use super::vk_fat_filedir::{FatDir, FatFile};
 use super::vk_fat_folder::FatFolder;
+use super::vk_fat_journal::FatJournal;
 use super::vk_fat_object::FatObject;
 use crate::traits::vk_filesys::{FileDir, FileMode, FileSys, FileType, FileVol};
+use crate::traits::vk_filesys::{FileCheck, FileFlags, FileStat, FileTime};
 use crate::traits::vk_linkedlist::LinkedList;
 use crate::register_filesys;
+use crate::debug_error;
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -24,6 +29,7 @@ struct FatVolume {
     file_cnt: usize,
     dirs: LinkedList<FatDir>,
     files: LinkedList<FatFile>,
+    journal_clust: u32,
 }
 
 // Impl FatVolume
@@ -37,6 +43,7 @@ impl FatVolume {
             file_cnt: 1,
             dirs: LinkedList::new(),
             files: LinkedList::new(),
+            journal_clust: 0,
         }
     }
 }
@@ -72,9 +79,9 @@ impl FatVolume {
 
         let mut someobj = Some(FatObject::root());
 
-        for i in 0..deep {
+        for name in names.iter().take(deep) {
             if let Some(fatobj) = someobj {
-                someobj = FatFolder::search(&mut self.diskio, fatobj, names[i]);
+                someobj = FatFolder::search(&mut self.diskio, fatobj, name);
             }
 
             if someobj.is_none() {
@@ -87,34 +94,140 @@ impl FatVolume {
 
     // Create path
     fn create_path(&mut self, path: &str, attr: u8) -> Option<FatObject> {
-        if let Some(mut parent) = self.search_path(path, 1) {
-            if parent.get_object_type() == FileType::Directory {
-                let name = self.base_name(path);
-                return FatFolder::create(&mut self.diskio, parent, &name, attr);
-            }
+        if let Some(mut parent) = self.search_path(path, 1)
+            && parent.get_object_type() == FileType::Directory
+        {
+            let name = self.base_name(path);
+            return FatFolder::create(&mut self.diskio, parent, &name, attr);
         }
         None
     }
 
-    // Delete path
+    // Delete path, the directory must be empty
     fn delete_path(&mut self, path: &str) -> bool {
-        if let Some(mut fatobj) = self.search_path(path, 0) {
-            if fatobj.get_object_type() == FileType::File ||
-               fatobj.get_object_type() == FileType::Directory
-            {
+        if let Some(mut fatobj) = self.search_entry(path) {
+            let typid = fatobj.get_object_type();
+
+            if self.is_journal(&mut fatobj) {
+                return false;
+            }
+
+            if typid == FileType::Directory && !FatFolder::is_empty(&mut self.diskio, fatobj.clone()) {
+                return false;
+            }
+
+            if typid == FileType::File || typid == FileType::Directory {
+                let fst_clust = fatobj.get_fst_clust();
                 FatFolder::remove(&mut self.diskio, fatobj);
+
+                // Free the cluster chain
+                if fst_clust >= 2 {
+                    self.diskio.free_cluster(fst_clust, 0);
+                }
                 return true;
             }
         }
         false
     }
+
+    // Rename path
+    fn rename_path(&mut self, source: &str, target: &str) -> bool {
+        // Can not move a directory into itself
+        let source_dir = format!("{}/", source.trim_end_matches('/')).to_uppercase();
+        if target.to_uppercase().starts_with(&source_dir) {
+            return false;
+        }
+
+        if let Some(mut srcobj) = self.search_entry(source) {
+            // The target must not exist, except the case of source name is changed
+            if let Some(mut tarobj) = self.search_path(target, 0)
+                && !tarobj.is_same_entry(&mut srcobj)
+            {
+                return false;
+            }
+
+            if self.is_journal(&mut srcobj) {
+                return false;
+            }
+
+            if let Some(mut parent) = self.search_path(target, 1)
+                && parent.get_object_type() == FileType::Directory
+            {
+                let name = self.base_name(target);
+                return FatFolder::rename(&mut self.diskio, srcobj, parent, &name).is_some();
+            }
+        }
+        false
+    }
+}
+
+// Impl FatVolume
+impl FatVolume {
+    // Count the clusters of chain
+    fn count_clusters(&mut self, fst_clust: u32) -> u32 {
+        let max_count = self.diskio.get_info().cluster_count;
+        let mut clust = fst_clust;
+        let mut count = 0;
+
+        while clust >= 2 && count < max_count {
+            count += 1;
+            clust = self.diskio.get_next_cluster(clust);
+        }
+
+        count
+    }
+
+    // Get object stat
+    fn object_stat(&mut self, fatobj: &mut FatObject) -> FileStat {
+        let mut stat = FileStat::new();
+
+        stat.name = fatobj.get_object_name();
+        stat.typid = fatobj.get_object_type();
+        stat.flags = fatobj.get_flags();
+        stat.size = fatobj.get_file_size() as u64;
+        stat.clusters = self.count_clusters(fatobj.get_fst_clust());
+        stat.create_time = fatobj.get_create_file_time();
+        stat.write_time = fatobj.get_write_file_time();
+        stat.access_time = fatobj.get_access_file_time();
+
+        stat
+    }
+
+    // Is journal, the journal file can not be removed or renamed
+    fn is_journal(&mut self, fatobj: &mut FatObject) -> bool {
+        self.journal_clust >= 2 && fatobj.get_fst_clust() == self.journal_clust
+    }
+
+    // Setup journal, the volume without journal file is not journaled
+    fn setup_journal(&mut self) {
+        if let Some((fst_clust, clust_cnt)) = FatJournal::locate(&mut self.diskio)
+            && self.diskio.setup_journal(fst_clust, clust_cnt)
+        {
+            self.journal_clust = fst_clust;
+        }
+    }
+
+    // Search the object can be updated, the root has no entry
+    fn search_entry(&mut self, name: &str) -> Option<FatObject> {
+        let names = name.split('/').filter(|s| !s.is_empty()).count();
+        if names == 0 {
+            return None;
+        }
+        self.search_path(name, 0)
+    }
 }
 
 // Impl FatVolume
 impl FileVol for FatVolume {
     // Setup
-    fn setup(&mut self, disk: &str, starting_lba: u32) -> bool {
-        self.diskio.setup(disk, starting_lba)
+    fn setup(&mut self, disk: &str) -> bool {
+        if !self.diskio.setup(disk) {
+            return false;
+        }
+        if !self.diskio.is_read_only() {
+            self.setup_journal();
+        }
+        true
     }
 
     // Exit
@@ -134,8 +247,9 @@ impl FileVol for FatVolume {
 
     // Set name
     fn set_name(&mut self, name: &str) -> bool {
+        self.diskio.begin();
         FatFolder::set_vol_lab(&mut self.diskio, name);
-        self.get_name() == name
+        self.diskio.commit() && self.get_name() == name
     }
 
     // Get name
@@ -143,12 +257,30 @@ impl FileVol for FatVolume {
         FatFolder::get_vol_lab(&mut self.diskio)
     }
 
+    // Get total size
+    fn get_total_size(&mut self) -> u64 {
+        let info = self.diskio.get_info();
+        info.cluster_count as u64 * info.sec_per_clust as u64 * info.bytes_per_sec as u64
+    }
+
+    // Get free size
+    fn get_free_size(&mut self) -> u64 {
+        let free = self.diskio.count_free_clusters() as u64;
+        let info = self.diskio.get_info();
+        free * info.sec_per_clust as u64 * info.bytes_per_sec as u64
+    }
+
     // Open
     fn open(&mut self, name: &str, mode: FileMode) -> usize {
         // Search or create path
         let someobj = match self.search_path(name, 0) {
             Some(obj) => Some(obj),
-            None if mode.contains(FileMode::CREATE_NEW) => self.create_path(name, FatEntryAttr::FILE),
+            None if mode.contains(FileMode::CREATE_NEW) => {
+                self.diskio.begin();
+                let someobj = self.create_path(name, FatEntryAttr::FILE);
+                let committed = self.diskio.commit();
+                someobj.filter(|_| committed)
+            }
             None => None,
         };
 
@@ -157,10 +289,16 @@ impl FileVol for FatVolume {
             let fd = self.assign_file_id();
             let mut file = FatFile::new(fd);
 
-            file.open(&mut self.diskio, fatobj, mode);
+            file.open(&mut self.diskio, fatobj);
 
             self.files.push(file);
 
+            // Truncate the file to zero length
+            if mode.contains(FileMode::TRUNCATE) && !self.set_len(fd, 0) {
+                self.close(fd);
+                return 0;
+            }
+
             return fd;
         }
         0
@@ -169,7 +307,7 @@ impl FileVol for FatVolume {
     // Write
     fn write(&mut self, fd: usize, data: &[u8], size: usize, offset: usize) -> usize {
         if let Some(file) = self.files.iter_mut().find(|f| f.id() == fd) {
-            return file.write(data, size, offset);
+            return file.write(&mut self.diskio, data, size, offset);
         }
         0
     }
@@ -177,7 +315,7 @@ impl FileVol for FatVolume {
     // Read
     fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize {
         if let Some(file) = self.files.iter_mut().find(|f| f.id() == fd) {
-            return file.read(data, size, offset);
+            return file.read(&mut self.diskio, data, size, offset);
         }
         0
     }
@@ -190,23 +328,40 @@ impl FileVol for FatVolume {
         0
     }
 
+    // Set len
+    fn set_len(&mut self, fd: usize, size: usize) -> bool {
+        if let Some(file) = self.files.iter_mut().find(|f| f.id() == fd) {
+            return file.set_len(&mut self.diskio, size);
+        }
+        false
+    }
+
     // Flush
     fn flush(&mut self, fd: usize) {
         if let Some(file) = self.files.iter_mut().find(|f| f.id() == fd) {
+            self.diskio.begin();
             file.flush(&mut self.diskio);
+            if !self.diskio.commit() {
+                debug_error!("Flush file {} failed", fd);
+            }
         }
     }
 
     // Close
     fn close(&mut self, fd: usize) {
+        let diskio = &mut self.diskio;
+        diskio.begin();
         self.files.retain_mut(|file| {
             if file.id() == fd {
-                file.close();
+                file.close(diskio);
                 false
             } else {
                 true
             }
         });
+        if !self.diskio.commit() {
+            debug_error!("Close file {} failed", fd);
+        }
     }
 
     // Open dir
@@ -215,7 +370,10 @@ impl FileVol for FatVolume {
         let someobj = match self.search_path(name, 0) {
             Some(obj) => Some(obj),
             None if mode.contains(FileMode::CREATE_NEW) => {
-                self.create_path(name, FatEntryAttr::DIRECTORY)
+                self.diskio.begin();
+                let someobj = self.create_path(name, FatEntryAttr::DIRECTORY);
+                let committed = self.diskio.commit();
+                someobj.filter(|_| committed)
             }
             None => None,
         };
@@ -262,6 +420,45 @@ impl FileVol for FatVolume {
         });
     }
 
+    // Stat
+    fn stat(&mut self, name: &str) -> Option<FileStat> {
+        let mut fatobj = self.search_path(name, 0)?;
+        Some(self.object_stat(&mut fatobj))
+    }
+
+    // Fstat
+    fn fstat(&mut self, fd: usize) -> Option<FileStat> {
+        let mut fatobj = self.files.iter_mut().find(|f| f.id() == fd)?.get_object();
+        Some(self.object_stat(&mut fatobj))
+    }
+
+    // Set flags
+    fn set_flags(&mut self, name: &str, flags: FileFlags) -> bool {
+        if let Some(mut fatobj) = self.search_entry(name) {
+            fatobj.set_flags(flags);
+            self.diskio.begin();
+            FatFolder::update(&mut self.diskio, fatobj);
+            return self.diskio.commit();
+        }
+        false
+    }
+
+    // Set time
+    fn set_time(&mut self, name: &str, write_time: FileTime, access_time: FileTime) -> bool {
+        if let Some(mut fatobj) = self.search_entry(name) {
+            if write_time.is_valid() {
+                fatobj.set_write_file_time(&write_time);
+            }
+            if access_time.is_valid() {
+                fatobj.set_access_file_time(&access_time);
+            }
+            self.diskio.begin();
+            FatFolder::update(&mut self.diskio, fatobj);
+            return self.diskio.commit();
+        }
+        false
+    }
+
     // Is exist
     fn exist(&mut self, name: &str, typeid: FileType) -> bool {
         if let Some(mut fatobj) = self.search_path(name, 0) {
@@ -272,7 +469,26 @@ impl FileVol for FatVolume {
 
     // Remove
     fn remove(&mut self, name: &str) -> bool {
-        self.delete_path(name)
+        self.diskio.begin();
+        let result = self.delete_path(name);
+        self.diskio.commit() && result
+    }
+
+    // Rename
+    fn rename(&mut self, source: &str, target: &str) -> bool {
+        self.diskio.begin();
+        let result = self.rename_path(source, target);
+        self.diskio.commit() && result
+    }
+
+    // Check, the repair is skipped when files are opened
+    fn check(&mut self, repair: bool) -> Option<FileCheck> {
+        let busy = !self.files.is_empty();
+        let mut report = FatChecker::new(&mut self.diskio, repair && !busy).check();
+        if repair && busy {
+            report.messages.push("Files are opened, the volume is not repaired".to_string());
+        }
+        Some(report)
     }
 }
 
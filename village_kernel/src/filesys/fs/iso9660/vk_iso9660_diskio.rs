@@ -0,0 +1,290 @@
+//###########################################################################
+// vk_iso9660_diskio.rs
+// The specific implementation of functions related to iso9660 diskio
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_iso9660_record::IsoRecord;
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const DEVICE_SECTOR_SIZE: u32 = 512;
+const ISO_SECTOR_SIZE: u32 = 2048;
+const FIRST_DESCRIPTOR: u32 = 16;
+const MAX_DESCRIPTORS: u32 = 32;
+const ROOT_RECORD_OFFSET: usize = 156;
+
+// Struct IsoDescType
+pub struct IsoDescType;
+
+// Impl IsoDescType
+impl IsoDescType {
+    pub const PRIMARY: u8 = 1;
+    pub const SUPPLEMENTARY: u8 = 2;
+    pub const TERMINATOR: u8 = 255;
+}
+
+// Enum IsoNaming
+#[derive(Clone, Copy, PartialEq)]
+pub enum IsoNaming {
+    Plain,
+    Joliet,
+    RockRidge,
+}
+
+// Struct PathTableEntry
+#[derive(Clone)]
+pub struct PathTableEntry {
+    pub name: String,
+    pub extent: u32,
+    pub parent: u16,
+}
+
+// Struct IsoDiskio
+pub struct IsoDiskio {
+    device: BlockFopt,
+    block_size: u32,
+    volume_blocks: u32,
+    volume_name: String,
+    naming: IsoNaming,
+    susp_skip: usize,
+    root: IsoRecord,
+    path_table: Vec<PathTableEntry>,
+}
+
+// Impl IsoDiskio
+impl IsoDiskio {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            device: BlockFopt::new(),
+            block_size: ISO_SECTOR_SIZE,
+            volume_blocks: 0,
+            volume_name: String::new(),
+            naming: IsoNaming::Plain,
+            susp_skip: 0,
+            root: IsoRecord::new(),
+            path_table: Vec::new(),
+        }
+    }
+
+    // Setup
+    pub fn setup(&mut self, disk: &str) -> bool {
+
+        if self.device.open(disk) {
+            if self.check_file_system() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        self.device.close();
+        self.path_table.clear();
+    }
+}
+
+// Impl IsoDiskio
+impl IsoDiskio {
+    // Check file system
+    fn check_file_system(&mut self) -> bool {
+        let mut primary = None;
+        let mut joliet = None;
+        let mut data = vec![0u8; ISO_SECTOR_SIZE as usize];
+
+        // Read the volume descriptors until terminator
+        for sector in FIRST_DESCRIPTOR..FIRST_DESCRIPTOR + MAX_DESCRIPTORS {
+            if self.read_iso_sector(&mut data, sector, 1) != 1 {
+                return false;
+            }
+            if &data[1..6] != b"CD001" {
+                break;
+            }
+
+            match data[0] {
+                IsoDescType::PRIMARY if primary.is_none() => primary = Some(data.clone()),
+                IsoDescType::SUPPLEMENTARY if joliet.is_none() && Self::is_joliet(&data) => {
+                    joliet = Some(data.clone())
+                }
+                IsoDescType::TERMINATOR => break,
+                _ => {}
+            }
+        }
+
+        let primary = match primary {
+            Some(primary) => primary,
+            None => return false,
+        };
+
+        self.block_size = u16::from_le_bytes([primary[128], primary[129]]) as u32;
+        self.volume_blocks = u32::from_le_bytes([primary[80], primary[81], primary[82], primary[83]]);
+        if self.block_size < DEVICE_SECTOR_SIZE || self.block_size > ISO_SECTOR_SIZE || ISO_SECTOR_SIZE % self.block_size != 0 {
+            return false;
+        }
+
+        // The rock ridge is detected by the SP entry of root, it is preferred to joliet
+        let root = match IsoRecord::from(&primary[ROOT_RECORD_OFFSET..], IsoNaming::Plain, 0) {
+            Some(root) => root,
+            None => return false,
+        };
+        self.root = root;
+        self.naming = IsoNaming::Plain;
+
+        let mut dot = vec![0u8; self.block_size as usize];
+        self.read_block(&mut dot, self.root.extent);
+        if let Some(skip) = IsoRecord::susp_skip(&dot) {
+            self.naming = IsoNaming::RockRidge;
+            self.susp_skip = skip;
+        }
+
+        let descriptor = match joliet {
+            Some(joliet) if self.naming != IsoNaming::RockRidge => {
+                self.naming = IsoNaming::Joliet;
+                joliet
+            }
+            _ => primary,
+        };
+
+        if let Some(root) = IsoRecord::from(&descriptor[ROOT_RECORD_OFFSET..], self.naming, 0) {
+            self.root = root;
+        }
+        self.volume_name = Self::decode_text(&descriptor[40..72], self.naming == IsoNaming::Joliet);
+        self.load_path_table(&descriptor);
+
+        true
+    }
+
+    // Is joliet supplementary descriptor, the escape sequence selects the ucs-2 level
+    fn is_joliet(data: &[u8]) -> bool {
+        let escape = &data[88..91];
+        escape == b"%/@" || escape == b"%/C" || escape == b"%/E"
+    }
+
+    // Decode the descriptor text, the padding spaces are removed
+    fn decode_text(data: &[u8], ucs2: bool) -> String {
+        let text = if ucs2 {
+            let chars: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&chars)
+        } else {
+            String::from_utf8_lossy(data).into_owned()
+        };
+        String::from(text.trim_end_matches([' ', '\0']))
+    }
+
+    // Load the little endian path table
+    fn load_path_table(&mut self, descriptor: &[u8]) {
+        let size = u32::from_le_bytes([descriptor[132], descriptor[133], descriptor[134], descriptor[135]]);
+        let location = u32::from_le_bytes([descriptor[140], descriptor[141], descriptor[142], descriptor[143]]);
+
+        self.path_table.clear();
+        if size == 0 || location == 0 {
+            return;
+        }
+
+        let mut data = vec![0u8; size as usize];
+        self.read_extent(location, &mut data, 0);
+
+        let mut offset = 0;
+        while offset + 8 <= data.len() {
+            let name_len = data[offset] as usize;
+            if name_len == 0 || offset + 8 + name_len > data.len() {
+                break;
+            }
+
+            let extent = u32::from_le_bytes([data[offset + 2], data[offset + 3], data[offset + 4], data[offset + 5]]);
+            let parent = u16::from_le_bytes([data[offset + 6], data[offset + 7]]);
+            let name = IsoRecord::decode_name(&data[offset + 8..offset + 8 + name_len], self.naming);
+
+            self.path_table.push(PathTableEntry { name, extent, parent });
+            offset += 8 + name_len + (name_len & 1);
+        }
+    }
+}
+
+// Impl IsoDiskio
+impl IsoDiskio {
+    // Get root record
+    pub fn get_root(&self) -> IsoRecord {
+        self.root.clone()
+    }
+
+    // Get naming
+    pub fn get_naming(&self) -> IsoNaming {
+        self.naming
+    }
+
+    // Get the skip bytes of system use area
+    pub fn get_susp_skip(&self) -> usize {
+        self.susp_skip
+    }
+
+    // Get volume name
+    pub fn get_volume_name(&self) -> String {
+        self.volume_name.clone()
+    }
+
+    // Get block size
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    // Get volume blocks
+    pub fn volume_blocks(&self) -> u32 {
+        self.volume_blocks
+    }
+
+    // Get path table
+    pub fn get_path_table(&self) -> &[PathTableEntry] {
+        &self.path_table
+    }
+
+    // Read extent data at offset
+    pub fn read_extent(&mut self, extent: u32, data: &mut [u8], offset: u64) -> usize {
+        let block_size = self.block_size as u64;
+        let mut buff = vec![0u8; block_size as usize];
+        let mut done = 0;
+
+        while done < data.len() {
+            let pos = offset + done as u64;
+            let block = extent + (pos / block_size) as u32;
+            let inner = (pos % block_size) as usize;
+            let len = (block_size as usize - inner).min(data.len() - done);
+
+            if self.read_block(&mut buff, block) != 1 {
+                break;
+            }
+            data[done..done + len].copy_from_slice(&buff[inner..inner + len]);
+            done += len;
+        }
+
+        done
+    }
+
+    // Read block
+    pub fn read_block(&mut self, data: &mut [u8], block: u32) -> u32 {
+        let sec_per_block = self.block_size / DEVICE_SECTOR_SIZE;
+        self.device.read(
+            data,
+            sec_per_block as usize,
+            (block * sec_per_block) as usize,
+        ) as u32
+            / sec_per_block
+    }
+
+    // Read iso sector, the descriptors are in 2048 bytes sectors
+    fn read_iso_sector(&mut self, data: &mut [u8], sector: u32, count: u32) -> u32 {
+        let scale = ISO_SECTOR_SIZE / DEVICE_SECTOR_SIZE;
+        self.device.read(
+            data,
+            (count * scale) as usize,
+            (sector * scale) as usize,
+        ) as u32
+            / scale
+    }
+}
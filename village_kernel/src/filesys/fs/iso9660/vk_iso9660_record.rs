@@ -0,0 +1,319 @@
+//###########################################################################
+// vk_iso9660_record.rs
+// The specific implementation of functions related to iso9660 record
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_iso9660_diskio::IsoNaming;
+use crate::traits::vk_filesys::{FileAttr, FileFlags, FileTime, FileType};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Const members
+const RECORD_HEADER_SIZE: usize = 33;
+const SUSP_HEADER_SIZE: usize = 4;
+
+// Struct IsoRecordFlags
+pub struct IsoRecordFlags;
+
+// Impl IsoRecordFlags
+impl IsoRecordFlags {
+    pub const HIDDEN: u8 = 0x01;
+    pub const DIRECTORY: u8 = 0x02;
+}
+
+// Struct RockRidgeMode
+pub struct RockRidgeMode;
+
+// Impl RockRidgeMode
+impl RockRidgeMode {
+    pub const TYPE_MASK: u32 = 0o170000;
+    pub const SYMLINK: u32 = 0o120000;
+    pub const PERM_MASK: u32 = 0o7777;
+}
+
+// Struct IsoRecord
+#[derive(Clone)]
+pub struct IsoRecord {
+    pub name: String,
+    pub extent: u32,
+    pub size: u32,
+    pub flags: u8,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub link: String,
+    pub create_time: FileTime,
+    pub modify_time: FileTime,
+    pub access_time: FileTime,
+    pub continuation: Option<(u32, u32, u32)>,
+    pub child_link: Option<u32>,
+    pub relocated: bool,
+    rr_name: Option<String>,
+    link_continue: bool,
+}
+
+// Impl IsoRecord
+impl IsoRecord {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            name: String::new(),
+            extent: 0,
+            size: 0,
+            flags: 0,
+            mode: 0,
+            uid: 0,
+            gid: 0,
+            link: String::new(),
+            create_time: FileTime::new(),
+            modify_time: FileTime::new(),
+            access_time: FileTime::new(),
+            continuation: None,
+            child_link: None,
+            relocated: false,
+            rr_name: None,
+            link_continue: false,
+        }
+    }
+
+    // From, the data starts with the directory record
+    pub fn from(data: &[u8], naming: IsoNaming, susp_skip: usize) -> Option<Self> {
+        let len = *data.first()? as usize;
+        let name_len = *data.get(32)? as usize;
+        if len < RECORD_HEADER_SIZE || data.len() < len || RECORD_HEADER_SIZE + name_len > len {
+            return None;
+        }
+
+        let mut record = Self::new();
+
+        record.extent = get_u32(data, 2);
+        record.size = get_u32(data, 10);
+        record.flags = data[25];
+        record.modify_time = Self::decode_time(&data[18..25]);
+        record.create_time = record.modify_time.clone();
+        record.access_time = record.modify_time.clone();
+        record.name = Self::decode_name(&data[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + name_len], naming);
+
+        // The system use area follows the padded name
+        if naming == IsoNaming::RockRidge {
+            let start = RECORD_HEADER_SIZE + name_len + (1 - name_len % 2) + susp_skip;
+            if start < len {
+                record.parse_susp(&data[start..len]);
+            }
+        }
+
+        Some(record)
+    }
+
+    // Decode name, the version and trailing dot of iso name are removed
+    pub fn decode_name(data: &[u8], naming: IsoNaming) -> String {
+        if data.len() == 1 && data[0] <= 1 {
+            return String::from(if data[0] == 0 { "." } else { ".." });
+        }
+
+        let name = if naming == IsoNaming::Joliet {
+            let chars: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&chars)
+        } else {
+            String::from_utf8_lossy(data).to_lowercase()
+        };
+
+        let name = match name.rfind(';') {
+            Some(pos) => &name[..pos],
+            None => &name,
+        };
+        let name = if naming == IsoNaming::Joliet { name } else { name.trim_end_matches('.') };
+        String::from(name)
+    }
+
+    // Decode the record time, the year is from 1900
+    fn decode_time(data: &[u8]) -> FileTime {
+        if data[1] == 0 {
+            return FileTime::new();
+        }
+        FileTime {
+            year: 1900 + data[0] as u16,
+            month: data[1],
+            day: data[2],
+            hour: data[3],
+            minute: data[4],
+            second: data[5],
+        }
+    }
+
+    // Decode the long form time, the digits are in ascii
+    fn decode_long_time(data: &[u8]) -> FileTime {
+        let digits = |start: usize, len: usize| -> u16 {
+            data[start..start + len]
+                .iter()
+                .fold(0, |value, c| value * 10 + c.wrapping_sub(b'0') as u16 % 10)
+        };
+        let time = FileTime {
+            year: digits(0, 4),
+            month: digits(4, 2) as u8,
+            day: digits(6, 2) as u8,
+            hour: digits(8, 2) as u8,
+            minute: digits(10, 2) as u8,
+            second: digits(12, 2) as u8,
+        };
+        if time.is_valid() { time } else { FileTime::new() }
+    }
+
+    // Get the skip bytes from the SP entry of root dot record
+    pub fn susp_skip(data: &[u8]) -> Option<usize> {
+        let len = *data.first()? as usize;
+        let name_len = *data.get(32)? as usize;
+        let start = RECORD_HEADER_SIZE + name_len + (1 - name_len % 2);
+        let sp = data.get(start..start + 7)?;
+        if start + 7 <= len && &sp[0..2] == b"SP" && sp[4] == 0xbe && sp[5] == 0xef {
+            return Some(sp[6] as usize);
+        }
+        None
+    }
+
+    // Parse the system use entries of rock ridge
+    pub fn parse_susp(&mut self, data: &[u8]) {
+        let mut offset = 0;
+
+        while offset + SUSP_HEADER_SIZE <= data.len() {
+            let sig = &data[offset..offset + 2];
+            let len = data[offset + 2] as usize;
+            if len < SUSP_HEADER_SIZE || offset + len > data.len() {
+                break;
+            }
+            let entry = &data[offset..offset + len];
+
+            match sig {
+                b"CE" if len >= 28 => {
+                    self.continuation = Some((get_u32(entry, 4), get_u32(entry, 12), get_u32(entry, 20)));
+                }
+                b"PX" if len >= 36 => {
+                    self.mode = get_u32(entry, 4);
+                    self.uid = get_u32(entry, 20);
+                    self.gid = get_u32(entry, 28);
+                }
+                b"NM" if len >= 5 => {
+                    // The current and parent flags are for the dot entries
+                    if entry[4] & 0x06 == 0 {
+                        let part = String::from_utf8_lossy(&entry[5..]);
+                        let name = self.rr_name.get_or_insert_with(String::new);
+                        name.push_str(&part);
+                        self.name = name.clone();
+                    }
+                }
+                b"SL" if len >= 5 => self.parse_symlink(&entry[5..]),
+                b"TF" if len >= 5 => self.parse_times(entry[4], &entry[5..]),
+                b"CL" if len >= 12 => self.child_link = Some(get_u32(entry, 4)),
+                b"RE" => self.relocated = true,
+                b"ST" => break,
+                _ => {}
+            }
+
+            offset += len;
+        }
+    }
+
+    // Parse the symbolic link components
+    fn parse_symlink(&mut self, data: &[u8]) {
+        let mut offset = 0;
+
+        while offset + 2 <= data.len() {
+            let flags = data[offset];
+            let len = data[offset + 1] as usize;
+            if offset + 2 + len > data.len() {
+                break;
+            }
+
+            if !self.link_continue && !self.link.is_empty() && !self.link.ends_with('/') {
+                self.link.push('/');
+            }
+
+            if flags & 0x02 != 0 {
+                self.link.push('.');
+            } else if flags & 0x04 != 0 {
+                self.link.push_str("..");
+            } else if flags & 0x08 != 0 {
+                self.link.push('/');
+            } else {
+                self.link.push_str(&String::from_utf8_lossy(&data[offset + 2..offset + 2 + len]));
+            }
+
+            self.link_continue = flags & 0x01 != 0;
+            offset += 2 + len;
+        }
+    }
+
+    // Parse the time stamps, the creation, modify and access are in order
+    fn parse_times(&mut self, flags: u8, data: &[u8]) {
+        let size = if flags & 0x80 != 0 { 17 } else { 7 };
+        let mut stamps = data.chunks_exact(size);
+
+        for bit in 0..3 {
+            if flags & (1 << bit) == 0 {
+                continue;
+            }
+            let stamp = match stamps.next() {
+                Some(stamp) => stamp,
+                None => break,
+            };
+            let time = if size == 17 { Self::decode_long_time(stamp) } else { Self::decode_time(stamp) };
+            match bit {
+                0 => self.create_time = time,
+                1 => self.modify_time = time,
+                _ => self.access_time = time,
+            }
+        }
+    }
+}
+
+// Impl IsoRecord
+impl IsoRecord {
+    // Is directory
+    pub fn is_dir(&self) -> bool {
+        self.flags & IsoRecordFlags::DIRECTORY != 0
+    }
+
+    // Is symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.mode & RockRidgeMode::TYPE_MASK == RockRidgeMode::SYMLINK
+    }
+
+    // Is dot entry
+    pub fn is_dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+
+    // Get object type
+    pub fn get_object_type(&self) -> FileType {
+        if self.is_dir() {
+            FileType::Directory
+        } else {
+            FileType::File
+        }
+    }
+
+    // Get object attr
+    pub fn get_object_attr(&self) -> FileAttr {
+        if self.flags & IsoRecordFlags::HIDDEN != 0 || (self.name.starts_with('.') && !self.is_dot()) {
+            FileAttr::Hidden
+        } else {
+            FileAttr::Visible
+        }
+    }
+
+    // Get flags, the whole volume is read only
+    pub fn get_flags(&self) -> FileFlags {
+        let mut flags = FileFlags::NONE;
+        flags.insert(FileFlags::READ_ONLY);
+        if self.get_object_attr() == FileAttr::Hidden {
+            flags.insert(FileFlags::HIDDEN);
+        }
+        flags
+    }
+}
+
+// Get u32, the both endian fields use the little endian half
+fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
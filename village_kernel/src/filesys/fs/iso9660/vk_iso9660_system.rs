@@ -0,0 +1,482 @@
+//###########################################################################
+// vk_iso9660_system.rs
+// The specific implementation of functions related to iso9660 system
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_iso9660_diskio::{IsoDiskio, IsoNaming};
+use super::vk_iso9660_record::{IsoRecord, IsoRecordFlags, RockRidgeMode};
+use crate::traits::vk_filesys::{FileDir, FileMode, FileSys, FileType, FileVol};
+use crate::traits::vk_filesys::{FileCheck, FileFlags, FileStat, FileTime};
+use crate::traits::vk_linkedlist::LinkedList;
+use crate::register_filesys;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const MAX_SYMLINKS: usize = 8;
+const MAX_CONTINUATIONS: usize = 8;
+
+// Struct IsoFile
+struct IsoFile {
+    file_id: usize,
+    record: IsoRecord,
+}
+
+// Struct IsoDir
+struct IsoDir {
+    dir_id: usize,
+    sub_dirs: Vec<FileDir>,
+}
+
+// Struct IsoVolume
+struct IsoVolume {
+    diskio: IsoDiskio,
+    mount_path: String,
+    dir_cnt: usize,
+    file_cnt: usize,
+    dirs: LinkedList<IsoDir>,
+    files: LinkedList<IsoFile>,
+}
+
+// Impl IsoVolume
+impl IsoVolume {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            diskio: IsoDiskio::new(),
+            mount_path: String::new(),
+            dir_cnt: 1,
+            file_cnt: 1,
+            dirs: LinkedList::new(),
+            files: LinkedList::new(),
+        }
+    }
+}
+
+// Impl IsoVolume
+impl IsoVolume {
+    // Assign file id
+    fn assign_file_id(&mut self) -> usize {
+        let id = self.file_cnt;
+        self.file_cnt += 1;
+        id
+    }
+
+    // Assign dir id
+    fn assign_dir_id(&mut self) -> usize {
+        let id = self.dir_cnt;
+        self.dir_cnt += 1;
+        id
+    }
+
+    // Is write mode, the volume is read only
+    fn is_write_mode(mode: FileMode) -> bool {
+        mode.contains(FileMode::WRITE)
+            || mode.contains(FileMode::CREATE_NEW)
+            || mode.contains(FileMode::CREATE_ALWAYS)
+    }
+
+    // Compare name, the plain iso names are not case sensitive
+    fn name_equal(&self, a: &str, b: &str) -> bool {
+        if self.diskio.get_naming() == IsoNaming::Plain {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    }
+
+    // Parse record with the continuation areas
+    fn parse_record(&mut self, data: &[u8]) -> Option<IsoRecord> {
+        let naming = self.diskio.get_naming();
+        let mut record = IsoRecord::from(data, naming, self.diskio.get_susp_skip())?;
+
+        for _ in 0..MAX_CONTINUATIONS {
+            let (block, offset, len) = match record.continuation.take() {
+                Some(ce) => ce,
+                None => break,
+            };
+            let mut area = vec![0u8; len as usize];
+            self.diskio.read_extent(block, &mut area, offset as u64);
+            record.parse_susp(&area);
+        }
+
+        Some(record)
+    }
+
+    // Load the dot record of directory extent
+    fn load_dot(&mut self, extent: u32) -> Option<IsoRecord> {
+        let mut data = vec![0u8; self.diskio.block_size() as usize];
+        self.diskio.read_extent(extent, &mut data, 0);
+        self.parse_record(&data)
+    }
+
+    // Read the records of directory, the records do not cross the block
+    fn read_records(&mut self, dir: &IsoRecord) -> Vec<IsoRecord> {
+        let block_size = self.diskio.block_size() as usize;
+        let mut data = vec![0u8; dir.size as usize];
+        let size = self.diskio.read_extent(dir.extent, &mut data, 0);
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        while offset < size {
+            let len = data[offset] as usize;
+            if len == 0 {
+                offset = (offset / block_size + 1) * block_size;
+                continue;
+            }
+            if offset + len > size {
+                break;
+            }
+
+            if let Some(mut record) = self.parse_record(&data[offset..offset + len]) {
+                // The relocated directory is linked by the child link
+                if let Some(extent) = record.child_link {
+                    if let Some(dot) = self.load_dot(extent) {
+                        record.extent = dot.extent;
+                        record.size = dot.size;
+                        record.flags |= IsoRecordFlags::DIRECTORY;
+                    }
+                }
+                if !record.relocated {
+                    records.push(record);
+                }
+            }
+            offset += len;
+        }
+
+        records
+    }
+
+    // Search name in directory
+    fn search(&mut self, dir: &IsoRecord, name: &str) -> Option<IsoRecord> {
+        self.read_records(dir)
+            .into_iter()
+            .find(|record| !record.is_dot() && self.name_equal(&record.name, name))
+    }
+
+    // Resolve the directories by path table, the rock ridge names are not in path table
+    fn resolve_table(&mut self, names: &[String]) -> Option<IsoRecord> {
+        let mut index = 1;
+
+        for name in names {
+            let table = self.diskio.get_path_table();
+            let pos = table
+                .iter()
+                .enumerate()
+                .skip(1)
+                .find(|(_, entry)| entry.parent as usize == index && self.name_equal(&entry.name, name))
+                .map(|(pos, _)| pos)?;
+            index = pos + 1;
+        }
+
+        let extent = self.diskio.get_path_table().get(index - 1)?.extent;
+        let mut record = self.load_dot(extent)?;
+        record.name = names.last().cloned().unwrap_or_default();
+        Some(record)
+    }
+
+    // Lookup path, the symbolic links are followed, the last one is followed when follow is set.
+    // The absolute link target is resolved in this volume
+    fn lookup(&mut self, path: &str, follow: bool) -> Option<IsoRecord> {
+        let mut names: Vec<String> = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .rev()
+            .map(|s| s.to_string())
+            .collect();
+        let mut stack = vec![self.diskio.get_root()];
+        let mut links = 0;
+
+        // Resolve the parent directories quickly by path table
+        if self.diskio.get_naming() != IsoNaming::RockRidge && names.len() > 1 {
+            let dirs: Vec<String> = names[1..].iter().rev().cloned().collect();
+            if !dirs.iter().any(|name| name == "." || name == "..") {
+                if let Some(record) = self.resolve_table(&dirs) {
+                    names.truncate(1);
+                    stack.push(record);
+                }
+            }
+        }
+
+        while let Some(name) = names.pop() {
+            let current = stack.last()?.clone();
+            if !current.is_dir() {
+                return None;
+            }
+
+            if name == "." {
+                continue;
+            } else if name == ".." {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                continue;
+            }
+
+            let record = self.search(&current, &name)?;
+
+            if record.is_symlink() && (follow || !names.is_empty()) {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return None;
+                }
+
+                if record.link.starts_with('/') {
+                    stack.truncate(1);
+                }
+                names.extend(record.link.split('/').filter(|s| !s.is_empty()).rev().map(|s| s.to_string()));
+                continue;
+            }
+
+            stack.push(record);
+        }
+
+        stack.pop()
+    }
+
+    // Get record stat
+    fn record_stat(&mut self, record: &IsoRecord) -> FileStat {
+        let mut stat = FileStat::new();
+
+        stat.name = record.name.clone();
+        stat.typid = record.get_object_type();
+        stat.flags = record.get_flags();
+        stat.size = record.size as u64;
+        stat.clusters = record.size.div_ceil(self.diskio.block_size());
+        stat.mode = (record.mode & RockRidgeMode::PERM_MASK) as u16;
+        stat.uid = record.uid as u16;
+        stat.gid = record.gid as u16;
+        stat.link = record.link.clone();
+        stat.create_time = record.create_time.clone();
+        stat.write_time = record.modify_time.clone();
+        stat.access_time = record.access_time.clone();
+
+        stat
+    }
+}
+
+// Impl IsoVolume
+impl FileVol for IsoVolume {
+    // Setup
+    fn setup(&mut self, disk: &str) -> bool {
+        self.diskio.setup(disk)
+    }
+
+    // Exit
+    fn exit(&mut self) {
+        self.files.clear();
+        self.dirs.clear();
+        self.diskio.exit();
+    }
+
+    // Set mount path
+    fn set_mount_path(&mut self, path: &str) {
+        self.mount_path = path.to_string();
+    }
+
+    // Get mount path
+    fn get_mount_path(&mut self) -> &str {
+        &self.mount_path
+    }
+
+    // Set name
+    fn set_name(&mut self, _name: &str) -> bool {
+        false
+    }
+
+    // Get name
+    fn get_name(&mut self) -> String {
+        let name = self.diskio.get_volume_name();
+        if name.is_empty() {
+            return "CDROM".to_string();
+        }
+        name
+    }
+
+    // Get total size
+    fn get_total_size(&mut self) -> u64 {
+        self.diskio.volume_blocks() as u64 * self.diskio.block_size() as u64
+    }
+
+    // Get free size
+    fn get_free_size(&mut self) -> u64 {
+        0
+    }
+
+    // Open
+    fn open(&mut self, name: &str, mode: FileMode) -> usize {
+        if Self::is_write_mode(mode) {
+            return 0;
+        }
+
+        if let Some(record) = self.lookup(name, true) {
+            if record.is_dir() {
+                return 0;
+            }
+
+            let fd = self.assign_file_id();
+            self.files.push(IsoFile { file_id: fd, record });
+            return fd;
+        }
+        0
+    }
+
+    // Write
+    fn write(&mut self, _fd: usize, _data: &[u8], _size: usize, _offset: usize) -> usize {
+        0
+    }
+
+    // Read
+    fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            let total = file.record.size as usize;
+            if offset >= total {
+                return 0;
+            }
+            let size = size.min(data.len()).min(total - offset);
+            let extent = file.record.extent;
+            return self.diskio.read_extent(extent, &mut data[..size], offset as u64);
+        }
+        0
+    }
+
+    // Size
+    fn size(&mut self, fd: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            return file.record.size as usize;
+        }
+        0
+    }
+
+    // Set len, the volume is read only
+    fn set_len(&mut self, _fd: usize, _size: usize) -> bool {
+        false
+    }
+
+    // Flush
+    fn flush(&mut self, _fd: usize) {}
+
+    // Close
+    fn close(&mut self, fd: usize) {
+        self.files.retain_mut(|file| file.file_id != fd);
+    }
+
+    // Open dir
+    fn opendir(&mut self, name: &str, mode: FileMode) -> usize {
+        if Self::is_write_mode(mode) {
+            return 0;
+        }
+
+        if let Some(record) = self.lookup(name, true) {
+            if !record.is_dir() {
+                return 0;
+            }
+
+            let mut sub_dirs = Vec::new();
+            for sub in self.read_records(&record) {
+                let mut dir = FileDir::new();
+                dir.typid = sub.get_object_type();
+                dir.attr = sub.get_object_attr();
+                dir.name = sub.name;
+                sub_dirs.push(dir);
+            }
+
+            let fd = self.assign_dir_id();
+            self.dirs.push(IsoDir { dir_id: fd, sub_dirs });
+            return fd;
+        }
+        0
+    }
+
+    // Read dir
+    fn readdir(&mut self, fd: usize, dirs: &mut [FileDir], size: usize, offset: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            let remain = dir.sub_dirs.len().saturating_sub(offset);
+            let size = size.min(dirs.len()).min(remain);
+            dirs[..size].clone_from_slice(&dir.sub_dirs[offset..offset + size]);
+            return size;
+        }
+        0
+    }
+
+    // Size dir
+    fn sizedir(&mut self, fd: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            return dir.sub_dirs.len();
+        }
+        0
+    }
+
+    // Close dir
+    fn closedir(&mut self, fd: usize) {
+        self.dirs.retain_mut(|dir| dir.dir_id != fd);
+    }
+
+    // Stat, the last symbolic link is not followed
+    fn stat(&mut self, name: &str) -> Option<FileStat> {
+        let record = self.lookup(name, false)?;
+        Some(self.record_stat(&record))
+    }
+
+    // Fstat
+    fn fstat(&mut self, fd: usize) -> Option<FileStat> {
+        let record = self.files.iter_mut().find(|f| f.file_id == fd)?.record.clone();
+        Some(self.record_stat(&record))
+    }
+
+    // Set flags
+    fn set_flags(&mut self, _name: &str, _flags: FileFlags) -> bool {
+        false
+    }
+
+    // Set time
+    fn set_time(&mut self, _name: &str, _write_time: FileTime, _access_time: FileTime) -> bool {
+        false
+    }
+
+    // Is exist
+    fn exist(&mut self, name: &str, typeid: FileType) -> bool {
+        if let Some(record) = self.lookup(name, true) {
+            return record.get_object_type() == typeid;
+        }
+        false
+    }
+
+    // Remove
+    fn remove(&mut self, _name: &str) -> bool {
+        false
+    }
+
+    // Rename
+    fn rename(&mut self, _source: &str, _target: &str) -> bool {
+        false
+    }
+
+    // Check, the consistency check is not supported
+    fn check(&mut self, _repair: bool) -> Option<FileCheck> {
+        None
+    }
+}
+
+// Struct iso9660 system
+struct Iso9660System;
+
+// Impl filesys for iso9660 system
+impl FileSys for Iso9660System {
+    // Get file system id
+    fn file_system_id(&self) -> usize {
+        const SYSTEM_ID: usize = 0x96;
+        SYSTEM_ID
+    }
+
+    // Create volume
+    fn create_volume(&mut self) -> Box<dyn FileVol> {
+        Box::new(IsoVolume::new())
+    }
+}
+
+// Register filesys
+register_filesys!(Iso9660System, iso9660);
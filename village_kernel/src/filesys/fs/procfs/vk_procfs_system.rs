@@ -0,0 +1,527 @@
+//###########################################################################
+// vk_procfs_system.rs
+// The specific implementation of functions related to procfs system
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::traits::vk_driver::DriverID;
+use crate::traits::vk_filesys::{FileAttr, FileDir, FileMode, FileSys, FileType, FileVol};
+use crate::traits::vk_filesys::{FileCheck, FileFlags, FileStat, FileTime};
+use crate::traits::vk_linkedlist::LinkedList;
+use crate::register_filesys;
+use crate::village::kernel;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// Constant members
+const ROOT_FILES: &[&str] = &[
+    "meminfo",
+    "tasks",
+    "devices",
+    "mounts",
+    "modules",
+    "libraries",
+    "interrupts",
+    "uptime",
+];
+const PROCESS_FILES: &[&str] = &["status", "cmdline"];
+
+// Enum ProcNode
+enum ProcNode {
+    Root,
+    File(&'static str),
+    Process,
+    ProcessFile(i32, &'static str),
+}
+
+// Struct ProcFile
+struct ProcFile {
+    file_id: usize,
+    node: String,
+    data: Vec<u8>,
+}
+
+// Struct ProcDir
+struct ProcDir {
+    dir_id: usize,
+    sub_dirs: Vec<FileDir>,
+}
+
+// Struct ProcVolume
+struct ProcVolume {
+    name: String,
+    mount_path: String,
+    dir_cnt: usize,
+    file_cnt: usize,
+    dirs: LinkedList<ProcDir>,
+    files: LinkedList<ProcFile>,
+}
+
+// Impl ProcVolume
+impl ProcVolume {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            name: String::new(),
+            mount_path: String::new(),
+            dir_cnt: 1,
+            file_cnt: 1,
+            dirs: LinkedList::new(),
+            files: LinkedList::new(),
+        }
+    }
+}
+
+// Impl ProcVolume
+impl ProcVolume {
+    // Assign file id
+    fn assign_file_id(&mut self) -> usize {
+        let id = self.file_cnt;
+        self.file_cnt += 1;
+        id
+    }
+
+    // Assign dir id
+    fn assign_dir_id(&mut self) -> usize {
+        let id = self.dir_cnt;
+        self.dir_cnt += 1;
+        id
+    }
+
+    // Parse path to node
+    fn parse(path: &str) -> Option<ProcNode> {
+        let names: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        match names.len() {
+            0 => Some(ProcNode::Root),
+            1 => {
+                if let Some(file) = ROOT_FILES.iter().find(|f| **f == names[0]) {
+                    return Some(ProcNode::File(file));
+                }
+                Self::parse_pid(names[0])?;
+                Some(ProcNode::Process)
+            }
+            2 => {
+                let pid = Self::parse_pid(names[0])?;
+                let file = PROCESS_FILES.iter().find(|f| **f == names[1])?;
+                Some(ProcNode::ProcessFile(pid, file))
+            }
+            _ => None,
+        }
+    }
+
+    // Parse pid, the process must be existed
+    fn parse_pid(name: &str) -> Option<i32> {
+        let pid = name.parse::<i32>().ok()?;
+        if kernel().process().is_exist_by_pid(pid) {
+            return Some(pid);
+        }
+        None
+    }
+
+    // Get node type
+    fn node_type(node: &ProcNode) -> FileType {
+        match node {
+            ProcNode::Root | ProcNode::Process => FileType::Directory,
+            ProcNode::File(_) | ProcNode::ProcessFile(_, _) => FileType::File,
+        }
+    }
+
+    // Make dir entry
+    fn dir_entry(name: &str, typid: FileType) -> FileDir {
+        let mut dir = FileDir::new();
+        dir.name = name.to_string();
+        dir.attr = FileAttr::Visible;
+        dir.typid = typid;
+        dir
+    }
+
+    // Generate file content
+    fn generate(node: &ProcNode) -> String {
+        match node {
+            ProcNode::File("meminfo") => Self::gen_meminfo(),
+            ProcNode::File("tasks") => Self::gen_tasks(),
+            ProcNode::File("devices") => Self::gen_devices(),
+            ProcNode::File("mounts") => Self::gen_mounts(),
+            ProcNode::File("modules") => Self::gen_modules(),
+            ProcNode::File("libraries") => Self::gen_libraries(),
+            ProcNode::File("interrupts") => Self::gen_interrupts(),
+            ProcNode::File("uptime") => Self::gen_uptime(),
+            ProcNode::ProcessFile(pid, "status") => Self::gen_status(*pid),
+            ProcNode::ProcessFile(pid, "cmdline") => Self::gen_cmdline(*pid),
+            _ => String::new(),
+        }
+    }
+}
+
+// Impl ProcVolume
+impl ProcVolume {
+    // Generate meminfo
+    fn gen_meminfo() -> String {
+        let size = kernel().memory().get_size();
+        let used = kernel().memory().get_used();
+        format!(
+            "MemTotal: {} B\nMemUsed:  {} B\nMemFree:  {} B\n",
+            size,
+            used,
+            size.saturating_sub(used)
+        )
+    }
+
+    // Generate tasks
+    fn gen_tasks() -> String {
+        let mut text = String::from("tid pid cpu stack_start stack_ended psp        state      name\n");
+        for task in kernel().thread().get_task_infos().iter() {
+            let pid = kernel().process().get_pid_by_tid(task.id);
+            text.push_str(&format!(
+                "{:<3} {:<3} {:<3} 0x{:08x}  0x{:08x}  0x{:08x} {:<10} {}\n",
+                task.id,
+                if pid < 0 { "-".to_string() } else { format!("{}", pid) },
+                task.cpu,
+                task.stack_start,
+                task.stack_ended,
+                task.psp,
+                task.state.as_str(),
+                task.name
+            ));
+        }
+        text
+    }
+
+    // Generate devices
+    fn gen_devices() -> String {
+        let mut text = String::new();
+        for driver_id in DriverID::iter() {
+            for driver in kernel().device().get_drivers().iter_mut() {
+                if driver_id == driver.id() {
+                    text.push_str(&format!("{:<12} {}\n", driver.name(), driver.id().as_str()));
+                }
+            }
+        }
+        text
+    }
+
+    // Generate mounts
+    fn gen_mounts() -> String {
+        let mut text = String::new();
+        for mount in kernel().filesys().get_mounts().iter_mut() {
+            let mode = if mount.is_read_only() { "ro" } else { "rw" };
+            text.push_str(&format!("{} {} {}\n", mount.source, mount.target, mode));
+        }
+        text
+    }
+
+    // Generate modules
+    fn gen_modules() -> String {
+        let mut text = String::new();
+        for module in kernel().module().get_modules().iter_mut() {
+            text.push_str(&format!("{}\n", module.path));
+        }
+        text
+    }
+
+    // Generate libraries
+    fn gen_libraries() -> String {
+        let mut text = String::new();
+        for library in kernel().library().get_libraries().iter_mut() {
+            text.push_str(&format!("{}\n", library.path));
+        }
+        text
+    }
+
+    // Generate interrupts, only the irqs with service function or count are listed
+    fn gen_interrupts() -> String {
+        let mut text = String::from("irq  count       handlers masked\n");
+        let interrupt = kernel().interrupt();
+        for irq in 0..interrupt.get_isr_num() as isize {
+            let size = interrupt.get_isr_cb_size(irq);
+            let count = interrupt.get_isr_count(irq);
+            if size == 0 && count == 0 {
+                continue;
+            }
+            let masked = if interrupt.is_masked(irq) { "yes" } else { "no" };
+            text.push_str(&format!("{:<4} {:<11} {:<8} {}\n", irq, count, size, masked));
+        }
+        text
+    }
+
+    // Generate uptime, the systick is 1ms
+    fn gen_uptime() -> String {
+        let ticks = kernel().system().get_ticks();
+        format!("{}.{:03}\n", ticks / 1000, ticks % 1000)
+    }
+
+    // Generate status of process
+    fn gen_status(pid: i32) -> String {
+        let mut text = String::new();
+        if let Some(process) = kernel().process().get_processes().iter_mut().find(|p| p.pid == pid) {
+            text.push_str(&format!("Pid:     {}\n", process.pid));
+            text.push_str(&format!("Tid:     {}\n", process.tid));
+            text.push_str(&format!("Path:    {}\n", process.path));
+            text.push_str(&format!("Threads: {}\n", process.threads.len()));
+
+            let mut tids = Vec::new();
+            tids.push(process.tid);
+            tids.extend(process.threads.iter().copied());
+
+            for tid in tids {
+                if let Some(task) = kernel().thread().get_task_info(tid) {
+                    text.push_str(&format!("Task:    {} {} {}\n", task.id, task.state.as_str(), task.name));
+                }
+            }
+        }
+        text
+    }
+
+    // Generate cmdline of process
+    fn gen_cmdline(pid: i32) -> String {
+        if let Some(process) = kernel().process().get_processes().iter_mut().find(|p| p.pid == pid) {
+            return format!("{}\n", process.argv.join(" "));
+        }
+        String::new()
+    }
+}
+
+// Impl ProcVolume
+impl FileVol for ProcVolume {
+    // Setup, the disk is the volume name
+    fn setup(&mut self, disk: &str) -> bool {
+        // Never claim a real disk
+        if disk.is_empty() || kernel().device().get_driver(disk).is_some() {
+            return false;
+        }
+        self.name = disk.to_string();
+        true
+    }
+
+    // Exit
+    fn exit(&mut self) {
+        self.files.clear();
+        self.dirs.clear();
+    }
+
+    // Set mount path
+    fn set_mount_path(&mut self, path: &str) {
+        self.mount_path = path.to_string();
+    }
+
+    // Get mount path
+    fn get_mount_path(&mut self) -> &str {
+        &self.mount_path
+    }
+
+    // Set name
+    fn set_name(&mut self, name: &str) -> bool {
+        self.name = name.to_string();
+        true
+    }
+
+    // Get name
+    fn get_name(&mut self) -> String {
+        self.name.clone()
+    }
+
+    // Get total size
+    fn get_total_size(&mut self) -> u64 {
+        0
+    }
+
+    // Get free size
+    fn get_free_size(&mut self) -> u64 {
+        0
+    }
+
+    // Open, the content is generated on open
+    fn open(&mut self, name: &str, mode: FileMode) -> usize {
+        // The files are read only
+        if mode.contains(FileMode::WRITE) || mode.contains(FileMode::CREATE_NEW) {
+            return 0;
+        }
+
+        let node = match Self::parse(name) {
+            Some(node) if Self::node_type(&node) == FileType::File => node,
+            _ => return 0,
+        };
+
+        let fd = self.assign_file_id();
+        self.files.push(ProcFile {
+            file_id: fd,
+            node: name.to_string(),
+            data: Self::generate(&node).into_bytes(),
+        });
+        fd
+    }
+
+    // Write
+    fn write(&mut self, _fd: usize, _data: &[u8], _size: usize, _offset: usize) -> usize {
+        0
+    }
+
+    // Read
+    fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            if offset >= file.data.len() {
+                return 0;
+            }
+            let size = size.min(data.len()).min(file.data.len() - offset);
+            data[..size].copy_from_slice(&file.data[offset..offset + size]);
+            return size;
+        }
+        0
+    }
+
+    // Size
+    fn size(&mut self, fd: usize) -> usize {
+        if let Some(file) = self.files.iter_mut().find(|f| f.file_id == fd) {
+            return file.data.len();
+        }
+        0
+    }
+
+    // Set len, the file is generated
+    fn set_len(&mut self, _fd: usize, _size: usize) -> bool {
+        false
+    }
+
+    // Flush
+    fn flush(&mut self, _fd: usize) {}
+
+    // Close
+    fn close(&mut self, fd: usize) {
+        self.files.retain_mut(|file| file.file_id != fd);
+    }
+
+    // Open dir
+    fn opendir(&mut self, name: &str, _mode: FileMode) -> usize {
+        let mut sub_dirs = Vec::new();
+
+        match Self::parse(name) {
+            Some(ProcNode::Root) => {
+                for file in ROOT_FILES.iter() {
+                    sub_dirs.push(Self::dir_entry(file, FileType::File));
+                }
+                for process in kernel().process().get_processes().iter_mut() {
+                    sub_dirs.push(Self::dir_entry(&format!("{}", process.pid), FileType::Directory));
+                }
+            }
+            Some(ProcNode::Process) => {
+                sub_dirs.push(Self::dir_entry(".", FileType::Directory));
+                sub_dirs.push(Self::dir_entry("..", FileType::Directory));
+                for file in PROCESS_FILES.iter() {
+                    sub_dirs.push(Self::dir_entry(file, FileType::File));
+                }
+            }
+            _ => return 0,
+        }
+
+        let fd = self.assign_dir_id();
+        self.dirs.push(ProcDir { dir_id: fd, sub_dirs });
+        fd
+    }
+
+    // Read dir
+    fn readdir(&mut self, fd: usize, dirs: &mut [FileDir], size: usize, offset: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            let remain = dir.sub_dirs.len().saturating_sub(offset);
+            let size = size.min(dirs.len()).min(remain);
+            dirs[..size].clone_from_slice(&dir.sub_dirs[offset..offset + size]);
+            return size;
+        }
+        0
+    }
+
+    // Size dir
+    fn sizedir(&mut self, fd: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            return dir.sub_dirs.len();
+        }
+        0
+    }
+
+    // Close dir
+    fn closedir(&mut self, fd: usize) {
+        self.dirs.retain_mut(|dir| dir.dir_id != fd);
+    }
+
+    // Stat, the size of file is the size of generated content
+    fn stat(&mut self, name: &str) -> Option<FileStat> {
+        let node = Self::parse(name)?;
+
+        let mut stat = FileStat::new();
+        stat.name = name.trim_matches('/').rsplit('/').next().unwrap_or("").to_string();
+        stat.typid = Self::node_type(&node);
+        stat.flags = FileFlags::READ_ONLY;
+
+        if stat.typid == FileType::File {
+            stat.size = Self::generate(&node).len() as u64;
+        }
+
+        Some(stat)
+    }
+
+    // Fstat
+    fn fstat(&mut self, fd: usize) -> Option<FileStat> {
+        let file = self.files.iter_mut().find(|f| f.file_id == fd)?;
+        let size = file.data.len() as u64;
+        let name = file.node.clone();
+
+        let mut stat = self.stat(&name)?;
+        stat.size = size;
+        Some(stat)
+    }
+
+    // Set flags
+    fn set_flags(&mut self, _name: &str, _flags: FileFlags) -> bool {
+        false
+    }
+
+    // Set time
+    fn set_time(&mut self, _name: &str, _write_time: FileTime, _access_time: FileTime) -> bool {
+        false
+    }
+
+    // Is exist
+    fn exist(&mut self, name: &str, typeid: FileType) -> bool {
+        match Self::parse(name) {
+            Some(node) => Self::node_type(&node) == typeid,
+            None => false,
+        }
+    }
+
+    // Remove
+    fn remove(&mut self, _name: &str) -> bool {
+        false
+    }
+
+    // Rename
+    fn rename(&mut self, _source: &str, _target: &str) -> bool {
+        false
+    }
+
+    // Check, the consistency check is not supported
+    fn check(&mut self, _repair: bool) -> Option<FileCheck> {
+        None
+    }
+}
+
+// Struct procfs system
+struct ProcfsSystem;
+
+// Impl filesys for procfs system
+impl FileSys for ProcfsSystem {
+    // Get file system id
+    fn file_system_id(&self) -> usize {
+        const SYSTEM_ID: usize = 0x103;
+        SYSTEM_ID
+    }
+
+    // Create volume
+    fn create_volume(&mut self) -> Box<dyn FileVol> {
+        Box::new(ProcVolume::new())
+    }
+}
+
+// Register filesys
+register_filesys!(ProcfsSystem, procfs);
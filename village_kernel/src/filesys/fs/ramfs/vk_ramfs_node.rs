@@ -0,0 +1,302 @@
+//###########################################################################
+// vk_ramfs_node.rs
+// The specific implementation of functions related to ramfs node
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::traits::vk_filesys::{FileFlags, FileStat, FileTime, FileType};
+use crate::village::kernel;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// Constant members
+pub const ROOT_NODE: usize = 0;
+const NODE_OVERHEAD: usize = 64;
+
+// Struct RamNode
+pub struct RamNode {
+    pub name: String,
+    pub typid: FileType,
+    pub flags: FileFlags,
+    pub parent: usize,
+    pub children: Vec<usize>,
+    pub data: Vec<u8>,
+    pub create_time: FileTime,
+    pub write_time: FileTime,
+    pub access_time: FileTime,
+}
+
+// Impl RamNode
+impl RamNode {
+    // New
+    pub fn new(name: &str, typid: FileType, parent: usize) -> Self {
+        let now = kernel().system().get_date_time();
+        Self {
+            name: name.to_string(),
+            typid,
+            flags: FileFlags::NONE,
+            parent,
+            children: Vec::new(),
+            data: Vec::new(),
+            create_time: now,
+            write_time: now,
+            access_time: now,
+        }
+    }
+
+    // Touch write time
+    pub fn touch_write(&mut self) {
+        self.write_time = kernel().system().get_date_time();
+        self.access_time = self.write_time;
+    }
+
+    // Touch access time
+    pub fn touch_access(&mut self) {
+        self.access_time = kernel().system().get_date_time();
+    }
+
+    // Get used bytes
+    pub fn used(&self) -> usize {
+        NODE_OVERHEAD + self.name.len() + self.data.len()
+    }
+
+    // Get stat
+    pub fn stat(&self) -> FileStat {
+        let mut stat = FileStat::new();
+        stat.name = self.name.clone();
+        stat.typid = self.typid.clone();
+        stat.flags = self.flags;
+        stat.size = self.data.len() as u64;
+        stat.create_time = self.create_time;
+        stat.write_time = self.write_time;
+        stat.access_time = self.access_time;
+        stat
+    }
+}
+
+// Struct RamTree
+pub struct RamTree {
+    nodes: Vec<Option<RamNode>>,
+    gens: Vec<u32>,
+    free: Vec<usize>,
+    limit: usize,
+    used: usize,
+}
+
+// Impl RamTree
+impl RamTree {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            gens: Vec::new(),
+            free: Vec::new(),
+            limit: 0,
+            used: 0,
+        }
+    }
+
+    // Setup
+    pub fn setup(&mut self, limit: usize) {
+        let root = RamNode::new("/", FileType::Directory, ROOT_NODE);
+        self.used = root.used();
+        self.limit = limit;
+        self.nodes.clear();
+        self.gens.clear();
+        self.free.clear();
+        self.nodes.push(Some(root));
+        self.gens.push(0);
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        self.nodes.clear();
+        self.gens.clear();
+        self.free.clear();
+        self.used = 0;
+    }
+
+    // Get limit
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // Get used
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    // Get node
+    pub fn node(&mut self, id: usize) -> Option<&mut RamNode> {
+        self.nodes.get_mut(id)?.as_mut()
+    }
+
+    // Get generation, it is changed when the node slot is reused
+    pub fn generation(&self, id: usize) -> u32 {
+        self.gens.get(id).copied().unwrap_or(0)
+    }
+
+    // Is the handle valid, the stale handle refers to the removed node
+    pub fn is_valid(&self, id: usize, generation: u32) -> bool {
+        self.gens.get(id) == Some(&generation) && matches!(self.nodes.get(id), Some(Some(_)))
+    }
+
+    // Search path, the reserve is the count of the last names to skip
+    pub fn search(&mut self, path: &str, reserve: usize) -> Option<usize> {
+        let names: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        if names.len() < reserve {
+            return None;
+        }
+
+        let mut id = ROOT_NODE;
+        for name in names.iter().take(names.len() - reserve) {
+            id = self.child(id, name)?;
+        }
+        Some(id)
+    }
+
+    // Get child by name
+    pub fn child(&mut self, id: usize, name: &str) -> Option<usize> {
+        let node = self.nodes.get(id)?.as_ref()?;
+        node.children
+            .iter()
+            .copied()
+            .find(|child| matches!(&self.nodes[*child], Some(n) if n.name == name))
+    }
+
+    // Create node under the parent directory
+    pub fn create(&mut self, parent: usize, name: &str, typid: FileType) -> Option<usize> {
+        if name.is_empty() || self.child(parent, name).is_some() {
+            return None;
+        }
+        if self.node(parent)?.typid != FileType::Directory {
+            return None;
+        }
+
+        let node = RamNode::new(name, typid, parent);
+        if !self.reserve(node.used()) {
+            return None;
+        }
+
+        // Reuse the slot of removed node
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = Some(node);
+                id
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.gens.push(0);
+                self.nodes.len() - 1
+            }
+        };
+
+        let parent = self.node(parent)?;
+        parent.children.push(id);
+        parent.touch_write();
+        Some(id)
+    }
+
+    // Remove node, the directory must be empty
+    pub fn remove(&mut self, id: usize) -> bool {
+        if id == ROOT_NODE {
+            return false;
+        }
+
+        let (parent, used) = match self.node(id) {
+            Some(node) if node.children.is_empty() => (node.parent, node.used()),
+            _ => return false,
+        };
+
+        if let Some(parent) = self.node(parent) {
+            parent.children.retain(|child| *child != id);
+            parent.touch_write();
+        }
+
+        // The generation is changed so the stale handles are refused
+        self.nodes[id] = None;
+        self.gens[id] = self.gens[id].wrapping_add(1);
+        self.free.push(id);
+        self.used -= used;
+        true
+    }
+
+    // Move node into the parent directory with the new name
+    pub fn rename(&mut self, id: usize, parent: usize, name: &str) -> bool {
+        if id == ROOT_NODE || name.is_empty() || self.child(parent, name).is_some() {
+            return false;
+        }
+        if !matches!(self.node(parent), Some(node) if node.typid == FileType::Directory) {
+            return false;
+        }
+
+        // The parent must not be the node itself or under the node
+        let mut ancestor = parent;
+        loop {
+            if ancestor == id {
+                return false;
+            }
+            if ancestor == ROOT_NODE {
+                break;
+            }
+            ancestor = match self.node(ancestor) {
+                Some(node) => node.parent,
+                None => return false,
+            };
+        }
+
+        let old_len = match self.node(id) {
+            Some(node) => node.name.len(),
+            None => return false,
+        };
+        if name.len() > old_len && !self.reserve(name.len() - old_len) {
+            return false;
+        }
+        if name.len() < old_len {
+            self.used -= old_len - name.len();
+        }
+
+        let old_parent = self.nodes[id].as_ref().map(|n| n.parent).unwrap_or(ROOT_NODE);
+        if let Some(node) = self.node(old_parent) {
+            node.children.retain(|child| *child != id);
+        }
+        if let Some(node) = self.node(parent) {
+            node.children.push(id);
+        }
+        if let Some(node) = self.node(id) {
+            node.parent = parent;
+            node.name = name.to_string();
+        }
+        true
+    }
+
+    // Resize data of node
+    pub fn resize(&mut self, id: usize, size: usize) -> bool {
+        let old = match self.node(id) {
+            Some(node) => node.data.len(),
+            None => return false,
+        };
+
+        if size > old && !self.reserve(size - old) {
+            return false;
+        }
+        if size < old {
+            self.used -= old - size;
+        }
+
+        if let Some(node) = self.node(id) {
+            node.data.resize(size, 0);
+            node.touch_write();
+        }
+        true
+    }
+
+    // Reserve bytes in the limit
+    fn reserve(&mut self, bytes: usize) -> bool {
+        if self.used + bytes > self.limit {
+            return false;
+        }
+        self.used += bytes;
+        true
+    }
+}
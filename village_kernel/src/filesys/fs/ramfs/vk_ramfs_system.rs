@@ -0,0 +1,441 @@
+//###########################################################################
+// vk_ramfs_system.rs
+// The specific implementation of functions related to ramfs system
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::vk_ramfs_node::{RamTree, ROOT_NODE};
+use crate::traits::vk_filesys::{FileAttr, FileDir, FileMode, FileSys, FileType, FileVol};
+use crate::traits::vk_filesys::{FileCheck, FileFlags, FileStat, FileTime};
+use crate::traits::vk_linkedlist::LinkedList;
+use crate::register_filesys;
+use crate::village::kernel;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// Constant members
+const BLOCK_SIZE: usize = 512;
+const DEFAULT_BLOCKS: usize = 2048;
+const MIN_BLOCKS: usize = 8;
+
+// Struct RamFile
+struct RamFile {
+    file_id: usize,
+    node: usize,
+    generation: u32,
+}
+
+// Struct RamDir
+struct RamDir {
+    dir_id: usize,
+    sub_dirs: Vec<FileDir>,
+}
+
+// Struct RamVolume
+struct RamVolume {
+    tree: RamTree,
+    capacity: usize,
+    name: String,
+    mount_path: String,
+    dir_cnt: usize,
+    file_cnt: usize,
+    dirs: LinkedList<RamDir>,
+    files: LinkedList<RamFile>,
+}
+
+// Impl RamVolume
+impl RamVolume {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            tree: RamTree::new(),
+            capacity: DEFAULT_BLOCKS * BLOCK_SIZE,
+            name: String::new(),
+            mount_path: String::new(),
+            dir_cnt: 1,
+            file_cnt: 1,
+            dirs: LinkedList::new(),
+            files: LinkedList::new(),
+        }
+    }
+}
+
+// Impl RamVolume
+impl RamVolume {
+    // Assign file id
+    fn assign_file_id(&mut self) -> usize {
+        let id = self.file_cnt;
+        self.file_cnt += 1;
+        id
+    }
+
+    // Get the node of file, the removed node is not got by the opened file
+    fn file_node(&mut self, fd: usize) -> Option<usize> {
+        let file = self.files.iter_mut().find(|f| f.file_id == fd)?;
+        if !self.tree.is_valid(file.node, file.generation) {
+            return None;
+        }
+        Some(file.node)
+    }
+
+    // Assign dir id
+    fn assign_dir_id(&mut self) -> usize {
+        let id = self.dir_cnt;
+        self.dir_cnt += 1;
+        id
+    }
+
+    // Get Base name
+    fn base_name(&mut self, path: &str) -> String {
+        match path.rfind('/') {
+            Some(pos) => path[pos + 1..].to_string(),
+            None => path.to_string(),
+        }
+    }
+
+    // Search or create path
+    fn search_or_create(&mut self, path: &str, mode: FileMode, typid: FileType) -> Option<usize> {
+        if let Some(id) = self.tree.search(path, 0) {
+            return Some(id);
+        }
+
+        if mode.contains(FileMode::CREATE_NEW) {
+            let parent = self.tree.search(path, 1)?;
+            let name = self.base_name(path);
+            return self.tree.create(parent, &name, typid);
+        }
+
+        None
+    }
+
+    // Make dir entry
+    fn dir_entry(name: &str, typid: FileType, flags: FileFlags) -> FileDir {
+        let mut dir = FileDir::new();
+        dir.name = name.to_string();
+        dir.typid = typid;
+        dir.attr = if flags.contains(FileFlags::HIDDEN) {
+            FileAttr::Hidden
+        } else {
+            FileAttr::Visible
+        };
+        dir
+    }
+
+    // Search the node can be updated, the root has no entry
+    fn search_entry(&mut self, name: &str) -> Option<usize> {
+        match self.tree.search(name, 0)? {
+            ROOT_NODE => None,
+            id => Some(id),
+        }
+    }
+}
+
+// Impl RamVolume
+impl FileVol for RamVolume {
+    // Setup, the disk is the volume name
+    fn setup(&mut self, disk: &str) -> bool {
+        // Never claim a real disk
+        if disk.is_empty() || kernel().device().get_driver(disk).is_some() {
+            return false;
+        }
+
+        self.name = disk.to_string();
+        self.tree.setup(self.capacity);
+        true
+    }
+
+    // Exit
+    fn exit(&mut self) {
+        self.files.clear();
+        self.dirs.clear();
+        self.tree.exit();
+    }
+
+    // Set mount path
+    fn set_mount_path(&mut self, path: &str) {
+        self.mount_path = path.to_string();
+    }
+
+    // Get mount path
+    fn get_mount_path(&mut self) -> &str {
+        &self.mount_path
+    }
+
+    // Set name
+    fn set_name(&mut self, name: &str) -> bool {
+        self.name = name.to_string();
+        true
+    }
+
+    // Get name
+    fn get_name(&mut self) -> String {
+        self.name.clone()
+    }
+
+    // Get total size
+    fn get_total_size(&mut self) -> u64 {
+        self.tree.limit() as u64
+    }
+
+    // Get free size
+    fn get_free_size(&mut self) -> u64 {
+        (self.tree.limit() - self.tree.used()) as u64
+    }
+
+    // Set capacity, the size is rounded up to blocks
+    fn set_capacity(&mut self, size: u64) -> bool {
+        let blocks = size.div_ceil(BLOCK_SIZE as u64) as usize;
+        if blocks < MIN_BLOCKS {
+            return false;
+        }
+        self.capacity = blocks * BLOCK_SIZE;
+        true
+    }
+
+    // Open
+    fn open(&mut self, name: &str, mode: FileMode) -> usize {
+        if let Some(node) = self.search_or_create(name, mode, FileType::File) {
+            if let Some(ramnode) = self.tree.node(node) {
+                ramnode.touch_access();
+            }
+
+            let fd = self.assign_file_id();
+            self.files.push(RamFile {
+                file_id: fd,
+                node,
+                generation: self.tree.generation(node),
+            });
+
+            // Truncate the file to zero length
+            if mode.contains(FileMode::TRUNCATE) && !self.set_len(fd, 0) {
+                self.close(fd);
+                return 0;
+            }
+            return fd;
+        }
+        0
+    }
+
+    // Write
+    fn write(&mut self, fd: usize, data: &[u8], size: usize, offset: usize) -> usize {
+        let id = match self.file_node(fd) {
+            Some(id) => id,
+            None => return 0,
+        };
+
+        let old_size = match self.tree.node(id) {
+            Some(node) => node.data.len(),
+            None => return 0,
+        };
+
+        // Write as much as the free space allows
+        let free = self.tree.limit() - self.tree.used() + old_size;
+        let size = size.min(data.len()).min(free.saturating_sub(offset));
+
+        // The file is extended when the data is written after the end
+        if !self.tree.resize(id, old_size.max(offset + size)) {
+            return 0;
+        }
+
+        if let Some(node) = self.tree.node(id) {
+            node.data[offset..offset + size].copy_from_slice(&data[..size]);
+        }
+
+        size
+    }
+
+    // Read
+    fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize {
+        if let Some(id) = self.file_node(fd) {
+            if let Some(node) = self.tree.node(id) {
+                if offset >= node.data.len() {
+                    return 0;
+                }
+                let size = size.min(data.len()).min(node.data.len() - offset);
+                data[..size].copy_from_slice(&node.data[offset..offset + size]);
+                return size;
+            }
+        }
+        0
+    }
+
+    // Size
+    fn size(&mut self, fd: usize) -> usize {
+        if let Some(id) = self.file_node(fd) {
+            if let Some(node) = self.tree.node(id) {
+                return node.data.len();
+            }
+        }
+        0
+    }
+
+    // Set len, the extended data is filled with zero
+    fn set_len(&mut self, fd: usize, size: usize) -> bool {
+        if let Some(id) = self.file_node(fd) {
+            return self.tree.resize(id, size);
+        }
+        false
+    }
+
+    // Flush
+    fn flush(&mut self, _fd: usize) {}
+
+    // Close
+    fn close(&mut self, fd: usize) {
+        self.files.retain_mut(|file| file.file_id != fd);
+    }
+
+    // Open dir
+    fn opendir(&mut self, name: &str, mode: FileMode) -> usize {
+        let id = match self.search_or_create(name, mode, FileType::Directory) {
+            Some(id) => id,
+            None => return 0,
+        };
+
+        // Snapshot the entries, the sub directory has the dot entries as fat
+        let mut sub_dirs = Vec::new();
+        let children = match self.tree.node(id) {
+            Some(node) if node.typid == FileType::Directory => node.children.clone(),
+            Some(_) => Vec::new(),
+            None => return 0,
+        };
+
+        if id != ROOT_NODE {
+            sub_dirs.push(Self::dir_entry(".", FileType::Directory, FileFlags::NONE));
+            sub_dirs.push(Self::dir_entry("..", FileType::Directory, FileFlags::NONE));
+        }
+
+        for child in children {
+            if let Some(node) = self.tree.node(child) {
+                sub_dirs.push(Self::dir_entry(&node.name, node.typid.clone(), node.flags));
+            }
+        }
+
+        let fd = self.assign_dir_id();
+        self.dirs.push(RamDir { dir_id: fd, sub_dirs });
+        fd
+    }
+
+    // Read dir
+    fn readdir(&mut self, fd: usize, dirs: &mut [FileDir], size: usize, offset: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            let remain = dir.sub_dirs.len().saturating_sub(offset);
+            let size = size.min(dirs.len()).min(remain);
+            dirs[..size].clone_from_slice(&dir.sub_dirs[offset..offset + size]);
+            return size;
+        }
+        0
+    }
+
+    // Size dir
+    fn sizedir(&mut self, fd: usize) -> usize {
+        if let Some(dir) = self.dirs.iter_mut().find(|d| d.dir_id == fd) {
+            return dir.sub_dirs.len();
+        }
+        0
+    }
+
+    // Close dir
+    fn closedir(&mut self, fd: usize) {
+        self.dirs.retain_mut(|dir| dir.dir_id != fd);
+    }
+
+    // Stat
+    fn stat(&mut self, name: &str) -> Option<FileStat> {
+        let id = self.tree.search(name, 0)?;
+        Some(self.tree.node(id)?.stat())
+    }
+
+    // Fstat
+    fn fstat(&mut self, fd: usize) -> Option<FileStat> {
+        let id = self.file_node(fd)?;
+        Some(self.tree.node(id)?.stat())
+    }
+
+    // Set flags
+    fn set_flags(&mut self, name: &str, flags: FileFlags) -> bool {
+        if let Some(id) = self.search_entry(name) {
+            if let Some(node) = self.tree.node(id) {
+                node.flags = flags;
+                return true;
+            }
+        }
+        false
+    }
+
+    // Set time
+    fn set_time(&mut self, name: &str, write_time: FileTime, access_time: FileTime) -> bool {
+        if let Some(id) = self.search_entry(name) {
+            if let Some(node) = self.tree.node(id) {
+                if write_time.is_valid() {
+                    node.write_time = write_time;
+                }
+                if access_time.is_valid() {
+                    node.access_time = access_time;
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    // Is exist
+    fn exist(&mut self, name: &str, typeid: FileType) -> bool {
+        if let Some(id) = self.tree.search(name, 0) {
+            if let Some(node) = self.tree.node(id) {
+                return node.typid == typeid;
+            }
+        }
+        false
+    }
+
+    // Remove, the directory must be empty
+    fn remove(&mut self, name: &str) -> bool {
+        match self.search_entry(name) {
+            Some(id) => self.tree.remove(id),
+            None => false,
+        }
+    }
+
+    // Rename
+    fn rename(&mut self, source: &str, target: &str) -> bool {
+        // The target must not exist
+        if self.tree.search(target, 0).is_some() {
+            return false;
+        }
+
+        if let Some(id) = self.search_entry(source) {
+            if let Some(parent) = self.tree.search(target, 1) {
+                let name = self.base_name(target);
+                return self.tree.rename(id, parent, &name);
+            }
+        }
+        false
+    }
+
+    // Check, the consistency check is not supported
+    fn check(&mut self, _repair: bool) -> Option<FileCheck> {
+        None
+    }
+}
+
+// Struct ramfs system
+struct RamfsSystem;
+
+// Impl filesys for ramfs system
+impl FileSys for RamfsSystem {
+    // Get file system id
+    fn file_system_id(&self) -> usize {
+        const SYSTEM_ID: usize = 0x101;
+        SYSTEM_ID
+    }
+
+    // Create volume
+    fn create_volume(&mut self) -> Box<dyn FileVol> {
+        Box::new(RamVolume::new())
+    }
+}
+
+// Register filesys
+register_filesys!(RamfsSystem, ramfs);
@@ -0,0 +1,437 @@
+//###########################################################################
+// vk_blkcache.rs
+// The specific implementation of functions related to block cache
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::misc::fopts::vk_dev_fopt::DevFopt;
+use crate::misc::lock::vk_mutex::Mutex;
+use crate::traits::vk_callback::Callback;
+use crate::traits::vk_driver::{BLKCommand, Command};
+use crate::traits::vk_filesys::BlockCacheInfo;
+use crate::village::kernel;
+use crate::debug_error;
+use crate::debug_info;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Constant members
+const DEFAULT_SECTOR_SIZE: usize = 512;
+const CACHE_CAPACITY: usize = 256;
+const FLUSH_INTERVAL: u32 = 3000;
+
+// Struct cache block
+struct CacheBlock {
+    data: Vec<u8>,
+    dirty: bool,
+    stamp: u64,
+}
+
+// Impl cache block
+impl CacheBlock {
+    // New
+    pub fn new(size: usize, stamp: u64) -> Self {
+        Self {
+            data: vec![0u8; size],
+            dirty: false,
+            stamp,
+        }
+    }
+}
+
+// Struct cache disk, the blocks of the disk and its partitions are indexed by absolute lba
+struct CacheDisk {
+    device: DevFopt,
+    sector_size: usize,
+    blocks: BTreeMap<usize, CacheBlock>,
+}
+
+// Struct cache route, the partition is resolved to the disk where it is
+struct CacheRoute {
+    disk: String,
+    offset: usize,
+    sectors: usize,
+}
+
+// Struct village block cache, the lru is indexed by the stamp of blocks
+pub struct VillageBlockCache {
+    disks: BTreeMap<String, CacheDisk>,
+    lru: BTreeMap<u64, (String, usize)>,
+    info: BlockCacheInfo,
+    stamp: u64,
+    mutex: Mutex,
+}
+
+// Impl village block cache
+impl VillageBlockCache {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            disks: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            info: BlockCacheInfo::new(),
+            stamp: 0,
+            mutex: Mutex::new(),
+        }
+    }
+}
+
+// Impl village block cache
+impl VillageBlockCache {
+    // Setup
+    pub fn setup(&mut self) {
+        // Reset info
+        self.info = BlockCacheInfo::new();
+        self.info.capacity = CACHE_CAPACITY;
+
+        // Create the flusher task
+        let flusher_cb = Callback::new(Self::flusher as u32).with_instance(self);
+        kernel().thread().create_task("BlockCache::flusher", flusher_cb);
+
+        // Output debug info
+        debug_info!("Block cache setup completed, {} blocks", CACHE_CAPACITY);
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        // Write back all dirty blocks
+        self.sync();
+
+        // Close devices
+        for disk in self.disks.values_mut() {
+            disk.device.close();
+        }
+
+        // Clear disks
+        self.disks.clear();
+        self.lru.clear();
+    }
+
+    // Flusher
+    fn flusher(&mut self) {
+        loop {
+            kernel().thread().sleep(FLUSH_INTERVAL);
+            self.sync();
+        }
+    }
+}
+
+// Impl village block cache
+impl VillageBlockCache {
+    // Resolve the partition to the disk and the starting lba, the sectors bound the partition
+    fn resolve(disk: &str) -> CacheRoute {
+        let mut route = CacheRoute {
+            disk: disk.to_string(),
+            offset: 0,
+            sectors: usize::MAX,
+        };
+
+        let mut command = Command::BLK(BLKCommand::SectorCount { count: 0 });
+        if let Some(driver) = kernel().device().get_driver(disk) {
+            if driver.ioctrl(&mut command) {
+                if let Command::BLK(BLKCommand::SectorCount { count }) = command {
+                    route.sectors = count;
+                }
+            }
+        }
+
+        loop {
+            let mut command = Command::BLK(BLKCommand::Parent { name: String::new(), offset: 0 });
+            let parent = match kernel().device().get_driver(&route.disk) {
+                Some(driver) => driver.ioctrl(&mut command),
+                None => false,
+            };
+            if !parent {
+                break;
+            }
+            if let Command::BLK(BLKCommand::Parent { name, offset }) = command {
+                route.disk = name;
+                route.offset += offset;
+            }
+        }
+
+        route
+    }
+
+    // Get disk, the device is opened when it is used for the first time
+    fn get_disk(&mut self, disk: &str) -> Option<&mut CacheDisk> {
+        if !self.disks.contains_key(disk) {
+            let mut device = DevFopt::new();
+            if !device.open(disk) {
+                debug_error!("Block cache open device {} failed", disk);
+                return None;
+            }
+
+            // Get the sector size of device
+            let mut sector_size = DEFAULT_SECTOR_SIZE;
+            let mut command = Command::BLK(BLKCommand::SectorSize { size: 0 });
+            if device.ioctrl(&mut command) {
+                if let Command::BLK(BLKCommand::SectorSize { size }) = command {
+                    if size != 0 {
+                        sector_size = size;
+                    }
+                }
+            }
+
+            let cache_disk = CacheDisk {
+                device,
+                sector_size,
+                blocks: BTreeMap::new(),
+            };
+            self.disks.insert(disk.to_string(), cache_disk);
+        }
+        self.disks.get_mut(disk)
+    }
+
+    // Write back block
+    fn write_back(&mut self, disk: &str, lba: usize, data: &[u8]) -> bool {
+        let result = match self.disks.get_mut(disk) {
+            Some(cache_disk) => cache_disk.device.write(data, 1, lba) == 1,
+            None => false,
+        };
+
+        if result {
+            self.info.writebacks += 1;
+        }
+        result
+    }
+
+    // Lookup block, the block is moved to the end of lru
+    fn lookup(&mut self, disk: &str, lba: usize) -> Option<&mut CacheBlock> {
+        self.stamp += 1;
+        let stamp = self.stamp;
+
+        let block = self.disks.get_mut(disk)?.blocks.get_mut(&lba)?;
+        let entry = self.lru.remove(&block.stamp)?;
+        self.lru.insert(stamp, entry);
+        block.stamp = stamp;
+        Some(block)
+    }
+
+    // Get block
+    fn block(&mut self, disk: &str, lba: usize) -> Option<&mut CacheBlock> {
+        self.disks.get_mut(disk)?.blocks.get_mut(&lba)
+    }
+
+    // Evict the least recently used block, the dirty block is written back first
+    fn evict(&mut self) -> bool {
+        let (stamp, (disk, lba)) = match self.lru.pop_first() {
+            Some(victim) => victim,
+            None => return false,
+        };
+
+        // Take the victim block
+        let block = match self.disks.get_mut(&disk).and_then(|d| d.blocks.remove(&lba)) {
+            Some(block) => block,
+            None => return false,
+        };
+
+        // The victim block is kept when it can not be written back
+        if block.dirty && !self.write_back(&disk, lba, &block.data) {
+            if let Some(cache_disk) = self.disks.get_mut(&disk) {
+                cache_disk.blocks.insert(lba, block);
+                self.lru.insert(stamp, (disk, lba));
+            }
+            return false;
+        }
+
+        true
+    }
+
+    // Alloc block, the least recently used block is evicted when the cache is full
+    fn alloc(&mut self, disk: &str, lba: usize) -> Option<&mut CacheBlock> {
+        if self.lru.len() >= CACHE_CAPACITY && !self.evict() {
+            return None;
+        }
+
+        self.stamp += 1;
+        let stamp = self.stamp;
+        let cache_disk = self.disks.get_mut(disk)?;
+        let block = CacheBlock::new(cache_disk.sector_size, stamp);
+        self.lru.insert(stamp, (disk.to_string(), lba));
+        Some(cache_disk.blocks.entry(lba).or_insert(block))
+    }
+
+    // Update info
+    fn update_info(&mut self) {
+        self.info.used = self.lru.len();
+        self.info.dirty = self
+            .disks
+            .values()
+            .map(|cache_disk| cache_disk.blocks.values().filter(|block| block.dirty).count())
+            .sum();
+    }
+}
+
+// Impl village block cache
+impl VillageBlockCache {
+    // Read blocks
+    pub fn read(&mut self, disk: &str, data: &mut [u8], count: usize, lba: usize) -> usize {
+        self.mutex.lock();
+
+        let route = Self::resolve(disk);
+        let size = match self.get_disk(&route.disk) {
+            Some(cache_disk) => cache_disk.sector_size,
+            None => {
+                self.mutex.unlock();
+                return 0;
+            }
+        };
+
+        let count = count.min(data.len() / size).min(route.sectors.saturating_sub(lba));
+        let mut result = 0;
+
+        for i in 0..count {
+            let buff = &mut data[i * size..(i + 1) * size];
+            let abs_lba = route.offset + lba + i;
+
+            if let Some(block) = self.lookup(&route.disk, abs_lba) {
+                buff.copy_from_slice(&block.data);
+                self.info.hits += 1;
+            } else {
+                self.info.misses += 1;
+
+                // Read the block from device
+                let readed = match self.disks.get_mut(&route.disk) {
+                    Some(cache_disk) => cache_disk.device.read(buff, 1, abs_lba),
+                    None => 0,
+                };
+                if readed != 1 {
+                    break;
+                }
+
+                // Fill the cache
+                if let Some(block) = self.alloc(&route.disk, abs_lba) {
+                    block.data.copy_from_slice(buff);
+                }
+            }
+
+            result += 1;
+        }
+
+        self.mutex.unlock();
+        result
+    }
+
+    // Write blocks, the blocks are written back by the flusher or sync
+    pub fn write(&mut self, disk: &str, data: &[u8], count: usize, lba: usize) -> usize {
+        self.mutex.lock();
+
+        let route = Self::resolve(disk);
+        let size = match self.get_disk(&route.disk) {
+            Some(cache_disk) => cache_disk.sector_size,
+            None => {
+                self.mutex.unlock();
+                return 0;
+            }
+        };
+
+        let count = count.min(data.len() / size).min(route.sectors.saturating_sub(lba));
+        let mut result = 0;
+
+        for i in 0..count {
+            let buff = &data[i * size..(i + 1) * size];
+            let abs_lba = route.offset + lba + i;
+
+            if self.lookup(&route.disk, abs_lba).is_some() {
+                self.info.hits += 1;
+            } else {
+                self.info.misses += 1;
+                self.alloc(&route.disk, abs_lba);
+            }
+
+            if let Some(block) = self.block(&route.disk, abs_lba) {
+                block.data.copy_from_slice(buff);
+                block.dirty = true;
+            } else {
+                // Write through when there is no block can be used
+                let written = match self.disks.get_mut(&route.disk) {
+                    Some(cache_disk) => cache_disk.device.write(buff, 1, abs_lba),
+                    None => 0,
+                };
+                if written != 1 {
+                    break;
+                }
+            }
+
+            result += 1;
+        }
+
+        self.mutex.unlock();
+        result
+    }
+
+    // Sync the dirty blocks of disk, the blocks of partition are in its disk
+    pub fn sync_disk(&mut self, disk: &str) -> bool {
+        let route = Self::resolve(disk);
+        self.mutex.lock();
+        let result = self.flush(Some(&route.disk));
+        self.mutex.unlock();
+        result
+    }
+
+    // Sync all dirty blocks
+    pub fn sync(&mut self) -> bool {
+        self.mutex.lock();
+        let result = self.flush(None);
+        self.mutex.unlock();
+        result
+    }
+
+    // Flush the dirty blocks, the blocks of each disk are written in lba order
+    fn flush(&mut self, disk: Option<&str>) -> bool {
+        let mut dirties = Vec::new();
+        for (name, cache_disk) in self.disks.iter() {
+            if disk.is_some_and(|disk| disk != name) {
+                continue;
+            }
+            for (lba, block) in cache_disk.blocks.iter() {
+                if block.dirty {
+                    dirties.push((name.clone(), *lba, block.data.clone()));
+                }
+            }
+        }
+
+        let mut result = true;
+
+        for (name, lba, data) in dirties.iter() {
+            if self.write_back(name, *lba, data) {
+                if let Some(block) = self.block(name, *lba) {
+                    block.dirty = false;
+                }
+            } else {
+                debug_error!("Block cache write back {} lba {} failed", name, lba);
+                result = false;
+            }
+        }
+
+        result
+    }
+
+    // Release disk, the dirty blocks are written back and the cached blocks are dropped,
+    // the blocks of partition are dropped with its disk
+    pub fn release(&mut self, disk: &str) {
+        let route = Self::resolve(disk);
+        self.sync_disk(disk);
+
+        self.mutex.lock();
+
+        if let Some(mut cache_disk) = self.disks.remove(&route.disk) {
+            self.lru.retain(|_, (disk, _)| *disk != route.disk);
+            cache_disk.device.close();
+        }
+
+        self.mutex.unlock();
+    }
+
+    // Get info
+    pub fn get_info(&mut self) -> BlockCacheInfo {
+        self.mutex.lock();
+        self.update_info();
+        let info = self.info.clone();
+        self.mutex.unlock();
+        info
+    }
+}
@@ -5,347 +5,26 @@
 // $Copyright: Copyright (C) village
 //###########################################################################
 use alloc::format;
-use alloc::vec;
-//use alloc::vec::Vec;
+use alloc::vec::Vec;
 use crate::debug_error;
 use crate::debug_info;
-use crate::misc::fopts::vk_dev_fopt::DevFopt;
-use crate::traits::vk_driver::DriverID;
-use crate::traits::vk_filesys::{FileSysWrapper, FileVol};
+use super::vk_blkcache::VillageBlockCache;
+use super::vk_partition::{PartitionDisk, PartitionTable, PartitionType};
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use crate::traits::vk_driver::{BLKCommand, Command, DriverID, DriverWrapper};
+use crate::traits::vk_filesys::{BlockCacheInfo, FileSysWrapper, FileType, FileVol};
+use crate::traits::vk_filesys::{FileMode, MountFlags, MountNode};
 use crate::traits::vk_kernel::FileSystem;
 use crate::traits::vk_linkedlist::LinkedList;
 use crate::village::kernel;
 use alloc::boxed::Box;
 use alloc::string::{String, ToString};
 
-// Enum BootIndicator
-pub enum BootIndicator {
-    NotBootable = 0x00,
-    Bootable = 0x80,
-}
-
-// Enum PartitionType
-enum PartitionType {
-    None = 0,
-    MBR,
-    GPT,
-}
-
-// Struct MBR partition record
-struct MBRPartition {
-    pub boot_indicator: u8,
-    pub starting_head: u8,
-    pub starting_chs: u16,
-    pub os_indicator: u8,
-    pub ending_head: u8,
-    pub ending_chs: u16,
-    pub starting_lba: u32,
-    pub size_in_lba: u32,
-}
-
-impl MBRPartition {
-    // New
-    const fn new() -> Self {
-        Self {
-            boot_indicator: 0,
-            starting_head: 0,
-            starting_chs: 0,
-            os_indicator: 0,
-            ending_head: 0,
-            ending_chs: 0,
-            starting_lba: 0,
-            size_in_lba: 0,
-        }
-    }
-
-    // From
-    const fn from(data: &[u8]) -> Self {
-        if data.len() < 16 {
-            return Self::new();
-        }
-
-        let boot_indicator = data[0];
-        let starting_head = data[1];
-        let starting_chs = u16::from_le_bytes([data[2], data[3]]);
-        let os_indicator = data[4];
-        let ending_head = data[5];
-        let ending_chs = u16::from_le_bytes([data[6], data[7]]);
-        let starting_lba = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
-        let size_in_lba = u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
-
-        Self {
-            boot_indicator,
-            starting_head,
-            starting_chs,
-            os_indicator,
-            ending_head,
-            ending_chs,
-            starting_lba,
-            size_in_lba,
-        }
-    }
-
-    // Check is vaild
-    pub fn is_vaild(&mut self) -> bool {
-        if self.os_indicator != 0 && self.size_in_lba != 0 {
-            return self.boot_indicator != 0
-                || self.starting_head != 0
-                || self.starting_chs != 0
-                || self.ending_head != 0
-                || self.ending_chs != 0
-                || self.starting_lba != 0;
-        }
-        false
-    }
-}
-
-// Struct MBR partition table
-struct MBR {
-    //pub boot: Vec<u8>,
-    pub partitions: [MBRPartition; 4],
-    pub magic: u16,
-}
-
-impl MBR {
-    // New
-    pub const fn new() -> Self {
-        Self {
-            //boot: Vec::new(),
-            partitions: [const { MBRPartition::new() }; 4],
-            magic: 0,
-        }
-    }
-
-    // From
-    pub fn from(data: &[u8]) -> Option<Self> {
-        if data.len() < 512 {
-            return None;
-        }
-
-        let mut mbr = Self::new();
-
-        // Copy boot code
-        //mbr.boot = vec![0u8; 446];
-        //mbr.boot.copy_from_slice(&data[0..446]);
-
-        // Parser partitions
-        mbr.partitions[0] = MBRPartition::from(&data[446..462]);
-        mbr.partitions[1] = MBRPartition::from(&data[462..478]);
-        mbr.partitions[2] = MBRPartition::from(&data[478..494]);
-        mbr.partitions[3] = MBRPartition::from(&data[494..510]);
-
-        // Get magic and check
-        mbr.magic = u16::from_le_bytes([data[510], data[511]]);
-
-        if mbr.magic != 0xAA55 {
-            return None;
-        }
-
-        Some(mbr)
-    }
-}
-
-// Struct GPT partition record
-struct GPTPartition {
-    pub partition_type_guid: [u8; 16],
-    pub unique_partition_guid: [u8; 16],
-    pub starting_lba: u64,
-    pub ending_lba: u64,
-    pub attributes: u64,
-    pub partition_name: [u16; 36],
-    //pub reserved: Vec<u8>,
-}
-
-// Impl GPTPartition
-impl GPTPartition {
-    // New
-    pub const fn new() -> Self {
-        Self {
-            partition_type_guid: [0u8; 16],
-            unique_partition_guid: [0u8; 16],
-            starting_lba: 0,
-            ending_lba: 0,
-            attributes: 0,
-            partition_name: [0u16; 36],
-            //reserved: Vec::new(),
-        }
-    }
-
-    // From
-    pub fn from(data: &[u8]) -> Option<Self> {
-        if data.len() < 512 {
-            return None;
-        }
-
-        let mut partition = Self::new();
-
-        // Parser guid
-        partition.partition_type_guid.copy_from_slice(&data[0..16]);
-        partition
-            .unique_partition_guid
-            .copy_from_slice(&data[16..32]);
-
-        // Parser lba
-        partition.starting_lba = u64::from_le_bytes(<[u8; 8]>::try_from(&data[32..40]).ok()?);
-        partition.ending_lba = u64::from_le_bytes(<[u8; 8]>::try_from(&data[40..48]).ok()?);
-        partition.attributes = u64::from_le_bytes(<[u8; 8]>::try_from(&data[48..56]).ok()?);
-
-        // Parser name
-        for i in 0..36 {
-            let idx = 56 + i * 2;
-            if idx + 1 >= data.len() {
-                break;
-            }
-            partition.partition_name[i] = u16::from_le_bytes([data[idx], data[idx + 1]]);
-        }
-
-        // Reserved
-        //partition.reserved = vec![0u8; 384];
-        //partition.reserved.copy_from_slice(&data[128..512]);
-
-        // Check
-        if Self::is_empty(&partition) {
-            return None;
-        }
-
-        Some(partition)
-    }
-
-    // Check is empty
-    pub fn is_empty(&self) -> bool {
-        self.partition_type_guid == [0u8; 16]
-    }
-}
-
-// Struct GPT partition record
-struct GPT {
-    pub signature: [u8; 8],
-    pub revision: u32,
-    pub header_size: u32,
-    pub header_crc32: u32,
-    pub reserved0: u32,
-    pub my_lba: u64,
-    pub alternate_lba: u64,
-    pub first_usable_lba: u64,
-    pub last_usable_lba: u64,
-    pub disk_guid: [u8; 16],
-    pub partition_entry_lba: u64,
-    pub number_of_partition_entries: u32,
-    pub size_of_partition_entry: u32,
-    pub partition_entry_array_crc32: u32,
-    //pub reserved1: Vec<u8>,
-}
-
-// Impl GPT
-impl GPT {
-    // New
-    pub const fn new() -> Self {
-        Self {
-            signature: [0u8; 8],
-            revision: 0,
-            header_size: 0,
-            header_crc32: 0,
-            reserved0: 0,
-            my_lba: 0,
-            alternate_lba: 0,
-            first_usable_lba: 0,
-            last_usable_lba: 0,
-            disk_guid: [0u8; 16],
-            partition_entry_lba: 0,
-            number_of_partition_entries: 0,
-            size_of_partition_entry: 0,
-            partition_entry_array_crc32: 0,
-            //reserved1: Vec::new(),
-        }
-    }
-
-    // From
-    pub fn from(data: &[u8]) -> Option<Self> {
-        if data.len() < 512 {
-            return None;
-        }
-
-        let mut gpt = Self::new();
-
-        // Get signature
-        gpt.signature.copy_from_slice(&data[0..8]);
-
-        // Copy header
-        gpt.revision = u32::from_le_bytes(<[u8; 4]>::try_from(&data[8..12]).ok()?);
-        gpt.header_size = u32::from_le_bytes(<[u8; 4]>::try_from(&data[12..16]).ok()?);
-        gpt.header_crc32 = u32::from_le_bytes(<[u8; 4]>::try_from(&data[16..20]).ok()?);
-        gpt.reserved0 = u32::from_le_bytes(<[u8; 4]>::try_from(&data[20..24]).ok()?);
-
-        // Copy lba
-        gpt.my_lba = u64::from_le_bytes(<[u8; 8]>::try_from(&data[24..32]).ok()?);
-        gpt.alternate_lba = u64::from_le_bytes(<[u8; 8]>::try_from(&data[32..40]).ok()?);
-        gpt.first_usable_lba = u64::from_le_bytes(<[u8; 8]>::try_from(&data[40..48]).ok()?);
-        gpt.last_usable_lba = u64::from_le_bytes(<[u8; 8]>::try_from(&data[48..56]).ok()?);
-
-        // Copy guid
-        gpt.disk_guid.copy_from_slice(&data[56..72]);
-
-        // Copy entry
-        gpt.partition_entry_lba = u64::from_le_bytes(<[u8; 8]>::try_from(&data[72..80]).ok()?);
-        gpt.number_of_partition_entries =
-            u32::from_le_bytes(<[u8; 4]>::try_from(&data[80..84]).ok()?);
-        gpt.size_of_partition_entry = u32::from_le_bytes(<[u8; 4]>::try_from(&data[84..88]).ok()?);
-        gpt.partition_entry_array_crc32 =
-            u32::from_le_bytes(<[u8; 4]>::try_from(&data[88..92]).ok()?);
-
-        // Copy reserved
-        //gpt.reserved1 = vec![0u8; 420];
-        //gpt.reserved1.copy_from_slice(&data[92..512]);
-
-        // Check is valid
-        if !Self::is_valid(&gpt) {
-            return None;
-        }
-
-        Some(gpt)
-    }
-
-    // Check is valid
-    pub fn is_valid(&self) -> bool {
-        &self.signature == b"EFI PART"
-            && self.header_size >= 92
-            && self.size_of_partition_entry >= 128
-            && self.number_of_partition_entries > 0
-    }
-}
-
-// Struct MountNode
-pub struct MountNode {
-    pub target: String,
-    pub source: String,
-    pub access: u16,
-}
-
-// Impl MountNode
-impl MountNode {
-    // New
-    pub const fn new() -> Self {
-        Self {
-            target: String::new(),
-            source: String::new(),
-            access: 0755,
-        }
-    }
-
-    // From
-    pub fn from(target: &str, source: &str, access: u16) -> Self {
-        Self {
-            target: target.to_string(),
-            source: source.to_string(),
-            access,
-        }
-    }
-}
-
 // Struct DiskMedia
 struct DiskMedia {
     typ: PartitionType,
     name: String,
+    parts: Vec<String>,
     vols: LinkedList<Box<dyn FileVol>>,
 }
 
@@ -355,6 +34,7 @@ impl DiskMedia {
         Self {
             typ: PartitionType::None,
             name: String::new(),
+            parts: Vec::new(),
             vols: LinkedList::new(),
         }
     }
@@ -375,6 +55,7 @@ pub struct VillageFileSystem {
     filesyses: LinkedList<FileSysWrapper>,
     medias: LinkedList<DiskMedia>,
     mounts: LinkedList<MountNode>,
+    cache: VillageBlockCache,
 }
 
 // Impl village file system
@@ -385,6 +66,7 @@ impl VillageFileSystem {
             filesyses: LinkedList::new(),
             medias: LinkedList::new(),
             mounts: LinkedList::new(),
+            cache: VillageBlockCache::new(),
         }
     }
 }
@@ -393,18 +75,45 @@ impl VillageFileSystem {
 impl VillageFileSystem {
     // Setup
     pub fn setup(&mut self) {
-        // Initialize all hard disk
+        // Setup block cache
+        self.cache.setup();
+
+        // Create an disks list, the drivers of partitions are registered when mounting
+        let mut disks = LinkedList::<String>::new();
+
+        // Clone all the block driver name
         for driver in kernel().device().get_drivers().iter_mut() {
             if driver.id() == DriverID::Block {
-                self.mount_hard_drive(driver.name());
+                disks.push(driver.name().to_string());
             }
         }
 
+        // Initialize all hard disk
+        for disk in disks.iter_mut() {
+            self.mount_hard_drive(disk);
+        }
+
         // Mount root node
         if self.mount_root_node() {
             debug_info!("File system setup completed!");
         } else {
             debug_error!("File system setup failed!");
+            return;
+        }
+
+        // Mount device node
+        if !self.mount_virtual_node("devfs", "DEV", "/dev") {
+            debug_error!("Mount device node failed");
+        }
+
+        // Mount process node
+        if !self.mount_virtual_node("procfs", "PROC", "/proc") {
+            debug_error!("Mount process node failed");
+        }
+
+        // Mount temporary node
+        if !self.mount_virtual_node("ramfs", "TMP", "/tmp") {
+            debug_error!("Mount temporary node failed");
         }
     }
 
@@ -431,6 +140,9 @@ impl VillageFileSystem {
 
         // Clear mount node
         self.mounts.clear();
+
+        // Exit block cache
+        self.cache.exit();
     }
 }
 
@@ -438,40 +150,183 @@ impl VillageFileSystem {
 impl VillageFileSystem {
     // Mount root node
     fn mount_root_node(&mut self) -> bool {
-        // Create root mount node
-        let root = MountNode::from("/", "/media/VILLAGE OS", 0755);
-
         // Try to mount root node
-        if self.mount_system_node(root) {
+        if self.mount("/media/VILLAGE OS", "/", MountFlags::NONE) {
             return true;
         }
 
         // Output info
         debug_error!("Mount root node failed, 'VILLAGE OS' not found");
+
+        // Use the ramfs as root node when no disk is present
+        if self.attach_volume("ramfs", "RAMROOT", 0) && self.mount("RAMROOT", "/", MountFlags::NONE) {
+            debug_info!("Mount ramfs as root node");
+            return true;
+        }
         false
     }
 
-    // Mount system node
-    fn mount_system_node(&mut self, mount: MountNode) -> bool {
-        for media in self.medias.iter_mut() {
-            for volume in media.vols.iter_mut() {
-                if volume.get_mount_path() == mount.source {
-                    self.mounts.push(mount);
-                    return true;
+    // Mount virtual node, such as ramfs and devfs
+    fn mount_virtual_node(&mut self, fs: &str, name: &str, target: &str) -> bool {
+        if !self.attach_volume(fs, name, 0) {
+            return false;
+        }
+
+        // Create the mount point when it is not existed
+        match self.resolve_volume(target) {
+            Some((volume, path)) => {
+                if !volume.exist(&path, FileType::Directory) {
+                    let fd = volume.opendir(&path, FileMode::CREATE_NEW);
+                    if fd == 0 {
+                        return false;
+                    }
+                    volume.closedir(fd);
+                }
+            }
+            None => return false,
+        }
+
+        self.mount(name, target, MountFlags::NONE)
+    }
+
+    // Normalize path, resolve the '.', '..' and duplicate slashes
+    fn normalize(path: &str) -> String {
+        let mut names: Vec<&str> = Vec::new();
+
+        for name in path.split('/') {
+            match name {
+                "" | "." => {}
+                ".." => {
+                    names.pop();
+                }
+                _ => names.push(name),
+            }
+        }
+
+        format!("/{}", names.join("/"))
+    }
+
+    // Is direct, the device backed by other volume is not cached, the cache is not reentrant
+    fn is_direct(disk: &str) -> bool {
+        let mut command = Command::BLK(BLKCommand::Direct { direct: false });
+        if let Some(driver) = kernel().device().get_driver(disk) {
+            if driver.ioctrl(&mut command) {
+                if let Command::BLK(BLKCommand::Direct { direct }) = command {
+                    return direct;
                 }
             }
         }
         false
     }
 
-    // Setup volume
-    fn setup_volume(&mut self, media: &mut DiskMedia, starting_lba: u32) {
+    // Is read only disk, the volumes on the device can not be written
+    fn is_read_only_disk(disk: &str) -> bool {
+        let mut device = BlockFopt::new();
+        device.open(disk) && device.is_read_only()
+    }
+
+    // Get the backing files of block devices, such as the image of loop device
+    fn backing_paths() -> Vec<String> {
+        let mut paths = Vec::new();
+        for driver in kernel().device().get_drivers().iter_mut() {
+            if driver.id() != DriverID::Block {
+                continue;
+            }
+            let mut command = Command::BLK(BLKCommand::Backing { path: String::new() });
+            if driver.ioctrl(&mut command) {
+                if let Command::BLK(BLKCommand::Backing { path }) = command {
+                    paths.push(path);
+                }
+            }
+        }
+        paths
+    }
+
+    // Is the path under the mount target
+    fn is_under(path: &str, target: &str) -> bool {
+        if target == "/" || path == target {
+            return true;
+        }
+        path.starts_with(target) && path.as_bytes().get(target.len()) == Some(&b'/')
+    }
+
+    // Match mount by the longest target, returns the source, the path in volume and the flags
+    fn match_mount(&mut self, path: &str) -> Option<(String, String, MountFlags)> {
+        let path = Self::normalize(path);
+        let mut result = None;
+        let mut length = 0;
+
+        for mount in self.mounts.iter_mut() {
+            if Self::is_under(&path, &mount.target)
+                && (result.is_none() || mount.target.len() > length)
+            {
+                length = mount.target.len();
+
+                let inner = if mount.target == "/" {
+                    path.clone()
+                } else if path.len() == length {
+                    "/".to_string()
+                } else {
+                    path[length..].to_string()
+                };
+
+                result = Some((mount.source.clone(), inner, mount.flags));
+            }
+        }
+
+        result
+    }
+
+    // Find volume by source
+    fn find_volume(&mut self, source: &str) -> Option<&mut Box<dyn FileVol>> {
+        for media in self.medias.iter_mut() {
+            let volume = media.get_volume(source);
+            if volume.is_some() {
+                return volume;
+            }
+        }
+        None
+    }
+
+    // Is partition, returns true when the disk is an partition of other disk
+    fn is_partition(&mut self, disk: &str) -> bool {
+        self.medias.iter_mut().any(|media| media.parts.iter().any(|part| part == disk))
+    }
+
+    // Register partitions, each partition is registered as an block driver
+    fn register_partitions(&mut self, disk: &str, table: &PartitionTable) -> Vec<String> {
+        let mut parts = Vec::new();
+
+        for partition in table.partitions.iter() {
+            let name = format!("{}p{}", disk, partition.index);
+
+            // The name is used by other driver
+            if kernel().device().get_driver(&name).is_some() {
+                debug_error!("Register partition {} failed, name is used", name);
+                continue;
+            }
+
+            kernel().device().register_driver(DriverWrapper::new(
+                Box::new(PartitionDisk::new(disk, partition)),
+                DriverID::Block,
+                &name,
+                core::ptr::null_mut(),
+            ));
+
+            parts.push(name);
+        }
+
+        parts
+    }
+
+    // Setup volume, the whole block device is used by the volume
+    fn setup_volume(&mut self, media: &mut DiskMedia) {
         for filesys in self.filesyses.iter_mut() {
             // Create new volume
             let mut volume = filesys.create_volume();
 
             // Setup volume
-            if volume.setup(&media.name, starting_lba) {
+            if volume.setup(&media.name) {
                 let mount_path = &format!("/media/{}", volume.get_name());
                 volume.set_mount_path(mount_path);
                 media.vols.push(volume);
@@ -494,12 +349,56 @@ impl FileSystem for VillageFileSystem {
             .retain_mut(|fs| !(fs.name() == name));
     }
 
+    // Attach volume, create an virtual volume without disk, such as ramfs,
+    // the volume uses its default size when the size is 0
+    fn attach_volume(&mut self, fs: &str, name: &str, size: u64) -> bool {
+        // Check the name is not used
+        if self.medias.iter_mut().any(|media| media.name == name) {
+            debug_error!("Attach volume {} failed, name is used", name);
+            return false;
+        }
+
+        // Get the file system
+        let filesys = match self.filesyses.iter_mut().find(|filesys| filesys.name() == fs) {
+            Some(filesys) => filesys,
+            None => {
+                debug_error!("Attach volume {} failed, file system {} not found", name, fs);
+                return false;
+            }
+        };
+
+        // Create and setup volume, the virtual volume uses the name as disk
+        let mut volume = filesys.create_volume();
+        if size != 0 && !volume.set_capacity(size) {
+            debug_error!("Attach volume {} failed, size {} is not supported", name, size);
+            return false;
+        }
+        if !volume.setup(name) {
+            debug_error!("Attach volume {} failed, setup failed", name);
+            return false;
+        }
+        volume.set_mount_path(&format!("/media/{}", name));
+
+        // Add to medias list
+        let mut media = DiskMedia::new();
+        media.name = name.to_string();
+        media.vols.push(volume);
+        self.medias.push(media);
+
+        true
+    }
+
     // Mount hard drive
     fn mount_hard_drive(&mut self, disk: &str) -> bool {
+        // The disk is mounted when the driver is registered
+        if self.medias.iter_mut().any(|media| media.name == disk) {
+            return true;
+        }
+
         debug_info!("Setup the hard drive {}", disk);
 
-        // Create an devstream object
-        let mut device = DevFopt::new();
+        // Create an block device object
+        let mut device = BlockFopt::new();
 
         // Open the disk device
         if !device.open(disk) {
@@ -507,76 +406,51 @@ impl FileSystem for VillageFileSystem {
             return false;
         }
 
-        // Read the master boot record from sector 0
-        let mut sector = vec![0u8; 512];
-        device.read(&mut sector, 1, 0);
-
-        // Parser master boot record
-        if let Some(mut mbr) = MBR::from(&sector) {
-            // Create an new disk media
-            let mut media = DiskMedia::new();
-
-            // Set the media name
-            media.name = disk.to_string();
-
-            // Media with partition table
-            if mbr.partitions[0].is_vaild() {
-                // Set the media for GPT partition format
-                if mbr.partitions[0].os_indicator == 0xee {
-                    // Set media partition type as GPT
-                    media.typ = PartitionType::GPT;
-
-                    // Read GPT header for staring lba sector
-                    device.read(&mut sector, 1, mbr.partitions[0].starting_lba as usize);
-
-                    // Parser gpt header
-                    if let Some(gpt) = GPT::from(&sector) {
-                        // Calculate the size of volume
-                        let size = gpt.number_of_partition_entries / gpt.size_of_partition_entry;
-
-                        // Setup partitions
-                        for i in 0..size {
-                            // Read partition record
-                            device.read(
-                                &mut sector,
-                                1,
-                                (gpt.partition_entry_lba as usize).wrapping_add(i as usize),
-                            );
-
-                            // Create GPT partition table object
-                            if let Some(partition) = GPTPartition::from(&sector) {
-                                // Setup GPT volume
-                                self.setup_volume(&mut media, partition.starting_lba as u32);
-                            }
-                        }
-                    }
-                }
-                // Set the media for MBR partition format
-                else {
-                    // Set media partition type as MBR
-                    media.typ = PartitionType::MBR;
-
-                    // Setup MBR partitions
-                    for i in 0..4 {
-                        if mbr.partitions[i].is_vaild() {
-                            self.setup_volume(&mut media, mbr.partitions[i].starting_lba);
-                        }
-                    }
-                }
-            } else {
-                // Setup single partition
-                self.setup_volume(&mut media, 0);
-            }
+        // Create an new disk media
+        let mut media = DiskMedia::new();
+
+        // Set the media name
+        media.name = disk.to_string();
 
-            // Close device
+        // The partition is mounted as whole disk, the nested table is not read
+        if self.is_partition(disk) {
+            self.setup_volume(&mut media);
             device.close();
+            self.medias.push(media);
+            return true;
+        }
+
+        // Read the partition table
+        let table = match PartitionTable::read(&mut device) {
+            Some(table) => table,
+            None => {
+                debug_error!("Not a vaild disk");
+                device.close();
+                return false;
+            }
+        };
+
+        // Close device
+        device.close();
+
+        // Set the partition type
+        media.typ = table.typ;
 
-            // Add to medias list
+        // Setup the whole disk without partition table
+        if table.typ == PartitionType::None {
+            self.setup_volume(&mut media);
             self.medias.push(media);
-        } else {
-            debug_error!("Not a vaild disk");
-            device.close();
-            return false;
+            return true;
+        }
+
+        // Add to medias list, the partitions are known before registering
+        media.parts = table.partitions.iter().map(|partition| format!("{}p{}", disk, partition.index)).collect();
+        self.medias.push(media);
+
+        // Register and mount partitions
+        let parts = self.register_partitions(disk, &table);
+        for part in parts.iter() {
+            self.mount_hard_drive(part);
         }
 
         true
@@ -586,40 +460,237 @@ impl FileSystem for VillageFileSystem {
     fn unmount_hard_drive(&mut self, disk: &str) -> bool {
         let mut result = false;
 
+        // Sources of the volumes on the disk
+        let mut sources = Vec::new();
+
+        // Partitions of the disk
+        let mut parts = Vec::new();
+
         // Get and remove media
         self.medias.retain_mut(|media| {
             // Get the disk media
             if media.name == disk {
                 // Exit volumes
                 for volume in media.vols.iter_mut() {
+                    sources.push(volume.get_mount_path().to_string());
                     volume.exit();
                 }
 
                 // Clear volumes
                 media.vols.clear();
 
+                // Take partitions
+                parts = core::mem::take(&mut media.parts);
+
                 result = true;
+                return false;
             }
-            !result
+            true
         });
 
+        // Remove the mount nodes of the volumes
+        self.mounts.retain_mut(|mount| !sources.contains(&mount.source));
+
+        // Write back and drop the cached blocks
+        self.cache.release(disk);
+
+        // Unmount and unregister partitions
+        for part in parts.iter() {
+            self.unmount_hard_drive(part);
+            kernel().device().unregister_driver(part);
+        }
+
         result
     }
 
-    // Get volume
-    fn get_volume(&mut self, name: &str) -> Option<&mut Box<dyn FileVol>> {
-        if let Some(mount) = self
+    // Is hard drive busy, the volumes on the disk or its partitions are mounted
+    fn is_hard_drive_busy(&mut self, disk: &str) -> bool {
+        let mut sources = Vec::new();
+        let mut parts = Vec::new();
+
+        for media in self.medias.iter_mut() {
+            if media.name == disk {
+                for volume in media.vols.iter_mut() {
+                    sources.push(volume.get_mount_path().to_string());
+                }
+                parts = media.parts.clone();
+            }
+        }
+
+        if self.mounts.iter_mut().any(|mount| sources.contains(&mount.source)) {
+            return true;
+        }
+
+        parts.iter().any(|part| self.is_hard_drive_busy(part))
+    }
+
+    // Mount
+    fn mount(&mut self, source: &str, target: &str, flags: MountFlags) -> bool {
+        let target = Self::normalize(target);
+
+        // The volume name can be used as source
+        let source = if source.starts_with('/') {
+            source.to_string()
+        } else {
+            format!("/media/{}", source)
+        };
+
+        // Check the volume and get its disk
+        let mut disk = None;
+        for media in self.medias.iter_mut() {
+            if media.get_volume(&source).is_some() {
+                disk = Some(media.name.clone());
+                break;
+            }
+        }
+        let disk = match disk {
+            Some(disk) => disk,
+            None => {
+                debug_error!("Mount {} failed, volume not found", source);
+                return false;
+            }
+        };
+
+        // The volumes on read only device are mounted read only
+        let mut flags = flags;
+        if Self::is_read_only_disk(&disk) {
+            flags.insert(MountFlags::READ_ONLY);
+        }
+
+        // Check the target is not mounted
+        if self.mounts.iter_mut().any(|mount| mount.target == target) {
+            debug_error!("Mount {} failed, {} is already mounted", source, target);
+            return false;
+        }
+
+        // The target must be an existing directory, except the root
+        if target != "/" {
+            let exist = match self.resolve_volume(&target) {
+                Some((volume, path)) => volume.exist(&path, FileType::Directory),
+                None => false,
+            };
+            if !exist {
+                debug_error!("Mount {} failed, {} is not a directory", source, target);
+                return false;
+            }
+        }
+
+        self.mounts.push(MountNode::from(&target, &source, 0o755, flags));
+        true
+    }
+
+    // Umount
+    fn umount(&mut self, target: &str) -> bool {
+        let target = Self::normalize(target);
+
+        // Check whether there are mount nodes under the target
+        if self
             .mounts
             .iter_mut()
-            .find(|mount| name.starts_with(&mount.target))
+            .any(|mount| mount.target != target && Self::is_under(&mount.target, &target))
         {
-            for media in self.medias.iter_mut() {
-                let volume = media.get_volume(&mount.source);
-                if volume.is_some() {
-                    return volume;
-                }
+            debug_error!("Umount {} failed, target is busy", target);
+            return false;
+        }
+
+        // Check whether the backing files of block devices are under the target
+        if Self::backing_paths().iter().any(|path| Self::is_under(path, &target)) {
+            debug_error!("Umount {} failed, backing file is used", target);
+            return false;
+        }
+
+        // Remove the mount node
+        let mut result = false;
+        self.mounts.retain_mut(|mount| {
+            if mount.target == target {
+                result = true;
+                return false;
             }
+            true
+        });
+
+        // Write back the dirty blocks
+        if result {
+            self.cache.sync();
         }
-        None
+
+        result
+    }
+
+    // Get mounts
+    fn get_mounts(&mut self) -> &mut LinkedList<MountNode> {
+        &mut self.mounts
+    }
+
+    // Normalize path
+    fn normalize_path(&mut self, path: &str) -> String {
+        Self::normalize(path)
+    }
+
+    // Is read only
+    fn is_read_only(&mut self, path: &str) -> bool {
+        match self.match_mount(path) {
+            Some((_, _, flags)) => flags.contains(MountFlags::READ_ONLY),
+            None => false,
+        }
+    }
+
+    // Is backing, the path or its children are used as backing files of block devices
+    fn is_backing(&mut self, path: &str) -> bool {
+        let path = Self::normalize(path);
+        Self::backing_paths().iter().any(|backing| Self::is_under(backing, &path))
+    }
+
+    // Get volume
+    fn get_volume(&mut self, path: &str) -> Option<&mut Box<dyn FileVol>> {
+        let (source, _, _) = self.match_mount(path)?;
+        self.find_volume(&source)
+    }
+
+    // Resolve volume, returns the volume and the path in volume
+    fn resolve_volume(&mut self, path: &str) -> Option<(&mut Box<dyn FileVol>, String)> {
+        let (source, inner, _) = self.match_mount(path)?;
+        let volume = self.find_volume(&source)?;
+        Some((volume, inner))
+    }
+
+    // Read block
+    fn read_block(&mut self, disk: &str, data: &mut [u8], count: usize, lba: usize) -> usize {
+        if Self::is_direct(disk) {
+            return match kernel().device().get_driver(disk) {
+                Some(driver) => driver.read(data, count, lba),
+                None => 0,
+            };
+        }
+        self.cache.read(disk, data, count, lba)
+    }
+
+    // Write block
+    fn write_block(&mut self, disk: &str, data: &[u8], count: usize, lba: usize) -> usize {
+        if Self::is_direct(disk) {
+            return match kernel().device().get_driver(disk) {
+                Some(driver) => driver.write(data, count, lba),
+                None => 0,
+            };
+        }
+        self.cache.write(disk, data, count, lba)
+    }
+
+    // Sync block
+    fn sync_block(&mut self, disk: &str) -> bool {
+        if Self::is_direct(disk) {
+            return true;
+        }
+        self.cache.sync_disk(disk)
+    }
+
+    // Sync
+    fn sync(&mut self) -> bool {
+        self.cache.sync()
+    }
+
+    // Get cache info
+    fn get_cache_info(&mut self) -> BlockCacheInfo {
+        self.cache.get_info()
     }
 }
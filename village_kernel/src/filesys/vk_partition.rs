@@ -0,0 +1,1052 @@
+//###########################################################################
+// vk_partition.rs
+// The specific implementation of functions related to partition
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::debug_error;
+use crate::debug_warning;
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use crate::misc::fopts::vk_dev_fopt::DevFopt;
+use crate::traits::vk_driver::{BLKCommand, Command, Driver};
+use crate::village::kernel;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Const members
+const SECTOR_SIZE: usize = 512;
+const MBR_ENTRY_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const EBR_MAX_COUNT: usize = 128;
+const GPT_HEADER_SIZE: u32 = 92;
+const GPT_ENTRY_COUNT: u32 = 128;
+const GPT_ENTRY_SIZE: u32 = 128;
+const GPT_ENTRY_SECTORS: u64 = 32;
+const GPT_MAX_ENTRY_BYTES: usize = 1024 * 1024;
+const ALIGN_LBA: u64 = 2048;
+
+// Enum BootIndicator
+pub enum BootIndicator {
+    NotBootable = 0x00,
+    Bootable = 0x80,
+}
+
+// Enum PartitionType
+#[derive(PartialEq, Clone, Copy)]
+pub enum PartitionType {
+    None = 0,
+    MBR,
+    GPT,
+}
+
+// Struct MBRType
+pub struct MBRType;
+
+// Impl MBRType
+impl MBRType {
+    pub const EMPTY: u8 = 0x00;
+    pub const FAT12: u8 = 0x01;
+    pub const FAT16: u8 = 0x06;
+    pub const EXTENDED: u8 = 0x05;
+    pub const EXFAT: u8 = 0x07;
+    pub const FAT32_LBA: u8 = 0x0c;
+    pub const FAT16_LBA: u8 = 0x0e;
+    pub const EXTENDED_LBA: u8 = 0x0f;
+    pub const LINUX: u8 = 0x83;
+    pub const LINUX_EXTENDED: u8 = 0x85;
+    pub const PROTECTIVE: u8 = 0xee;
+    pub const EFI_SYSTEM: u8 = 0xef;
+}
+
+// Struct GPTType, the first three fields of guid are little endian
+pub struct GPTType;
+
+// Impl GPTType
+impl GPTType {
+    pub const UNUSED: [u8; 16] = [0u8; 16];
+    pub const BASIC_DATA: [u8; 16] = [
+        0xa2, 0xa0, 0xd0, 0xeb, 0xe5, 0xb9, 0x33, 0x44, 0x87, 0xc0, 0x68, 0xb6, 0xb7, 0x26, 0x99, 0xc7,
+    ];
+    pub const LINUX: [u8; 16] = [
+        0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d, 0xe4,
+    ];
+    pub const EFI_SYSTEM: [u8; 16] = [
+        0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b,
+    ];
+}
+
+// Struct MBR partition record
+#[derive(Clone)]
+pub struct MBRPartition {
+    pub boot_indicator: u8,
+    pub starting_head: u8,
+    pub starting_chs: u16,
+    pub os_indicator: u8,
+    pub ending_head: u8,
+    pub ending_chs: u16,
+    pub starting_lba: u32,
+    pub size_in_lba: u32,
+}
+
+// Impl MBRPartition
+impl MBRPartition {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            boot_indicator: 0,
+            starting_head: 0,
+            starting_chs: 0,
+            os_indicator: 0,
+            ending_head: 0,
+            ending_chs: 0,
+            starting_lba: 0,
+            size_in_lba: 0,
+        }
+    }
+
+    // Lba, the chs fields are set to the maximum to use lba only
+    pub const fn lba(bootable: bool, os_indicator: u8, starting_lba: u32, size_in_lba: u32) -> Self {
+        Self {
+            boot_indicator: if bootable { BootIndicator::Bootable as u8 } else { BootIndicator::NotBootable as u8 },
+            starting_head: 0xfe,
+            starting_chs: 0xffff,
+            os_indicator,
+            ending_head: 0xfe,
+            ending_chs: 0xffff,
+            starting_lba,
+            size_in_lba,
+        }
+    }
+
+    // From
+    pub const fn from(data: &[u8]) -> Self {
+        if data.len() < 16 {
+            return Self::new();
+        }
+
+        let boot_indicator = data[0];
+        let starting_head = data[1];
+        let starting_chs = u16::from_le_bytes([data[2], data[3]]);
+        let os_indicator = data[4];
+        let ending_head = data[5];
+        let ending_chs = u16::from_le_bytes([data[6], data[7]]);
+        let starting_lba = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
+        let size_in_lba = u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+
+        Self {
+            boot_indicator,
+            starting_head,
+            starting_chs,
+            os_indicator,
+            ending_head,
+            ending_chs,
+            starting_lba,
+            size_in_lba,
+        }
+    }
+
+    // As bytes
+    pub fn as_bytes(&self) -> [u8; 16] {
+        let mut data = [0u8; 16];
+        data[0] = self.boot_indicator;
+        data[1] = self.starting_head;
+        data[2..4].copy_from_slice(&self.starting_chs.to_le_bytes());
+        data[4] = self.os_indicator;
+        data[5] = self.ending_head;
+        data[6..8].copy_from_slice(&self.ending_chs.to_le_bytes());
+        data[8..12].copy_from_slice(&self.starting_lba.to_le_bytes());
+        data[12..16].copy_from_slice(&self.size_in_lba.to_le_bytes());
+        data
+    }
+
+    // Check is vaild, the boot code of a volume boot record is not a partition
+    pub fn is_vaild(&self) -> bool {
+        if self.os_indicator != 0 && self.size_in_lba != 0 && (self.boot_indicator & 0x7f) == 0 {
+            return self.boot_indicator != 0
+                || self.starting_head != 0
+                || self.starting_chs != 0
+                || self.ending_head != 0
+                || self.ending_chs != 0
+                || self.starting_lba != 0;
+        }
+        false
+    }
+
+    // Check is extended
+    pub fn is_extended(&self) -> bool {
+        self.os_indicator == MBRType::EXTENDED
+            || self.os_indicator == MBRType::EXTENDED_LBA
+            || self.os_indicator == MBRType::LINUX_EXTENDED
+    }
+}
+
+// Struct MBR partition table
+pub struct MBR {
+    pub partitions: [MBRPartition; 4],
+    pub magic: u16,
+}
+
+// Impl MBR
+impl MBR {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            partitions: [const { MBRPartition::new() }; 4],
+            magic: 0xAA55,
+        }
+    }
+
+    // From
+    pub fn from(data: &[u8]) -> Option<Self> {
+        if data.len() < SECTOR_SIZE {
+            return None;
+        }
+
+        let mut mbr = Self::new();
+
+        // Parser partitions
+        for i in 0..4 {
+            let offset = MBR_ENTRY_OFFSET + i * MBR_ENTRY_SIZE;
+            mbr.partitions[i] = MBRPartition::from(&data[offset..offset + MBR_ENTRY_SIZE]);
+        }
+
+        // Get magic and check
+        mbr.magic = u16::from_le_bytes([data[510], data[511]]);
+
+        if mbr.magic != 0xAA55 {
+            return None;
+        }
+
+        Some(mbr)
+    }
+
+    // Write to sector, the boot code of sector is kept
+    pub fn write_to(&self, data: &mut [u8]) {
+        for i in 0..4 {
+            let offset = MBR_ENTRY_OFFSET + i * MBR_ENTRY_SIZE;
+            data[offset..offset + MBR_ENTRY_SIZE].copy_from_slice(&self.partitions[i].as_bytes());
+        }
+        data[510..512].copy_from_slice(&self.magic.to_le_bytes());
+    }
+}
+
+// Struct GPT partition record
+pub struct GPTPartition {
+    pub partition_type_guid: [u8; 16],
+    pub unique_partition_guid: [u8; 16],
+    pub starting_lba: u64,
+    pub ending_lba: u64,
+    pub attributes: u64,
+    pub partition_name: [u16; 36],
+}
+
+// Impl GPTPartition
+impl GPTPartition {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            partition_type_guid: [0u8; 16],
+            unique_partition_guid: [0u8; 16],
+            starting_lba: 0,
+            ending_lba: 0,
+            attributes: 0,
+            partition_name: [0u16; 36],
+        }
+    }
+
+    // From, the entry is at least 128 bytes
+    pub fn from(data: &[u8]) -> Option<Self> {
+        if data.len() < GPT_ENTRY_SIZE as usize {
+            return None;
+        }
+
+        let mut partition = Self::new();
+
+        // Parser guid
+        partition.partition_type_guid.copy_from_slice(&data[0..16]);
+        partition.unique_partition_guid.copy_from_slice(&data[16..32]);
+
+        // Parser lba
+        partition.starting_lba = get_u64(data, 32);
+        partition.ending_lba = get_u64(data, 40);
+        partition.attributes = get_u64(data, 48);
+
+        // Parser name
+        for i in 0..36 {
+            let idx = 56 + i * 2;
+            partition.partition_name[i] = u16::from_le_bytes([data[idx], data[idx + 1]]);
+        }
+
+        // Check
+        if partition.is_empty() || partition.ending_lba < partition.starting_lba {
+            return None;
+        }
+
+        Some(partition)
+    }
+
+    // As bytes
+    pub fn as_bytes(&self) -> [u8; 128] {
+        let mut data = [0u8; 128];
+        data[0..16].copy_from_slice(&self.partition_type_guid);
+        data[16..32].copy_from_slice(&self.unique_partition_guid);
+        data[32..40].copy_from_slice(&self.starting_lba.to_le_bytes());
+        data[40..48].copy_from_slice(&self.ending_lba.to_le_bytes());
+        data[48..56].copy_from_slice(&self.attributes.to_le_bytes());
+        for (i, c) in self.partition_name.iter().enumerate() {
+            data[56 + i * 2..58 + i * 2].copy_from_slice(&c.to_le_bytes());
+        }
+        data
+    }
+
+    // Check is empty
+    pub fn is_empty(&self) -> bool {
+        self.partition_type_guid == GPTType::UNUSED
+    }
+
+    // Get name
+    pub fn get_name(&self) -> String {
+        let len = self.partition_name.iter().position(|c| *c == 0).unwrap_or(36);
+        String::from_utf16_lossy(&self.partition_name[..len])
+    }
+
+    // Set name
+    pub fn set_name(&mut self, name: &str) {
+        self.partition_name = [0u16; 36];
+        for (i, c) in name.encode_utf16().take(36).enumerate() {
+            self.partition_name[i] = c;
+        }
+    }
+}
+
+// Struct GPT header
+pub struct GPT {
+    pub signature: [u8; 8],
+    pub revision: u32,
+    pub header_size: u32,
+    pub header_crc32: u32,
+    pub reserved0: u32,
+    pub my_lba: u64,
+    pub alternate_lba: u64,
+    pub first_usable_lba: u64,
+    pub last_usable_lba: u64,
+    pub disk_guid: [u8; 16],
+    pub partition_entry_lba: u64,
+    pub number_of_partition_entries: u32,
+    pub size_of_partition_entry: u32,
+    pub partition_entry_array_crc32: u32,
+}
+
+// Impl GPT
+impl GPT {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            signature: *b"EFI PART",
+            revision: 0x00010000,
+            header_size: GPT_HEADER_SIZE,
+            header_crc32: 0,
+            reserved0: 0,
+            my_lba: 0,
+            alternate_lba: 0,
+            first_usable_lba: 0,
+            last_usable_lba: 0,
+            disk_guid: [0u8; 16],
+            partition_entry_lba: 0,
+            number_of_partition_entries: GPT_ENTRY_COUNT,
+            size_of_partition_entry: GPT_ENTRY_SIZE,
+            partition_entry_array_crc32: 0,
+        }
+    }
+
+    // From, the header crc is checked
+    pub fn from(data: &[u8]) -> Option<Self> {
+        if data.len() < SECTOR_SIZE {
+            return None;
+        }
+
+        let mut gpt = Self::new();
+
+        // Get signature
+        gpt.signature.copy_from_slice(&data[0..8]);
+
+        // Copy header
+        gpt.revision = get_u32(data, 8);
+        gpt.header_size = get_u32(data, 12);
+        gpt.header_crc32 = get_u32(data, 16);
+        gpt.reserved0 = get_u32(data, 20);
+
+        // Copy lba
+        gpt.my_lba = get_u64(data, 24);
+        gpt.alternate_lba = get_u64(data, 32);
+        gpt.first_usable_lba = get_u64(data, 40);
+        gpt.last_usable_lba = get_u64(data, 48);
+
+        // Copy guid
+        gpt.disk_guid.copy_from_slice(&data[56..72]);
+
+        // Copy entry
+        gpt.partition_entry_lba = get_u64(data, 72);
+        gpt.number_of_partition_entries = get_u32(data, 80);
+        gpt.size_of_partition_entry = get_u32(data, 84);
+        gpt.partition_entry_array_crc32 = get_u32(data, 88);
+
+        // Check is valid
+        if !gpt.is_valid() {
+            return None;
+        }
+
+        // Check header crc, the crc field is zero when calculating
+        let mut header = data[..gpt.header_size as usize].to_vec();
+        header[16..20].fill(0);
+        if crc32(&header) != gpt.header_crc32 {
+            return None;
+        }
+
+        Some(gpt)
+    }
+
+    // As bytes, the header crc is updated
+    pub fn as_bytes(&mut self) -> [u8; 512] {
+        let mut data = [0u8; 512];
+        data[0..8].copy_from_slice(&self.signature);
+        data[8..12].copy_from_slice(&self.revision.to_le_bytes());
+        data[12..16].copy_from_slice(&self.header_size.to_le_bytes());
+        data[20..24].copy_from_slice(&self.reserved0.to_le_bytes());
+        data[24..32].copy_from_slice(&self.my_lba.to_le_bytes());
+        data[32..40].copy_from_slice(&self.alternate_lba.to_le_bytes());
+        data[40..48].copy_from_slice(&self.first_usable_lba.to_le_bytes());
+        data[48..56].copy_from_slice(&self.last_usable_lba.to_le_bytes());
+        data[56..72].copy_from_slice(&self.disk_guid);
+        data[72..80].copy_from_slice(&self.partition_entry_lba.to_le_bytes());
+        data[80..84].copy_from_slice(&self.number_of_partition_entries.to_le_bytes());
+        data[84..88].copy_from_slice(&self.size_of_partition_entry.to_le_bytes());
+        data[88..92].copy_from_slice(&self.partition_entry_array_crc32.to_le_bytes());
+
+        self.header_crc32 = crc32(&data[..self.header_size as usize]);
+        data[16..20].copy_from_slice(&self.header_crc32.to_le_bytes());
+        data
+    }
+
+    // Check is valid
+    pub fn is_valid(&self) -> bool {
+        &self.signature == b"EFI PART"
+            && self.header_size >= GPT_HEADER_SIZE
+            && self.header_size as usize <= SECTOR_SIZE
+            && self.size_of_partition_entry >= GPT_ENTRY_SIZE
+            && self.size_of_partition_entry.is_multiple_of(8)
+            && self.number_of_partition_entries > 0
+            && (self.number_of_partition_entries as usize * self.size_of_partition_entry as usize)
+                <= GPT_MAX_ENTRY_BYTES
+    }
+
+    // Get the sector count of entry array
+    pub fn entry_sectors(&self) -> u64 {
+        let bytes = self.number_of_partition_entries as u64 * self.size_of_partition_entry as u64;
+        bytes.div_ceil(SECTOR_SIZE as u64)
+    }
+}
+
+// Struct Partition
+#[derive(Clone)]
+pub struct Partition {
+    pub index: usize,
+    pub starting_lba: u64,
+    pub size_in_lba: u64,
+    pub os_indicator: u8,
+    pub type_guid: [u8; 16],
+    pub unique_guid: [u8; 16],
+    pub attributes: u64,
+    pub name: String,
+    pub bootable: bool,
+    pub logical: bool,
+}
+
+// Impl Partition
+impl Partition {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            index: 0,
+            starting_lba: 0,
+            size_in_lba: 0,
+            os_indicator: 0,
+            type_guid: [0u8; 16],
+            unique_guid: [0u8; 16],
+            attributes: 0,
+            name: String::new(),
+            bootable: false,
+            logical: false,
+        }
+    }
+
+    // Get ending lba
+    pub fn ending_lba(&self) -> u64 {
+        self.starting_lba + self.size_in_lba - 1
+    }
+
+    // Get type name
+    pub fn type_name(&self, typ: PartitionType) -> String {
+        if typ == PartitionType::GPT {
+            let name = match self.type_guid {
+                GPTType::BASIC_DATA => "basic data",
+                GPTType::LINUX => "linux filesystem",
+                GPTType::EFI_SYSTEM => "efi system",
+                _ => return guid_string(&self.type_guid),
+            };
+            return String::from(name);
+        }
+
+        let name = match self.os_indicator {
+            MBRType::FAT12 => "fat12",
+            MBRType::FAT16 | MBRType::FAT16_LBA => "fat16",
+            MBRType::FAT32_LBA | 0x0b => "fat32",
+            MBRType::EXFAT => "exfat/ntfs",
+            MBRType::LINUX => "linux",
+            MBRType::EFI_SYSTEM => "efi system",
+            _ => "unknown",
+        };
+        format!("{:#04x} {}", self.os_indicator, name)
+    }
+}
+
+// Struct PartitionTable
+pub struct PartitionTable {
+    pub typ: PartitionType,
+    pub sectors: u64,
+    pub partitions: Vec<Partition>,
+    pub backup_used: bool,
+    extended: Option<MBRPartition>,
+    extended_slot: usize,
+    disk_guid: [u8; 16],
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    entry_count: u32,
+    seed: u32,
+}
+
+// Impl PartitionTable
+impl PartitionTable {
+    // New, the sectors of disk are needed by gpt
+    pub fn new(typ: PartitionType, sectors: u64) -> Self {
+        let mut table = Self {
+            typ,
+            sectors,
+            partitions: Vec::new(),
+            backup_used: false,
+            extended: None,
+            extended_slot: 0,
+            disk_guid: [0u8; 16],
+            first_usable_lba: 1,
+            last_usable_lba: sectors.saturating_sub(1).min(u32::MAX as u64),
+            entry_count: 4,
+            seed: kernel().system().get_ticks() ^ (sectors as u32) ^ 0x5a5a5a5a,
+        };
+
+        if typ == PartitionType::GPT {
+            table.disk_guid = table.new_guid();
+            table.entry_count = GPT_ENTRY_COUNT;
+            table.first_usable_lba = 2 + GPT_ENTRY_SECTORS;
+            table.last_usable_lba = sectors.saturating_sub(2 + GPT_ENTRY_SECTORS);
+        }
+
+        table
+    }
+
+    // Read, returns None when sector 0 has no boot signature
+    pub fn read(device: &mut BlockFopt) -> Option<Self> {
+        let mut sector = vec![0u8; SECTOR_SIZE];
+        device.read(&mut sector, 1, 0);
+
+        let mbr = MBR::from(&sector)?;
+        let sectors = device.get_sector_count() as u64;
+
+        // The protective mbr covers the gpt disk
+        let protective = &mbr.partitions[0];
+        if protective.os_indicator == MBRType::PROTECTIVE && protective.is_vaild() {
+            let end = protective.starting_lba as u64 + protective.size_in_lba as u64;
+            let mut table = Self::new(PartitionType::GPT, if sectors != 0 { sectors } else { end });
+            if !table.read_gpt(device) {
+                debug_error!("The primary and backup gpt are both damaged");
+            }
+            return Some(table);
+        }
+
+        // Mbr partitions
+        if mbr.partitions.iter().any(|partition| partition.is_vaild()) {
+            let mut table = Self::new(PartitionType::MBR, sectors);
+            table.read_mbr(device, &mbr);
+            return Some(table);
+        }
+
+        // Single partition
+        Some(Self::new(PartitionType::None, sectors))
+    }
+
+    // Read mbr partitions, the logical partitions are numbered from 5
+    fn read_mbr(&mut self, device: &mut BlockFopt, mbr: &MBR) {
+        for (slot, entry) in mbr.partitions.iter().enumerate() {
+            if !entry.is_vaild() {
+                continue;
+            }
+
+            if entry.is_extended() {
+                self.extended = Some(entry.clone());
+                self.extended_slot = slot;
+                self.read_ebr(device, entry.starting_lba as u64);
+                continue;
+            }
+
+            let mut partition = Partition::new();
+            partition.index = slot + 1;
+            partition.starting_lba = entry.starting_lba as u64;
+            partition.size_in_lba = entry.size_in_lba as u64;
+            partition.os_indicator = entry.os_indicator;
+            partition.bootable = entry.boot_indicator == BootIndicator::Bootable as u8;
+            self.partitions.push(partition);
+        }
+    }
+
+    // Read the extended boot records chain
+    fn read_ebr(&mut self, device: &mut BlockFopt, extended_lba: u64) {
+        let mut sector = vec![0u8; SECTOR_SIZE];
+        let mut ebr_lba = extended_lba;
+        let mut index = 5;
+
+        for _ in 0..EBR_MAX_COUNT {
+            device.read(&mut sector, 1, ebr_lba as usize);
+
+            let ebr = match MBR::from(&sector) {
+                Some(ebr) => ebr,
+                None => break,
+            };
+
+            // The logical partition is relative to this ebr
+            let entry = &ebr.partitions[0];
+            if entry.os_indicator != MBRType::EMPTY && entry.size_in_lba != 0 {
+                let mut partition = Partition::new();
+                partition.index = index;
+                partition.starting_lba = ebr_lba + entry.starting_lba as u64;
+                partition.size_in_lba = entry.size_in_lba as u64;
+                partition.os_indicator = entry.os_indicator;
+                partition.logical = true;
+                self.partitions.push(partition);
+                index += 1;
+            }
+
+            // The next ebr is relative to the extended partition
+            let next = &ebr.partitions[1];
+            if !next.is_extended() || next.starting_lba == 0 {
+                break;
+            }
+            ebr_lba = extended_lba + next.starting_lba as u64;
+        }
+    }
+
+    // Read gpt, the backup header is used when the primary is damaged
+    fn read_gpt(&mut self, device: &mut BlockFopt) -> bool {
+        let mut candidates = vec![1];
+        if self.sectors > 1 {
+            candidates.push(self.sectors - 1);
+        }
+
+        for (i, lba) in candidates.into_iter().enumerate() {
+            if let Some((gpt, entries)) = Self::read_gpt_header(device, lba) {
+                if i > 0 {
+                    debug_warning!("The primary gpt header is damaged, the backup header is used");
+                }
+                self.backup_used = i > 0;
+                self.load_gpt(&gpt, &entries);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Read gpt header and entries, both crc are checked
+    fn read_gpt_header(device: &mut BlockFopt, lba: u64) -> Option<(GPT, Vec<u8>)> {
+        let mut sector = vec![0u8; SECTOR_SIZE];
+        device.read(&mut sector, 1, lba as usize);
+
+        let gpt = GPT::from(&sector)?;
+        if gpt.my_lba != lba {
+            return None;
+        }
+
+        let count = gpt.entry_sectors() as usize;
+        let mut entries = vec![0u8; count * SECTOR_SIZE];
+        if device.read(&mut entries, count, gpt.partition_entry_lba as usize) != count {
+            return None;
+        }
+
+        let size = gpt.number_of_partition_entries as usize * gpt.size_of_partition_entry as usize;
+        if crc32(&entries[..size]) != gpt.partition_entry_array_crc32 {
+            return None;
+        }
+
+        Some((gpt, entries))
+    }
+
+    // Load gpt partitions
+    fn load_gpt(&mut self, gpt: &GPT, entries: &[u8]) {
+        self.disk_guid = gpt.disk_guid;
+        self.first_usable_lba = gpt.first_usable_lba;
+        self.last_usable_lba = gpt.last_usable_lba;
+        self.entry_count = gpt.number_of_partition_entries;
+
+        let size = gpt.size_of_partition_entry as usize;
+        for i in 0..gpt.number_of_partition_entries as usize {
+            if let Some(entry) = GPTPartition::from(&entries[i * size..(i + 1) * size]) {
+                let mut partition = Partition::new();
+                partition.index = i + 1;
+                partition.starting_lba = entry.starting_lba;
+                partition.size_in_lba = entry.ending_lba - entry.starting_lba + 1;
+                partition.type_guid = entry.partition_type_guid;
+                partition.unique_guid = entry.unique_partition_guid;
+                partition.attributes = entry.attributes;
+                partition.name = entry.get_name();
+                self.partitions.push(partition);
+            }
+        }
+    }
+}
+
+// Impl PartitionTable
+impl PartitionTable {
+    // Get the used ranges, the extended partition is a used range
+    fn used_ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = self
+            .partitions
+            .iter()
+            .filter(|partition| !partition.logical)
+            .map(|partition| (partition.starting_lba, partition.ending_lba()))
+            .collect();
+
+        if let Some(extended) = &self.extended {
+            let start = extended.starting_lba as u64;
+            ranges.push((start, start + extended.size_in_lba as u64 - 1));
+        }
+
+        ranges.sort();
+        ranges
+    }
+
+    // Get the free ranges between the usable lba
+    pub fn free_ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let mut start = self.first_usable_lba;
+
+        for (used_start, used_end) in self.used_ranges() {
+            if used_start > start {
+                ranges.push((start, used_start - 1));
+            }
+            start = start.max(used_end + 1);
+        }
+
+        if start <= self.last_usable_lba {
+            ranges.push((start, self.last_usable_lba));
+        }
+        ranges
+    }
+
+    // Get a free index
+    fn free_index(&self) -> Option<usize> {
+        let used_slot = |index: usize| {
+            self.partitions.iter().any(|partition| partition.index == index)
+                || (self.extended.is_some() && self.extended_slot + 1 == index)
+        };
+        (1..=self.entry_count as usize).find(|index| !used_slot(*index))
+    }
+
+    // Add, the start is the first aligned free lba when it is 0 and the size fills the
+    // free range when it is 0. Returns the index of new partition
+    pub fn add(&mut self, start: u64, size: u64, os_indicator: u8, type_guid: [u8; 16]) -> Option<usize> {
+        if self.typ == PartitionType::None {
+            return None;
+        }
+
+        let index = self.free_index()?;
+        let align = if self.last_usable_lba >= ALIGN_LBA * 16 { ALIGN_LBA } else { 1 };
+
+        // Find the free range
+        let (start, end) = self.free_ranges().into_iter().find_map(|(free_start, free_end)| {
+            let start = if start == 0 { free_start.div_ceil(align) * align } else { start };
+            let end = if size == 0 { free_end } else { start.checked_add(size - 1)? };
+            (start >= free_start && start <= free_end && end <= free_end).then_some((start, end))
+        })?;
+
+        // The mbr fields are 32 bits
+        if self.typ == PartitionType::MBR && end > u32::MAX as u64 {
+            return None;
+        }
+
+        let mut partition = Partition::new();
+        partition.index = index;
+        partition.starting_lba = start;
+        partition.size_in_lba = end - start + 1;
+        partition.os_indicator = os_indicator;
+        if self.typ == PartitionType::GPT {
+            partition.type_guid = type_guid;
+            partition.unique_guid = self.new_guid();
+        }
+
+        self.partitions.push(partition);
+        self.partitions.sort_by_key(|partition| partition.index);
+        Some(index)
+    }
+
+    // Remove, the logical partitions can not be removed
+    pub fn remove(&mut self, index: usize) -> bool {
+        let pos = self.partitions.iter().position(|partition| partition.index == index && !partition.logical);
+        match pos {
+            Some(pos) => {
+                self.partitions.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Get partition
+    pub fn get_partition(&self, index: usize) -> Option<&Partition> {
+        self.partitions.iter().find(|partition| partition.index == index)
+    }
+
+    // Write the partition table to device
+    pub fn write(&mut self, device: &mut BlockFopt) -> bool {
+        let result = match self.typ {
+            PartitionType::MBR => self.write_mbr(device),
+            PartitionType::GPT => self.write_gpt(device),
+            PartitionType::None => false,
+        };
+        result && device.sync()
+    }
+
+    // Write mbr, the boot code of sector 0 is kept
+    fn write_mbr(&mut self, device: &mut BlockFopt) -> bool {
+        let mut sector = vec![0u8; SECTOR_SIZE];
+        device.read(&mut sector, 1, 0);
+
+        let mut mbr = MBR::new();
+        for partition in self.partitions.iter().filter(|partition| !partition.logical) {
+            mbr.partitions[partition.index - 1] = MBRPartition::lba(
+                partition.bootable,
+                partition.os_indicator,
+                partition.starting_lba as u32,
+                partition.size_in_lba as u32,
+            );
+        }
+
+        if let Some(extended) = &self.extended {
+            mbr.partitions[self.extended_slot] = extended.clone();
+        }
+
+        mbr.write_to(&mut sector);
+        device.write(&sector, 1, 0) == 1
+    }
+
+    // Write gpt, the protective mbr, primary and backup tables are written
+    fn write_gpt(&mut self, device: &mut BlockFopt) -> bool {
+        if self.sectors < 2 * (2 + GPT_ENTRY_SECTORS)
+            || self.partitions.iter().any(|partition| partition.index > GPT_ENTRY_COUNT as usize)
+        {
+            return false;
+        }
+
+        // Protective mbr
+        let mut sector = vec![0u8; SECTOR_SIZE];
+        device.read(&mut sector, 1, 0);
+
+        let mut mbr = MBR::new();
+        let size = (self.sectors - 1).min(u32::MAX as u64) as u32;
+        mbr.partitions[0] = MBRPartition::lba(false, MBRType::PROTECTIVE, 1, size);
+        mbr.partitions[0].starting_head = 0;
+        mbr.partitions[0].starting_chs = 0x0002;
+        mbr.write_to(&mut sector);
+
+        // Partition entries
+        let mut entries = vec![0u8; (GPT_ENTRY_SECTORS as usize) * SECTOR_SIZE];
+        for partition in self.partitions.iter() {
+            let mut entry = GPTPartition::new();
+            entry.partition_type_guid = partition.type_guid;
+            entry.unique_partition_guid = partition.unique_guid;
+            entry.starting_lba = partition.starting_lba;
+            entry.ending_lba = partition.ending_lba();
+            entry.attributes = partition.attributes;
+            entry.set_name(&partition.name);
+
+            let offset = (partition.index - 1) * GPT_ENTRY_SIZE as usize;
+            entries[offset..offset + GPT_ENTRY_SIZE as usize].copy_from_slice(&entry.as_bytes());
+        }
+
+        // Headers
+        let last_lba = self.sectors - 1;
+        let backup_entry_lba = last_lba - GPT_ENTRY_SECTORS;
+
+        let mut primary = GPT::new();
+        primary.my_lba = 1;
+        primary.alternate_lba = last_lba;
+        primary.first_usable_lba = self.first_usable_lba;
+        primary.last_usable_lba = self.last_usable_lba;
+        primary.disk_guid = self.disk_guid;
+        primary.partition_entry_lba = 2;
+        primary.number_of_partition_entries = GPT_ENTRY_COUNT;
+        primary.partition_entry_array_crc32 = crc32(&entries);
+
+        let mut backup = GPT::new();
+        backup.my_lba = last_lba;
+        backup.alternate_lba = 1;
+        backup.first_usable_lba = self.first_usable_lba;
+        backup.last_usable_lba = self.last_usable_lba;
+        backup.disk_guid = self.disk_guid;
+        backup.partition_entry_lba = backup_entry_lba;
+        backup.number_of_partition_entries = GPT_ENTRY_COUNT;
+        backup.partition_entry_array_crc32 = primary.partition_entry_array_crc32;
+
+        let count = GPT_ENTRY_SECTORS as usize;
+        let result = device.write(&sector, 1, 0) == 1
+            && device.write(&primary.as_bytes(), 1, 1) == 1
+            && device.write(&entries, count, 2) == count
+            && device.write(&entries, count, backup_entry_lba as usize) == count
+            && device.write(&backup.as_bytes(), 1, last_lba as usize) == 1;
+
+        self.entry_count = GPT_ENTRY_COUNT;
+        self.backup_used = false;
+        result
+    }
+
+    // New guid, the random version 4 guid
+    fn new_guid(&mut self) -> [u8; 16] {
+        let mut guid = [0u8; 16];
+        for chunk in guid.chunks_exact_mut(4) {
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            chunk.copy_from_slice(&self.seed.to_le_bytes());
+        }
+        guid[7] = (guid[7] & 0x0f) | 0x40;
+        guid[8] = (guid[8] & 0x3f) | 0x80;
+        guid
+    }
+}
+
+// Struct PartitionDisk, the partition is exposed as an block device
+pub struct PartitionDisk {
+    parent: DevFopt,
+    parent_name: String,
+    starting_lba: usize,
+    size_in_lba: usize,
+}
+
+// Impl PartitionDisk
+impl PartitionDisk {
+    // New
+    pub fn new(parent: &str, partition: &Partition) -> Self {
+        Self {
+            parent: DevFopt::new(),
+            parent_name: parent.to_string(),
+            starting_lba: partition.starting_lba as usize,
+            size_in_lba: partition.size_in_lba as usize,
+        }
+    }
+
+    // Get the count of sectors can be accessed from the offset
+    fn bounded(&self, count: usize, offset: usize) -> usize {
+        if offset >= self.size_in_lba {
+            return 0;
+        }
+        count.min(self.size_in_lba - offset)
+    }
+}
+
+// Impl Driver for PartitionDisk
+impl Driver for PartitionDisk {
+    // Open
+    fn open(&mut self, _data: *mut ()) -> bool {
+        let name = self.parent_name.clone();
+        self.parent.open(&name)
+    }
+
+    // Write, the sectors out of the partition are not written
+    fn write(&mut self, data: &[u8], count: usize, offset: usize) -> usize {
+        let count = self.bounded(count, offset);
+        if count == 0 {
+            return 0;
+        }
+        self.parent.write(data, count, self.starting_lba + offset)
+    }
+
+    // Read, the sectors out of the partition are not read
+    fn read(&mut self, data: &mut [u8], count: usize, offset: usize) -> usize {
+        let count = self.bounded(count, offset);
+        if count == 0 {
+            return 0;
+        }
+        self.parent.read(data, count, self.starting_lba + offset)
+    }
+
+    // IOctrl, the sector count is the size of partition,
+    // the parent is the device where the partition starts
+    fn ioctrl(&mut self, command: &mut Command) -> bool {
+        match command {
+            Command::BLK(BLKCommand::SectorCount { count }) => {
+                *count = self.size_in_lba;
+                true
+            }
+            Command::BLK(BLKCommand::Parent { name, offset }) => {
+                *name = self.parent_name.clone();
+                *offset = self.starting_lba;
+                true
+            }
+            _ => self.parent.ioctrl(command),
+        }
+    }
+
+    // Close
+    fn close(&mut self) {
+        self.parent.close();
+    }
+}
+
+// Guid string, the first three fields are little endian
+pub fn guid_string(guid: &[u8; 16]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        get_u32(guid, 0),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        guid[8],
+        guid[9],
+        guid[10],
+        guid[11],
+        guid[12],
+        guid[13],
+        guid[14],
+        guid[15]
+    )
+}
+
+// Crc32, the ieee polynomial used by gpt
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// Get u32
+fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+// Get u64
+fn get_u64(data: &[u8], offset: usize) -> u64 {
+    get_u32(data, offset) as u64 | (get_u32(data, offset + 4) as u64) << 32
+}
@@ -11,6 +11,7 @@ use crate::traits::vk_driver::PlatDrvWrapper;
 use crate::traits::vk_kernel::Device;
 use crate::traits::vk_linkedlist::LinkedList;
 use crate::village::kernel;
+use alloc::string::ToString;
 use crate::debug_info;
 
 // Struct village device
@@ -158,36 +159,36 @@ impl VillageDevice {
 impl Device for VillageDevice {
     // Register driver
     fn register_driver(&mut self, driver: DriverWrapper) {
+        let id = driver.id();
+        let name = driver.name().to_string();
+
+        // The driver is added first, so it can be opened when mounting
+        self.base_devs.push(driver);
+
         if self.is_runtime {
-            if driver.id() == DriverID::Block {
-                kernel()
-                    .filesys()
-                    .mount_hard_drive(driver.name());
-            } else if driver.id() == DriverID::Input {
-                kernel().event().init_input_device(driver.name());
+            if id == DriverID::Block {
+                kernel().filesys().mount_hard_drive(&name);
+            } else if id == DriverID::Input {
+                kernel().event().init_input_device(&name);
             }
         }
-        self.base_devs.push(driver);
     }
 
     // Unregister driver
     fn unregister_driver(&mut self, name: &str) {
-        self.base_devs.retain_mut(|driver| {
-            if driver.name() == name {
-                if self.is_runtime {
-                    if driver.id() == DriverID::Block {
-                        kernel()
-                            .filesys()
-                            .unmount_hard_drive(driver.name());
-                    } else if driver.id() == DriverID::Input {
-                        kernel().event().exit_input_device(driver.name());
-                    }
+        // The block drivers of partitions are unregistered when unmounting
+        if self.is_runtime {
+            if let Some(driver) = self.get_driver(name) {
+                let id = driver.id();
+                if id == DriverID::Block {
+                    kernel().filesys().unmount_hard_drive(name);
+                } else if id == DriverID::Input {
+                    kernel().event().exit_input_device(name);
                 }
-                false
-            } else {
-                true
             }
-        });
+        }
+
+        self.base_devs.retain_mut(|driver| driver.name() != name);
     }
 
     // Register plat drvier
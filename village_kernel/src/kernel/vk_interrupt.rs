@@ -5,6 +5,9 @@
 // $Copyright: Copyright (C) village
 //###########################################################################
 use crate::arch::ia32::legacy::vk_exception::{VillageException, ISR_NUM, RSVD_ISR_SIZE};
+use crate::arch::ia32::legacy::vk_irqchip::VillageIrqChip;
+use crate::arch::ia32::legacy::vk_system::{irq_restore, irq_save};
+use crate::misc::lock::vk_spinlock::SpinLock;
 use crate::traits::vk_callback::Callback;
 use crate::traits::vk_kernel::Interrupt;
 use crate::traits::vk_linkedlist::LinkedList;
@@ -12,11 +15,17 @@ use crate::debug_info;
 use crate::debug_error;
 use crate::debug_warning;
 
+// Constant members
+const MAX_ISR_CBS: usize = 8;
+
 // Struct village interrupt
 pub struct VillageInterrupt {
     exception: VillageException,
+    irqchip: VillageIrqChip,
     warnings: [u8; ISR_NUM],
+    counts: [u32; ISR_NUM],
     isr_tabs: [LinkedList<Callback>; ISR_NUM],
+    lock: SpinLock,
     is_ready: bool,
 }
 
@@ -25,8 +34,11 @@ impl VillageInterrupt {
     pub const fn new() -> Self {
         Self {
             exception: VillageException::new(),
+            irqchip: VillageIrqChip::new(),
             warnings: [0; ISR_NUM],
+            counts: [0; ISR_NUM],
             isr_tabs: [const { LinkedList::new() }; ISR_NUM],
+            lock: SpinLock::new(),
             is_ready: false,
         }
     }
@@ -39,6 +51,9 @@ impl VillageInterrupt {
         // Setupt exception
         self.exception.setup();
 
+        // Setup irq chip
+        self.irqchip.setup();
+
         // Set ready flag
         self.is_ready = true;
 
@@ -52,9 +67,16 @@ impl VillageInterrupt {
         self.is_ready = false;
 
         // Clear isr table
+        let flags = irq_save();
+        self.lock.lock();
         for tab in &mut self.isr_tabs {
             tab.clear();
         }
+        self.lock.unlock();
+        irq_restore(flags);
+
+        // Exit irq chip
+        self.irqchip.exit();
 
         // Exit exception
         self.exception.exit();
@@ -69,26 +91,45 @@ impl Interrupt for VillageInterrupt {
         self.add_isr_cb(irq, callback);
     }
 
-    // Add ISR function callback
+    // Add ISR function callback, the handler calls at most MAX_ISR_CBS callbacks
     fn add_isr_cb(&mut self, irq: isize, callback: Callback) {
         let irq_idx = (irq + RSVD_ISR_SIZE as isize) as usize;
-        self.isr_tabs[irq_idx].push(callback);
+        let flags = irq_save();
+        self.lock.lock();
+        let is_full = self.isr_tabs[irq_idx].len() >= MAX_ISR_CBS;
+        if !is_full {
+            self.isr_tabs[irq_idx].push(callback);
+        }
+        self.lock.unlock();
+        irq_restore(flags);
+
+        if is_full {
+            debug_error!("IRQ {} has too many interrupt service functions, the max is {}", irq, MAX_ISR_CBS);
+        }
     }
 
     // Del ISR function callback
     fn del_isr_cb(&mut self, irq: isize, callback: Callback) {
         let irq_idx = (irq + RSVD_ISR_SIZE as isize) as usize;
+        let flags = irq_save();
+        self.lock.lock();
         let isrs = &mut self.isr_tabs[irq_idx];
         isrs.retain_mut(|cb| {
             !(cb.instance == callback.instance
                 && core::ptr::fn_addr_eq(cb.callback, callback.callback))
         });
+        self.lock.unlock();
+        irq_restore(flags);
     }
 
     // Clear ISR callbacks
     fn clear_isr_cb(&mut self, irq: isize) {
         let irq_idx = (irq + RSVD_ISR_SIZE as isize) as usize;
+        let flags = irq_save();
+        self.lock.lock();
         self.isr_tabs[irq_idx].clear();
+        self.lock.unlock();
+        irq_restore(flags);
     }
 
     // Replace ISR handler
@@ -97,6 +138,53 @@ impl Interrupt for VillageInterrupt {
         self.exception.install(irq_idx, handler);
     }
 
+    // Mask irq
+    fn mask(&mut self, irq: isize) {
+        self.irqchip.mask(irq);
+    }
+
+    // Unmask irq
+    fn unmask(&mut self, irq: isize) {
+        self.irqchip.unmask(irq);
+    }
+
+    // Is irq masked
+    fn is_masked(&mut self, irq: isize) -> bool {
+        self.irqchip.is_masked(irq)
+    }
+
+    // Set irq priority
+    fn set_priority(&mut self, irq: isize, priority: u8) -> bool {
+        self.irqchip.set_priority(irq, priority)
+    }
+
+    // Get irq priority
+    fn get_priority(&mut self, irq: isize) -> u8 {
+        self.irqchip.get_priority(irq)
+    }
+
+    // Get isr num
+    fn get_isr_num(&mut self) -> usize {
+        ISR_NUM - RSVD_ISR_SIZE
+    }
+
+    // Get the size of isr callbacks
+    fn get_isr_cb_size(&mut self, irq: isize) -> usize {
+        let irq_idx = (irq + RSVD_ISR_SIZE as isize) as usize;
+        let flags = irq_save();
+        self.lock.lock();
+        let size = self.isr_tabs[irq_idx].len();
+        self.lock.unlock();
+        irq_restore(flags);
+        size
+    }
+
+    // Get the count of irq handled
+    fn get_isr_count(&mut self, irq: isize) -> u32 {
+        let irq_idx = (irq + RSVD_ISR_SIZE as isize) as usize;
+        self.counts[irq_idx]
+    }
+
     // Interrupt handler
     fn handler(&mut self, irq: isize) {
         if !self.is_ready {
@@ -104,9 +192,22 @@ impl Interrupt for VillageInterrupt {
         }
 
         let irq_idx = (irq + RSVD_ISR_SIZE as isize) as usize;
+
+        // Count the irq
+        self.counts[irq_idx] = self.counts[irq_idx].wrapping_add(1);
+
+        // Take a snapshot of the callbacks, the callback may switch task and never
+        // return to here in time, so it must not be called with the lock held
+        let mut callbacks: [Option<Callback>; MAX_ISR_CBS] = [None; MAX_ISR_CBS];
+        self.lock.lock();
         let isrs = &mut self.isr_tabs[irq_idx];
+        let count = isrs.len();
+        for (slot, callback) in callbacks.iter_mut().zip(isrs.iter_mut()) {
+            *slot = Some(*callback);
+        }
+        self.lock.unlock();
 
-        if isrs.len() == 0 {
+        if count == 0 {
             if self.warnings[irq_idx] >= 10 {
                 debug_error!("IRQ {} no being handled correctly, system will halt on here", irq);
                 loop {}
@@ -118,7 +219,7 @@ impl Interrupt for VillageInterrupt {
             self.warnings[irq_idx] = 0;
         }
 
-        for callback in isrs.iter_mut() {
+        for callback in callbacks.iter_mut().flatten() {
             callback.call();
         }
     }
@@ -4,6 +4,7 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use crate::arch::ia32::legacy::vk_system::{irq_restore, irq_save};
 use crate::debug_error;
 use crate::debug_info;
 use crate::traits::vk_kernel::Memory;
@@ -365,7 +366,7 @@ impl VillageMemory {
 
     // Setup
     pub fn setup(&mut self) {
-        ALLOCATOR.memory.lock().initiate();
+        ALLOCATOR.locked(|memory| memory.initiate());
 
         // Output debug info
         debug_info!("Memory setup completed!");
@@ -373,7 +374,7 @@ impl VillageMemory {
 
     // Exit
     pub fn exit(&mut self) {
-        ALLOCATOR.memory.lock().exit();
+        ALLOCATOR.locked(|memory| memory.exit());
     }
 }
 
@@ -381,27 +382,27 @@ impl VillageMemory {
 impl Memory for VillageMemory {
     // Alloc
     fn alloc(&mut self, size: u32) -> u32 {
-        ALLOCATOR.memory.lock().alloc(size)
+        ALLOCATOR.locked(|memory| memory.alloc(size))
     }
 
     // Dealloc
     fn dealloc(&mut self, address: u32, size: u32) {
-        ALLOCATOR.memory.lock().dealloc(address, size);
+        ALLOCATOR.locked(|memory| memory.dealloc(address, size));
     }
 
     // Get size
     fn get_size(&mut self) -> u32 {
-        ALLOCATOR.memory.lock().get_size()
+        ALLOCATOR.locked(|memory| memory.get_size())
     }
 
     // Get used
     fn get_used(&mut self) -> u32 {
-        ALLOCATOR.memory.lock().get_used()
+        ALLOCATOR.locked(|memory| memory.get_used())
     }
 
     // Get curr addr
     fn get_curr_addr(&mut self) -> u32 {
-        ALLOCATOR.memory.lock().get_curr_addr()
+        ALLOCATOR.locked(|memory| memory.get_curr_addr())
     }
 }
 
@@ -410,6 +411,17 @@ struct GlobalAllocator {
     memory: Mutex<MemoryAllocator>,
 }
 
+// Impl global allocator
+impl GlobalAllocator {
+    // Lock the allocator, the irq is disabled to avoid deadlock with isr
+    fn locked<R>(&self, f: impl FnOnce(&mut MemoryAllocator) -> R) -> R {
+        let flags = irq_save();
+        let result = f(&mut self.memory.lock());
+        irq_restore(flags);
+        result
+    }
+}
+
 // Set global allocator
 #[global_allocator]
 static ALLOCATOR: GlobalAllocator = GlobalAllocator {
@@ -420,11 +432,11 @@ static ALLOCATOR: GlobalAllocator = GlobalAllocator {
 unsafe impl GlobalAlloc for GlobalAllocator {
     // Alloc
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.memory.lock().alloc(layout.size() as u32) as *mut u8
+        self.locked(|memory| memory.alloc(layout.size() as u32)) as *mut u8
     }
 
     // Dealloc
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        self.memory.lock().dealloc(ptr as u32, layout.size() as u32);
+        self.locked(|memory| memory.dealloc(ptr as u32, layout.size() as u32));
     }
 }
@@ -10,6 +10,7 @@ use crate::traits::vk_linkedlist::LinkedList;
 use crate::village::kernel;
 use crate::debug_error;
 use crate::debug_info;
+use alloc::format;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 
@@ -66,12 +67,35 @@ impl VillageProcess {
     // Monitor
     fn monitor(&mut self) {
         loop {
-            self.processes.retain_mut(|data| 
-                kernel().thread().is_task_alive(data.tid)
-            );
+            self.processes.retain_mut(|data| {
+                // Remove the threads that already exited
+                data.threads.retain(|tid| kernel().thread().is_task_alive(*tid));
+
+                // Terminate the remaining threads when the main thread exited
+                if !kernel().thread().is_task_alive(data.tid) {
+                    Self::stop_threads(data);
+                    return false;
+                }
+                true
+            });
             kernel().thread().sleep(10);
         }
     }
+
+    // Stop threads
+    fn stop_threads(data: &mut ProcessData) {
+        for tid in data.threads.iter() {
+            kernel().thread().stop_task(*tid);
+        }
+        data.threads.clear();
+    }
+
+    // Get process by tid
+    fn get_process_by_tid(&mut self, tid: i32) -> Option<&mut ProcessData> {
+        self.processes
+            .iter_mut()
+            .find(|p| p.tid == tid || p.threads.contains(&tid))
+    }
 }
 
 // Impl process for village process
@@ -90,8 +114,9 @@ impl Process for VillageProcess {
         // New data object
         let mut process = ProcessData::new();
 
-        // Set the path
+        // Set the path and argv
         process.path = path.to_string();
+        process.argv = argv.iter().map(|arg| arg.to_string()).collect();
 
         // Create runner
         process.container = kernel().director().create_prog_container(path);
@@ -136,6 +161,7 @@ impl Process for VillageProcess {
         if let Some(process) = self.processes
             .iter_mut().find(|p| p.path == path)
         {
+            Self::stop_threads(process);
             if let Some(runner) = &mut process.container {
                 runner.kill();
             }
@@ -147,12 +173,80 @@ impl Process for VillageProcess {
         if let Some(process) = self.processes
             .iter_mut().find(|p| p.pid == pid)
         {
+            Self::stop_threads(process);
             if let Some(runner) = &mut process.container {
                 runner.kill();
             }
         }
     }
 
+    // Create thread
+    fn create_thread(&mut self, name: &str, callback: Callback) -> i32 {
+        // Get the process of the calling task
+        let curr_tid = kernel().thread().get_task_id();
+        let process = match self.get_process_by_tid(curr_tid) {
+            Some(process) => process,
+            None => {
+                debug_error!("create thread {} failed, task {} is not a process", name, curr_tid);
+                return -1;
+            }
+        };
+
+        // Create thread task
+        let thread_na = format!("{}::{}", process.path, name);
+        let tid = kernel().thread().create_task(&thread_na, callback);
+
+        // The thread shares the tls image of the calling task
+        if let Some(task) = kernel().thread().get_task_info(curr_tid) {
+            kernel().thread().set_task_tls(tid, task.tls_image);
+        }
+
+        // Add into the process threads
+        process.threads.push(tid);
+
+        // Start task
+        kernel().thread().start_task(tid);
+
+        tid
+    }
+
+    // Join thread
+    fn join_thread(&mut self, tid: i32) -> bool {
+        // A thread can not join itself
+        let curr_tid = kernel().thread().get_task_id();
+        if tid == curr_tid {
+            debug_error!("join thread {} failed, a thread can not join itself", tid);
+            return false;
+        }
+
+        // The thread must belong to the process of the calling task
+        match self.get_process_by_tid(curr_tid) {
+            Some(process) if process.tid == tid || process.threads.contains(&tid) => {}
+            _ => {
+                debug_error!("join thread {} failed, not a thread of task {}'s process", tid, curr_tid);
+                return false;
+            }
+        }
+
+        // Block until the thread is terminated
+        kernel().thread().wait_for_task(tid);
+
+        // Remove from the process threads
+        if let Some(process) = self.get_process_by_tid(curr_tid) {
+            process.threads.retain(|t| *t != tid);
+        }
+        true
+    }
+
+    // Kill threads
+    fn kill_threads(&mut self, pid: i32) {
+        if let Some(process) = self.processes
+            .iter_mut().find(|p| p.pid == pid)
+        {
+            Self::stop_threads(process);
+        }
+    }
+
     // Is exist by path
     fn is_exist_by_path(&mut self, path: &str) -> bool {
         if let Some(_) = self.processes.iter_mut().find(|p| p.path == path) {
@@ -169,6 +263,14 @@ impl Process for VillageProcess {
         false
     }
 
+    // Get pid by tid
+    fn get_pid_by_tid(&mut self, tid: i32) -> i32 {
+        if let Some(process) = self.get_process_by_tid(tid) {
+            return process.pid;
+        }
+        -1
+    }
+
     // Get processes
     fn get_processes(&mut self) -> &mut LinkedList<ProcessData> {
         &mut self.processes
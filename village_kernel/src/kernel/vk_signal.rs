@@ -4,17 +4,22 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use crate::misc::lock::vk_spinlock::SpinLock;
 use crate::traits::vk_kernel::{Signal, Signals};
 use crate::village::kernel;
 use crate::debug_info;
 
 // Struct village signal
-pub struct VillageSignal;
+pub struct VillageSignal {
+    lock: SpinLock,
+}
 
 // Impl village signal
 impl VillageSignal {
     pub const fn new() -> Self {
-        Self {}
+        Self {
+            lock: SpinLock::new(),
+        }
     }
 }
 
@@ -34,7 +39,8 @@ impl VillageSignal {
 impl Signal for VillageSignal {
     // Raising
     fn raising(&mut self, signal: Signals) {
-        kernel().system().disable_irq();
+        let flags = kernel().system().irq_save();
+        self.lock.lock();
 
         match signal {
             Signals::None => todo!(),
@@ -45,6 +51,7 @@ impl Signal for VillageSignal {
             Signals::Kill => todo!(),
         }
 
-        kernel().system().enable_irq();
+        self.lock.unlock();
+        kernel().system().irq_restore(flags);
     }
 }
@@ -4,22 +4,31 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
-use crate::arch::ia32::legacy::vk_registers::TaskContext;
+use crate::arch::ia32::legacy::vk_registers::{TaskContext, FPU_STATE_SIZE};
+use crate::arch::ia32::legacy::vk_smp::MAX_CPUS;
+use crate::misc::lock::vk_spinlock::SpinLock;
 use crate::traits::vk_callback::{Callback, FnCallback};
-use crate::traits::vk_kernel::{Thread, ThreadState, ThreadTask};
+use crate::traits::vk_kernel::{Thread, ThreadState, ThreadTask, TlsImage, TaskInfo, TaskLocalData};
 use crate::traits::vk_linkedlist::LinkedList;
 use crate::village::kernel;
 use crate::debug_info;
+use alloc::format;
 use alloc::string::ToString;
+use alloc::vec::Vec;
 use core::ptr;
 
 // Static constants
 const TASK_STACK_SIZE: u32 = 8192;
 const PSP_FRAME_SIZE: u32 = core::mem::size_of::<TaskContext>() as u32;
+const TLS_TCB_SIZE: u32 = 4;
 
 // Struct village thread
 pub struct VillageThread {
     tasks: LinkedList<ThreadTask>,
+    queues: [LinkedList<*mut ThreadTask>; MAX_CPUS],
+    idles: [i32; MAX_CPUS],
+    fpus: [u32; MAX_CPUS],
+    lock: SpinLock,
     id_cnt: i32,
 }
 
@@ -29,6 +38,10 @@ impl VillageThread {
     pub const fn new() -> Self {
         VillageThread {
             tasks: LinkedList::new(),
+            queues: [const { LinkedList::new() }; MAX_CPUS],
+            idles: [-1; MAX_CPUS],
+            fpus: [0; MAX_CPUS],
+            lock: SpinLock::new(),
             id_cnt: 0,
         }
     }
@@ -37,7 +50,7 @@ impl VillageThread {
     pub fn setup(&mut self) {
         // Create idle task
         let idle_task_cb = Callback::new(Self::idle_task as u32).with_instance(self);
-        self.create_task("Thread::idle", idle_task_cb);
+        self.idles[0] = self.create_task("Thread::idle", idle_task_cb);
 
         // Create a monitor thread alive task
         let monitor_cb = Callback::new(Self::monitor as u32).with_instance(self);
@@ -49,20 +62,216 @@ impl VillageThread {
 
     // Start
     pub fn start(&mut self) {
-        // Set all task state to ready
-        for task in &mut self.tasks.iter_mut() {
+        let cpu_count = kernel().system().get_cpu_count();
+
+        // Create idle tasks for application processors
+        for cpu in 1..cpu_count {
+            let idle_task_cb = Callback::new(Self::idle_task as u32).with_instance(self);
+            self.idles[cpu] = self.create_task(&format!("Thread::idle{}", cpu), idle_task_cb);
+        }
+
+        let flags = self.lock();
+
+        // Clear the run queues
+        for queue in self.queues.iter_mut() {
+            queue.clear();
+        }
+
+        // Set all task state to ready and balance tasks between processors
+        let mut next = 0;
+        for task in self.tasks.iter_mut() {
             task.state = ThreadState::Ready;
+            task.cpu = match self.idles.iter().position(|id| *id == task.id) {
+                Some(cpu) => cpu,
+                None => {
+                    next += 1;
+                    (next - 1) % cpu_count
+                }
+            };
+            self.queues[task.cpu].push(task as *mut ThreadTask);
         }
+
+        self.unlock(flags);
     }
 
     // Exit
     pub fn exit(&mut self) {
+        for queue in self.queues.iter_mut() {
+            queue.clear();
+        }
         for task in &mut self.tasks.iter_mut() {
-            if task.stack_start != 0 {
-                kernel().memory().dealloc(task.stack_start, 0);
-            }
+            Self::release_task(task);
         }
         self.tasks.clear();
+        self.fpus = [0; MAX_CPUS];
+    }
+
+    // Lock the tasks, the irq is disabled to avoid deadlock with scheduler
+    fn lock(&mut self) -> u32 {
+        let flags = kernel().system().irq_save();
+        self.lock.lock();
+        flags
+    }
+
+    // Unlock the tasks
+    fn unlock(&mut self, flags: u32) {
+        self.lock.unlock();
+        kernel().system().irq_restore(flags);
+    }
+
+    // Get the current task of current processor, the current task is never removed
+    fn current(&mut self) -> Option<&mut ThreadTask> {
+        let cpu = kernel().system().get_cpu_id();
+        let flags = self.lock();
+        let task = self.queues[cpu].item().map(|task| *task);
+        self.unlock(flags);
+        task.map(|task| unsafe { &mut *task })
+    }
+
+    // Set the state of current task with the lock, returns the current task
+    fn set_current_state(&mut self, state: ThreadState, ticks: u32) -> Option<*mut ThreadTask> {
+        let cpu = kernel().system().get_cpu_id();
+        let flags = self.lock();
+        let task = self.queues[cpu].item().map(|task| *task);
+        if let Some(task) = task {
+            unsafe {
+                (*task).state = state;
+                (*task).ticks = ticks;
+            }
+        }
+        self.unlock(flags);
+        task
+    }
+
+    // Wait until the state of task is changed by the scheduler or other processor
+    fn wait_state(&mut self, task: *mut ThreadTask, state: ThreadState) {
+        loop {
+            let flags = self.lock();
+            let changed = unsafe { (*task).state } != state;
+            self.unlock(flags);
+            if changed {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    // Is the task running on any processor
+    fn is_current(queues: &mut [LinkedList<*mut ThreadTask>; MAX_CPUS], task: *mut ThreadTask) -> bool {
+        queues
+            .iter_mut()
+            .any(|queue| queue.item().map(|t| *t == task).unwrap_or(false))
+    }
+
+    // Get the processor with the least tasks
+    fn select_cpu(&mut self) -> usize {
+        let cpu_count = kernel().system().get_cpu_count();
+        (0..cpu_count)
+            .min_by_key(|cpu| self.queues[*cpu].len())
+            .unwrap_or(0)
+    }
+
+    // Find the task by id and access it with the lock
+    fn with_task<R>(&mut self, tid: i32, f: impl FnOnce(&mut ThreadTask) -> R) -> Option<R> {
+        let flags = self.lock();
+        let result = self.tasks.iter_mut().find(|t| t.id == tid).map(f);
+        self.unlock(flags);
+        result
+    }
+
+    // Wake the tasks waiting for the task, the lock must be held
+    fn wake_waiters(tasks: &mut LinkedList<ThreadTask>, tid: i32) {
+        let waiters = match tasks.iter_mut().find(|task| task.id == tid) {
+            Some(task) => core::mem::take(&mut task.waiters),
+            None => return,
+        };
+
+        for task in tasks.iter_mut() {
+            if waiters.contains(&task.id) && task.state == ThreadState::Blocked {
+                task.state = ThreadState::Ready;
+            }
+        }
+    }
+
+    // Remove terminated tasks which are not running on any processor,
+    // the task whose fpu state is loaded is removed after the state is saved
+    fn remove_tasks(&mut self, f: impl Fn(&ThreadTask) -> bool) {
+        let flags = self.lock();
+        let queues = &mut self.queues;
+        let fpus = &self.fpus;
+        self.tasks.retain_mut(|task| {
+            let ptr = task as *mut ThreadTask;
+            if f(task) && !Self::is_current(queues, ptr) && (task.fpu == 0 || !fpus.contains(&task.fpu)) {
+                queues[task.cpu].retain(|t| *t != ptr);
+                Self::release_task(task);
+                false
+            } else {
+                true
+            }
+        });
+        self.unlock(flags);
+    }
+
+    // Alloc the tls block, the tls data is placed below the thread pointer,
+    // the task with an empty tls image has no tls block
+    fn alloc_tls(task: &mut ThreadTask, image: TlsImage) {
+        if image.mem_size == 0 {
+            task.tls = 0;
+            task.tls_block = 0;
+            task.tls_image = image;
+            return;
+        }
+
+        let align = if image.align > TLS_TCB_SIZE { image.align } else { TLS_TCB_SIZE };
+        let tls_size = (image.mem_size + align - 1) / align * align;
+        let block_size = tls_size + align + TLS_TCB_SIZE;
+        let block = kernel().memory().alloc(block_size);
+        let tls = (block + tls_size + align - 1) / align * align;
+
+        unsafe {
+            // Clear the tls block
+            ptr::write_bytes(block as *mut u8, 0, block_size as usize);
+
+            // Copy the initialized tls data
+            if image.file_size != 0 {
+                ptr::copy_nonoverlapping(
+                    image.addr as *const u8,
+                    (tls - tls_size) as *mut u8,
+                    image.file_size as usize,
+                );
+            }
+
+            // The first word of tcb points to itself
+            ptr::write(tls as *mut u32, tls);
+        }
+
+        task.tls = tls;
+        task.tls_block = block;
+        task.tls_image = image;
+    }
+
+    // Release the stack, tls block, fpu state and task locals
+    fn release_task(task: &mut ThreadTask) {
+        if task.stack_start != 0 {
+            kernel().memory().dealloc(task.stack_start, 0);
+            task.stack_start = 0;
+        }
+
+        if task.tls_block != 0 {
+            kernel().memory().dealloc(task.tls_block, 0);
+            task.tls_block = 0;
+            task.tls = 0;
+        }
+
+        if task.fpu != 0 {
+            kernel().memory().dealloc(task.fpu, 0);
+            task.fpu = 0;
+        }
+
+        for local in task.locals.iter() {
+            (local.drop)(local.data);
+        }
+        task.locals.clear();
     }
 
     // Task function handler
@@ -75,10 +284,9 @@ impl VillageThread {
     // Idle task
     fn idle_task(&mut self) {
         loop {
-            if let Some(task) = self.tasks.item() {
-                task.state = ThreadState::Ready;
+            if let Some(task) = self.set_current_state(ThreadState::Ready, 0) {
                 kernel().scheduler().sched();
-                while task.state == ThreadState::Ready {}
+                self.wait_state(task, ThreadState::Ready);
             }
         }
     }
@@ -86,14 +294,7 @@ impl VillageThread {
     // Monitor task
     fn monitor(&mut self) {
         loop {
-            self.tasks.retain_mut(|task| {
-                if task.state == ThreadState::Terminated {
-                    kernel().memory().dealloc(task.stack_start, 0);
-                    false
-                } else {
-                    true
-                }
-            });
+            self.remove_tasks(|task| task.state == ThreadState::Terminated);
             self.sleep(10);
         }
     }
@@ -124,80 +325,129 @@ impl Thread for VillageThread {
         let tid = self.id_cnt;
         self.id_cnt += 1;
 
-        let task = ThreadTask {
+        let mut task = ThreadTask {
             name: name.to_string(),
             id: tid,
             psp,
             ticks: 0,
             stack_start,
             stack_ended,
+            tls: 0,
+            tls_block: 0,
+            tls_image: TlsImage::new(),
+            locals: Vec::new(),
+            fpu: 0,
+            cpu: 0,
+            waiters: Vec::new(),
             state: ThreadState::New,
         };
 
-        // Add task into tasks list
+        let flags = self.lock();
+
+        // Add task into tasks list and the run queue of the least loaded processor
+        task.cpu = self.select_cpu();
         self.tasks.push(task);
+        if let Some(task) = self.tasks.iter_mut().next_back() {
+            let ptr = task as *mut ThreadTask;
+            self.queues[task.cpu].push(ptr);
+        }
+
+        self.unlock(flags);
         tid
     }
 
     // Start task
     fn start_task(&mut self, tid: i32) {
-        if let Some(task) = self.tasks.iter_mut().find(|t| t.id == tid) {
-            task.state = ThreadState::Ready;
-        }
+        self.with_task(tid, |task| task.state = ThreadState::Ready);
     }
 
-    // Stop task
+    // Stop task, the tasks waiting for it are waked
     fn stop_task(&mut self, tid: i32) {
-        if let Some(task) = self.tasks.iter_mut().find(|t| t.id == tid) {
+        let flags = self.lock();
+        if let Some(task) = self.tasks.iter_mut().find(|task| task.id == tid) {
             task.state = ThreadState::Terminated;
         }
+        Self::wake_waiters(&mut self.tasks, tid);
+        self.unlock(flags);
     }
 
-    // Thread wait for task
+    // Thread wait for task, the current task is blocked until the task is terminated
     fn wait_for_task(&mut self, tid: i32) {
-        if let Some(task) = self.tasks.iter_mut().find(|t| t.id == tid) {
-            while task.state != ThreadState::Terminated {}
+        loop {
+            let cpu = kernel().system().get_cpu_id();
+            let flags = self.lock();
+
+            // Add the current task into the waiters of the task
+            let current = self.queues[cpu].item().map(|task| *task);
+            let mut waiting = false;
+            if let Some(current) = current {
+                let id = unsafe { (*current).id };
+                let alive = self.tasks.iter_mut().find(|task| task.id == tid && task.state != ThreadState::Terminated);
+                if let Some(task) = alive {
+                    if id != tid {
+                        if !task.waiters.contains(&id) {
+                            task.waiters.push(id);
+                        }
+                        unsafe {
+                            (*current).state = ThreadState::Blocked;
+                            (*current).ticks = 0;
+                        }
+                        waiting = true;
+                    }
+                }
+            }
+
+            self.unlock(flags);
+
+            // The task is terminated or the task can not wait for itself
+            let current = match current {
+                Some(current) if waiting => current,
+                _ => break,
+            };
+
+            kernel().scheduler().sched();
+            self.wait_state(current, ThreadState::Blocked);
         }
     }
 
     // Exit task blocked state
     fn exit_blocked(&mut self, tid: i32) {
-        if let Some(task) = self.tasks.iter_mut().find(|t| t.id == tid) {
+        self.with_task(tid, |task| {
             if task.state == ThreadState::Blocked {
                 task.state = ThreadState::Ready;
             }
-        }
+        });
     }
 
     // Thread delete task
     fn delete_task(&mut self, tid: i32) {
-        self.tasks.retain(|task| {
-            if task.id == tid {
-                kernel().memory().dealloc(task.stack_start, 0);
-                false
-            } else {
-                true
-            }
-        });
+        // The running task is terminated and released by monitor
+        self.stop_task(tid);
+        self.remove_tasks(|task| task.id == tid);
     }
 
     // Thread check task is alive
     fn is_task_alive(&mut self, tid: i32) -> bool {
-        self.tasks
-            .iter_mut()
-            .find(|t| t.id == tid)
-            .map(|t| t.state != ThreadState::Terminated)
+        self.with_task(tid, |task| task.state != ThreadState::Terminated)
             .unwrap_or(false)
     }
 
-    // Get tasks
-    fn get_tasks(&mut self) -> &mut LinkedList<ThreadTask> {
-        &mut self.tasks
+    // Get task info
+    fn get_task_info(&mut self, tid: i32) -> Option<TaskInfo> {
+        self.with_task(tid, |task| TaskInfo::from_task(task))
+    }
+
+    // Get task infos
+    fn get_task_infos(&mut self) -> Vec<TaskInfo> {
+        let flags = self.lock();
+        let infos = self.tasks.iter_mut().map(|task| TaskInfo::from_task(task)).collect();
+        self.unlock(flags);
+        infos
     }
 
     // Get current task id
     fn get_task_id(&mut self) -> i32 {
-        if let Some(task) = self.tasks.item() {
+        if let Some(task) = self.current() {
             return task.id;
         }
         -1
@@ -205,73 +455,188 @@ impl Thread for VillageThread {
 
     // Thread Sleep
     fn sleep(&mut self, ticks: u32) {
-        if let Some(task) = self.tasks.item() {
-            task.state = ThreadState::Blocked;
-            task.ticks = kernel().system().get_ticks() + ticks;
+        let ticks = kernel().system().get_ticks() + ticks;
+        if let Some(task) = self.set_current_state(ThreadState::Blocked, ticks) {
             kernel().scheduler().sched();
-            while task.state == ThreadState::Blocked {}
+            self.wait_state(task, ThreadState::Blocked);
         }
     }
 
     // Thread Blocked
     fn blocked(&mut self) {
-        if let Some(task) = self.tasks.item() {
-            task.state = ThreadState::Blocked;
-            task.ticks = 0;
+        if let Some(task) = self.set_current_state(ThreadState::Blocked, 0) {
             kernel().scheduler().sched();
-            while task.state == ThreadState::Blocked {}
+            self.wait_state(task, ThreadState::Blocked);
         }
     }
 
     // Thread Terminated
     fn terminated(&mut self) {
-        if let Some(task) = self.tasks.item() {
-            task.state = ThreadState::Terminated;
+        if let Some(task) = self.current() {
+            let tid = task.id;
+            self.stop_task(tid);
             kernel().scheduler().sched();
         }
     }
 
+    // Set task tls image
+    fn set_task_tls(&mut self, tid: i32, image: TlsImage) -> bool {
+        self.with_task(tid, |task| {
+            if task.state != ThreadState::New {
+                return false;
+            }
+            if task.tls_block != 0 {
+                kernel().memory().dealloc(task.tls_block, 0);
+            }
+            Self::alloc_tls(task, image);
+            true
+        })
+        .unwrap_or(false)
+    }
+
+    // Get current task tls
+    fn get_task_tls(&mut self) -> u32 {
+        if let Some(task) = self.current() {
+            task.tls
+        } else {
+            0
+        }
+    }
+
+    // Get current task local
+    fn get_task_local(&mut self, key: usize) -> *mut () {
+        if let Some(task) = self.current() {
+            if let Some(local) = task.locals.iter().find(|l| l.key == key) {
+                return local.data;
+            }
+        }
+        ptr::null_mut()
+    }
+
+    // Set current task local
+    fn set_task_local(&mut self, key: usize, data: *mut (), drop: fn(*mut ())) {
+        if let Some(task) = self.current() {
+            if let Some(local) = task.locals.iter_mut().find(|l| l.key == key) {
+                (local.drop)(local.data);
+                local.data = data;
+                local.drop = drop;
+            } else {
+                task.locals.push(TaskLocalData { key, data, drop });
+            }
+        }
+    }
+
+    // Get current task fpu area
+    fn get_task_fpu(&mut self) -> u32 {
+        if let Some(task) = self.current() {
+            task.fpu
+        } else {
+            0
+        }
+    }
+
+    // Alloc current task fpu area, the area is allocated on the first use of fpu
+    fn alloc_task_fpu(&mut self) -> u32 {
+        let Some(task) = self.current() else {
+            return 0;
+        };
+
+        if task.fpu == 0 {
+            let fpu = kernel().memory().alloc(FPU_STATE_SIZE);
+            if fpu != 0 {
+                unsafe {
+                    ptr::write_bytes(fpu as *mut u8, 0, FPU_STATE_SIZE as usize);
+                }
+            }
+            task.fpu = fpu;
+        }
+        task.fpu
+    }
+
+    // Get the fpu area loaded on current processor
+    fn get_fpu_owner(&mut self) -> u32 {
+        self.fpus[kernel().system().get_cpu_id()]
+    }
+
+    // Set the fpu area loaded on current processor
+    fn set_fpu_owner(&mut self, area: u32) {
+        let cpu = kernel().system().get_cpu_id();
+        let flags = self.lock();
+        self.fpus[cpu] = area;
+        self.unlock(flags);
+    }
+
     // Save task PSP, the first item must be None
     fn save_task_psp(&mut self, psp: u32) {
-        if let Some(task) = self.tasks.item() {
+        if let Some(task) = self.current() {
             task.psp = psp;
         }
     }
 
     // Get current task psp
     fn get_task_psp(&mut self) -> u32 {
-        if let Some(task) = self.tasks.item() {
+        if let Some(task) = self.current() {
             task.psp
         } else {
             0
         }
     }
 
-    // Select next task
+    // Select next task of current processor
     fn select_next_task(&mut self) {
-        loop {
+        let cpu = kernel().system().get_cpu_id();
+
+        self.lock.lock();
+
+        // The queue has no task to run
+        if self.queues[cpu].len() == 0 {
+            self.lock.unlock();
+            return;
+        }
+
+        // Scan the queue once, the lock is not held while spinning
+        let count = self.queues[cpu].len();
+        let ticks = kernel().system().get_ticks();
+        for _ in 0..count {
             // Get next task
-            if let Some(task) = self.tasks.cycle() {
+            if let Some(task) = self.queues[cpu].cycle().map(|task| unsafe { &mut **task }) {
                 // Task state is Blocked
                 if task.state == ThreadState::Blocked {
-                    if task.ticks != 0 && task.ticks <= kernel().system().get_ticks() {
+                    if task.ticks != 0 && task.ticks <= ticks {
                         task.ticks = 0;
                         task.state = ThreadState::Running;
-                        break;
+                        self.lock.unlock();
+                        return;
                     }
                 }
-                
+
                 // Task state is ready
                 else if task.state == ThreadState::Ready {
                     task.state = ThreadState::Running;
-                    break;
+                    self.lock.unlock();
+                    return;
                 }
 
                 // Task state is running
                 else if task.state == ThreadState::Running {
+                    self.lock.unlock();
+                    return;
+                }
+            }
+        }
+
+        // No task can run, the idle task of processor is selected,
+        // the current task is kept when the processor has no idle task
+        let idle = self.idles[cpu];
+        for _ in 0..count {
+            if let Some(task) = self.queues[cpu].cycle().map(|task| unsafe { &mut **task }) {
+                if task.id == idle {
+                    task.state = ThreadState::Running;
                     break;
                 }
             }
         }
+
+        self.lock.unlock();
     }
 }
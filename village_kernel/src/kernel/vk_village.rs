@@ -170,6 +170,9 @@ impl Kernel for VillageKernel {
 
     // Start
     fn start(&mut self) {
+        // Start application processors
+        self.system.start_cpus();
+
         // Start thread
         self.thread.start();
 
@@ -18,9 +18,17 @@ pub mod village;
 pub mod arch {
     pub mod ia32 {
         pub mod legacy {
+            pub mod vk_acpi;
             pub mod vk_exception;
+            pub mod vk_fpu;
+            pub mod vk_ioapic;
+            pub mod vk_irqchip;
+            pub mod vk_lapic;
             pub mod vk_registers;
+            pub mod vk_rtc;
             pub mod vk_scheduler;
+            pub mod vk_segment;
+            pub mod vk_smp;
             pub mod vk_system;
         }
     }
@@ -82,21 +90,59 @@ pub mod drivers {
             pub mod vk_pic32_uart;
         }
     }
+    pub mod virtdrv {
+        pub mod block {
+            pub mod vk_loop_disk;
+        }
+    }
 }
 
 // import filesys modules
 pub mod filesys {
     pub mod fs {
+        pub mod devfs {
+            pub mod vk_devfs_system;
+        }
+        pub mod ext2 {
+            pub mod vk_ext2_diskio;
+            pub mod vk_ext2_folder;
+            pub mod vk_ext2_inode;
+            pub mod vk_ext2_system;
+        }
+        pub mod exfat {
+            pub mod vk_exfat_diskio;
+            pub mod vk_exfat_entry;
+            pub mod vk_exfat_folder;
+            pub mod vk_exfat_system;
+        }
         pub mod fat {
+            pub mod vk_fat_checker;
+            pub mod vk_fat_codepage;
             pub mod vk_fat_diskio;
             pub mod vk_fat_entry;
             pub mod vk_fat_filedir;
             pub mod vk_fat_folder;
+            pub mod vk_fat_format;
+            pub mod vk_fat_journal;
             pub mod vk_fat_object;
             pub mod vk_fat_system;
         }
+        pub mod iso9660 {
+            pub mod vk_iso9660_diskio;
+            pub mod vk_iso9660_record;
+            pub mod vk_iso9660_system;
+        }
+        pub mod procfs {
+            pub mod vk_procfs_system;
+        }
+        pub mod ramfs {
+            pub mod vk_ramfs_node;
+            pub mod vk_ramfs_system;
+        }
     }
+    pub mod vk_blkcache;
     pub mod vk_filesystem;
+    pub mod vk_partition;
 }
 
 // import kernel modules
@@ -122,6 +168,7 @@ pub mod kernel {
 // import misc modules
 pub mod misc {
     pub mod fopts {
+        pub mod vk_block_fopt;
         pub mod vk_dev_fopt;
         pub mod vk_dir_fopt;
         pub mod vk_file_fopt;
@@ -153,12 +200,16 @@ pub mod terminal {
         pub mod vk_cmd_debug;
         pub mod vk_cmd_device;
         pub mod vk_cmd_echo;
+        pub mod vk_cmd_fdisk;
         pub mod vk_cmd_filesys;
         pub mod vk_cmd_help;
         pub mod vk_cmd_kill;
         pub mod vk_cmd_lib;
+        pub mod vk_cmd_losetup;
         pub mod vk_cmd_memory;
+        pub mod vk_cmd_mkfs;
         pub mod vk_cmd_mod;
+        pub mod vk_cmd_mount;
         pub mod vk_cmd_null;
         pub mod vk_cmd_power;
         pub mod vk_cmd_process;
@@ -182,6 +233,7 @@ pub mod traits {
     pub mod vk_linkedlist;
     pub mod vk_extension;
     pub mod vk_macro;
+    pub mod vk_task_local;
 }
 
 // import vendor modules
@@ -200,7 +252,9 @@ pub mod vendor {
 // import vklibs modules
 pub mod vklibs {
     pub mod libc {
+        pub mod errno;
         pub mod stdlib;
         pub mod string;
+        pub mod unistd;
     }
 }
@@ -0,0 +1,97 @@
+//###########################################################################
+// vk_block_fopt.rs
+// The specific implementation of functions related to block opt
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::village::kernel;
+use crate::traits::vk_driver::{BLKCommand, Command, DriverID};
+use alloc::string::{String, ToString};
+
+// Struct BlockFopt
+pub struct BlockFopt {
+    name: String,
+}
+
+// Impl BlockFopt
+impl BlockFopt {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            name: String::new(),
+        }
+    }
+}
+
+// Impl BlockFopt
+impl BlockFopt {
+    // Open
+    pub fn open(&mut self, name: &str) -> bool {
+        self.name = name.to_string();
+        if let Some(driver) = kernel().device().get_driver(name) {
+            return driver.id() == DriverID::Block;
+        }
+        false
+    }
+
+    // Write
+    pub fn write(&mut self, data: &[u8], count: usize, lba: usize) -> usize {
+        kernel().filesys().write_block(&self.name, data, count, lba)
+    }
+
+    // Read
+    pub fn read(&mut self, data: &mut [u8], count: usize, lba: usize) -> usize {
+        kernel().filesys().read_block(&self.name, data, count, lba)
+    }
+
+    // Get sector count
+    pub fn get_sector_count(&mut self) -> usize {
+        let mut command = Command::BLK(BLKCommand::SectorCount { count: 0 });
+        if let Some(driver) = kernel().device().get_driver(&self.name)
+            && driver.ioctrl(&mut command)
+            && let Command::BLK(BLKCommand::SectorCount { count }) = command
+        {
+            return count;
+        }
+        0
+    }
+
+    // Get starting lba, the sector where the partition starts in its disk
+    pub fn get_starting_lba(&mut self) -> usize {
+        let mut command = Command::BLK(BLKCommand::Parent { name: String::new(), offset: 0 });
+        if let Some(driver) = kernel().device().get_driver(&self.name)
+            && driver.ioctrl(&mut command)
+            && let Command::BLK(BLKCommand::Parent { offset, .. }) = command
+        {
+            return offset;
+        }
+        0
+    }
+
+    // Is read only, the device can not be written
+    pub fn is_read_only(&mut self) -> bool {
+        let mut command = Command::BLK(BLKCommand::ReadOnly { read_only: false });
+        if let Some(driver) = kernel().device().get_driver(&self.name)
+            && driver.ioctrl(&mut command)
+            && let Command::BLK(BLKCommand::ReadOnly { read_only }) = command
+        {
+            return read_only;
+        }
+        false
+    }
+
+    // Sync
+    pub fn sync(&mut self) -> bool {
+        kernel().filesys().sync_block(&self.name)
+    }
+
+    // Close
+    pub fn close(&mut self) {
+        self.sync();
+    }
+
+    // Get_name
+    pub fn get_name(&mut self) -> &str {
+        &self.name
+    }
+}
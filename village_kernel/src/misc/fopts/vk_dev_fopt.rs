@@ -61,7 +61,7 @@ impl DevFopt {
     // Close
     pub fn close(&mut self) {
         if let Some(driver) = kernel().device().get_driver(&self.name) {
-            return driver.close();
+            driver.close();
         }
     }
 
@@ -29,8 +29,8 @@ impl DirFopt {
 impl DirFopt {
     // Is exist
     pub fn exist(&mut self, path: &str) -> bool {
-        if let Some(volume) = kernel().filesys().get_volume(path) {
-            return volume.exist(path, FileType::Directory);
+        if let Some((volume, name)) = kernel().filesys().resolve_volume(path) {
+            return volume.exist(&name, FileType::Directory);
         }
         false
     }
@@ -38,8 +38,14 @@ impl DirFopt {
     // Open
     pub fn open(&mut self, path: &str, mode: FileMode) -> bool {
         self.path = path.to_string();
-        if let Some(volume) = kernel().filesys().get_volume(&self.path) {
-            self.fd = volume.opendir(path, mode);
+
+        // The directory can not be created on read only mount
+        if kernel().filesys().is_read_only(path) && mode.contains(FileMode::CREATE_NEW) {
+            return false;
+        }
+
+        if let Some((volume, name)) = kernel().filesys().resolve_volume(path) {
+            self.fd = volume.opendir(&name, mode);
             return self.fd != 0;
         }
         false
@@ -4,7 +4,7 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
-use crate::traits::vk_filesys::{FileMode, FileType};
+use crate::traits::vk_filesys::{FileMode, FileStat, FileType, SeekFrom};
 use crate::village::kernel;
 use alloc::string::{String, ToString};
 
@@ -12,6 +12,8 @@ use alloc::string::{String, ToString};
 pub struct FileFopt {
     path: String,
     fd: usize,
+    pos: usize,
+    append: bool,
 }
 
 // Imp FileFopt
@@ -21,6 +23,8 @@ impl FileFopt {
         Self {
             path: String::new(),
             fd: 0,
+            pos: 0,
+            append: false,
         }
     }
 }
@@ -29,38 +33,102 @@ impl FileFopt {
 impl FileFopt {
     // Is exist
     pub fn exist(&mut self, path: &str) -> bool {
-        if let Some(volume) = kernel().filesys().get_volume(path) {
-            return volume.exist(path, FileType::File);
+        if let Some((volume, name)) = kernel().filesys().resolve_volume(path) {
+            return volume.exist(&name, FileType::File);
         }
         false
     }
 
-    // Open
+    // Open, the position is at the start of file
     pub fn open(&mut self, path: &str, mode: FileMode) -> bool {
         self.path = path.to_string();
-        if let Some(volume) = kernel().filesys().get_volume(&self.path) {
-            self.fd = volume.open(path, mode);
+        self.pos = 0;
+        self.append = mode.contains_all(FileMode::OPEN_APPEND);
+
+        // The file can not be modified on read only mount
+        if kernel().filesys().is_read_only(path)
+            && (mode.contains(FileMode::WRITE)
+                || mode.contains(FileMode::CREATE_NEW)
+                || mode.contains(FileMode::TRUNCATE)
+                || mode.contains(FileMode::CREATE_ALWAYS))
+        {
+            return false;
+        }
+
+        if let Some((volume, name)) = kernel().filesys().resolve_volume(path) {
+            self.fd = volume.open(&name, mode);
             return self.fd != 0;
         }
         false
     }
 
-    // Write
-    pub fn write(&mut self, data: &[u8], size: usize, offset: usize) -> usize {
+    // Write at the position, the data is appended to the end in append mode
+    pub fn write(&mut self, data: &[u8], size: usize) -> usize {
+        if self.append {
+            self.pos = self.size();
+        }
+        let written = self.write_at(data, size, self.pos);
+        self.pos += written;
+        written
+    }
+
+    // Read at the position
+    pub fn read(&mut self, data: &mut [u8], size: usize) -> usize {
+        let readed = self.read_at(data, size, self.pos);
+        self.pos += readed;
+        readed
+    }
+
+    // Write at the offset, the position is not changed
+    pub fn write_at(&mut self, data: &[u8], size: usize, offset: usize) -> usize {
+        if kernel().filesys().is_read_only(&self.path) {
+            return 0;
+        }
         if let Some(volume) = kernel().filesys().get_volume(&self.path) {
             return volume.write(self.fd, data, size, offset);
         }
         0
     }
 
-    // Read
-    pub fn read(&mut self, data: &mut [u8], size: usize, offset: usize) -> usize {
+    // Read at the offset, the position is not changed
+    pub fn read_at(&mut self, data: &mut [u8], size: usize, offset: usize) -> usize {
         if let Some(volume) = kernel().filesys().get_volume(&self.path) {
             return volume.read(self.fd, data, size, offset);
         }
         0
     }
 
+    // Seek, the position can be after the end of file
+    pub fn seek(&mut self, pos: SeekFrom) -> Option<usize> {
+        self.pos = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::End(offset) => self.size().checked_add_signed(offset)?,
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset)?,
+        };
+        Some(self.pos)
+    }
+
+    // Tell
+    pub fn tell(&mut self) -> usize {
+        self.pos
+    }
+
+    // Set len, the file is truncated or extended with zero
+    pub fn set_len(&mut self, size: usize) -> bool {
+        if kernel().filesys().is_read_only(&self.path) {
+            return false;
+        }
+        if let Some(volume) = kernel().filesys().get_volume(&self.path) {
+            return volume.set_len(self.fd, size);
+        }
+        false
+    }
+
+    // Truncate the file at the position
+    pub fn truncate(&mut self) -> bool {
+        self.set_len(self.pos)
+    }
+
     // Size
     pub fn size(&mut self) -> usize {
         if let Some(volume) = kernel().filesys().get_volume(&self.path) {
@@ -69,6 +137,11 @@ impl FileFopt {
         0
     }
 
+    // Fstat
+    pub fn fstat(&mut self) -> Option<FileStat> {
+        kernel().filesys().get_volume(&self.path)?.fstat(self.fd)
+    }
+
     // Flush
     pub fn flush(&mut self) {
         if let Some(volume) = kernel().filesys().get_volume(&self.path) {
@@ -4,8 +4,17 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_dir_fopt::DirFopt;
+use super::vk_file_fopt::FileFopt;
+use crate::traits::vk_filesys::{FileDir, FileFlags, FileMode, FileStat, FileTime, FileType};
 use crate::village::kernel;
-use alloc::string::String;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Constant members
+const COPY_BLOCK_SIZE: usize = 4096;
 
 // Struct FilesysFopt
 pub struct FilesysFopt {
@@ -24,28 +33,249 @@ impl FilesysFopt {
 
 // Impl FilesysFopt
 impl FilesysFopt {
-    // Moving
+    // Moving, the volume renames the entry when the source and target are on the same volume
     pub fn moving(&mut self, source: &str, target: &str) -> bool {
-        let _ = source;
-        let _ = target;
-        false
+        if kernel().filesys().is_read_only(source) || kernel().filesys().is_read_only(target) {
+            return false;
+        }
+        if kernel().filesys().is_backing(source) || kernel().filesys().is_backing(target) {
+            return false;
+        }
+
+        let target = self.target_path(source, target);
+
+        // Rename on the same volume
+        let source_vol = self.volume_of(source);
+        if source_vol.is_some() && source_vol == self.volume_of(&target) {
+            let name = match kernel().filesys().resolve_volume(&target) {
+                Some((_, name)) => name,
+                None => return false,
+            };
+            if let Some((volume, source)) = kernel().filesys().resolve_volume(source) {
+                return volume.rename(&source, &name);
+            }
+            return false;
+        }
+
+        // Copy and remove across volumes
+        if target.starts_with(&format!("{}/", source)) {
+            return false;
+        }
+        let mut report = |_: &str, _: bool| {};
+        self.copy_tree(source, &target, &mut report) && self.remove_recursive(source, &mut report)
     }
 
-    // Copy
+    // Copy file
     pub fn copy(&mut self, source: &str, target: &str) -> bool {
-        let _ = source;
-        let _ = target;
-        false
+        match self.stat(source) {
+            Some(stat) if stat.typid == FileType::File => {
+                let target = self.target_path(source, target);
+                self.copy_file(source, &target)
+            }
+            _ => false,
+        }
+    }
+
+    // Copy file or directory recursively, the result of each item is reported
+    pub fn copy_recursive(&mut self, source: &str, target: &str, report: &mut dyn FnMut(&str, bool)) -> bool {
+        let target = self.target_path(source, target);
+
+        // Can not copy a directory into itself
+        if target == source || target.starts_with(&format!("{}/", source)) {
+            report(&target, false);
+            return false;
+        }
+
+        self.copy_tree(source, &target, report)
+    }
+
+    // Remove file or directory recursively, the result of each item is reported
+    pub fn remove_recursive(&mut self, path: &str, report: &mut dyn FnMut(&str, bool)) -> bool {
+        let mut result = true;
+
+        if let Some(stat) = self.stat(path) {
+            if stat.typid == FileType::Directory {
+                for name in self.list_names(path) {
+                    let child = Self::join_path(path, &name);
+                    result &= self.remove_recursive(&child, report);
+                }
+            }
+        }
+
+        let removed = self.remove(path);
+        report(path, removed);
+        result && removed
     }
 
     // Remove
     pub fn remove(&mut self, source: &str) -> bool {
-        if let Some(volume) = kernel().filesys().get_volume(source) {
-            return volume.remove(source);
+        if kernel().filesys().is_read_only(source) || kernel().filesys().is_backing(source) {
+            return false;
+        }
+        if let Some((volume, name)) = kernel().filesys().resolve_volume(source) {
+            return volume.remove(&name);
         }
         false
     }
 
+    // Stat
+    pub fn stat(&mut self, path: &str) -> Option<FileStat> {
+        let (volume, name) = kernel().filesys().resolve_volume(path)?;
+        volume.stat(&name)
+    }
+
+    // Set flags
+    pub fn set_flags(&mut self, path: &str, flags: FileFlags) -> bool {
+        if kernel().filesys().is_read_only(path) {
+            return false;
+        }
+        if let Some((volume, name)) = kernel().filesys().resolve_volume(path) {
+            return volume.set_flags(&name, flags);
+        }
+        false
+    }
+
+    // Set time
+    pub fn set_time(&mut self, path: &str, write_time: FileTime, access_time: FileTime) -> bool {
+        if kernel().filesys().is_read_only(path) {
+            return false;
+        }
+        if let Some((volume, name)) = kernel().filesys().resolve_volume(path) {
+            return volume.set_time(&name, write_time, access_time);
+        }
+        false
+    }
+
+    // Join path
+    fn join_path(path: &str, name: &str) -> String {
+        if path.ends_with('/') {
+            format!("{}{}", path, name)
+        } else {
+            format!("{}/{}", path, name)
+        }
+    }
+
+    // Get the mount path of volume which the path is on
+    fn volume_of(&mut self, path: &str) -> Option<String> {
+        let (volume, _) = kernel().filesys().resolve_volume(path)?;
+        Some(volume.get_mount_path().to_string())
+    }
+
+    // Get target path, the source is placed into the target when the target is a directory
+    fn target_path(&mut self, source: &str, target: &str) -> String {
+        if DirFopt::new().exist(target) {
+            if let Some(pos) = source.rfind('/') {
+                return Self::join_path(target, &source[pos + 1..]);
+            }
+        }
+        target.to_string()
+    }
+
+    // List the names of directory, the dot entries are skipped
+    fn list_names(&mut self, path: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut dir = DirFopt::new();
+
+        if dir.open(path, FileMode::READ) {
+            let size = dir.size();
+            let mut dirs = vec![FileDir::new(); size];
+            let size = dir.read(&mut dirs, size);
+
+            for entry in dirs.iter().take(size) {
+                if entry.name == "." || entry.name == ".." {
+                    continue;
+                }
+                if entry.typid == FileType::File || entry.typid == FileType::Directory {
+                    names.push(entry.name.clone());
+                }
+            }
+
+            dir.close();
+        }
+
+        names
+    }
+
+    // Copy tree
+    fn copy_tree(&mut self, source: &str, target: &str, report: &mut dyn FnMut(&str, bool)) -> bool {
+        let stat = match self.stat(source) {
+            Some(stat) => stat,
+            None => {
+                report(source, false);
+                return false;
+            }
+        };
+
+        if stat.typid != FileType::Directory {
+            let result = self.copy_file(source, target);
+            report(target, result);
+            return result;
+        }
+
+        // Create the target directory
+        let mut dir = DirFopt::new();
+        let created = dir.exist(target) || dir.open(target, FileMode::CREATE_NEW);
+        if created {
+            dir.close();
+        }
+        report(target, created);
+        if !created {
+            return false;
+        }
+
+        // Copy the children
+        let mut result = true;
+        for name in self.list_names(source) {
+            let src = Self::join_path(source, &name);
+            let dst = Self::join_path(target, &name);
+            result &= self.copy_tree(&src, &dst, report);
+        }
+        result
+    }
+
+    // Copy file in blocks
+    fn copy_file(&mut self, source: &str, target: &str) -> bool {
+        let mut src = FileFopt::new();
+        if !src.open(source, FileMode::READ) {
+            return false;
+        }
+
+        // Replace the existing target
+        let mut dst = FileFopt::new();
+        if dst.exist(target) && !self.remove(target) {
+            src.close();
+            return false;
+        }
+
+        let mut mode = FileMode::CREATE_NEW;
+        mode.insert(FileMode::WRITE);
+        if !dst.open(target, mode) {
+            src.close();
+            return false;
+        }
+
+        // The data is written at the position of file
+        let size = src.size();
+        let mut buff = vec![0u8; COPY_BLOCK_SIZE];
+        let mut offset = 0;
+        let mut result = true;
+
+        while offset < size {
+            let len = src.read(&mut buff, COPY_BLOCK_SIZE.min(size - offset));
+            if len == 0 || dst.write(&buff, len) != len {
+                result = false;
+                break;
+            }
+            offset += len;
+        }
+
+        dst.flush();
+        dst.close();
+        src.close();
+
+        result
+    }
+
     // Get_name
     pub fn get_name(&mut self) -> &str {
         &self.name
@@ -40,7 +40,7 @@ impl RcParser {
             let size = file.size();
             let mut data = vec![0u8; size];
 
-            if file.read(&mut data, size, 0) == size {
+            if file.read(&mut data, size) == size {
                 self.decode(&String::from_utf8_lossy(&data));
                 result = true;
             }
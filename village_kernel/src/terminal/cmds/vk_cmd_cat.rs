@@ -14,6 +14,9 @@ use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 
+// Constant members
+const DEVICE_BUFFER_SIZE: usize = 512;
+
 // Struct cmd cat
 struct CmdCat;
 
@@ -24,12 +27,15 @@ impl CmdCat {
         let mut file = FileFopt::new();
 
         if file.open(path, FileMode::READ) {
-            let size = file.size();
+            // The device file has no size, read an buffer from it
+            let size = match file.size() {
+                0 => DEVICE_BUFFER_SIZE,
+                size => size,
+            };
             let mut data = vec![0u8; size];
 
-            if file.read(&mut data, size, 0) == size {
-                console.println(&String::from_utf8_lossy(&data));
-            }
+            let readed = file.read(&mut data, size);
+            console.println(&String::from_utf8_lossy(&data[..readed]));
 
             file.close();
         } else {
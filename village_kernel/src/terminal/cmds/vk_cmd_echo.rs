@@ -21,9 +21,10 @@ impl CmdEcho {
         if path != "" {
             // Set mode
             let mut filemode = FileMode::CREATE_NEW;
+            filemode.insert(FileMode::WRITE);
 
             if mode == ">" {
-                filemode.insert(FileMode::WRITE);
+                filemode.insert(FileMode::TRUNCATE);
             } else if mode == ">>" {
                 filemode.insert(FileMode::OPEN_APPEND);
             } else {
@@ -39,12 +40,12 @@ impl CmdEcho {
 
             if file.open(&filepath, filemode) {
                 if file.size() != 0 && mode == ">>" {
-                    file.write("\r\n".as_bytes(), 3, 0);
+                    file.write("\r\n".as_bytes(), 2);
                 }
 
                 for i in 0..size {
-                    file.write(data[i].as_bytes(), data[i].len(), 0);
-                    file.write(" ".as_bytes(), 1, 0);
+                    file.write(data[i].as_bytes(), data[i].len());
+                    file.write(" ".as_bytes(), 1);
                 }
 
                 file.flush();
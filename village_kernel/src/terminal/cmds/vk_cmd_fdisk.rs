@@ -0,0 +1,212 @@
+//###########################################################################
+// vk_cmd_fdisk.rs
+// The specific implementation of functions related to cmd fdisk
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::filesys::vk_partition::{GPTType, MBRType, PartitionTable, PartitionType};
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use crate::register_cmd;
+use crate::traits::vk_command::{Cmd, Console};
+use crate::village::kernel;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Struct cmd fdisk
+struct CmdFdisk;
+
+// Impl cmd fdisk
+impl CmdFdisk {
+    // Usage
+    fn usage(&mut self, console: &mut dyn Console) {
+        console.println("Usage: fdisk <device>");
+        console.println("       fdisk <device> mklabel <mbr|gpt>");
+        console.println("       fdisk <device> add <start> <sectors> [fat|fat16|exfat|linux|efi|0xNN] [name]");
+        console.println("       fdisk <device> del <number>");
+    }
+
+    // Parse type, returns the mbr os indicator and gpt type guid
+    fn parse_type(name: &str) -> Option<(u8, [u8; 16])> {
+        match name {
+            "fat" | "fat32" => Some((MBRType::FAT32_LBA, GPTType::BASIC_DATA)),
+            "fat16" => Some((MBRType::FAT16_LBA, GPTType::BASIC_DATA)),
+            "exfat" => Some((MBRType::EXFAT, GPTType::BASIC_DATA)),
+            "linux" | "ext2" => Some((MBRType::LINUX, GPTType::LINUX)),
+            "efi" => Some((MBRType::EFI_SYSTEM, GPTType::EFI_SYSTEM)),
+            _ => {
+                let os_indicator = u8::from_str_radix(name.trim_start_matches("0x"), 16).ok()?;
+                Some((os_indicator, GPTType::BASIC_DATA))
+            }
+        }
+    }
+
+    // List partitions
+    fn list(&mut self, console: &mut dyn Console, disk: &str, table: &PartitionTable) {
+        let typ = match table.typ {
+            PartitionType::MBR => "mbr",
+            PartitionType::GPT => "gpt",
+            PartitionType::None => "none",
+        };
+        console.println(&format!("{}: {} sectors, partition table {}", disk, table.sectors, typ));
+
+        if table.backup_used {
+            console.warn("The primary gpt is damaged, the backup gpt is used");
+        }
+
+        if table.partitions.is_empty() {
+            return;
+        }
+
+        console.println(&format!("{:<4} {:<12} {:<12} {:<12} {}", "#", "start", "end", "sectors", "type"));
+        for partition in table.partitions.iter() {
+            let mut line = format!(
+                "{:<4} {:<12} {:<12} {:<12} {}",
+                partition.index,
+                partition.starting_lba,
+                partition.ending_lba(),
+                partition.size_in_lba,
+                partition.type_name(table.typ)
+            );
+            if !partition.name.is_empty() {
+                line.push_str(&format!(" '{}'", partition.name));
+            }
+            if partition.bootable {
+                line.push_str(" boot");
+            }
+            console.println(&line);
+        }
+    }
+
+    // Edit the table, returns false when the arguments are invalid
+    fn edit(&mut self, console: &mut dyn Console, table: &mut PartitionTable, sectors: u64, args: &[&str]) -> bool {
+        if table.typ == PartitionType::None && args.first() != Some(&"mklabel") {
+            console.error("No partition table, create it with mklabel first!");
+            return false;
+        }
+
+        match args {
+            ["mklabel", "mbr"] => *table = PartitionTable::new(PartitionType::MBR, sectors),
+            ["mklabel", "gpt"] => {
+                if sectors == 0 {
+                    console.error("The size of device is unknown!");
+                    return false;
+                }
+                *table = PartitionTable::new(PartitionType::GPT, sectors);
+            }
+            ["add", start, size, rest @ ..] if rest.len() <= 2 => {
+                let (start, size) = match (start.parse::<u64>(), size.parse::<u64>()) {
+                    (Ok(start), Ok(size)) => (start, size),
+                    _ => {
+                        self.usage(console);
+                        return false;
+                    }
+                };
+                let (os_indicator, type_guid) = match Self::parse_type(rest.first().unwrap_or(&"fat")) {
+                    Some(typ) => typ,
+                    None => {
+                        self.usage(console);
+                        return false;
+                    }
+                };
+
+                match table.add(start, size, os_indicator, type_guid) {
+                    Some(index) => {
+                        if let Some(name) = rest.get(1) {
+                            if let Some(partition) = table.partitions.iter_mut().find(|p| p.index == index) {
+                                partition.name = String::from(*name);
+                            }
+                        }
+                        console.println(&format!("Partition {} created", index));
+                    }
+                    None => {
+                        console.error("No free space or partition slot for the new partition!");
+                        return false;
+                    }
+                }
+            }
+            ["del", index] => {
+                let index = match index.parse::<usize>() {
+                    Ok(index) => index,
+                    Err(_) => {
+                        self.usage(console);
+                        return false;
+                    }
+                };
+                if !table.remove(index) {
+                    console.error(&format!("Partition {} not found or is logical!", index));
+                    return false;
+                }
+                console.println(&format!("Partition {} deleted", index));
+            }
+            _ => {
+                self.usage(console);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Impl cmd for cmd fdisk
+impl Cmd for CmdFdisk {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
+        let args: Vec<&str> = argv.into_iter().skip(1).filter(|arg| !arg.is_empty()).collect();
+
+        if args.is_empty() {
+            self.usage(console);
+            return;
+        }
+
+        let disk = args[0].trim_start_matches("/dev/");
+        let mut device = BlockFopt::new();
+        if !device.open(disk) {
+            console.error(&format!("{} is not a block device!", disk));
+            return;
+        }
+
+        let sectors = device.get_sector_count() as u64;
+        let mut table = PartitionTable::read(&mut device)
+            .unwrap_or_else(|| PartitionTable::new(PartitionType::None, sectors));
+
+        // List only
+        if args.len() == 1 {
+            self.list(console, disk, &table);
+            device.close();
+            return;
+        }
+
+        if !self.edit(console, &mut table, sectors, &args[1..]) {
+            device.close();
+            return;
+        }
+
+        // The files of mounted volumes can be opened, the table is not changed under them
+        if kernel().filesys().is_hard_drive_busy(disk) {
+            console.error(&format!("{} is busy, umount the volumes first!", disk));
+            device.close();
+            return;
+        }
+
+        // The volumes of disk are mounted again with the new table
+        kernel().filesys().unmount_hard_drive(disk);
+
+        if !table.write(&mut device) {
+            console.error(&format!("Write partition table to {} failed!", disk));
+        }
+
+        device.close();
+
+        kernel().filesys().mount_hard_drive(disk);
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd fdisk: list, create and delete the mbr or gpt partitions");
+    }
+}
+
+// Register cmd
+register_cmd!(CmdFdisk, fdisk);
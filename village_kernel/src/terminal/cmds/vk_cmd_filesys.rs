@@ -13,6 +13,8 @@ use crate::traits::vk_filesys::FileAttr;
 use crate::traits::vk_filesys::FileDir;
 use crate::traits::vk_filesys::FileMode;
 use crate::traits::vk_filesys::FileType;
+use crate::traits::vk_filesys::{FileFlags, FileStat, FileTime};
+use crate::village::kernel;
 use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::{String, ToString};
@@ -24,35 +26,12 @@ struct CmdCd;
 
 // Impl cmd cd
 impl CmdCd {
-    // Change directory
+    // Change directory, the path is normalized by console
     fn change_directory(&mut self, console: &mut dyn Console, path: &str) {
         let mut dir = DirFopt::new();
 
         if dir.exist(path) {
-            let mut new_path = path.to_string();
-
-            if let Some(last_slash_pos) = path.rfind('/') {
-                let dir_part = &path[last_slash_pos..];
-
-                // Handle "." dir
-                if dir_part == "/." {
-                    new_path.truncate(last_slash_pos);
-                }
-                // Handle ".." dir
-                else if dir_part == "/.." {
-                    new_path.truncate(last_slash_pos);
-                    if let Some(prev_slash) = new_path.rfind('/') {
-                        // If we're at root, keep the slash, otherwise truncate
-                        if prev_slash == 0 {
-                            new_path.truncate(1);
-                        } else {
-                            new_path.truncate(prev_slash);
-                        }
-                    }
-                }
-            }
-
-            console.set_path(&new_path);
+            console.set_path(path);
         } else {
             console.error(&format!(
                 "{} is not a valid path, please confirm whether the path is correct",
@@ -86,8 +65,49 @@ struct CmdList;
 
 // Impl cmd list
 impl CmdList {
+    // Format file type and flags
+    fn format_mode(stat: &FileStat) -> String {
+        let typ = match stat.typid {
+            FileType::Directory => 'd',
+            FileType::Volume => 'v',
+            _ => '-',
+        };
+
+        let mut mode = String::new();
+        mode.push(typ);
+        for (flag, ch) in [
+            (FileFlags::READ_ONLY, 'r'),
+            (FileFlags::HIDDEN, 'h'),
+            (FileFlags::SYSTEM, 's'),
+            (FileFlags::ARCHIVE, 'a'),
+        ] {
+            mode.push(if stat.flags.contains(flag) { ch } else { '-' });
+        }
+        mode
+    }
+
+    // Format time
+    fn format_time(time: &FileTime) -> String {
+        if !time.is_valid() {
+            return "----------------".to_string();
+        }
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            time.year, time.month, time.day, time.hour, time.minute
+        )
+    }
+
+    // Join path
+    fn join_path(path: &str, name: &str) -> String {
+        if path.ends_with('/') {
+            format!("{}{}", path, name)
+        } else {
+            format!("{}/{}", path, name)
+        }
+    }
+
     // List directory
-    fn list_directory(&mut self, console: &mut dyn Console, path: &str) {
+    fn list_directory(&mut self, console: &mut dyn Console, path: &str, long: bool, all: bool) {
         let mut dir = DirFopt::new();
 
         if dir.open(path, FileMode::READ) {
@@ -95,17 +115,39 @@ impl CmdList {
             let mut dirs = vec![FileDir::new(); size];
 
             if dir.read(&mut dirs, size) == size {
+                let mut filesys_opt = FilesysFopt::new();
+
                 for i in 0..size {
-                    if dirs[i].attr == FileAttr::Visible {
-                        if FileType::Directory == dirs[i].typid
-                            || FileType::File == dirs[i].typid
-                        {
-                            console.print(&format!("{}  ", dirs[i].name));
+                    if !all && dirs[i].attr != FileAttr::Visible {
+                        continue;
+                    }
+                    if FileType::Directory != dirs[i].typid && FileType::File != dirs[i].typid {
+                        continue;
+                    }
+
+                    if long {
+                        let entry = Self::join_path(path, &dirs[i].name);
+                        match filesys_opt.stat(&entry) {
+                            Some(stat) => console.println(&format!(
+                                "{} {:>10} {} {}",
+                                Self::format_mode(&stat),
+                                stat.size,
+                                Self::format_time(&stat.write_time),
+                                dirs[i].name
+                            )),
+                            None => console.println(&format!("?????     ? {}", dirs[i].name)),
                         }
+                    } else {
+                        console.print(&format!("{}  ", dirs[i].name));
                     }
                 }
-                console.print("\r\n");
+
+                if !long {
+                    console.print("\r\n");
+                }
             }
+
+            dir.close();
         } else {
             console.error(&format!(
                 "{} is not a valid path, please confirm whether the path is correct",
@@ -120,22 +162,39 @@ impl Cmd for CmdList {
     // Execute
     fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
         if argv.len() < 1 {
-            console.println("Usage: ls [directory]");
+            console.println("Usage: ls [-l] [-a] [directory]");
             return;
         }
 
-        let path: String;
-        if argv.len() == 1 {
-            path = console.get_path().to_string();
-        } else {
-            path = console.real_path(argv[1]);
+        let mut long = false;
+        let mut all = false;
+        let mut path = console.get_path().to_string();
+
+        for arg in argv.iter().skip(1) {
+            if arg.is_empty() {
+                continue;
+            } else if let Some(opts) = arg.strip_prefix('-') {
+                for opt in opts.chars() {
+                    match opt {
+                        'l' => long = true,
+                        'a' => all = true,
+                        _ => {
+                            console.println("Usage: ls [-l] [-a] [directory]");
+                            return;
+                        }
+                    }
+                }
+            } else {
+                path = console.real_path(arg);
+            }
         }
-        self.list_directory(console, &path);
+
+        self.list_directory(console, &path, long, all);
     }
 
     // Help
     fn help(&mut self, console: &mut dyn Console) {
-        console.println("cmd ls: list directory");
+        console.println("cmd ls: list directory, -l long format, -a show hidden");
     }
 }
 
@@ -255,11 +314,23 @@ struct CmdCopy;
 
 // Impl cmd copy
 impl CmdCopy {
-    // Change directory
-    fn copy(&mut self, console: &mut dyn Console, source: &str, target: &str) {
+    // Copy
+    fn copy(&mut self, console: &mut dyn Console, source: &str, target: &str, recursive: bool) {
         let mut filesys_opt = FilesysFopt::new();
 
-        if !filesys_opt.copy(source, target) {
+        if recursive {
+            let mut report = |path: &str, result: bool| {
+                if result {
+                    console.println(&format!("'{}' copied", path));
+                } else {
+                    console.error(&format!("copy '{}' failed!", path));
+                }
+            };
+
+            if !filesys_opt.copy_recursive(source, target, &mut report) {
+                console.error(&format!("copy {} to {} failed!", source, target));
+            }
+        } else if !filesys_opt.copy(source, target) {
             console.error(&format!("copy {} to {} failed!", source, target));
         }
     }
@@ -269,19 +340,26 @@ impl CmdCopy {
 impl Cmd for CmdCopy {
     // Execute
     fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
-        if argv.len() < 3 {
-            console.println("Usage: cp <source> <target>");
+        let recursive = argv.iter().any(|arg| *arg == "-r");
+        let paths: Vec<&str> = argv
+            .into_iter()
+            .skip(1)
+            .filter(|arg| !arg.is_empty() && *arg != "-r")
+            .collect();
+
+        if paths.len() < 2 {
+            console.println("Usage: cp [-r] <source> <target>");
             return;
         }
 
-        let path1 = console.real_path(argv[1]);
-        let path2 = console.real_path(argv[2]);
-        self.copy(console,&path1, &path2);
+        let path1 = console.real_path(paths[0]);
+        let path2 = console.real_path(paths[1]);
+        self.copy(console, &path1, &path2, recursive);
     }
 
     // Help
     fn help(&mut self, console: &mut dyn Console) {
-        console.println("cmd cp: copy file or directory");
+        console.println("cmd cp: copy file or directory, -r copy directory recursively");
     }
 }
 
@@ -291,10 +369,22 @@ struct CmdRemove;
 // Impl cmd remove
 impl CmdRemove {
     // Remove
-    fn remove(&mut self, console: &mut dyn Console, path: &str) {
+    fn remove(&mut self, console: &mut dyn Console, path: &str, recursive: bool) {
         let mut filesys_opt = FilesysFopt::new();
 
-        if !filesys_opt.remove(path) {
+        if recursive {
+            let mut report = |path: &str, result: bool| {
+                if result {
+                    console.println(&format!("'{}' removed", path));
+                } else {
+                    console.error(&format!("Remove '{}' failed!", path));
+                }
+            };
+
+            if !filesys_opt.remove_recursive(path, &mut report) {
+                console.error(&format!("Remove {} failed!", path));
+            }
+        } else if !filesys_opt.remove(path) {
             console.error(&format!("Remove {} failed!", path));
         }
     }
@@ -302,20 +392,213 @@ impl CmdRemove {
 
 // Impl cmd for cmd remove
 impl Cmd for CmdRemove {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
+        let recursive = argv.iter().any(|arg| *arg == "-r");
+        let paths: Vec<&str> = argv
+            .into_iter()
+            .skip(1)
+            .filter(|arg| !arg.is_empty() && *arg != "-r")
+            .collect();
+
+        if paths.len() < 1 {
+            console.println("Usage: rm [-r] <file/directory>");
+            return;
+        }
+
+        let path = console.real_path(paths[0]);
+        self.remove(console, &path, recursive);
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd rm: remove file or directory, -r remove directory recursively");
+    }
+}
+
+// Struct cmd stat
+struct CmdStat;
+
+// Impl cmd stat
+impl CmdStat {
+    // Format flags
+    fn format_flags(flags: FileFlags) -> String {
+        let mut names = Vec::new();
+        for (flag, name) in [
+            (FileFlags::READ_ONLY, "read-only"),
+            (FileFlags::HIDDEN, "hidden"),
+            (FileFlags::SYSTEM, "system"),
+            (FileFlags::ARCHIVE, "archive"),
+        ] {
+            if flags.contains(flag) {
+                names.push(name);
+            }
+        }
+
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(" ")
+        }
+    }
+
+    // Show stat
+    fn show_stat(&mut self, console: &mut dyn Console, path: &str) {
+        let mut filesys_opt = FilesysFopt::new();
+
+        if let Some(stat) = filesys_opt.stat(path) {
+            let typ = match stat.typid {
+                FileType::File => "file",
+                FileType::Directory => "directory",
+                FileType::Volume => "volume",
+                FileType::Unknown => "unknown",
+            };
+
+            console.println(&format!("  File: {}", path));
+            console.println(&format!("  Type: {}", typ));
+            console.println(&format!("  Size: {}  Clusters: {}", stat.size, stat.clusters));
+            console.println(&format!(" Flags: {}", Self::format_flags(stat.flags)));
+            if stat.mode != 0 {
+                console.println(&format!("  Mode: {:04o}  Uid: {}  Gid: {}", stat.mode, stat.uid, stat.gid));
+            }
+            if !stat.link.is_empty() {
+                console.println(&format!("  Link: {}", stat.link));
+            }
+            console.println(&format!("Create: {}", CmdList::format_time(&stat.create_time)));
+            console.println(&format!("Modify: {}", CmdList::format_time(&stat.write_time)));
+            console.println(&format!("Access: {}", CmdList::format_time(&stat.access_time)));
+        } else {
+            console.error(&format!("Stat {} failed!", path));
+        }
+    }
+}
+
+// Impl cmd for cmd stat
+impl Cmd for CmdStat {
     // Execute
     fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
         if argv.len() < 2 {
-            console.println("Usage: rm <file/directory>");
+            console.println("Usage: stat <file/directory>");
             return;
         }
 
         let path = console.real_path(argv[1]);
-        self.remove(console, &path);
+        self.show_stat(console, &path);
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd stat: show file or directory status");
+    }
+}
+
+// Struct cmd sync
+struct CmdSync;
+
+// Impl cmd for cmd sync
+impl Cmd for CmdSync {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, _argv: Vec<&str>) {
+        if !kernel().filesys().sync() {
+            console.error("Sync block cache failed!");
+        }
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd sync: write back the dirty blocks of block cache");
+    }
+}
+
+// Struct cmd block cache
+struct CmdBlockCache;
+
+// Impl cmd for cmd block cache
+impl Cmd for CmdBlockCache {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, _argv: Vec<&str>) {
+        let info = kernel().filesys().get_cache_info();
+        let total = info.hits + info.misses;
+        let per = if total > 0 {
+            info.hits as f32 * 100.0 / total as f32
+        } else {
+            0.0
+        };
+        console.println(&format!(
+            "blocks: {}/{}, dirty: {}, hits: {}, misses: {}, hit rate: {:0.2} %, writebacks: {}",
+            info.used, info.capacity, info.dirty, info.hits, info.misses, per, info.writebacks
+        ));
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd bcache: show block cache information");
+    }
+}
+
+// Struct cmd fsck
+struct CmdFsck;
+
+// Impl cmd fsck
+impl CmdFsck {
+    // Check
+    fn check(&mut self, console: &mut dyn Console, path: &str, repair: bool) {
+        let volume = match kernel().filesys().get_volume(path) {
+            Some(volume) => volume,
+            None => {
+                console.error(&format!("{} is not mounted!", path));
+                return;
+            }
+        };
+
+        let report = match volume.check(repair) {
+            Some(report) => report,
+            None => {
+                console.error(&format!("{} does not support check!", path));
+                return;
+            }
+        };
+
+        for message in report.messages.iter() {
+            console.println(message);
+        }
+
+        if report.errors == 0 {
+            console.println(&format!("{}: clean", path));
+        } else {
+            console.println(&format!("{}: {} errors, {} repaired", path, report.errors, report.repaired));
+        }
+
+        // Write back the repaired blocks
+        if report.repaired > 0 && !kernel().filesys().sync() {
+            console.error("Sync block cache failed!");
+        }
+    }
+}
+
+// Impl cmd for cmd fsck
+impl Cmd for CmdFsck {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
+        let repair = argv.iter().any(|arg| *arg == "-r");
+        let paths: Vec<&str> = argv
+            .into_iter()
+            .skip(1)
+            .filter(|arg| !arg.is_empty() && *arg != "-r")
+            .collect();
+
+        if paths.len() < 1 {
+            console.println("Usage: fsck [-r] <path>");
+            return;
+        }
+
+        let path = console.real_path(paths[0]);
+        self.check(console, &path, repair);
     }
 
     // Help
     fn help(&mut self, console: &mut dyn Console) {
-        console.println("cmd rm: remove file or directory");
+        console.println("cmd fsck: check volume consistency, -r repair the errors");
     }
 }
 
@@ -327,3 +610,7 @@ register_cmd!(CmdMkdir, mkdir);
 register_cmd!(CmdMove, mv);
 register_cmd!(CmdCopy, cp);
 register_cmd!(CmdRemove, rm);
+register_cmd!(CmdStat, stat);
+register_cmd!(CmdSync, sync);
+register_cmd!(CmdBlockCache, bcache);
+register_cmd!(CmdFsck, fsck);
@@ -0,0 +1,85 @@
+//###########################################################################
+// vk_cmd_losetup.rs
+// The specific implementation of functions related to cmd losetup
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::drivers::virtdrv::block::vk_loop_disk::LoopDisk;
+use crate::register_cmd;
+use crate::traits::vk_command::{Cmd, Console};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// Struct cmd losetup
+struct CmdLosetup {
+    loops: Vec<(String, String)>,
+}
+
+// Impl cmd losetup
+impl CmdLosetup {
+    // New
+    const fn new() -> Self {
+        Self { loops: Vec::new() }
+    }
+
+    // Usage
+    fn usage(&mut self, console: &mut dyn Console) {
+        console.println("Usage: losetup [<image file>]");
+        console.println("       losetup -d <loop device>");
+    }
+
+    // List loop devices
+    fn list(&mut self, console: &mut dyn Console) {
+        for (name, path) in self.loops.iter() {
+            console.println(&format!("{}: {} (ro)", name, path));
+        }
+    }
+
+    // Attach
+    fn attach(&mut self, console: &mut dyn Console, path: &str) {
+        match LoopDisk::attach(path) {
+            Some(name) => {
+                console.println(&name);
+                self.loops.push((name, path.to_string()));
+            }
+            None => console.error(&format!("Attach {} failed, not found, empty or no free loop device!", path)),
+        }
+    }
+
+    // Detach
+    fn detach(&mut self, console: &mut dyn Console, name: &str) {
+        if !self.loops.iter().any(|(loop_name, _)| loop_name == name) || !LoopDisk::detach(name) {
+            console.error(&format!("{} is not an attached loop device!", name));
+            return;
+        }
+        self.loops.retain(|(loop_name, _)| loop_name != name);
+    }
+}
+
+// Impl cmd for cmd losetup
+impl Cmd for CmdLosetup {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
+        let args: Vec<&str> = argv.into_iter().skip(1).filter(|arg| !arg.is_empty()).collect();
+
+        match args.as_slice() {
+            [] => self.list(console),
+            ["-d", name] => self.detach(console, name.trim_start_matches("/dev/")),
+            [path] if !path.starts_with('-') => {
+                let path = console.real_path(path);
+                self.attach(console, &path);
+            }
+            _ => self.usage(console),
+        }
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd losetup: attach disk image file as loop block device, -d detach, list without arguments");
+    }
+}
+
+// Register cmd
+register_cmd!(CmdLosetup::new(), losetup);
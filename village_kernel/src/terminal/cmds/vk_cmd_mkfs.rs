@@ -0,0 +1,126 @@
+//###########################################################################
+// vk_cmd_mkfs.rs
+// The specific implementation of functions related to cmd mkfs
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::filesys::fs::fat::vk_fat_diskio::FatType;
+use crate::filesys::fs::fat::vk_fat_format::FatFormat;
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use crate::register_cmd;
+use crate::traits::vk_command::{Cmd, Console};
+use crate::village::kernel;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+
+// Struct cmd mkfs fat
+struct CmdMkfsFat;
+
+// Impl cmd mkfs fat
+impl CmdMkfsFat {
+    // Usage
+    fn usage(&mut self, console: &mut dyn Console) {
+        console.println("Usage: mkfs.fat [-F 12|16|32] [-s sectors per cluster] [-n label] [-j] <device>");
+    }
+
+    // Format, the volumes of device are unmounted and mounted again
+    fn format(&mut self, console: &mut dyn Console, disk: &str, mut format: FatFormat) {
+        if !format.setup(disk, 0) {
+            console.error(&format!("{} is not a block device or the size is unknown!", disk));
+            format.exit();
+            return;
+        }
+
+        // The hidden sectors are the starting lba of partition
+        let mut device = BlockFopt::new();
+        if device.open(disk) {
+            format.set_hidden_sectors(device.get_starting_lba() as u32);
+        }
+
+        kernel().filesys().unmount_hard_drive(disk);
+
+        match format.format() {
+            Some(info) => {
+                let fat_type = match info.fat_type {
+                    FatType::Fat12 => "FAT12",
+                    FatType::Fat16 => "FAT16",
+                    _ => "FAT32",
+                };
+                console.println(&format!(
+                    "{}: {}, {} clusters of {} bytes, label {}{}",
+                    disk,
+                    fat_type,
+                    info.cluster_count,
+                    info.sec_per_clust * info.bytes_per_sec,
+                    format.get_label(),
+                    if format.has_journal() { ", journaled" } else { "" }
+                ));
+            }
+            None => console.error(&format!("Format {} failed, the size does not fit the fat type!", disk)),
+        }
+
+        format.exit();
+
+        kernel().filesys().mount_hard_drive(disk);
+    }
+}
+
+// Impl cmd for cmd mkfs fat
+impl Cmd for CmdMkfsFat {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
+        let mut format = FatFormat::new();
+        let mut disk = None;
+
+        // Parse the options
+        let mut iter = argv.into_iter().skip(1).filter(|arg| !arg.is_empty());
+        while let Some(arg) = iter.next() {
+            let valid = match arg {
+                "-F" => match iter.next() {
+                    Some("12") => Some(FatType::Fat12),
+                    Some("16") => Some(FatType::Fat16),
+                    Some("32") => Some(FatType::Fat32),
+                    _ => None,
+                }
+                .map(|fat_type| format.set_fat_type(fat_type))
+                .is_some(),
+                "-s" => match iter.next().and_then(|size| size.parse::<u32>().ok()) {
+                    Some(size) => size != 0 && format.set_sec_per_clust(size),
+                    None => false,
+                },
+                "-n" => match iter.next() {
+                    Some(label) => format.set_label(label),
+                    None => false,
+                },
+                "-j" => {
+                    format.set_journal(true);
+                    true
+                }
+                _ => {
+                    disk = Some(arg.trim_start_matches("/dev/"));
+                    true
+                }
+            };
+
+            if !valid {
+                console.error(&format!("Invalid option {}!", arg));
+                self.usage(console);
+                return;
+            }
+        }
+
+        match disk {
+            Some(disk) => self.format(console, disk, format),
+            None => self.usage(console),
+        }
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd mkfs.fat: format block device as fat12/16/32, -F fat type, -s sectors per cluster, -n volume label, -j metadata journal");
+    }
+}
+
+// Register cmd
+register_cmd!(CmdMkfsFat, mkfs_fat, "mkfs.fat");
@@ -0,0 +1,198 @@
+//###########################################################################
+// vk_cmd_mount.rs
+// The specific implementation of functions related to cmd mount
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::register_cmd;
+use crate::traits::vk_command::{Cmd, Console};
+use crate::traits::vk_filesys::MountFlags;
+use crate::village::kernel;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Struct cmd mount
+struct CmdMount;
+
+// Impl cmd mount
+impl CmdMount {
+    // Parse size, such as 512, 64k and 8m
+    fn parse_size(text: &str) -> Option<u64> {
+        let (digits, scale) = match text.as_bytes().last()? {
+            b'k' | b'K' => (&text[..text.len() - 1], 1024),
+            b'm' | b'M' => (&text[..text.len() - 1], 1024 * 1024),
+            _ => (text, 1),
+        };
+        digits.parse::<u64>().ok()?.checked_mul(scale)
+    }
+
+    // Parse options, such as size=8m
+    fn parse_options(options: &str, size: &mut u64) -> bool {
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            match option.split_once('=') {
+                Some(("size", value)) => match Self::parse_size(value) {
+                    Some(value) if value != 0 => *size = value,
+                    _ => return false,
+                },
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    // List mounts
+    fn list_mounts(&mut self, console: &mut dyn Console) {
+        for mount in kernel().filesys().get_mounts().iter_mut() {
+            let mode = if mount.is_read_only() { "ro" } else { "rw" };
+            console.println(&format!("{} on {} ({})", mount.source, mount.target, mode));
+        }
+    }
+}
+
+// Impl cmd for cmd mount
+impl Cmd for CmdMount {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
+        let args: Vec<&str> = argv.into_iter().filter(|arg| !arg.is_empty()).collect();
+
+        if args.len() == 1 {
+            self.list_mounts(console);
+            return;
+        }
+
+        // Parse the options
+        let mut flags = MountFlags::NONE;
+        let mut fstype = None;
+        let mut size = 0;
+        let mut paths = Vec::new();
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match *arg {
+                "-r" => flags.insert(MountFlags::READ_ONLY),
+                "-t" => fstype = iter.next().copied(),
+                "-o" => {
+                    if !Self::parse_options(iter.next().copied().unwrap_or(""), &mut size) {
+                        console.error("Invalid mount options!");
+                        return;
+                    }
+                }
+                _ => paths.push(*arg),
+            }
+        }
+
+        if paths.len() < 2 {
+            console.println("Usage: mount [-r] [-t ramfs] [-o size=<bytes>[k|m]] <volume> <directory>");
+            return;
+        }
+
+        // The size is only used by the virtual volume
+        if size != 0 && fstype.is_none() {
+            console.error("The size option needs the file system type!");
+            return;
+        }
+
+        // The volume name may contain spaces
+        let target = console.real_path(paths[paths.len() - 1]);
+        let source = paths[..paths.len() - 1].join(" ");
+
+        // Create the virtual volume by the file system type
+        if let Some(fstype) = fstype {
+            if !kernel().filesys().attach_volume(fstype, &source, size) {
+                console.error(&format!("Create {} volume {} failed!", fstype, source));
+                return;
+            }
+        }
+
+        if !kernel().filesys().mount(&source, &target, flags) {
+            console.error(&format!("Mount {} on {} failed!", source, target));
+        }
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd mount: mount volume on directory or list mounts");
+    }
+}
+
+// Struct cmd umount
+struct CmdUmount;
+
+// Impl cmd for cmd umount
+impl Cmd for CmdUmount {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, argv: Vec<&str>) {
+        if argv.len() < 2 {
+            console.println("Usage: umount <directory>");
+            return;
+        }
+
+        let target = console.real_path(argv[1]);
+        if !kernel().filesys().umount(&target) {
+            console.error(&format!("Umount {} failed!", target));
+        }
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd umount: unmount directory");
+    }
+}
+
+// Struct cmd df
+struct CmdDf;
+
+// Impl cmd df
+impl CmdDf {
+    // Format size in KB
+    fn kbytes(size: u64) -> String {
+        format!("{}K", size / 1024)
+    }
+}
+
+// Impl cmd for cmd df
+impl Cmd for CmdDf {
+    // Execute
+    fn exec(&mut self, console: &mut dyn Console, _argv: Vec<&str>) {
+        // Clone the mount targets, the volume is got by target
+        let mut targets = Vec::new();
+        for mount in kernel().filesys().get_mounts().iter_mut() {
+            targets.push((mount.source.clone(), mount.target.clone()));
+        }
+
+        console.println(&format!(
+            "{:<24} {:>10} {:>10} {:>10} {:>5}  {}",
+            "Volume", "Size", "Used", "Avail", "Use%", "Mounted on"
+        ));
+
+        for (source, target) in targets.iter() {
+            if let Some(volume) = kernel().filesys().get_volume(target) {
+                let total = volume.get_total_size();
+                let free = volume.get_free_size();
+                let used = total.saturating_sub(free);
+                let per = if total > 0 { used * 100 / total } else { 0 };
+
+                console.println(&format!(
+                    "{:<24} {:>10} {:>10} {:>10} {:>4}%  {}",
+                    source,
+                    Self::kbytes(total),
+                    Self::kbytes(used),
+                    Self::kbytes(free),
+                    per,
+                    target
+                ));
+            }
+        }
+    }
+
+    // Help
+    fn help(&mut self, console: &mut dyn Console) {
+        console.println("cmd df: show disk space usage of mounts");
+    }
+}
+
+// Register cmd
+register_cmd!(CmdMount, mount);
+register_cmd!(CmdUmount, umount);
+register_cmd!(CmdDf, df);
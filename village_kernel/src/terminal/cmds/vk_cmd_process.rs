@@ -20,9 +20,19 @@ impl Cmd for CmdProcess {
     fn exec(&mut self, console: &mut dyn Console, _argv: Vec<&str>) {
         for process in kernel().process().get_processes().iter_mut() {
             console.println(&format!(
-                "pid {:<2}, pid {:<2}, path {}",
-                process.pid, process.tid, process.path,
+                "pid {:<2}, tid {:<2}, threads {:<2}, path {}",
+                process.pid, process.tid, process.threads.len(), process.path,
             ));
+
+            // Output the threads of process
+            for tid in process.threads.iter() {
+                if let Some(task) = kernel().thread().get_task_info(*tid) {
+                    console.println(&format!(
+                        "    |- tid {:<2}, state {:<10}, name {}",
+                        task.id, task.state.as_str(), task.name,
+                    ));
+                }
+            }
         }
     }
 
@@ -18,11 +18,14 @@ struct CmdTasker;
 impl Cmd for CmdTasker {
     // Execute
     fn exec(&mut self, console: &mut dyn Console, _argv: Vec<&str>) {
-        console.println("tid   stack  start  ~  end   psp         size        used        state        name ");
-        for task in kernel().thread().get_tasks().iter_mut() {
+        console.println("tid   pid   cpu  stack  start  ~  end   psp         size        used        state        name ");
+        for task in kernel().thread().get_task_infos().iter() {
+            let pid = kernel().process().get_pid_by_tid(task.id);
             console.println(&format!(
-                "{:<4}  0x{:08x}~0x{:08x}  0x{:08x}  0x{:08x}  0x{:08x}  {:<12} {}",
+                "{:<4}  {:<4}  {:<3}  0x{:08x}~0x{:08x}  0x{:08x}  0x{:08x}  0x{:08x}  {:<12} {}",
                 task.id,
+                if pid < 0 { format!("-") } else { format!("{}", pid) },
+                task.cpu,
                 task.stack_start,
                 task.stack_ended,
                 task.psp,
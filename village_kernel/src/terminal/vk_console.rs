@@ -150,7 +150,7 @@ impl Console for VillageConsole {
 
         abs_path.push_str(path);
 
-        abs_path
+        kernel().filesys().normalize_path(&abs_path)
     }
 
     // Log
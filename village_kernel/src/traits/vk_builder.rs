@@ -4,6 +4,7 @@
 //
 // $Copyright: Copyright (C) village
 //###########################################################################
+use super::vk_kernel::TlsImage;
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 
@@ -36,6 +37,7 @@ pub trait LibBuilder {
 // ProgLoader
 pub trait ProgLoader {
     fn init(&mut self, path: &str, data: &mut Vec<u8>) -> bool;
+    fn tls(&mut self) -> TlsImage;
     fn exit(&mut self) -> bool;
 }
 
@@ -13,6 +13,7 @@ fn convert_to_cb(fn_addr: u32) -> FnCallback {
 }
 
 // Structure to hold callback function, instance, and userdata
+#[derive(Clone, Copy)]
 pub struct Callback {
     pub callback: FnCallback,
     pub instance: *mut (),
@@ -29,6 +30,15 @@ impl Callback {
         }
     }
 
+    // Create a callback from a function
+    pub fn from_fn(callback: FnCallback) -> Self {
+        Self {
+            callback,
+            instance: core::ptr::null_mut(),
+            userdata: core::ptr::null_mut(),
+        }
+    }
+
     // Set the instance
     pub fn with_instance<T>(mut self, instance: &mut T) -> Self {
         self.instance = instance as *mut T as *mut ();
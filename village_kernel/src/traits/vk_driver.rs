@@ -6,6 +6,7 @@
 //###########################################################################
 use crate::village::kernel;
 use alloc::boxed::Box;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
 // Driver id
@@ -66,6 +67,7 @@ impl DriverID {
 // Common command
 pub enum Command {
     FB(FBCommand),
+    BLK(BLKCommand),
     NULL(),
 }
 
@@ -80,6 +82,16 @@ pub enum FBCommand {
     Clear     { },
 }
 
+// BLKCommand
+pub enum BLKCommand {
+    SectorCount { count: usize },
+    SectorSize  { size: usize },
+    Direct      { direct: bool },
+    ReadOnly    { read_only: bool },
+    Backing     { path: String },
+    Parent      { name: String, offset: usize },
+}
+
 // Trait Driver
 pub trait Driver {
     fn open(&mut self, data: *mut ()) -> bool;
@@ -140,7 +152,7 @@ impl PlatData {
     // Detach
     pub fn detach(&mut self) {
         if self.is_attach {
-            kernel().device().unregister_driver(&self.drvname);
+            kernel().device().unregister_driver(self.drvname);
             self.is_attach = false;
         }
     }
@@ -183,7 +195,7 @@ pub trait PlatDevice {
 // Struct driver wrapper
 pub struct DriverWrapper {
     id: DriverID,
-    name: &'static str,
+    name: String,
     data: *mut (),
     inner: Box<dyn Driver>,
 }
@@ -192,10 +204,10 @@ pub struct DriverWrapper {
 impl DriverWrapper {
     // New with
     #[inline]
-    pub fn new(inner: Box<dyn Driver>, id: DriverID, name: &'static str, data: *mut ()) -> Self {
+    pub fn new(inner: Box<dyn Driver>, id: DriverID, name: &str, data: *mut ()) -> Self {
         Self {
             id,
-            name,
+            name: name.to_string(),
             data,
             inner,
         }
@@ -272,7 +284,7 @@ impl PlatDrvWrapper {
     // Get name
     #[inline]
     pub fn name(&self) -> &str {
-        &self.name
+        self.name
     }
 
     // Probe
@@ -316,7 +328,7 @@ impl PlatDevWrapper {
     // Get name
     #[inline]
     pub fn name(&self) -> &str {
-        &self.name
+        self.name
     }
 
     // Plat
@@ -5,7 +5,8 @@
 // $Copyright: Copyright (C) village
 //###########################################################################
 use alloc::boxed::Box;
-use alloc::string::String;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 // struct FileMode
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -18,6 +19,7 @@ impl FileMode {
     pub const WRITE: Self = FileMode(0x02);
     pub const READ_WRITE: Self = FileMode(0x03);
     pub const CREATE_NEW: Self = FileMode(0x04);
+    pub const TRUNCATE: Self = FileMode(0x08);
     pub const CREATE_ALWAYS: Self = FileMode(0x10);
     pub const OPEN_APPEND: Self = FileMode(0x30);
 
@@ -26,6 +28,44 @@ impl FileMode {
         (self.0 & flag.0) != 0
     }
 
+    // Contains all, all bits of flag are set
+    pub fn contains_all(self, flag: Self) -> bool {
+        (self.0 & flag.0) == flag.0
+    }
+
+    // Insert
+    pub fn insert(&mut self, flag: Self) {
+        self.0 |= flag.0
+    }
+}
+
+// Enum SeekFrom, the position of file handle
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(usize),
+    End(isize),
+    Current(isize),
+}
+
+// struct MountFlags
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct MountFlags(u32);
+
+// Impl MountFlags
+impl MountFlags {
+    pub const NONE: Self = MountFlags(0x00);
+    pub const READ_ONLY: Self = MountFlags(0x01);
+
+    // Contains
+    pub fn contains(self, flag: Self) -> bool {
+        (self.0 & flag.0) != 0
+    }
+
+    // Contains all, all bits of flag are set
+    pub fn contains_all(self, flag: Self) -> bool {
+        (self.0 & flag.0) == flag.0
+    }
+
     // Insert
     pub fn insert(&mut self, flag: Self) {
         self.0 |= flag.0
@@ -71,10 +111,193 @@ impl FileDir {
     }
 }
 
+// Struct MountNode
+pub struct MountNode {
+    pub target: String,
+    pub source: String,
+    pub access: u16,
+    pub flags: MountFlags,
+}
+
+// Impl MountNode
+impl MountNode {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            target: String::new(),
+            source: String::new(),
+            access: 0o755,
+            flags: MountFlags::NONE,
+        }
+    }
+
+    // From
+    pub fn from(target: &str, source: &str, access: u16, flags: MountFlags) -> Self {
+        Self {
+            target: target.to_string(),
+            source: source.to_string(),
+            access,
+            flags,
+        }
+    }
+
+    // Is read only
+    pub fn is_read_only(&self) -> bool {
+        self.flags.contains(MountFlags::READ_ONLY)
+    }
+}
+
+// Struct BlockCacheInfo
+#[derive(Clone)]
+pub struct BlockCacheInfo {
+    pub capacity: usize,
+    pub used: usize,
+    pub dirty: usize,
+    pub hits: u32,
+    pub misses: u32,
+    pub writebacks: u32,
+}
+
+// Impl BlockCacheInfo
+impl BlockCacheInfo {
+    pub const fn new() -> Self {
+        Self {
+            capacity: 0,
+            used: 0,
+            dirty: 0,
+            hits: 0,
+            misses: 0,
+            writebacks: 0,
+        }
+    }
+}
+
+// struct FileFlags
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FileFlags(u32);
+
+// Impl FileFlags
+impl FileFlags {
+    pub const NONE: Self = FileFlags(0x00);
+    pub const READ_ONLY: Self = FileFlags(0x01);
+    pub const HIDDEN: Self = FileFlags(0x02);
+    pub const SYSTEM: Self = FileFlags(0x04);
+    pub const ARCHIVE: Self = FileFlags(0x20);
+
+    // Contains
+    pub fn contains(self, flag: Self) -> bool {
+        (self.0 & flag.0) != 0
+    }
+
+    // Contains all, all bits of flag are set
+    pub fn contains_all(self, flag: Self) -> bool {
+        (self.0 & flag.0) == flag.0
+    }
+
+    // Insert
+    pub fn insert(&mut self, flag: Self) {
+        self.0 |= flag.0
+    }
+}
+
+// Struct FileTime
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FileTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+// Impl FileTime
+impl FileTime {
+    pub const fn new() -> Self {
+        Self {
+            year: 0,
+            month: 0,
+            day: 0,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        }
+    }
+
+    // Is valid
+    pub fn is_valid(&self) -> bool {
+        self.month >= 1 && self.month <= 12 && self.day >= 1 && self.day <= 31
+    }
+}
+
+// Struct FileStat
+#[derive(Clone)]
+pub struct FileStat {
+    pub name: String,
+    pub typid: FileType,
+    pub flags: FileFlags,
+    pub size: u64,
+    pub clusters: u32,
+    pub mode: u16,
+    pub uid: u16,
+    pub gid: u16,
+    pub link: String,
+    pub create_time: FileTime,
+    pub write_time: FileTime,
+    pub access_time: FileTime,
+}
+
+// Impl FileStat
+impl FileStat {
+    pub const fn new() -> Self {
+        Self {
+            name: String::new(),
+            typid: FileType::Unknown,
+            flags: FileFlags::NONE,
+            size: 0,
+            clusters: 0,
+            mode: 0,
+            uid: 0,
+            gid: 0,
+            link: String::new(),
+            create_time: FileTime::new(),
+            write_time: FileTime::new(),
+            access_time: FileTime::new(),
+        }
+    }
+}
+
+// Struct FileCheck
+pub struct FileCheck {
+    pub errors: usize,
+    pub repaired: usize,
+    pub messages: Vec<String>,
+}
+
+// Impl FileCheck
+impl FileCheck {
+    pub const fn new() -> Self {
+        Self {
+            errors: 0,
+            repaired: 0,
+            messages: Vec::new(),
+        }
+    }
+
+    // Add error message, the fixed error is counted as repaired
+    pub fn error(&mut self, message: String, fixed: bool) {
+        self.errors += 1;
+        if fixed {
+            self.repaired += 1;
+        }
+        self.messages.push(message);
+    }
+}
+
 // Trait FileVol
 pub trait FileVol {
     // Methods
-    fn setup(&mut self, disk: &str, starting_lba: u32) -> bool;
+    fn setup(&mut self, disk: &str) -> bool;
     fn exit(&mut self);
 
     // Volume methods
@@ -82,12 +305,18 @@ pub trait FileVol {
     fn get_mount_path(&mut self) -> &str;
     fn set_name(&mut self, name: &str) -> bool;
     fn get_name(&mut self) -> String;
+    fn get_total_size(&mut self) -> u64;
+    fn get_free_size(&mut self) -> u64;
 
-    // File methods
+    // Capacity methods, the size of virtual volume is set before setup
+    fn set_capacity(&mut self, _size: u64) -> bool { false }
+
+    // File methods, the data is written and read at the offset
     fn open(&mut self, name: &str, mode: FileMode) -> usize;
     fn write(&mut self, fd: usize, data: &[u8], size: usize, offset: usize) -> usize;
     fn read(&mut self, fd: usize, data: &mut [u8], size: usize, offset: usize) -> usize;
     fn size(&mut self, fd: usize) -> usize;
+    fn set_len(&mut self, fd: usize, size: usize) -> bool;
     fn flush(&mut self, fd: usize);
     fn close(&mut self, fd: usize);
 
@@ -97,9 +326,19 @@ pub trait FileVol {
     fn sizedir(&mut self, fd: usize) -> usize;
     fn closedir(&mut self, fd: usize);
 
+    // Stat methods
+    fn stat(&mut self, name: &str) -> Option<FileStat>;
+    fn fstat(&mut self, fd: usize) -> Option<FileStat>;
+    fn set_flags(&mut self, name: &str, flags: FileFlags) -> bool;
+    fn set_time(&mut self, name: &str, write_time: FileTime, access_time: FileTime) -> bool;
+
     // Opt methods
     fn exist(&mut self, name: &str, typeid: FileType) -> bool;
     fn remove(&mut self, name: &str) -> bool;
+    fn rename(&mut self, source: &str, target: &str) -> bool;
+
+    // Check methods
+    fn check(&mut self, repair: bool) -> Option<FileCheck>;
 }
 
 // FileSys
@@ -128,7 +367,7 @@ impl FileSysWrapper {
     // Get name
     #[inline]
     pub fn name(&self) -> &str {
-        &self.name
+        self.name
     }
 
     // Get system id
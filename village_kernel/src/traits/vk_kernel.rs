@@ -8,7 +8,7 @@ use super::vk_callback::Callback;
 use super::vk_command::CmdWrapper;
 use super::vk_driver::{DriverWrapper, PlatDevWrapper, PlatDrvWrapper};
 use super::vk_builder::{LibContainer, LibBuilderWrapper, ProgContainer, ProgBuilderWrapper};
-use super::vk_filesys::{FileSysWrapper, FileVol};
+use super::vk_filesys::{BlockCacheInfo, FileSysWrapper, FileTime, FileVol, MountFlags, MountNode};
 use super::vk_linkedlist::LinkedList;
 use super::vk_extension::ExtensionWrapper;
 use alloc::boxed::Box;
@@ -18,10 +18,17 @@ use alloc::vec::Vec;
 // System
 pub trait System {
     fn get_ticks(&mut self) -> u32;
+    fn get_date_time(&mut self) -> FileTime;
     fn delay_ms(&mut self, millis: u32);
 
     fn enable_irq(&mut self);
     fn disable_irq(&mut self);
+    fn irq_save(&mut self) -> u32;
+    fn irq_restore(&mut self, flags: u32);
+
+    fn get_cpu_id(&mut self) -> usize;
+    fn get_cpu_count(&mut self) -> usize;
+    fn send_ipi(&mut self, cpu: usize, irq: isize);
 
     fn sleep(&mut self);
     fn standby(&mut self);
@@ -107,6 +114,20 @@ pub trait Interrupt {
     // Replace Methods
     fn replace(&mut self, irq: isize, handler: usize);
 
+    // Mask Methods
+    fn mask(&mut self, irq: isize);
+    fn unmask(&mut self, irq: isize);
+    fn is_masked(&mut self, irq: isize) -> bool;
+
+    // Priority Methods
+    fn set_priority(&mut self, irq: isize, priority: u8) -> bool;
+    fn get_priority(&mut self, irq: isize) -> u8;
+
+    // Data Methods
+    fn get_isr_num(&mut self) -> usize;
+    fn get_isr_cb_size(&mut self, irq: isize) -> usize;
+    fn get_isr_count(&mut self, irq: isize) -> u32;
+
     // Feature Methods
     fn handler(&mut self, irq: isize);
 }
@@ -158,13 +179,35 @@ pub trait FileSystem {
     // Hard drive methods
     fn mount_hard_drive(&mut self, disk: &str) -> bool;
     fn unmount_hard_drive(&mut self, disk: &str) -> bool;
+    fn is_hard_drive_busy(&mut self, disk: &str) -> bool;
 
     // Register methods
     fn register_fs(&mut self, fs: FileSysWrapper);
     fn unregister_fs(&mut self, name: &str);
 
+    // Virtual volume methods
+    fn attach_volume(&mut self, fs: &str, name: &str, size: u64) -> bool;
+
+    // Mount methods
+    fn mount(&mut self, source: &str, target: &str, flags: MountFlags) -> bool;
+    fn umount(&mut self, target: &str) -> bool;
+    fn get_mounts(&mut self) -> &mut LinkedList<MountNode>;
+
+    // Path methods
+    fn normalize_path(&mut self, path: &str) -> String;
+    fn is_read_only(&mut self, path: &str) -> bool;
+    fn is_backing(&mut self, path: &str) -> bool;
+
     // Volume methods
-    fn get_volume(&mut self, name: &str) -> Option<&mut Box<dyn FileVol>>;
+    fn get_volume(&mut self, path: &str) -> Option<&mut Box<dyn FileVol>>;
+    fn resolve_volume(&mut self, path: &str) -> Option<(&mut Box<dyn FileVol>, String)>;
+
+    // Block cache methods
+    fn read_block(&mut self, disk: &str, data: &mut [u8], count: usize, lba: usize) -> usize;
+    fn write_block(&mut self, disk: &str, data: &[u8], count: usize, lba: usize) -> usize;
+    fn sync_block(&mut self, disk: &str) -> bool;
+    fn sync(&mut self) -> bool;
+    fn get_cache_info(&mut self) -> BlockCacheInfo;
 }
 
 // Enum EventType
@@ -331,11 +374,12 @@ pub trait Symbol {
 // Scheduler
 pub trait Scheduler {
     fn start(&mut self);
+    fn start_cpu(&mut self);
     fn sched(&mut self);
 }
 
 // Thread state
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum ThreadState {
     New = 0,
     Ready,
@@ -357,6 +401,35 @@ impl ThreadState {
     }
 }
 
+// Struct tls image
+#[derive(Clone, Copy)]
+pub struct TlsImage {
+    pub addr: u32,
+    pub file_size: u32,
+    pub mem_size: u32,
+    pub align: u32,
+}
+
+// Impl tls image
+impl TlsImage {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            addr: 0,
+            file_size: 0,
+            mem_size: 0,
+            align: 0,
+        }
+    }
+}
+
+// Struct task local data
+pub struct TaskLocalData {
+    pub key: usize,
+    pub data: *mut (),
+    pub drop: fn(*mut ()),
+}
+
 // Thread task
 pub struct ThreadTask {
     pub name: String,
@@ -365,6 +438,13 @@ pub struct ThreadTask {
     pub ticks: u32,
     pub stack_start: u32,
     pub stack_ended: u32,
+    pub tls: u32,
+    pub tls_block: u32,
+    pub tls_image: TlsImage,
+    pub locals: Vec<TaskLocalData>,
+    pub fpu: u32,
+    pub cpu: usize,
+    pub waiters: Vec<i32>,
     pub state: ThreadState,
 }
 
@@ -379,11 +459,48 @@ impl ThreadTask {
             ticks: 0,
             stack_start: 0,
             stack_ended: 0,
+            tls: 0,
+            tls_block: 0,
+            tls_image: TlsImage::new(),
+            locals: Vec::new(),
+            fpu: 0,
+            cpu: 0,
+            waiters: Vec::new(),
             state: ThreadState::New,
         }
     }
 }
 
+// Struct task info, the snapshot of thread task
+#[derive(Clone)]
+pub struct TaskInfo {
+    pub name: String,
+    pub id: i32,
+    pub psp: u32,
+    pub stack_start: u32,
+    pub stack_ended: u32,
+    pub tls_image: TlsImage,
+    pub cpu: usize,
+    pub state: ThreadState,
+}
+
+// Impl task info
+impl TaskInfo {
+    // From task
+    pub fn from_task(task: &ThreadTask) -> Self {
+        Self {
+            name: task.name.clone(),
+            id: task.id,
+            psp: task.psp,
+            stack_start: task.stack_start,
+            stack_ended: task.stack_ended,
+            tls_image: task.tls_image,
+            cpu: task.cpu,
+            state: task.state,
+        }
+    }
+}
+
 // Thread
 pub trait Thread {
     // Create Methods
@@ -396,7 +513,8 @@ pub trait Thread {
     fn exit_blocked(&mut self, tid: i32);
     fn delete_task(&mut self, tid: i32);
     fn is_task_alive(&mut self, tid: i32) -> bool;
-    fn get_tasks(&mut self) -> &mut LinkedList<ThreadTask>;
+    fn get_task_info(&mut self, tid: i32) -> Option<TaskInfo>;
+    fn get_task_infos(&mut self) -> Vec<TaskInfo>;
 
     // State Methods
     fn get_task_id(&mut self) -> i32;
@@ -404,6 +522,18 @@ pub trait Thread {
     fn blocked(&mut self);
     fn terminated(&mut self);
 
+    // Local Storage Methods
+    fn set_task_tls(&mut self, tid: i32, image: TlsImage) -> bool;
+    fn get_task_tls(&mut self) -> u32;
+    fn get_task_local(&mut self, key: usize) -> *mut ();
+    fn set_task_local(&mut self, key: usize, data: *mut (), drop: fn(*mut ()));
+
+    // Fpu Methods
+    fn get_task_fpu(&mut self) -> u32;
+    fn alloc_task_fpu(&mut self) -> u32;
+    fn get_fpu_owner(&mut self) -> u32;
+    fn set_fpu_owner(&mut self, area: u32);
+
     // Scheduler Methods
     fn save_task_psp(&mut self, psp: u32);
     fn get_task_psp(&mut self) -> u32;
@@ -420,8 +550,10 @@ pub enum ProcessBehavior {
 // Process data
 pub struct ProcessData {
     pub path: String,
+    pub argv: Vec<String>,
     pub pid: i32,
     pub tid: i32,
+    pub threads: Vec<i32>,
     pub container: Option<Box<dyn ProgContainer>>,
 }
 
@@ -431,8 +563,10 @@ impl ProcessData {
     pub fn new() -> Self {
         ProcessData {
             path: "None".to_string(),
+            argv: Vec::new(),
             pid: -1,
             tid: -1,
+            threads: Vec::new(),
             container: None,
         }
     }
@@ -448,9 +582,15 @@ pub trait Process {
     fn kill_by_path(&mut self, path: &str);
     fn kill_by_pid(&mut self, pid: i32);
 
+    // Thread Methods
+    fn create_thread(&mut self, name: &str, callback: Callback) -> i32;
+    fn join_thread(&mut self, tid: i32) -> bool;
+    fn kill_threads(&mut self, pid: i32);
+
     // Check Methods
     fn is_exist_by_path(&mut self, path: &str) -> bool;
     fn is_exist_by_pid(&mut self, pid: i32) -> bool;
+    fn get_pid_by_tid(&mut self, tid: i32) -> i32;
 
     // Data Methods
     fn get_processes(&mut self) -> &mut LinkedList<ProcessData>;
@@ -83,6 +83,11 @@ impl<T> LinkedList<T> {
     pub fn len(&self) -> usize {
         self.len
     }
+
+    // List is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 }
 
 // LinkedList self iterator
@@ -104,7 +109,7 @@ impl<T> LinkedList<T> {
     }
 
     // List item
-    pub fn item(&self) -> Option<&mut T> {
+    pub fn item(&mut self) -> Option<&mut T> {
         let iter_ptr = self.iter.load(Ordering::Acquire);
         if iter_ptr.is_null() {
             None
@@ -165,6 +165,9 @@ macro_rules! register_prog_builder {
 #[macro_export]
 macro_rules! register_cmd {
     ($cmd:expr, $name:ident) => {
+        $crate::register_cmd!($cmd, $name, stringify!($name));
+    };
+    ($cmd:expr, $name:ident, $cmdname:expr) => {
         paste::paste! {
             #[used]
             #[unsafe(link_section = ".init_array")]
@@ -176,13 +179,13 @@ macro_rules! register_cmd {
 
             fn [<$name _init>]() {
                 let command = crate::traits::vk_command::CmdWrapper::new(
-                    Box::new($cmd), stringify!($name)
+                    Box::new($cmd), $cmdname
                 );
                 crate::village::kernel().terminal().register_cmd(command);
             }
 
             fn [<$name _exit>]() {
-                crate::village::kernel().terminal().unregister_cmd(stringify!($name));
+                crate::village::kernel().terminal().unregister_cmd($cmdname);
             }
         }
     };
@@ -322,3 +325,13 @@ macro_rules! register_filesys {
         }
     };
 }
+
+// Task local macro
+#[macro_export]
+macro_rules! task_local {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr;) => {
+        $(#[$attr])*
+        $vis static $name: crate::traits::vk_task_local::TaskLocal<$t> =
+            crate::traits::vk_task_local::TaskLocal::new(|| $init);
+    };
+}
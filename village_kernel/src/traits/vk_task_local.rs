@@ -0,0 +1,94 @@
+//###########################################################################
+// vk_task_local.rs
+// The specific implementation of functions related to task local
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::village::kernel;
+use alloc::boxed::Box;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+// Const members
+const FALLBACK_SLOTS: usize = 8;
+
+// Struct task local
+pub struct TaskLocal<T: 'static> {
+    init: fn() -> T,
+    fallback: [AtomicPtr<T>; FALLBACK_SLOTS],
+}
+
+// Impl task local
+impl<T: 'static> TaskLocal<T> {
+    // New
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            fallback: [const { AtomicPtr::new(null_mut()) }; FALLBACK_SLOTS],
+        }
+    }
+
+    // Key
+    fn key(&'static self) -> usize {
+        self as *const _ as usize
+    }
+
+    // Drop data
+    fn drop_data(data: *mut ()) {
+        unsafe { drop(Box::from_raw(data as *mut T)) };
+    }
+
+    // Get the fallback value of current cpu, it is never freed
+    fn fallback(&'static self) -> *mut T {
+        let slot = &self.fallback[kernel().system().get_cpu_id() % FALLBACK_SLOTS];
+
+        let data = slot.load(Ordering::Acquire);
+        if !data.is_null() {
+            return data;
+        }
+
+        let value = Box::into_raw(Box::new((self.init)()));
+        match slot.compare_exchange(null_mut(), value, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => value,
+            Err(data) => {
+                unsafe { drop(Box::from_raw(value)) };
+                data
+            }
+        }
+    }
+
+    // Access the value of current task, it is initialized on first access
+    pub fn with<R>(&'static self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut data = kernel().thread().get_task_local(self.key());
+
+        if data.is_null() {
+            let value = Box::into_raw(Box::new((self.init)())) as *mut ();
+            kernel().thread().set_task_local(self.key(), value, Self::drop_data);
+            data = kernel().thread().get_task_local(self.key());
+
+            // No task is running, the value of current cpu is used
+            if data.is_null() {
+                unsafe { drop(Box::from_raw(value as *mut T)) };
+                return f(unsafe { &mut *self.fallback() });
+            }
+        }
+
+        f(unsafe { &mut *(data as *mut T) })
+    }
+
+    // Set the value of current task
+    pub fn set(&'static self, value: T) {
+        self.with(|v| *v = value);
+    }
+}
+
+// Impl task local
+impl<T: Copy + 'static> TaskLocal<T> {
+    // Get the value of current task
+    pub fn get(&'static self) -> T {
+        self.with(|v| *v)
+    }
+}
+
+// Impl sync for task local
+unsafe impl<T: 'static> Sync for TaskLocal<T> {}
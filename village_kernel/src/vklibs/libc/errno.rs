@@ -0,0 +1,30 @@
+//###########################################################################
+// errno.rs
+// The specific implementation of functions related to errno
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::task_local;
+
+// Errno of each task
+task_local! {
+    static ERRNO: i32 = 0;
+}
+
+// errno location
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn __errno_location() -> *mut i32 {
+    ERRNO.with(|errno| errno as *mut i32)
+}
+
+// Errno values
+pub const ENOENT: i32 = 2;
+pub const EIO: i32 = 5;
+pub const EBADF: i32 = 9;
+pub const EEXIST: i32 = 17;
+pub const EINVAL: i32 = 22;
+
+// Set errno of current task
+pub fn set_errno(errno: i32) {
+    ERRNO.set(errno);
+}
@@ -0,0 +1,222 @@
+//###########################################################################
+// unistd.rs
+// The specific implementation of functions related to unistd
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use super::errno::{set_errno, EBADF, EEXIST, EINVAL, EIO, ENOENT};
+use crate::misc::fopts::vk_file_fopt::FileFopt;
+use crate::misc::lock::vk_mutex::Mutex;
+use crate::traits::vk_filesys::{FileMode, SeekFrom};
+use alloc::collections::BTreeMap;
+use core::cell::UnsafeCell;
+use core::ffi::{c_char, CStr};
+
+// Open flags
+pub const O_RDONLY: i32 = 0x0000;
+pub const O_WRONLY: i32 = 0x0001;
+pub const O_RDWR: i32 = 0x0002;
+pub const O_ACCMODE: i32 = 0x0003;
+pub const O_CREAT: i32 = 0x0040;
+pub const O_EXCL: i32 = 0x0080;
+pub const O_TRUNC: i32 = 0x0200;
+pub const O_APPEND: i32 = 0x0400;
+
+// Seek whence
+pub const SEEK_SET: i32 = 0;
+pub const SEEK_CUR: i32 = 1;
+pub const SEEK_END: i32 = 2;
+
+// The first fd, 0 to 2 are the standard streams
+const FD_START: i32 = 3;
+
+// Struct file table, the open files are indexed by fd
+struct FileTable {
+    files: UnsafeCell<BTreeMap<i32, FileFopt>>,
+    mutex: Mutex,
+}
+
+// Impl sync for file table
+unsafe impl Sync for FileTable {}
+
+// Static file table
+static FILE_TABLE: FileTable = FileTable {
+    files: UnsafeCell::new(BTreeMap::new()),
+    mutex: Mutex::new(),
+};
+
+// Impl file table
+impl FileTable {
+    // Insert file, returns the lowest free fd
+    fn insert(&self, file: FileFopt) -> i32 {
+        self.mutex.lock();
+        let files = unsafe { &mut *self.files.get() };
+        let mut fd = FD_START;
+        while files.contains_key(&fd) {
+            fd += 1;
+        }
+        files.insert(fd, file);
+        self.mutex.unlock();
+        fd
+    }
+
+    // Remove file
+    fn remove(&self, fd: i32) -> Option<FileFopt> {
+        self.mutex.lock();
+        let file = unsafe { &mut *self.files.get() }.remove(&fd);
+        self.mutex.unlock();
+        file
+    }
+
+    // With file, the file can not be closed while it is used
+    fn with<R>(&self, fd: i32, f: impl FnOnce(&mut FileFopt) -> R) -> Option<R> {
+        self.mutex.lock();
+        let result = unsafe { &mut *self.files.get() }.get_mut(&fd).map(f);
+        self.mutex.unlock();
+        result
+    }
+}
+
+// Convert open flags to file mode
+fn file_mode(flags: i32) -> Option<FileMode> {
+    let mut mode = match flags & O_ACCMODE {
+        O_RDONLY => FileMode::READ,
+        O_WRONLY => FileMode::WRITE,
+        O_RDWR => FileMode::READ_WRITE,
+        _ => return None,
+    };
+    if (flags & O_CREAT) != 0 {
+        mode.insert(FileMode::CREATE_NEW);
+    }
+    if (flags & O_TRUNC) != 0 {
+        mode.insert(FileMode::TRUNCATE);
+    }
+    if (flags & O_APPEND) != 0 {
+        mode.insert(FileMode::OPEN_APPEND);
+    }
+    Some(mode)
+}
+
+// open
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn open(path: *const c_char, flags: i32) -> i32 {
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(path) => path,
+        Err(_) => {
+            set_errno(EINVAL);
+            return -1;
+        }
+    };
+    let mode = match file_mode(flags) {
+        Some(mode) => mode,
+        None => {
+            set_errno(EINVAL);
+            return -1;
+        }
+    };
+
+    let mut file = FileFopt::new();
+    let exist = file.exist(path);
+    if exist && (flags & O_CREAT) != 0 && (flags & O_EXCL) != 0 {
+        set_errno(EEXIST);
+        return -1;
+    }
+    if !file.open(path, mode) {
+        set_errno(if exist || (flags & O_CREAT) != 0 { EIO } else { ENOENT });
+        return -1;
+    }
+
+    FILE_TABLE.insert(file)
+}
+
+// close
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn close(fd: i32) -> i32 {
+    match FILE_TABLE.remove(fd) {
+        Some(mut file) => {
+            file.close();
+            0
+        }
+        None => {
+            set_errno(EBADF);
+            -1
+        }
+    }
+}
+
+// read, the position is advanced
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn read(fd: i32, buf: *mut u8, count: usize) -> isize {
+    let data = unsafe { core::slice::from_raw_parts_mut(buf, count) };
+    match FILE_TABLE.with(fd, |file| file.read(data, count)) {
+        Some(readed) => readed as isize,
+        None => {
+            set_errno(EBADF);
+            -1
+        }
+    }
+}
+
+// write, the position is advanced
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn write(fd: i32, buf: *const u8, count: usize) -> isize {
+    let data = unsafe { core::slice::from_raw_parts(buf, count) };
+    match FILE_TABLE.with(fd, |file| file.write(data, count)) {
+        Some(written) if written == 0 && count != 0 => {
+            set_errno(EIO);
+            -1
+        }
+        Some(written) => written as isize,
+        None => {
+            set_errno(EBADF);
+            -1
+        }
+    }
+}
+
+// lseek, the position can be after the end of file
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lseek(fd: i32, offset: isize, whence: i32) -> isize {
+    let pos = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as usize),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        _ => {
+            set_errno(EINVAL);
+            return -1;
+        }
+    };
+
+    match FILE_TABLE.with(fd, |file| file.seek(pos)) {
+        Some(Some(pos)) if pos <= isize::MAX as usize => pos as isize,
+        Some(_) => {
+            set_errno(EINVAL);
+            -1
+        }
+        None => {
+            set_errno(EBADF);
+            -1
+        }
+    }
+}
+
+// ftruncate, the file is truncated or extended with zero
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ftruncate(fd: i32, length: isize) -> i32 {
+    if length < 0 {
+        set_errno(EINVAL);
+        return -1;
+    }
+
+    match FILE_TABLE.with(fd, |file| file.set_len(length as usize)) {
+        Some(true) => 0,
+        Some(false) => {
+            set_errno(EIO);
+            -1
+        }
+        None => {
+            set_errno(EBADF);
+            -1
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//###########################################################################
+// main.rs
+// The specific implementation of functions related to main
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+#![no_std]
+#![no_main]
+#![feature(thread_local)]
+
+// import alloc
+extern crate alloc;
+
+// import village
+pub mod village;
+pub use village::traits as traits;
+pub use village::misc as misc;
+
+use crate::traits::vk_callback::Callback;
+use crate::village::kernel;
+
+// Thread local counter, every thread starts from the initialized value
+#[thread_local]
+static mut COUNTER: u32 = 100;
+
+// Count
+fn count(name: &str, times: u32) {
+    for _ in 0..times {
+        unsafe { COUNTER += 1 };
+        kernel().thread().sleep(1);
+    }
+    println!("{} counter {}", name, unsafe { COUNTER });
+}
+
+// Worker
+extern "C" fn worker(_instance: *mut (), _userdata: *mut ()) {
+    count("worker", 5);
+}
+
+// Main
+#[unsafe(no_mangle)]
+pub fn main(_argv: &[&str]) {
+    println!("tls village demo");
+
+    // Create the worker threads, the counter of each thread is 105
+    let mut tids = [0; 2];
+    for tid in tids.iter_mut() {
+        *tid = kernel().process().create_thread("worker", Callback::from_fn(worker));
+    }
+
+    // The counter of main thread is 110
+    count("main", 10);
+
+    // Wait for the worker threads
+    for tid in tids {
+        if tid >= 0 {
+            kernel().process().join_thread(tid);
+        }
+    }
+}
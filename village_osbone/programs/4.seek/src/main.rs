@@ -0,0 +1,58 @@
+//###########################################################################
+// main.rs
+// The specific implementation of functions related to main
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+#![no_std]
+#![no_main]
+
+// import alloc
+extern crate alloc;
+
+// import village
+pub mod village;
+pub use village::traits as traits;
+pub use village::misc as misc;
+
+use crate::misc::fopts::vk_file_fopt::FileFopt;
+use crate::traits::vk_filesys::{FileMode, SeekFrom};
+
+// Main
+#[unsafe(no_mangle)]
+pub fn main(argv: &[&str]) {
+    println!("seek village demo");
+
+    let path = if argv.len() > 1 { argv[1] } else { "/seek.txt" };
+
+    let mut mode = FileMode::READ_WRITE;
+    mode.insert(FileMode::CREATE_ALWAYS);
+
+    let mut file = FileFopt::new();
+    if !file.open(path, mode) {
+        println!("open {} failed", path);
+        return;
+    }
+
+    // Write at the start, then overwrite the middle
+    file.write(b"hello village", 13);
+    file.seek(SeekFrom::Start(6));
+    file.write(b"VILLAGE", 7);
+    println!("tell {}", file.tell());
+
+    // Seek after the end, the gap is filled with zero
+    file.seek(SeekFrom::End(3));
+    file.write(b"!", 1);
+    println!("size {}", file.size());
+
+    // Shrink the file and read it back
+    file.set_len(5);
+    let mut data = [0u8; 16];
+    file.seek(SeekFrom::Start(0));
+    let readed = file.read(&mut data, 16);
+    if let Ok(text) = core::str::from_utf8(&data[..readed]) {
+        println!("read {}", text);
+    }
+
+    file.close();
+}
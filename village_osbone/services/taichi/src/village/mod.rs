@@ -31,11 +31,13 @@ pub mod traits {
     pub mod vk_linkedlist;
     pub mod vk_extension;
     pub mod vk_macro;
+    pub mod vk_task_local;
 }
 
 // import misc modules
 pub mod misc {
     pub mod fopts {
+        pub mod vk_block_fopt;
         pub mod vk_dev_fopt;
         pub mod vk_dir_fopt;
         pub mod vk_file_fopt;
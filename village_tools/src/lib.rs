@@ -0,0 +1,19 @@
+//###########################################################################
+// lib.rs
+// The specific implementation of functions related to lib
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+// import alloc
+extern crate alloc;
+
+// import village, the kernel sources are built for host,
+// the kernel objects are created by const new without default
+#[allow(clippy::new_without_default)]
+pub mod village;
+pub use village::filesys as filesys;
+pub use village::misc as misc;
+pub use village::traits as traits;
+
+// import image
+pub mod vk_image;
@@ -0,0 +1,172 @@
+//###########################################################################
+// main.rs
+// The specific implementation of functions related to vkimage
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use std::path::Path;
+use std::process::ExitCode;
+use vt::filesys::fs::fat::vk_fat_diskio::FatType;
+use vt::filesys::vk_partition::PartitionType;
+use vt::traits::vk_filesys::FileType;
+use vt::vk_image::{ImageOptions, VkImage};
+
+// Usage
+fn usage() {
+    println!("Usage: vkimage create <image> <size mb> [-t mbr|gpt|none] [-F 12|16|32] [-n label] [-j]");
+    println!("       vkimage ls <image> [path] [-p partition]");
+    println!("       vkimage mkdir <image> <path> [-p partition]");
+    println!("       vkimage copy <image> <host path> <path> [-p partition]");
+    println!("       vkimage extract <image> <path> <host path> [-p partition]");
+    println!("       vkimage rm <image> <path> [-p partition]");
+    println!("       vkimage rc <image> <dir> <extension> [-p partition]");
+}
+
+// Take option, the option and its value are removed from args
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<Option<String>> {
+    let pos = args.iter().position(|arg| arg == name)?;
+    args.remove(pos);
+    if pos < args.len() {
+        Some(Some(args.remove(pos)))
+    } else {
+        Some(None)
+    }
+}
+
+// Take flag, the flag is removed from args
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(pos) => {
+            args.remove(pos);
+            true
+        }
+        None => false,
+    }
+}
+
+// Create image
+fn create(mut args: Vec<String>) -> bool {
+    let mut options = ImageOptions::new();
+
+    if let Some(table) = take_option(&mut args, "-t") {
+        options.table = match table.as_deref() {
+            Some("mbr") => PartitionType::MBR,
+            Some("gpt") => PartitionType::GPT,
+            Some("none") => PartitionType::None,
+            _ => return false,
+        };
+    }
+
+    if let Some(fat_type) = take_option(&mut args, "-F") {
+        options.fat_type = match fat_type.as_deref() {
+            Some("12") => FatType::Fat12,
+            Some("16") => FatType::Fat16,
+            Some("32") => FatType::Fat32,
+            _ => return false,
+        };
+    }
+
+    if let Some(label) = take_option(&mut args, "-n") {
+        match label {
+            Some(label) => options.label = label,
+            None => return false,
+        }
+    }
+
+    options.journal = take_flag(&mut args, "-j");
+
+    let [path, size] = args.as_slice() else {
+        usage();
+        return false;
+    };
+
+    options.size_mb = match size.parse::<u64>() {
+        Ok(size) if size != 0 => size,
+        _ => {
+            eprintln!("Invalid size {}", size);
+            return false;
+        }
+    };
+
+    VkImage::new().create(path, &options)
+}
+
+// List dir
+fn list(image: &mut VkImage, path: &str) -> bool {
+    let dirs = match image.list(path) {
+        Some(dirs) => dirs,
+        None => {
+            eprintln!("{} is not a directory", path);
+            return false;
+        }
+    };
+
+    for dir in dirs.iter() {
+        if dir.typid == FileType::Directory {
+            println!("{}/", dir.name);
+        } else {
+            println!("{}", dir.name);
+        }
+    }
+    true
+}
+
+// Execute the commands of image
+fn execute(cmd: &str, mut args: Vec<String>) -> bool {
+    let index = match take_option(&mut args, "-p") {
+        Some(Some(index)) => match index.parse::<usize>() {
+            Ok(index) => index,
+            Err(_) => return false,
+        },
+        Some(None) => return false,
+        None => 0,
+    };
+
+    if args.is_empty() {
+        usage();
+        return false;
+    }
+
+    let mut image = VkImage::new();
+    if !image.setup(&args[0], index) {
+        return false;
+    }
+
+    let args: Vec<&str> = args.iter().skip(1).map(|arg| arg.as_str()).collect();
+
+    match (cmd, args.as_slice()) {
+        ("ls", []) => list(&mut image, "/"),
+        ("ls", [path]) => list(&mut image, path),
+        ("mkdir", [path]) => image.mkdir(path),
+        ("copy", [host, path]) => image.copy_in(Path::new(host), path),
+        ("extract", [path, host]) => image.copy_out(path, Path::new(host)),
+        ("rm", [path]) => image.remove(path),
+        ("rc", [dir, ext]) => image.write_rc(dir, ext).is_some(),
+        _ => {
+            usage();
+            false
+        }
+    }
+}
+
+// Main
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.is_empty() {
+        usage();
+        return ExitCode::FAILURE;
+    }
+
+    let cmd = args.remove(0);
+    let result = match cmd.as_str() {
+        "create" => create(args),
+        "ls" | "mkdir" | "copy" | "extract" | "rm" | "rc" => execute(&cmd, args),
+        _ => {
+            usage();
+            false
+        }
+    };
+
+    if result { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
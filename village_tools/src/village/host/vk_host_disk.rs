@@ -0,0 +1,102 @@
+//###########################################################################
+// vk_host_disk.rs
+// The specific implementation of functions related to host disk
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::traits::vk_driver::{BLKCommand, Command, Driver};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+// Const members
+const SECTOR_SIZE: usize = 512;
+
+// Struct HostDisk, the image file is used as block device
+pub struct HostDisk {
+    file: File,
+    sectors: usize,
+}
+
+// Impl HostDisk
+impl HostDisk {
+    // New
+    pub fn new(file: File) -> Self {
+        let sectors = match file.metadata() {
+            Ok(meta) => meta.len() as usize / SECTOR_SIZE,
+            Err(_) => 0,
+        };
+        Self { file, sectors }
+    }
+
+    // Get the count of sectors can be accessed from the offset
+    fn bounded(&self, count: usize, offset: usize) -> usize {
+        if offset >= self.sectors {
+            return 0;
+        }
+        count.min(self.sectors - offset)
+    }
+}
+
+// Impl Driver for HostDisk
+impl Driver for HostDisk {
+    // Open
+    fn open(&mut self, _data: *mut ()) -> bool {
+        self.sectors != 0
+    }
+
+    // Write
+    fn write(&mut self, data: &[u8], count: usize, offset: usize) -> usize {
+        let count = self.bounded(count, offset).min(data.len() / SECTOR_SIZE);
+        if count == 0 || self.file.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64)).is_err() {
+            return 0;
+        }
+        match self.file.write_all(&data[..count * SECTOR_SIZE]) {
+            Ok(_) => count,
+            Err(_) => 0,
+        }
+    }
+
+    // Read
+    fn read(&mut self, data: &mut [u8], count: usize, offset: usize) -> usize {
+        let count = self.bounded(count, offset).min(data.len() / SECTOR_SIZE);
+        if count == 0 || self.file.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64)).is_err() {
+            return 0;
+        }
+        match self.file.read_exact(&mut data[..count * SECTOR_SIZE]) {
+            Ok(_) => count,
+            Err(_) => 0,
+        }
+    }
+
+    // IOctrl
+    fn ioctrl(&mut self, command: &mut Command) -> bool {
+        match command {
+            Command::BLK(blk_cmd) => match blk_cmd {
+                BLKCommand::SectorCount { count } => {
+                    *count = self.sectors;
+                    true
+                }
+                BLKCommand::SectorSize { size } => {
+                    *size = SECTOR_SIZE;
+                    true
+                }
+                BLKCommand::Direct { direct } => {
+                    *direct = true;
+                    true
+                }
+                BLKCommand::ReadOnly { read_only } => {
+                    *read_only = false;
+                    true
+                }
+                BLKCommand::Backing { .. } => false,
+                BLKCommand::Parent { .. } => false,
+            },
+            _ => false,
+        }
+    }
+
+    // Close
+    fn close(&mut self) {
+        let _ = self.file.flush();
+    }
+}
@@ -0,0 +1,152 @@
+//###########################################################################
+// vk_host_kernel.rs
+// The specific implementation of functions related to host kernel
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::traits::vk_driver::DriverWrapper;
+use crate::traits::vk_linkedlist::LinkedList;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Struct HostSystem
+pub struct HostSystem;
+
+// Impl HostSystem
+impl HostSystem {
+    // Get ticks, the seconds and nanoseconds of host time are mixed
+    pub fn get_ticks(&mut self) -> u32 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(time) => (time.as_secs() as u32) ^ time.subsec_nanos(),
+            Err(_) => 0,
+        }
+    }
+}
+
+// Struct HostDebug
+pub struct HostDebug;
+
+// Impl HostDebug
+impl HostDebug {
+    // Info
+    pub fn info(&mut self, info: &str) {
+        eprintln!("[Info] {}", info);
+    }
+
+    // Error
+    pub fn error(&mut self, error: &str) {
+        eprintln!("[Error] {}", error);
+    }
+
+    // Warning
+    pub fn warning(&mut self, warning: &str) {
+        eprintln!("[Warning] {}", warning);
+    }
+}
+
+// Struct HostDevice
+pub struct HostDevice {
+    base_devs: LinkedList<DriverWrapper>,
+}
+
+// Impl HostDevice
+impl HostDevice {
+    // Register driver, the driver is opened after it is added
+    pub fn register_driver(&mut self, driver: DriverWrapper) {
+        let name = driver.name().to_string();
+        self.base_devs.push(driver);
+        if let Some(driver) = self.get_driver(&name) {
+            driver.open();
+        }
+    }
+
+    // Unregister driver, the driver is closed before it is removed
+    pub fn unregister_driver(&mut self, name: &str) {
+        if let Some(driver) = self.get_driver(name) {
+            driver.close();
+        }
+        self.base_devs.retain_mut(|driver| driver.name() != name);
+    }
+
+    // Get driver
+    pub fn get_driver(&mut self, name: &str) -> Option<&mut DriverWrapper> {
+        self.base_devs.iter_mut().find(|driver| driver.name() == name)
+    }
+}
+
+// Struct HostFileSystem, the blocks are not cached on host
+pub struct HostFileSystem;
+
+// Impl HostFileSystem
+impl HostFileSystem {
+    // Read block
+    pub fn read_block(&mut self, disk: &str, data: &mut [u8], count: usize, lba: usize) -> usize {
+        match kernel().device().get_driver(disk) {
+            Some(driver) => driver.read(data, count, lba),
+            None => 0,
+        }
+    }
+
+    // Write block
+    pub fn write_block(&mut self, disk: &str, data: &[u8], count: usize, lba: usize) -> usize {
+        match kernel().device().get_driver(disk) {
+            Some(driver) => driver.write(data, count, lba),
+            None => 0,
+        }
+    }
+
+    // Sync block
+    pub fn sync_block(&mut self, disk: &str) -> bool {
+        kernel().device().get_driver(disk).is_some()
+    }
+}
+
+// Struct HostKernel, it provides the kernel methods used by the filesys sources
+pub struct HostKernel {
+    system: HostSystem,
+    debug: HostDebug,
+    device: HostDevice,
+    filesys: HostFileSystem,
+}
+
+// Impl HostKernel
+impl HostKernel {
+    // New
+    const fn new() -> Self {
+        Self {
+            system: HostSystem,
+            debug: HostDebug,
+            device: HostDevice {
+                base_devs: LinkedList::new(),
+            },
+            filesys: HostFileSystem,
+        }
+    }
+
+    // System
+    pub fn system(&mut self) -> &mut HostSystem {
+        &mut self.system
+    }
+
+    // Debug
+    pub fn debug(&mut self) -> &mut HostDebug {
+        &mut self.debug
+    }
+
+    // Device
+    pub fn device(&mut self) -> &mut HostDevice {
+        &mut self.device
+    }
+
+    // Filesys
+    pub fn filesys(&mut self) -> &mut HostFileSystem {
+        &mut self.filesys
+    }
+}
+
+// Host kernel, the tool is single threaded
+static mut HOST_KERNEL: HostKernel = HostKernel::new();
+
+// Kernel
+pub fn kernel() -> &'static mut HostKernel {
+    unsafe { &mut *core::ptr::addr_of_mut!(HOST_KERNEL) }
+}
@@ -0,0 +1,44 @@
+//###########################################################################
+// vk_host_macro.rs
+// The specific implementation of functions related to host macro
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+
+// Debug info macro
+#[macro_export]
+macro_rules! debug_info {
+    ($($arg:tt)*) => {
+        crate::village::kernel().debug().info(&alloc::format!($($arg)*));
+    };
+}
+
+// Debug error macro
+#[macro_export]
+macro_rules! debug_error {
+    ($($arg:tt)*) => {
+        crate::village::kernel().debug().error(&alloc::format!($($arg)*));
+    };
+}
+
+// Debug warning macro
+#[macro_export]
+macro_rules! debug_warning {
+    ($($arg:tt)*) => {
+        crate::village::kernel().debug().warning(&alloc::format!($($arg)*));
+    };
+}
+
+// Register filesys macro, the host has no init array, the filesys is created by function
+#[macro_export]
+macro_rules! register_filesys {
+    ($filsys:expr, $name:ident) => {
+        paste::paste! {
+            pub fn [<$name _filesys>]() -> crate::traits::vk_filesys::FileSysWrapper {
+                crate::traits::vk_filesys::FileSysWrapper::new(
+                    Box::new($filsys), stringify!($name)
+                )
+            }
+        }
+    };
+}
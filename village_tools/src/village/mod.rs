@@ -0,0 +1,50 @@
+//###########################################################################
+// village.rs
+// The specific implementation of functions related to village mod
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+// import village module
+pub use host::vk_host_kernel::kernel;
+
+// import host modules, the host shim only implements what the kernel sources use
+#[allow(clippy::all, unused)]
+pub mod host {
+    pub mod vk_host_disk;
+    pub mod vk_host_kernel;
+    pub mod vk_host_macro;
+}
+
+// import traits modules
+pub mod traits {
+    pub mod vk_driver;
+    pub mod vk_filesys;
+    pub mod vk_linkedlist;
+}
+
+// import misc modules
+pub mod misc {
+    pub mod fopts {
+        pub mod vk_block_fopt;
+        pub mod vk_dev_fopt;
+    }
+}
+
+// import filesys modules
+pub mod filesys {
+    pub mod fs {
+        pub mod fat {
+            pub mod vk_fat_checker;
+            pub mod vk_fat_codepage;
+            pub mod vk_fat_diskio;
+            pub mod vk_fat_entry;
+            pub mod vk_fat_filedir;
+            pub mod vk_fat_folder;
+            pub mod vk_fat_format;
+            pub mod vk_fat_journal;
+            pub mod vk_fat_object;
+            pub mod vk_fat_system;
+        }
+    }
+    pub mod vk_partition;
+}
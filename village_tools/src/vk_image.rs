@@ -0,0 +1,489 @@
+//###########################################################################
+// vk_image.rs
+// The specific implementation of functions related to image
+//
+// $Copyright: Copyright (C) village
+//###########################################################################
+use crate::filesys::fs::fat::vk_fat_diskio::FatType;
+use crate::filesys::fs::fat::vk_fat_format::FatFormat;
+use crate::filesys::fs::fat::vk_fat_system::fat_filesys;
+use crate::filesys::vk_partition::{GPTType, MBRType, PartitionDisk, PartitionTable, PartitionType};
+use crate::misc::fopts::vk_block_fopt::BlockFopt;
+use crate::traits::vk_driver::{DriverID, DriverWrapper};
+use crate::traits::vk_filesys::{FileDir, FileMode, FileType, FileVol};
+use crate::village::host::vk_host_disk::HostDisk;
+use crate::village::kernel;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+
+// Const members
+const IMAGE_DISK: &str = "image";
+const RC_FILE_NAME: &str = "_load_.rc";
+
+// Struct ImageOptions, the options used to create image
+pub struct ImageOptions {
+    pub size_mb: u64,
+    pub table: PartitionType,
+    pub fat_type: FatType,
+    pub label: String,
+    pub journal: bool,
+}
+
+// Impl ImageOptions
+impl ImageOptions {
+    // New
+    pub fn new() -> Self {
+        Self {
+            size_mb: 64,
+            table: PartitionType::MBR,
+            fat_type: FatType::None,
+            label: String::from("VILLAGE OS"),
+            journal: false,
+        }
+    }
+}
+
+// Impl Default for ImageOptions
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Struct VkImage
+pub struct VkImage {
+    disks: Vec<String>,
+    volume: Option<Box<dyn FileVol>>,
+}
+
+// Impl VkImage
+impl VkImage {
+    // New
+    pub const fn new() -> Self {
+        Self {
+            disks: Vec::new(),
+            volume: None,
+        }
+    }
+
+    // Setup, the partition is the first one when index is 0
+    pub fn setup(&mut self, path: &str, index: usize) -> bool {
+        let file = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("Open {} failed, {}", path, err);
+                return false;
+            }
+        };
+        self.register_disk(file);
+
+        // Read the partition table
+        let mut device = BlockFopt::new();
+        device.open(IMAGE_DISK);
+        let table = match PartitionTable::read(&mut device) {
+            Some(table) => table,
+            None => {
+                eprintln!("{} is not a vaild disk image", path);
+                return false;
+            }
+        };
+
+        // Select the disk of volume
+        let disk = if table.typ == PartitionType::None {
+            IMAGE_DISK.to_string()
+        } else {
+            let partition = match table.partitions.iter().find(|p| index == 0 || p.index == index) {
+                Some(partition) => partition,
+                None => {
+                    eprintln!("Partition {} not found in {}", index, path);
+                    return false;
+                }
+            };
+            self.register_partition(PartitionDisk::new(IMAGE_DISK, partition), partition.index)
+        };
+
+        // Setup volume
+        let mut volume = fat_filesys().create_volume();
+        if !volume.setup(&disk) {
+            eprintln!("{} is not a fat volume", disk);
+            return false;
+        }
+        self.volume = Some(volume);
+        true
+    }
+
+    // Exit
+    pub fn exit(&mut self) {
+        if let Some(mut volume) = self.volume.take() {
+            volume.exit();
+        }
+        for disk in self.disks.drain(..).rev() {
+            kernel().device().unregister_driver(&disk);
+        }
+    }
+}
+
+// Impl VkImage
+impl VkImage {
+    // Register disk
+    fn register_disk(&mut self, file: File) {
+        kernel().device().register_driver(DriverWrapper::new(
+            Box::new(HostDisk::new(file)),
+            DriverID::Block,
+            IMAGE_DISK,
+            core::ptr::null_mut(),
+        ));
+        self.disks.push(IMAGE_DISK.to_string());
+    }
+
+    // Register partition, returns the name of partition disk
+    fn register_partition(&mut self, partition: PartitionDisk, index: usize) -> String {
+        let name = format!("{}p{}", IMAGE_DISK, index);
+        kernel().device().register_driver(DriverWrapper::new(
+            Box::new(partition),
+            DriverID::Block,
+            &name,
+            core::ptr::null_mut(),
+        ));
+        self.disks.push(name.clone());
+        name
+    }
+
+    // Get volume
+    fn volume(&mut self) -> &mut Box<dyn FileVol> {
+        self.volume.as_mut().expect("image is not setup")
+    }
+
+    // Join path
+    fn join(dir: &str, name: &str) -> String {
+        if dir.ends_with('/') {
+            format!("{}{}", dir, name)
+        } else {
+            format!("{}/{}", dir, name)
+        }
+    }
+}
+
+// Impl VkImage
+impl VkImage {
+    // Create, the image has one fat partition covers the whole disk
+    pub fn create(&mut self, path: &str, options: &ImageOptions) -> bool {
+        let mut open = OpenOptions::new();
+        open.read(true).write(true).create(true).truncate(true);
+
+        let file = match open.open(path).and_then(|file| file.set_len(options.size_mb << 20).map(|_| file)) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("Create {} failed, {}", path, err);
+                return false;
+            }
+        };
+        self.register_disk(file);
+
+        let mut device = BlockFopt::new();
+        device.open(IMAGE_DISK);
+        let sectors = device.get_sector_count() as u64;
+
+        // Create partition table
+        let mut table = PartitionTable::new(options.table, sectors);
+        let mut hidden_sectors = 0;
+        let disk = if options.table == PartitionType::None {
+            IMAGE_DISK.to_string()
+        } else {
+            let index = match table.add(0, 0, MBRType::FAT32_LBA, GPTType::BASIC_DATA) {
+                Some(index) => index,
+                None => {
+                    eprintln!("The image is too small for partition");
+                    return false;
+                }
+            };
+            let partition = table.get_partition(index).unwrap().clone();
+            hidden_sectors = partition.starting_lba as u32;
+            self.register_partition(PartitionDisk::new(IMAGE_DISK, &partition), index)
+        };
+
+        // Format volume
+        let mut format = FatFormat::new();
+        format.set_fat_type(options.fat_type);
+        format.set_journal(options.journal);
+        format.set_hidden_sectors(hidden_sectors);
+        if !options.label.is_empty() && !format.set_label(&options.label) {
+            eprintln!("Invalid label {}", options.label);
+            return false;
+        }
+        if !format.setup(&disk, 0) {
+            eprintln!("Setup format of {} failed", disk);
+            return false;
+        }
+        let info = format.format();
+        format.exit();
+
+        let info = match info {
+            Some(info) => info,
+            None => {
+                eprintln!("Format {} failed, the size does not fit the fat type", disk);
+                return false;
+            }
+        };
+
+        // Write the partition table with the os indicator of fat type
+        if options.table != PartitionType::None {
+            table.partitions[0].os_indicator = match info.fat_type {
+                FatType::Fat12 => MBRType::FAT12,
+                FatType::Fat16 => MBRType::FAT16_LBA,
+                _ => MBRType::FAT32_LBA,
+            };
+            table.partitions[0].name = options.label.clone();
+            if !table.write(&mut device) {
+                eprintln!("Write partition table of {} failed", path);
+                return false;
+            }
+        }
+        device.close();
+
+        true
+    }
+}
+
+// Impl VkImage
+impl VkImage {
+    // Is dir
+    pub fn is_dir(&mut self, path: &str) -> bool {
+        path == "/" || self.volume().exist(path, FileType::Directory)
+    }
+
+    // List dir
+    pub fn list(&mut self, path: &str) -> Option<Vec<FileDir>> {
+        if !self.is_dir(path) {
+            return None;
+        }
+
+        let volume = self.volume();
+        let fd = volume.opendir(path, FileMode::READ);
+        if fd == 0 {
+            return None;
+        }
+
+        let size = volume.sizedir(fd);
+        let mut dirs: Vec<FileDir> = (0..size).map(|_| FileDir::new()).collect();
+        let size = volume.readdir(fd, &mut dirs, size, 0);
+        volume.closedir(fd);
+
+        dirs.truncate(size);
+        dirs.retain(|dir| dir.name != "." && dir.name != ".." && dir.typid != FileType::Volume);
+        Some(dirs)
+    }
+
+    // Make dir, the parent dirs are created
+    pub fn mkdir(&mut self, path: &str) -> bool {
+        let mut dir = String::new();
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            dir = Self::join(&dir, name);
+
+            if self.is_dir(&dir) {
+                continue;
+            }
+
+            let volume = self.volume();
+            if volume.exist(&dir, FileType::File) {
+                eprintln!("{} is not a directory", dir);
+                return false;
+            }
+
+            let fd = volume.opendir(&dir, FileMode::CREATE_NEW);
+            if fd == 0 {
+                eprintln!("Create directory {} failed", dir);
+                return false;
+            }
+            volume.closedir(fd);
+        }
+        true
+    }
+
+    // Write file, the existing file is replaced
+    pub fn write_file(&mut self, path: &str, data: &[u8]) -> bool {
+        let volume = self.volume();
+
+        let mut mode = FileMode::CREATE_NEW;
+        mode.insert(FileMode::WRITE);
+        mode.insert(FileMode::TRUNCATE);
+
+        let fd = volume.open(path, mode);
+        if fd == 0 {
+            eprintln!("Create file {} failed", path);
+            return false;
+        }
+
+        // The old data is dropped by truncating on open
+        let written = volume.write(fd, data, data.len(), 0);
+        volume.close(fd);
+
+        if written != data.len() {
+            eprintln!("Write file {} failed, {} of {} bytes written", path, written, data.len());
+            return false;
+        }
+        true
+    }
+
+    // Read file
+    pub fn read_file(&mut self, path: &str) -> Option<Vec<u8>> {
+        let volume = self.volume();
+        if !volume.exist(path, FileType::File) {
+            return None;
+        }
+
+        let fd = volume.open(path, FileMode::READ);
+        if fd == 0 {
+            return None;
+        }
+
+        let size = volume.size(fd);
+        let mut data = vec![0u8; size];
+        let readed = volume.read(fd, &mut data, size, 0);
+        volume.close(fd);
+
+        data.truncate(readed);
+        Some(data)
+    }
+
+    // Remove, the dir is removed with its contents
+    pub fn remove(&mut self, path: &str) -> bool {
+        if let Some(dirs) = self.list(path) {
+            for dir in dirs.iter() {
+                if !self.remove(&Self::join(path, &dir.name)) {
+                    return false;
+                }
+            }
+        }
+        self.volume().remove(path)
+    }
+}
+
+// Impl VkImage
+impl VkImage {
+    // Copy in, the contents of host dir are copied into the dir of image
+    pub fn copy_in(&mut self, host: &Path, path: &str) -> bool {
+        if host.is_dir() {
+            if !self.mkdir(path) {
+                return false;
+            }
+
+            let mut entries: Vec<_> = match fs::read_dir(host) {
+                Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+                Err(err) => {
+                    eprintln!("Read {} failed, {}", host.display(), err);
+                    return false;
+                }
+            };
+            entries.sort();
+
+            for entry in entries.iter() {
+                let name = entry.file_name().unwrap_or_default().to_string_lossy();
+                if !self.copy_in(entry, &Self::join(path, &name)) {
+                    return false;
+                }
+            }
+            return true;
+        }
+
+        let data = match fs::read(host) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Read {} failed, {}", host.display(), err);
+                return false;
+            }
+        };
+
+        // The file is copied into the dir when the target is dir
+        let target = if self.is_dir(path) {
+            let name = host.file_name().unwrap_or_default().to_string_lossy();
+            Self::join(path, &name)
+        } else {
+            path.to_string()
+        };
+
+        self.write_file(&target, &data)
+    }
+
+    // Copy out, the dir of image is copied into the host dir
+    pub fn copy_out(&mut self, path: &str, host: &Path) -> bool {
+        if let Some(dirs) = self.list(path) {
+            if let Err(err) = fs::create_dir_all(host) {
+                eprintln!("Create {} failed, {}", host.display(), err);
+                return false;
+            }
+            for dir in dirs.iter() {
+                if !self.copy_out(&Self::join(path, &dir.name), &host.join(&dir.name)) {
+                    return false;
+                }
+            }
+            return true;
+        }
+
+        let data = match self.read_file(path) {
+            Some(data) => data,
+            None => {
+                eprintln!("{} not found", path);
+                return false;
+            }
+        };
+
+        if let Err(err) = fs::write(host, data) {
+            eprintln!("Write {} failed, {}", host.display(), err);
+            return false;
+        }
+        true
+    }
+
+    // Collect files with the extension
+    fn collect_files(&mut self, path: &str, ext: &str, files: &mut Vec<String>) {
+        if let Some(dirs) = self.list(path) {
+            for dir in dirs.iter() {
+                let child = Self::join(path, &dir.name);
+                if dir.typid == FileType::Directory {
+                    self.collect_files(&child, ext, files);
+                } else if dir.name.ends_with(ext) && dir.name != RC_FILE_NAME {
+                    files.push(child);
+                }
+            }
+        }
+    }
+
+    // Write rc, the files with the extension under the dir are loaded by kernel
+    pub fn write_rc(&mut self, path: &str, ext: &str) -> Option<Vec<String>> {
+        if !self.is_dir(path) {
+            eprintln!("{} is not a directory", path);
+            return None;
+        }
+
+        let mut files = Vec::new();
+        self.collect_files(path, ext, &mut files);
+        files.sort();
+
+        let mut rc = String::new();
+        for file in files.iter() {
+            rc.push_str(file);
+            rc.push('\n');
+        }
+
+        if !self.write_file(&Self::join(path, RC_FILE_NAME), rc.as_bytes()) {
+            return None;
+        }
+        Some(files)
+    }
+}
+
+// Impl Default for VkImage
+impl Default for VkImage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Impl Drop for VkImage
+impl Drop for VkImage {
+    fn drop(&mut self) {
+        self.exit();
+    }
+}